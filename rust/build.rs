@@ -8,6 +8,7 @@ fn main() {
         lchmod: { any(target_os = "netbsd", target_os = "freebsd", target_os = "dragonfly") },
         lchflags: { any(target_os = "openbsd", target_os = "netbsd", target_os = "freebsd",
                     target_os = "dragonfly", target_os = "macos", target_os = "ios") },
+        chflagsat: { any(target_os = "freebsd", target_os = "dragonfly", target_os = "openbsd") },
         // OS-exclusive features
         file_flags: { any(target_os = "openbsd", target_os = "netbsd", target_os = "freebsd",
                     target_os = "dragonfly", target_os = "macos", target_os = "ios") },
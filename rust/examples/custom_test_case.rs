@@ -0,0 +1,23 @@
+//! Example of a crate adding its own test cases to the test suite,
+//! which are run by the same runner as the test cases of pjdfstest.
+//!
+//! Run with `cargo run --example custom_test_case -- -p /mnt/test custom_test_case`.
+
+use pjdfstest::context::{FileType, TestContext};
+
+pjdfstest::register_test_case! {
+    /// A new file is empty
+    new_file_is_empty => [Regular, Dir]
+}
+fn new_file_is_empty(ctx: &mut TestContext, ft: FileType) {
+    let path = ctx.create(ft.clone()).unwrap();
+
+    match ft {
+        FileType::Dir => assert_eq!(std::fs::read_dir(path).unwrap().count(), 0),
+        _ => assert_eq!(std::fs::metadata(path).unwrap().len(), 0),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    pjdfstest::cli::main()
+}
@@ -0,0 +1,132 @@
+//! Capture of the directory of a failed test, to debug the failure after the cleanup.
+//!
+//! The entries are copied to the `tree` subdirectory of the destination,
+//! with a `MANIFEST` file recording their type, mode, owner, size, timestamps,
+//! flags and ACL (on the platforms supporting NFSv4 ACLs).
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::fs::symlink,
+    path::Path,
+};
+
+use nix::sys::stat::{lstat, FileStat, SFlag};
+
+/// Name of the file recording the state of the entries.
+const MANIFEST: &str = "MANIFEST";
+
+/// Name of the directory where the entries are copied.
+const TREE: &str = "tree";
+
+/// Return the type of an entry as a short name.
+fn file_type(file_stat: &FileStat) -> &'static str {
+    match SFlag::from_bits_truncate(file_stat.st_mode) & SFlag::S_IFMT {
+        SFlag::S_IFREG => "regular",
+        SFlag::S_IFDIR => "dir",
+        SFlag::S_IFIFO => "fifo",
+        SFlag::S_IFBLK => "block",
+        SFlag::S_IFCHR => "char",
+        SFlag::S_IFSOCK => "socket",
+        SFlag::S_IFLNK => "symlink",
+        _ => "unknown",
+    }
+}
+
+/// Describe the ACL of an entry.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn describe_acl(path: &Path, file_stat: &FileStat) -> String {
+    if SFlag::from_bits_truncate(file_stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFLNK {
+        return String::from("-");
+    }
+
+    exacl::getfacl(path, None)
+        .and_then(|entries| exacl::to_string(&entries))
+        .map(|acl| acl.lines().collect::<Vec<_>>().join(","))
+        .unwrap_or_else(|e| format!("<{e}>"))
+}
+
+/// Describe the state of an entry on a single line.
+#[cfg_attr(
+    not(any(target_os = "macos", target_os = "freebsd")),
+    allow(unused_variables)
+)]
+fn describe(path: &Path, file_stat: &FileStat) -> String {
+    let line = format!(
+        "type={} mode={:o} uid={} gid={} size={} nlink={} atime={}.{:09} mtime={}.{:09} ctime={}.{:09}",
+        file_type(file_stat),
+        file_stat.st_mode & 0o7777,
+        file_stat.st_uid,
+        file_stat.st_gid,
+        file_stat.st_size,
+        file_stat.st_nlink,
+        file_stat.st_atime,
+        file_stat.st_atime_nsec,
+        file_stat.st_mtime,
+        file_stat.st_mtime_nsec,
+        file_stat.st_ctime,
+        file_stat.st_ctime_nsec,
+    );
+
+    #[cfg(file_flags)]
+    let line = format!("{line} flags={:#x}", file_stat.st_flags);
+
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    let line = format!("{line} acl={}", describe_acl(path, file_stat));
+
+    line
+}
+
+/// Copy an entry to `dest`, if its content can be copied.
+fn copy_entry(path: &Path, file_stat: &FileStat, dest: &Path) -> io::Result<()> {
+    match SFlag::from_bits_truncate(file_stat.st_mode) & SFlag::S_IFMT {
+        SFlag::S_IFDIR => fs::create_dir_all(dest),
+        SFlag::S_IFREG => fs::copy(path, dest).map(drop),
+        SFlag::S_IFLNK => symlink(fs::read_link(path)?, dest),
+        // Only recorded in the manifest
+        _ => Ok(()),
+    }
+}
+
+/// Copy the tree rooted at `root` to `dest` and record the state of its entries,
+/// without following symlinks.
+/// A previous capture at `dest` is replaced.
+pub fn capture(root: &Path, dest: &Path) -> io::Result<()> {
+    if dest.exists() {
+        fs::remove_dir_all(dest)?;
+    }
+    fs::create_dir_all(dest.join(TREE))?;
+    let mut manifest = File::create(dest.join(MANIFEST))?;
+
+    for entry in walkdir::WalkDir::new(root).min_depth(1) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                writeln!(manifest, "<{e}>")?;
+                continue;
+            }
+        };
+        let relative_path = entry.path().strip_prefix(root).unwrap();
+
+        let file_stat = match lstat(entry.path()) {
+            Ok(file_stat) => file_stat,
+            Err(e) => {
+                writeln!(manifest, "{}\t<{e}>", relative_path.display())?;
+                continue;
+            }
+        };
+
+        let mut line = describe(entry.path(), &file_stat);
+        if let Err(e) = copy_entry(
+            entry.path(),
+            &file_stat,
+            &dest.join(TREE).join(relative_path),
+        ) {
+            let _ = write!(line, " copy=<{e}>");
+        }
+        writeln!(manifest, "{}\t{line}", relative_path.display())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! Baseline of the behavior of a file system, to detect when it drifts between runs.
+//!
+//! A baseline records the enabled features, the status of each test
+//! and the errors chosen by the file system when the tests accept several.
+//! It is saved with `--save-baseline` and compared with `--compare-baseline`,
+//! as TOML, or as JSON if the name of the file ends with `.json`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    runner::{RunSummary, TestStatus},
+};
+
+/// Behavior of a file system over a run.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    /// Features enabled in the configuration.
+    #[serde(default)]
+    pub features: BTreeSet<String>,
+    /// Status of each test.
+    #[serde(default)]
+    pub tests: BTreeMap<String, TestStatus>,
+    /// Errors returned by the file system for each set of accepted errors, by test.
+    #[serde(default)]
+    pub errors: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Baseline {
+    /// Create the baseline of a run.
+    pub fn new(config: &Config, summary: &RunSummary) -> Self {
+        Baseline {
+            features: config
+                .features
+                .fs_features
+                .keys()
+                .map(|feature| feature.to_string())
+                .collect(),
+            tests: summary.statuses.clone(),
+            errors: summary.profile.by_test(),
+        }
+    }
+
+    /// Load a baseline from a TOML or JSON file.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("cannot read baseline {}", path.display()))?;
+
+        let baseline = if is_json(path) {
+            serde_json::from_str(&content).map_err(anyhow::Error::from)
+        } else {
+            Self::parse(&content)
+        };
+        baseline.with_context(|| format!("invalid baseline {}", path.display()))
+    }
+
+    /// Parse a baseline from its TOML representation.
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+
+    /// Save the baseline to a TOML or JSON file.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = if is_json(path) {
+            serde_json::to_string_pretty(self)?
+        } else {
+            toml::to_string(self)?
+        };
+        fs::write(path, content)
+            .with_context(|| format!("cannot write baseline {}", path.display()))
+    }
+
+    /// Describe how the behavior differs from a previous baseline.
+    /// Only the tests run in both are compared, and their errors only if they passed in both.
+    pub fn diff(&self, previous: &Baseline) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        for feature in self.features.difference(&previous.features) {
+            differences.push(format!("feature {feature} has been enabled"));
+        }
+        for feature in previous.features.difference(&self.features) {
+            differences.push(format!("feature {feature} is no longer enabled"));
+        }
+
+        let no_errors = BTreeMap::new();
+        for (name, status) in &self.tests {
+            let Some(previous_status) = previous.tests.get(name) else {
+                continue;
+            };
+            if status != previous_status {
+                differences.push(format!(
+                    "{name}: {}, was {}",
+                    status_name(*status),
+                    status_name(*previous_status)
+                ));
+                continue;
+            }
+            if *status != TestStatus::Passed {
+                continue;
+            }
+
+            let errors = self.errors.get(name).unwrap_or(&no_errors);
+            let previous_errors = previous.errors.get(name).unwrap_or(&no_errors);
+            let accepted: BTreeSet<_> = errors.keys().chain(previous_errors.keys()).collect();
+            for accepted in accepted {
+                let returned = errors.get(accepted);
+                let previous_returned = previous_errors.get(accepted);
+                if returned != previous_returned {
+                    differences.push(format!(
+                        "{name}: {accepted} returned {}, was {}",
+                        errors_names(returned),
+                        errors_names(previous_returned)
+                    ));
+                }
+            }
+        }
+
+        differences
+    }
+}
+
+/// Return whether the baseline is saved as JSON.
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Return the name of a status as written in the baseline.
+pub(crate) fn status_name(status: TestStatus) -> &'static str {
+    match status {
+        TestStatus::Passed => "passed",
+        TestStatus::Failed => "failed",
+        TestStatus::Skipped => "skipped",
+        TestStatus::ExpectedFailure => "expected_failure",
+        TestStatus::UnexpectedPass => "unexpected_pass",
+    }
+}
+
+/// Return the list of the returned errors, if any.
+pub(crate) fn errors_names(errors: Option<&BTreeSet<String>>) -> String {
+    match errors {
+        Some(errors) if !errors.is_empty() => errors.iter().cloned().collect::<Vec<_>>().join(", "),
+        _ => String::from("nothing"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use crate::runner::TestStatus;
+
+    use super::Baseline;
+
+    fn baseline() -> Baseline {
+        Baseline {
+            features: BTreeSet::from([String::from("chflags")]),
+            tests: BTreeMap::from([
+                (String::from("rmdir::enotempty"), TestStatus::Passed),
+                (String::from("chflags::set"), TestStatus::Skipped),
+            ]),
+            errors: BTreeMap::from([(
+                String::from("rmdir::enotempty"),
+                BTreeMap::from([(
+                    String::from("EEXIST or ENOTEMPTY"),
+                    BTreeSet::from([String::from("ENOTEMPTY")]),
+                )]),
+            )]),
+        }
+    }
+
+    #[test]
+    fn serialization_round_trip() {
+        let baseline = baseline();
+        let serialized = toml::to_string(&baseline).unwrap();
+
+        assert_eq!(toml::from_str::<Baseline>(&serialized).unwrap(), baseline);
+    }
+
+    #[test]
+    fn json_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baseline.json");
+        baseline().save(&path).unwrap();
+
+        assert!(serde_json::from_str::<serde_json::Value>(
+            &std::fs::read_to_string(&path).unwrap()
+        )
+        .is_ok());
+        assert_eq!(Baseline::load(&path).unwrap(), baseline());
+    }
+
+    #[test]
+    fn no_differences() {
+        assert!(baseline().diff(&baseline()).is_empty());
+    }
+
+    #[test]
+    fn differences() {
+        let previous = baseline();
+        let mut current = baseline();
+        current.features.clear();
+        current
+            .tests
+            .insert(String::from("chflags::set"), TestStatus::Failed);
+        current
+            .tests
+            .insert(String::from("chmod::new"), TestStatus::Passed);
+        current.errors.get_mut("rmdir::enotempty").unwrap().insert(
+            String::from("EEXIST or ENOTEMPTY"),
+            BTreeSet::from([String::from("EEXIST")]),
+        );
+
+        assert_eq!(
+            current.diff(&previous),
+            [
+                "feature chflags is no longer enabled",
+                "chflags::set: failed, was skipped",
+                "rmdir::enotempty: EEXIST or ENOTEMPTY returned EEXIST, was ENOTEMPTY",
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Benchmark of the metadata operations of a file system, for `pjdfstest bench`.
+//!
+//! Each operation is run on a set of entries of a temporary directory of the file system,
+//! and its throughput and latency percentiles are reported,
+//! as text or as JSON to be compared with a later run.
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use gumdrop::Options;
+use nix::{fcntl::OFlag, sys::stat::Mode};
+use serde::{Deserialize, Serialize};
+use tempfile::tempdir_in;
+
+use crate::{
+    config::Config,
+    context::TestContext,
+    utils::{mkdir, open, rename, rmdir, stat, unlink},
+};
+
+/// Number of entries each operation is run on, by default.
+const DEFAULT_COUNT: usize = 1000;
+
+/// Regression of the throughput (in percent) tolerated by `--compare`, by default.
+const DEFAULT_TOLERANCE: f64 = 20.0;
+
+/// Options of the `bench` subcommand.
+#[derive(Debug, Options)]
+pub struct BenchOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(help = "Path of the configuration file")]
+    configuration_file: Option<PathBuf>,
+
+    #[options(help = "Path of a directory on the file system to benchmark")]
+    path: Option<PathBuf>,
+
+    #[options(
+        short = "n",
+        help = "Number of entries each operation is run on (1000 by default)"
+    )]
+    count: Option<usize>,
+
+    #[options(no_short, help = "Print the results as JSON")]
+    json: bool,
+
+    #[options(
+        no_short,
+        help = "Compare the throughput with the JSON results of a previous run"
+    )]
+    compare: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Regression of the throughput tolerated by --compare, in percent (20 by default)"
+    )]
+    tolerance: Option<f64>,
+}
+
+/// Results of an operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationResult {
+    pub name: String,
+    /// Number of operations run.
+    pub count: usize,
+    /// Operations per second.
+    pub throughput: f64,
+    /// Latency percentiles, in microseconds.
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl OperationResult {
+    /// Compute the results of an operation from the latency of each of its runs
+    /// and the total duration.
+    fn new(name: &str, mut latencies: Vec<Duration>, elapsed: Duration) -> Self {
+        latencies.sort();
+        let percentile = |p: usize| {
+            let index = (latencies.len() * p / 100).min(latencies.len().saturating_sub(1));
+            latencies.get(index).map_or(0.0, micros)
+        };
+
+        OperationResult {
+            name: name.to_owned(),
+            count: latencies.len(),
+            throughput: latencies.len() as f64 / elapsed.as_secs_f64(),
+            p50_us: percentile(50),
+            p90_us: percentile(90),
+            p99_us: percentile(99),
+            max_us: latencies.last().map_or(0.0, micros),
+        }
+    }
+}
+
+fn micros(duration: &Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
+
+/// Results of a benchmark.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResults {
+    pub operations: Vec<OperationResult>,
+}
+
+impl fmt::Display for BenchResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for op in &self.operations {
+            writeln!(
+                f,
+                "{:8} {:>10.0} ops/s    p50 {:>8.1}µs    p90 {:>8.1}µs    p99 {:>8.1}µs    max {:>8.1}µs",
+                op.name, op.throughput, op.p50_us, op.p90_us, op.p99_us, op.max_us
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl BenchResults {
+    /// Compare the throughput with the results of a previous run,
+    /// and return the change of each operation in percent.
+    pub fn compare<'a>(&'a self, previous: &BenchResults) -> Vec<(&'a str, f64)> {
+        self.operations
+            .iter()
+            .filter_map(|op| {
+                let previous = previous.operations.iter().find(|p| p.name == op.name)?;
+                let change = (op.throughput / previous.throughput - 1.0) * 100.0;
+                Some((op.name.as_str(), change))
+            })
+            .collect()
+    }
+}
+
+/// Run the operation on each entry, measuring the latency of each run.
+fn measure<T>(
+    results: &mut BenchResults,
+    name: &str,
+    entries: &[T],
+    mut operation: impl FnMut(&T) -> nix::Result<()>,
+) -> anyhow::Result<()> {
+    let mut latencies = Vec::with_capacity(entries.len());
+    let start = Instant::now();
+    for entry in entries {
+        let op_start = Instant::now();
+        operation(entry).map_err(|e| anyhow::anyhow!("{name} failed: {e}"))?;
+        latencies.push(op_start.elapsed());
+    }
+    results
+        .operations
+        .push(OperationResult::new(name, latencies, start.elapsed()));
+
+    Ok(())
+}
+
+/// Benchmark the metadata operations on `count` entries of the context.
+pub fn bench(ctx: &TestContext, count: usize) -> anyhow::Result<BenchResults> {
+    let mut results = BenchResults::default();
+
+    let files: Vec<_> = (0..count).map(|_| ctx.gen_path()).collect();
+    measure(&mut results, "create", &files, |path| {
+        open(
+            path,
+            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+            Mode::from_bits_truncate(0o644),
+        )
+        .map(drop)
+    })?;
+    measure(&mut results, "stat", &files, |path| stat(path).map(drop))?;
+
+    let renamed: Vec<_> = files.iter().map(|path| (path, ctx.gen_path())).collect();
+    measure(&mut results, "rename", &renamed, |(from, to)| {
+        rename(from.as_path(), to.as_path())
+    })?;
+    measure(&mut results, "unlink", &renamed, |(_, path)| unlink(path))?;
+
+    let dirs: Vec<_> = (0..count).map(|_| ctx.gen_path()).collect();
+    measure(&mut results, "mkdir", &dirs, |path| {
+        mkdir(path, Mode::from_bits_truncate(0o755))
+    })?;
+    measure(&mut results, "rmdir", &dirs, rmdir)?;
+
+    Ok(results)
+}
+
+/// Run the `bench` subcommand with its arguments.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let options = BenchOptions::parse_args_default(args)?;
+    if options.help {
+        println!(
+            "Usage: pjdfstest bench [OPTIONS]\n\n{}",
+            BenchOptions::usage()
+        );
+        return Ok(());
+    }
+
+    let config = Config::load(options.configuration_file.as_deref())?;
+    let path = match options.path {
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    let previous = options.compare.as_deref().map(load).transpose()?;
+
+    let dir = tempdir_in(&path)?;
+    let ctx = TestContext::new(&config, &config.dummy_auth.entries, dir.path());
+    let results = bench(&ctx, options.count.unwrap_or(DEFAULT_COUNT))?;
+
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        print!("{results}");
+    }
+
+    if let Some(previous) = previous {
+        let tolerance = options.tolerance.unwrap_or(DEFAULT_TOLERANCE);
+        let mut regressions = vec![];
+        // The comparison doesn't mix with the JSON output
+        let report = !options.json;
+        if report {
+            println!("\nChange of the throughput:");
+        }
+        for (name, change) in results.compare(&previous) {
+            if report {
+                println!("\t{name}: {change:+.1}%");
+            }
+            if change < -tolerance {
+                regressions.push(name);
+            }
+        }
+        if !regressions.is_empty() {
+            anyhow::bail!(
+                "the throughput regressed by more than {tolerance}% for: {}",
+                regressions.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the JSON results of a previous run.
+fn load(path: &Path) -> anyhow::Result<BenchResults> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("invalid results in {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{BenchResults, OperationResult};
+
+    #[test]
+    fn percentiles() {
+        let latencies = (1..=100).map(Duration::from_micros).collect();
+        let op = OperationResult::new("stat", latencies, Duration::from_millis(10));
+
+        assert_eq!(op.count, 100);
+        assert_eq!(op.throughput, 10000.0);
+        assert_eq!(op.p50_us, 51.0);
+        assert_eq!(op.p99_us, 100.0);
+        assert_eq!(op.max_us, 100.0);
+    }
+
+    #[test]
+    fn comparison() {
+        let results = |throughput| BenchResults {
+            operations: vec![OperationResult::new(
+                "create",
+                vec![Duration::from_micros(1); throughput],
+                Duration::from_secs(1),
+            )],
+        };
+
+        assert_eq!(results(150).compare(&results(100)), vec![("create", 50.0)]);
+        assert_eq!(results(50).compare(&results(100)), vec![("create", -50.0)]);
+    }
+}
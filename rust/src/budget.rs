@@ -0,0 +1,157 @@
+//! Budget of the entries created by each test, enabled with the `[settings.budget]` section
+//! of the configuration, to abort the runaway tests (e.g. a loop building ever longer paths)
+//! before they fill the file system under test.
+//!
+//! The wrappers of [`crate::utils`] and the [`FileBuilder`](crate::context::FileBuilder)
+//! report each entry they create. The test fails as soon as it has created more entries
+//! than allowed, or when its directory uses more space than allowed.
+//! The space is measured periodically while the entries are created, and after the test.
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::config::BudgetConfig;
+
+/// Number of created entries after which the space used is measured for the first time.
+/// It is measured again each time this number doubles, so that the cost stays linear.
+const FIRST_SIZE_CHECK: usize = 256;
+
+/// Budget of the running test.
+#[derive(Debug)]
+struct Budget {
+    limits: BudgetConfig,
+    /// Directory of the test.
+    dir: PathBuf,
+    /// Number of entries created by the test.
+    entries: usize,
+    /// Number of created entries after which the space used is measured next.
+    next_size_check: usize,
+}
+
+static BUDGET: Mutex<Option<Budget>> = Mutex::new(None);
+
+/// Start the budget of a test running in `dir`, if there are limits.
+pub fn start(limits: Option<&BudgetConfig>, dir: &Path) {
+    *BUDGET.lock().unwrap() = limits.map(|limits| Budget {
+        limits: limits.clone(),
+        dir: dir.to_path_buf(),
+        entries: 0,
+        next_size_check: FIRST_SIZE_CHECK,
+    });
+}
+
+/// End the budget of the test, returning why the test exceeded it, if it did.
+pub fn finish() -> Option<String> {
+    let budget = BUDGET.lock().unwrap().take()?;
+    check_size(&budget).err()
+}
+
+/// Record the creation of an entry by the test.
+///
+/// # Panics
+///
+/// Panics when the test exceeds its budget, to fail it.
+pub fn created(path: &Path) {
+    let result = {
+        let mut guard = BUDGET.lock().unwrap();
+        let Some(budget) = guard.as_mut() else {
+            return;
+        };
+        budget.entries += 1;
+        if budget.entries > budget.limits.max_entries {
+            Err(format!(
+                "the test has created more than {} entries (the last one is {}), it may be stuck in a loop",
+                budget.limits.max_entries,
+                path.display()
+            ))
+        } else if budget.entries >= budget.next_size_check {
+            budget.next_size_check *= 2;
+            check_size(budget)
+        } else {
+            Ok(())
+        }
+    };
+
+    // The lock is released so that it is not poisoned
+    if let Err(message) = result {
+        // Only the first excess is reported, the test is unwinding afterwards
+        BUDGET.lock().unwrap().take();
+        panic!("{message}");
+    }
+}
+
+/// Check that the directory of the test doesn't use more space than allowed.
+fn check_size(budget: &Budget) -> Result<(), String> {
+    let size = disk_usage(&budget.dir);
+    if size > budget.limits.max_size {
+        Err(format!(
+            "the directory of the test uses {size} bytes, more than the {} allowed ({} entries created)",
+            budget.limits.max_size, budget.entries
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Return the space allocated to the entries of a directory, including itself.
+/// The entries which cannot be accessed are ignored.
+fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    let mut size = metadata.blocks() * 512;
+    if metadata.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            size += entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_usage(&entry.path()))
+                .sum::<u64>();
+        }
+    }
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, panic::catch_unwind, path::Path};
+
+    use crate::config::BudgetConfig;
+
+    use super::{created, finish, start};
+
+    // The budget is global, a single test exercises it
+    #[test]
+    fn budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = BudgetConfig {
+            max_entries: 3,
+            max_size: 1 << 20,
+        };
+
+        start(Some(&limits), dir.path());
+        for _ in 0..3 {
+            created(Path::new("file"));
+        }
+        let message = catch_unwind(|| created(Path::new("last"))).unwrap_err();
+        assert!(message
+            .downcast_ref::<String>()
+            .unwrap()
+            .contains("more than 3 entries (the last one is last)"));
+        assert_eq!(finish(), None);
+
+        start(Some(&limits), dir.path());
+        fs::write(dir.path().join("file"), vec![1; 2 << 20]).unwrap();
+        assert!(finish().unwrap().contains("more than the 1048576 allowed"));
+
+        // Without limits, nothing is checked
+        start(None, dir.path());
+        for _ in 0..4 {
+            created(Path::new("file"));
+        }
+        assert_eq!(finish(), None);
+    }
+}
@@ -0,0 +1,685 @@
+//! Command-line runner of the test suite. It is responsible for parsing
+//! command line arguments, reading the configuration file, and running the tests.
+//! At the end of the test suite, the number of failed, skipped, and passed tests is printed.
+//!
+//! It is the `pjdfstest` binary, and can be the binary of other crates
+//! which register their own test cases (see [`register_test_case`](crate::register_test_case)).
+
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use gumdrop::Options;
+use strum::{EnumMessage, IntoEnumIterator};
+
+use tempfile::tempdir_in;
+
+use crate::{
+    baseline::Baseline,
+    bench,
+    compare::Comparison,
+    config::Config,
+    conformance::ConformanceProfile,
+    context::Cleanup,
+    events::ReportSocket,
+    fuzz, measure_naptime, merge, probe,
+    remote::{RemoteRun, RemoteTarget},
+    repeat::{parse_duration, FlakeStats},
+    report::HtmlReport,
+    resume::ResumeState,
+    rng,
+    runner::{
+        handle_interruption, interrupted, run_test_cases, shuffle_test_cases, test_group,
+        RunOptions, RunSummary, TestStatus,
+    },
+    sandbox, setup,
+    syscall_trace::{self, SyscallTracer},
+    test::{FileSystemFeature, TestCase},
+    test_cases, Filter,
+};
+
+#[derive(Debug, Options)]
+struct ArgOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(help = "Path of the configuration file")]
+    configuration_file: Option<PathBuf>,
+
+    #[options(help = "List opt-in features")]
+    list_features: bool,
+
+    #[options(help = "Match names exactly")]
+    exact: bool,
+
+    #[options(help = "Verbose mode")]
+    verbose: bool,
+
+    #[options(
+        help = "Path where the test suite will be executed, repeated to compare several file systems"
+    )]
+    path: Vec<PathBuf>,
+
+    #[options(free, help = "Filter test names")]
+    test_patterns: Vec<String>,
+
+    #[options(help = "Path to a secondary file system")]
+    secondary_fs: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Remount the file system between test groups to check metadata durability"
+    )]
+    remount_between_tests: bool,
+
+    #[options(
+        no_short,
+        help = "Run each test in a forked child process, isolated from the others"
+    )]
+    isolate: bool,
+
+    #[options(
+        no_short,
+        help = "Record the file system operations of the tests, and report those of the failed tests"
+    )]
+    trace: bool,
+
+    #[options(
+        no_short,
+        help = "Run the failed tests again under a syscall tracer (strace, truss...), saving the trace with their artifacts"
+    )]
+    syscall_trace: bool,
+
+    #[options(
+        no_short,
+        help = "Measure the latency of the file system operations, and print a histogram for each syscall"
+    )]
+    latency: bool,
+
+    #[options(
+        no_short,
+        help = "Run the large tests, which create tens of thousands of entries"
+    )]
+    large_tests: bool,
+
+    #[options(
+        no_short,
+        help = "Run the tests interrupting blocking operations with a signal"
+    )]
+    interrupt_tests: bool,
+
+    #[options(
+        no_short,
+        help = "Seed of the random generator, to reproduce the names and data of a previous run"
+    )]
+    seed: Option<u64>,
+
+    #[options(
+        no_short,
+        help = "Run the tests in a random order, from the seed of the run unless --shuffle-seed is given"
+    )]
+    shuffle: bool,
+
+    #[options(
+        no_short,
+        help = "Seed of the random order of the tests, to reproduce the order of a previous run (implies --shuffle)"
+    )]
+    shuffle_seed: Option<u64>,
+
+    #[options(
+        no_short,
+        help = "Directory where the directories of the failed tests are captured"
+    )]
+    artifacts_dir: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Keep the directories of the failed tests, with their permissions and flags"
+    )]
+    keep_failed: bool,
+
+    #[options(
+        no_short,
+        help = "Keep the directories of all the tests, with their permissions and flags"
+    )]
+    no_cleanup: bool,
+
+    #[options(
+        no_short,
+        help = "Run the tests N times, with a different seed for each run"
+    )]
+    repeat: Option<usize>,
+
+    #[options(
+        no_short,
+        help = "Run the tests again with a different seed until the duration (e.g. 90s, 30m, 1h) has elapsed",
+        parse(try_from_str = "parse_duration")
+    )]
+    duration: Option<Duration>,
+
+    #[options(
+        no_short,
+        help = "Save the status of the tests to a state file as they are run, and do not run again those which passed in a previous run with the same file"
+    )]
+    resume: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Save the behavior of the file system (features, test statuses and chosen errors) to a file"
+    )]
+    save_baseline: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Fail if the behavior of the file system differs from a saved baseline"
+    )]
+    compare_baseline: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Write an HTML report of the run, with the details of each test, to a file"
+    )]
+    html_report: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Stream the events of the run (tests started, finished, skipped) as JSON lines to a UNIX socket"
+    )]
+    report_socket: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Run the test suite on a remote machine over SSH, given as [user@]host:/path"
+    )]
+    remote: Option<RemoteTarget>,
+
+    #[options(
+        no_short,
+        help = "Binary copied to the remote machine, built for its platform (the runner itself by default)"
+    )]
+    remote_binary: Option<PathBuf>,
+}
+
+impl ArgOptions {
+    /// Return the arguments given to the runner on a remote machine.
+    /// The configuration file, the path and the baselines are handled by [`RemoteRun`] and locally.
+    fn remote_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let flags = [
+            (self.exact, "--exact"),
+            (self.verbose, "--verbose"),
+            (self.remount_between_tests, "--remount-between-tests"),
+            (self.isolate, "--isolate"),
+            (self.trace, "--trace"),
+            (self.syscall_trace, "--syscall-trace"),
+            (self.latency, "--latency"),
+            (self.large_tests, "--large-tests"),
+            (self.interrupt_tests, "--interrupt-tests"),
+            (self.shuffle, "--shuffle"),
+            (self.keep_failed, "--keep-failed"),
+            (self.no_cleanup, "--no-cleanup"),
+        ];
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            args.push(flag.to_string());
+        }
+
+        if let Some(secondary_fs) = &self.secondary_fs {
+            args.extend([
+                "--secondary-fs".to_owned(),
+                secondary_fs.display().to_string(),
+            ]);
+        }
+        if let Some(seed) = self.seed {
+            args.extend(["--seed".to_owned(), seed.to_string()]);
+        }
+        if let Some(shuffle_seed) = self.shuffle_seed {
+            args.extend(["--shuffle-seed".to_owned(), shuffle_seed.to_string()]);
+        }
+        if let Some(artifacts_dir) = &self.artifacts_dir {
+            args.extend([
+                "--artifacts-dir".to_owned(),
+                artifacts_dir.display().to_string(),
+            ]);
+        }
+        if let Some(repeat) = self.repeat {
+            args.extend(["--repeat".to_owned(), repeat.to_string()]);
+        }
+        if let Some(duration) = self.duration {
+            args.extend(["--duration".to_owned(), format!("{}s", duration.as_secs())]);
+        }
+
+        args.push("--".to_owned());
+        args.extend(self.test_patterns.iter().cloned());
+
+        args
+    }
+}
+
+/// Run the command-line runner, with the arguments of the process.
+pub fn main() -> anyhow::Result<()> {
+    let raw_args: Vec<String> = std::env::args().skip(1).collect();
+    match raw_args.first().map(String::as_str) {
+        Some("probe") => return probe::run(&raw_args[1..]),
+        Some("bench") => return bench::run(&raw_args[1..]),
+        Some("fuzz") => return fuzz::run(&raw_args[1..]),
+        Some("merge") => return merge::run(&raw_args[1..]),
+        _ => {}
+    }
+
+    let args = ArgOptions::parse_args_default_or_exit();
+
+    if args.list_features {
+        for feature in FileSystemFeature::iter() {
+            println!("{feature}: {}", feature.get_documentation().unwrap());
+        }
+        return Ok(());
+    }
+
+    if let Some(target) = &args.remote {
+        return run_remote(target, &args);
+    }
+
+    let mut config = Config::load(args.configuration_file.as_deref())?;
+    config.features.secondary_fs = args.secondary_fs.clone();
+    config.settings.large_tests |= args.large_tests;
+    config.settings.interrupt_tests |= args.interrupt_tests;
+
+    let remount = if args.remount_between_tests {
+        let remount = config.settings.remount.clone().ok_or_else(|| {
+            anyhow::anyhow!("remount commands have to be configured to remount between tests")
+        })?;
+        Some(remount)
+    } else {
+        None
+    };
+
+    let previous_baseline = args
+        .compare_baseline
+        .as_deref()
+        .map(Baseline::load)
+        .transpose()?;
+
+    let targets = if !args.path.is_empty() {
+        args.path.clone()
+    } else if !config.settings.targets.is_empty() {
+        config.settings.targets.clone()
+    } else {
+        vec![current_dir()?]
+    };
+    if targets.len() > 1 {
+        if remount.is_some() {
+            anyhow::bail!("the file system cannot be remounted between tests with several targets");
+        }
+        if args.save_baseline.is_some() {
+            anyhow::bail!("a single baseline cannot be saved for several targets");
+        }
+        if args.html_report.is_some() {
+            anyhow::bail!("a single report cannot be written for several targets");
+        }
+        if args.resume.is_some() {
+            anyhow::bail!("a run cannot be resumed with several targets");
+        }
+    }
+    if args.resume.is_some() && (args.repeat.is_some() || args.duration.is_some()) {
+        anyhow::bail!("a run cannot be resumed with --repeat or --duration");
+    }
+
+    setup();
+    handle_interruption();
+
+    let filter = Filter {
+        patterns: args.test_patterns.clone(),
+        exact: args.exact,
+    };
+    let mut test_cases = test_cases(&config, &filter);
+
+    let resume = args
+        .resume
+        .as_deref()
+        .map(|path| {
+            let selection: Vec<_> = test_cases.iter().map(|tc| tc.name).collect();
+            ResumeState::open(path, &selection)
+        })
+        .transpose()?;
+
+    // A resumed run goes on with the seed of the first one
+    let seed = args
+        .seed
+        .or(resume.as_ref().and_then(ResumeState::seed))
+        .or(config.settings.seed)
+        .unwrap_or_else(rng::gen_seed);
+    println!("Using seed {seed}");
+    let profile = config.settings.conformance_profile;
+    if profile != ConformanceProfile::StrictPosix {
+        println!("Using the {profile} conformance profile");
+    }
+
+    if args.shuffle || args.shuffle_seed.is_some() {
+        let shuffle_seed = args.shuffle_seed.unwrap_or(seed);
+        shuffle_test_cases(&mut test_cases, shuffle_seed);
+        println!(
+            "Running the tests in a random order (reproduce with --shuffle-seed {shuffle_seed})"
+        );
+    }
+    // Tests of a group have to be run together to be checked after a remount
+    if remount.is_some() {
+        test_cases.sort_by_key(|tc| test_group(tc.name));
+    }
+    if let Some(resume) = &resume {
+        let passed = resume.passed_count();
+        if passed > 0 {
+            println!("Resuming the run, {passed} tests have already passed");
+        }
+        resume.start(seed)?;
+    }
+
+    let syscall_tracer = if args.syscall_trace {
+        if args.artifacts_dir.is_none() {
+            anyhow::bail!(
+                "the syscall traces are saved with the artifacts, which need --artifacts-dir"
+            );
+        }
+        let command = config
+            .settings
+            .syscall_tracer
+            .as_deref()
+            .or(syscall_trace::default_command())
+            .ok_or_else(|| {
+                anyhow::anyhow!("a syscall tracer has to be configured on this platform")
+            })?;
+        let mut tracer_args = vec![];
+        if let Some(configuration_file) = &args.configuration_file {
+            tracer_args.extend(["-c".to_owned(), configuration_file.display().to_string()]);
+        }
+        if let Some(secondary_fs) = &args.secondary_fs {
+            tracer_args.extend([
+                "--secondary-fs".to_owned(),
+                secondary_fs.display().to_string(),
+            ]);
+        }
+        if args.large_tests {
+            tracer_args.push("--large-tests".to_owned());
+        }
+        if args.interrupt_tests {
+            tracer_args.push("--interrupt-tests".to_owned());
+        }
+        Some(SyscallTracer {
+            command: command.to_owned(),
+            args: tracer_args,
+        })
+    } else {
+        None
+    };
+
+    let report_socket = args
+        .report_socket
+        .as_deref()
+        .map(ReportSocket::connect)
+        .transpose()?;
+
+    // The directories of the failed tests of each target are captured separately
+    let artifacts_dirs: Vec<_> = targets
+        .iter()
+        .map(|path| {
+            let dir = args.artifacts_dir.as_ref()?;
+            if targets.len() > 1 {
+                let name = path.to_string_lossy().trim_matches('/').replace('/', ".");
+                Some(dir.join(name))
+            } else {
+                Some(dir.clone())
+            }
+        })
+        .collect();
+
+    let mut options = RunOptions {
+        verbose: args.verbose,
+        isolate: args.isolate,
+        remount: remount.as_ref(),
+        seed,
+        artifacts_dir: None,
+        cleanup: if args.no_cleanup {
+            Cleanup::Never
+        } else if args.keep_failed {
+            Cleanup::KeepFailed
+        } else {
+            Cleanup::Always
+        },
+        trace: args.trace,
+        syscall_tracer: syscall_tracer.as_ref(),
+        latency: args.latency,
+        report_socket: report_socket.as_ref(),
+        resume: resume.as_ref(),
+    };
+    let configured_naptime = config.settings.naptime;
+    let mut comparison = Comparison::default();
+    let mut results = Vec::new();
+    for (path, artifacts_dir) in targets.iter().zip(&artifacts_dirs) {
+        if targets.len() > 1 {
+            println!("\nRunning the tests on {}", path.display());
+        }
+        config.settings.naptime = configured_naptime;
+        options.artifacts_dir = artifacts_dir.as_deref();
+        let result = run_target(
+            path,
+            &test_cases,
+            &mut config,
+            &args,
+            &mut options,
+            previous_baseline.as_ref(),
+        )?;
+        comparison.add(path.clone(), Baseline::new(&config, &result.summary));
+        results.push(result);
+        if interrupted() {
+            break;
+        }
+    }
+
+    if targets.len() > 1 {
+        print!("\n{comparison}");
+    }
+    println!("Seed: {seed} (reproduce with --seed {seed})");
+
+    let interrupted = results.iter().any(|result| result.summary.interrupted);
+    if let Some(path) = args.save_baseline.as_deref() {
+        // A partial baseline would report the tests which were not run as removed
+        if interrupted {
+            println!("Baseline not saved, the run has been interrupted");
+        } else {
+            Baseline::new(&config, &results[0].summary).save(path)?;
+            println!("Baseline saved to {}", path.display());
+        }
+    }
+    if let Some(path) = args.html_report.as_deref() {
+        HtmlReport {
+            path: &targets[0],
+            seed,
+            summary: &results[0].summary,
+            test_cases: &test_cases,
+        }
+        .save(path)?;
+        println!("Report written to {}", path.display());
+    }
+
+    if interrupted {
+        Err(anyhow::anyhow!("The run has been interrupted"))
+    } else if results.iter().any(|result| result.summary.failed > 0) {
+        Err(anyhow::anyhow!("Some tests have failed"))
+    } else if results
+        .iter()
+        .any(|result| result.summary.unexpected_passes > 0)
+    {
+        Err(anyhow::anyhow!("Some tests expected to fail have passed"))
+    } else if results.iter().any(|result| !result.differences.is_empty()) {
+        Err(anyhow::anyhow!("The behavior differs from the baseline"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Results of the tests on a target directory.
+struct TargetResult {
+    summary: RunSummary,
+    /// Differences with the baseline the behavior is compared to.
+    differences: Vec<String>,
+}
+
+/// Run the tests (repeatedly if requested) in a target directory, and print their results.
+fn run_target(
+    path: &Path,
+    test_cases: &[TestCase],
+    config: &mut Config,
+    args: &ArgOptions,
+    options: &mut RunOptions,
+    previous_baseline: Option<&Baseline>,
+) -> anyhow::Result<TargetResult> {
+    let base_dir = tempdir_in(path)?;
+    measure_naptime(config, base_dir.path())?;
+
+    if let Some(restrictions) = sandbox::restrictions(base_dir.path()) {
+        let denied = restrictions
+            .denied
+            .iter()
+            .map(|(syscall, denial)| format!("{syscall} ({denial})"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "File system operations are restricted by {}, tests for these syscalls will be skipped: {denied}",
+            restrictions.sandbox()
+        );
+    }
+
+    // Every target is run with the same seeds, so that their results can be compared
+    let seed = options.seed;
+    let start = Instant::now();
+    let run_again = |runs: usize| {
+        !interrupted()
+            && (args.repeat.is_some() || args.duration.is_some())
+            && args.repeat.is_none_or(|repeat| runs < repeat)
+            && args
+                .duration
+                .is_none_or(|duration| start.elapsed() < duration)
+    };
+    let mut flake_stats = FlakeStats::default();
+    let mut summary = run_test_cases(test_cases, config, base_dir, options)?;
+    flake_stats.add(&summary);
+    while run_again(flake_stats.runs()) {
+        // Each run uses fresh directories and a seed derived from the seed of the first one
+        options.seed = seed.wrapping_add(flake_stats.runs() as u64);
+        println!("\nRun {} (seed {})", flake_stats.runs() + 1, options.seed);
+        let run_summary = run_test_cases(test_cases, config, tempdir_in(path)?, options)?;
+        flake_stats.add(&run_summary);
+        summary.merge(run_summary);
+    }
+    options.seed = seed;
+
+    if flake_stats.flaky_tests().next().is_some() {
+        print!("\n{flake_stats}");
+    }
+    if !summary.profile.is_empty() {
+        print!("\n{}", summary.profile);
+    }
+    if !summary.latencies.is_empty() {
+        print!("\n{}", summary.latencies);
+    }
+
+    let differences = previous_baseline
+        .map(|previous| Baseline::new(config, &summary).diff(previous))
+        .unwrap_or_default();
+    if !differences.is_empty() {
+        println!("\nDifferences with the baseline:");
+        for difference in &differences {
+            println!("\t{difference}");
+        }
+    }
+
+    let mut counts = format!(
+        "{} failed, {} skipped, {} passed",
+        summary.failed, summary.skipped, summary.passed
+    );
+    if summary.expected_failures > 0 || summary.unexpected_passes > 0 {
+        counts += &format!(
+            ", {} expected failures, {} unexpected passes",
+            summary.expected_failures, summary.unexpected_passes
+        );
+    }
+    let interrupted = if summary.interrupted {
+        " (interrupted)"
+    } else {
+        ""
+    };
+    println!(
+        "\nTests: {counts}, {} total{interrupted}",
+        summary.failed
+            + summary.skipped
+            + summary.passed
+            + summary.expected_failures
+            + summary.unexpected_passes
+    );
+
+    Ok(TargetResult {
+        summary,
+        differences,
+    })
+}
+
+/// Run the test suite on a remote machine, and check its results locally.
+fn run_remote(target: &RemoteTarget, args: &ArgOptions) -> anyhow::Result<()> {
+    if !args.path.is_empty() {
+        anyhow::bail!("the path is part of the remote target, and cannot be given with --path");
+    }
+    if args.html_report.is_some() {
+        anyhow::bail!(
+            "the details of the tests needed by the report are not brought back from a remote run"
+        );
+    }
+    if args.report_socket.is_some() {
+        anyhow::bail!("the events of a remote run cannot be streamed to a local socket");
+    }
+    if args.resume.is_some() {
+        anyhow::bail!(
+            "a remote run cannot be resumed, its state would not outlive the remote directory"
+        );
+    }
+
+    let previous_baseline = args
+        .compare_baseline
+        .as_deref()
+        .map(Baseline::load)
+        .transpose()?;
+
+    let baseline = RemoteRun {
+        target,
+        binary: args.remote_binary.as_deref(),
+        configuration_file: args.configuration_file.as_deref(),
+        args: args.remote_args(),
+    }
+    .run()?;
+
+    let differences = previous_baseline
+        .map(|previous| baseline.diff(&previous))
+        .unwrap_or_default();
+    if !differences.is_empty() {
+        println!("\nDifferences with the baseline:");
+        for difference in &differences {
+            println!("\t{difference}");
+        }
+    }
+
+    if let Some(path) = args.save_baseline.as_deref() {
+        baseline.save(path)?;
+        println!("Baseline saved to {}", path.display());
+    }
+
+    let has_status = |status| baseline.tests.values().any(|s| *s == status);
+    if has_status(TestStatus::Failed) {
+        Err(anyhow::anyhow!("Some tests have failed"))
+    } else if has_status(TestStatus::UnexpectedPass) {
+        Err(anyhow::anyhow!("Some tests expected to fail have passed"))
+    } else if !differences.is_empty() {
+        Err(anyhow::anyhow!("The behavior differs from the baseline"))
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,248 @@
+//! Comparison of the behavior of the file systems tested in the same invocation,
+//! when several target directories are given.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::PathBuf,
+};
+
+use crate::{
+    baseline::{errors_names, status_name, Baseline},
+    runner::TestStatus,
+};
+
+/// Errors returned on each target for a set of accepted errors, when they differ.
+#[derive(Debug)]
+pub struct ErrorDifference<'a> {
+    pub test: &'a str,
+    /// Description of the accepted errors.
+    pub accepted: &'a str,
+    /// Errors returned on each target.
+    pub returned: Vec<Option<&'a BTreeSet<String>>>,
+}
+
+/// Behavior of each target over the run.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    targets: Vec<(PathBuf, Baseline)>,
+}
+
+impl Comparison {
+    /// Add the behavior of a target.
+    pub fn add(&mut self, path: PathBuf, baseline: Baseline) {
+        self.targets.push((path, baseline));
+    }
+
+    /// Return the baselines of the targets, in the order they were added.
+    pub fn baselines(&self) -> impl Iterator<Item = &Baseline> {
+        self.targets.iter().map(|(_, baseline)| baseline)
+    }
+
+    /// Return the tests run on any target, with the status on each target
+    /// (`None` if the test was not run on it).
+    pub fn statuses(&self) -> Vec<(&str, Vec<Option<TestStatus>>)> {
+        let names: BTreeSet<_> = self
+            .baselines()
+            .flat_map(|baseline| baseline.tests.keys())
+            .collect();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let statuses = self
+                    .baselines()
+                    .map(|baseline| baseline.tests.get(name).copied())
+                    .collect();
+                (name.as_str(), statuses)
+            })
+            .collect()
+    }
+
+    /// Return the tests whose status differs between the targets,
+    /// with the status on each target (`None` if the test was not run on it).
+    pub fn status_differences(&self) -> Vec<(&str, Vec<Option<TestStatus>>)> {
+        self.statuses()
+            .into_iter()
+            .filter(|(_, statuses)| differs(statuses))
+            .collect()
+    }
+
+    /// Return the errors which differ between the targets, for the tests which passed on all of them.
+    pub fn error_differences(&self) -> Vec<ErrorDifference<'_>> {
+        let Some((_, first)) = self.targets.first() else {
+            return vec![];
+        };
+
+        let mut differences = vec![];
+        for (name, status) in &first.tests {
+            let passed_everywhere = *status == TestStatus::Passed
+                && self
+                    .targets
+                    .iter()
+                    .all(|(_, baseline)| baseline.tests.get(name) == Some(&TestStatus::Passed));
+            if !passed_everywhere {
+                continue;
+            }
+
+            let errors: Vec<_> = self
+                .targets
+                .iter()
+                .map(|(_, baseline)| baseline.errors.get(name))
+                .collect();
+            let accepted: BTreeSet<_> = errors
+                .iter()
+                .flatten()
+                .flat_map(|errors| errors.keys())
+                .collect();
+            for accepted in accepted {
+                let returned: Vec<_> = errors
+                    .iter()
+                    .map(|errors| errors.and_then(|errors| errors.get(accepted)))
+                    .collect();
+                if returned.iter().any(|r| *r != returned[0]) {
+                    differences.push(ErrorDifference {
+                        test: name,
+                        accepted,
+                        returned,
+                    });
+                }
+            }
+        }
+
+        differences
+    }
+}
+
+/// Return whether the status of a test differs between the targets.
+pub fn differs(statuses: &[Option<TestStatus>]) -> bool {
+    statuses.iter().any(|status| *status != statuses[0])
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Comparison of {} targets:", self.targets.len())?;
+        for (path, baseline) in &self.targets {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for status in baseline.tests.values() {
+                *counts.entry(status_name(*status)).or_default() += 1;
+            }
+            let counts = counts
+                .iter()
+                .map(|(status, count)| format!("{count} {status}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "\t{}: {counts}", path.display())?;
+        }
+
+        let status_differences = self.status_differences();
+        if !status_differences.is_empty() {
+            writeln!(f, "\nTests whose status differs:")?;
+        }
+        for (name, statuses) in status_differences {
+            let statuses = self
+                .targets
+                .iter()
+                .zip(statuses)
+                .map(|((path, _), status)| {
+                    let status = status.map_or("not run", status_name);
+                    format!("{status} on {}", path.display())
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "\t{name}: {statuses}")?;
+        }
+
+        let error_differences = self.error_differences();
+        if !error_differences.is_empty() {
+            writeln!(f, "\nErrors which differ:")?;
+        }
+        for difference in error_differences {
+            let returned = self
+                .targets
+                .iter()
+                .zip(difference.returned)
+                .map(|((path, _), errors)| {
+                    format!("{} on {}", errors_names(errors), path.display())
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                f,
+                "\t{}: {} returned {returned}",
+                difference.test, difference.accepted
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        path::PathBuf,
+    };
+
+    use crate::{baseline::Baseline, runner::TestStatus};
+
+    use super::Comparison;
+
+    fn baseline(open_dir: TestStatus, enotempty: &str) -> Baseline {
+        Baseline {
+            features: BTreeSet::new(),
+            tests: BTreeMap::from([
+                (String::from("rmdir::open_dir"), open_dir),
+                (String::from("rmdir::enotempty"), TestStatus::Passed),
+            ]),
+            errors: BTreeMap::from([(
+                String::from("rmdir::enotempty"),
+                BTreeMap::from([(
+                    String::from("EEXIST or ENOTEMPTY"),
+                    BTreeSet::from([String::from(enotempty)]),
+                )]),
+            )]),
+        }
+    }
+
+    #[test]
+    fn same_behavior() {
+        let mut comparison = Comparison::default();
+        comparison.add(
+            PathBuf::from("/a"),
+            baseline(TestStatus::Passed, "ENOTEMPTY"),
+        );
+        comparison.add(
+            PathBuf::from("/b"),
+            baseline(TestStatus::Passed, "ENOTEMPTY"),
+        );
+
+        assert!(comparison.status_differences().is_empty());
+        assert!(comparison.error_differences().is_empty());
+    }
+
+    #[test]
+    fn different_behavior() {
+        let mut comparison = Comparison::default();
+        comparison.add(
+            PathBuf::from("/a"),
+            baseline(TestStatus::Passed, "ENOTEMPTY"),
+        );
+        comparison.add(PathBuf::from("/b"), baseline(TestStatus::Failed, "EEXIST"));
+
+        assert_eq!(
+            comparison.to_string(),
+            "Comparison of 2 targets:
+\t/a: 2 passed
+\t/b: 1 failed, 1 passed
+
+Tests whose status differs:
+\trmdir::open_dir: passed on /a, failed on /b
+
+Errors which differ:
+\trmdir::enotempty: EEXIST or ENOTEMPTY returned ENOTEMPTY on /a, EEXIST on /b
+"
+        );
+    }
+}
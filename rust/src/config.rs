@@ -7,19 +7,39 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use crate::conformance::ConformanceProfile;
 use crate::test::FileFlags;
 use crate::test::FileSystemFeature;
+use figment::{
+    providers::{Format, Serialized, Toml},
+    Figment,
+};
 use serde::{Deserialize, Serialize};
 
 mod auth;
 pub use auth::*;
+mod validate;
+pub use validate::*;
 
 /// Configuration for dummy authentication.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CommonFeatureConfig {}
 
+/// Policy followed by the file system to update access times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AtimePolicy {
+    /// Access time is updated on every access.
+    Strict,
+    /// Access time is updated only if it is older than the modification
+    /// or change time (or older than a day).
+    Relatime,
+    /// Access time is never updated on access.
+    Noatime,
+}
+
 /// Configuration for file-system specific features.
 /// Please see the book for more details.
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -27,10 +47,18 @@ pub struct FeaturesConfig {
     /// File flags available in the file system.
     #[serde(default)]
     pub file_flags: HashSet<FileFlags>,
+    /// File flags which are not supported by the file system,
+    /// and which cannot be set.
+    #[serde(default)]
+    pub unsupported_file_flags: HashSet<FileFlags>,
     /// Secondary file system to use for cross-file-system tests.
     // TODO: Move to another part of the configuration when refactoring
     #[serde(default)]
     pub secondary_fs: Option<PathBuf>,
+    /// Access time update policy of the file system,
+    /// usually determined by the mount options.
+    #[serde(default)]
+    pub atime_policy: Option<AtimePolicy>,
     /// File-system specific features which are enabled
     /// and do not require any additional configuration.
     #[serde(flatten)]
@@ -39,30 +67,169 @@ pub struct FeaturesConfig {
 
 /// Adjustable file-system specific settings.
 /// Please see the book for more details.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct SettingsConfig {
     /// Time to sleep within tests (in seconds)
     /// between modifications to the file system.
     /// It should be set to a value that is at least greater than
     /// the timestamp granularity of the file system under test.
-    #[serde(default = "default_naptime")]
-    pub naptime: f64,
+    /// If unset, it is derived from the granularity measured
+    /// on the file system under test before running the tests.
+    #[serde(default)]
+    pub naptime: Option<f64>,
     /// Allow remounting the file system with different settings during tests
     /// (required for example by the `erofs` tests).
     pub allow_remount: bool,
+    /// Commands to unmount and mount again the file system under test,
+    /// used with `--remount-between-tests`.
+    #[serde(default)]
+    pub remount: Option<RemountConfig>,
+    /// Shell command run before each test,
+    /// with the test name and the path of its directory as arguments.
+    #[serde(default)]
+    pub pre_test_hook: Option<String>,
+    /// Shell command run after each test,
+    /// with the test name and the path of its directory as arguments.
+    #[serde(default)]
+    pub post_test_hook: Option<String>,
+    /// Maximum duration of a hook (in seconds).
+    #[serde(default)]
+    pub hook_timeout: Option<f64>,
+    /// Seed of the random generator used for the names and the data of the tests.
+    /// If unset, a new seed is generated for each run.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Switch users with setuid/setgid in a forked child process
+    /// instead of only changing the effective IDs.
+    #[serde(default)]
+    pub real_ids: bool,
+    /// Enable the stress tests, which run concurrent operations on the file system.
+    #[serde(default)]
+    pub stress: Option<StressConfig>,
+    /// Enable the large tests, which create tens of thousands of entries.
+    /// Also enabled by `--large-tests`.
+    #[serde(default)]
+    pub large_tests: bool,
+    /// Enable the tests interrupting blocking operations with a signal.
+    /// Also enabled by `--interrupt-tests`.
+    #[serde(default)]
+    pub interrupt_tests: bool,
+    /// Enable the tests opening device nodes created on the file system.
+    #[serde(default)]
+    pub devices: Option<DevicesConfig>,
+    /// Directories where the test suite is executed, one after the other,
+    /// to compare the behavior of several file systems.
+    /// Overridden by `--path`.
+    #[serde(default)]
+    pub targets: Vec<PathBuf>,
+    /// Shell command running the command given as positional arguments under a syscall tracer,
+    /// writing the trace to `$PJDFSTEST_TRACE_FILE`, used with `--syscall-trace`.
+    /// If unset, strace or truss is used on the platforms which have them.
+    #[serde(default)]
+    pub syscall_tracer: Option<String>,
+    /// Limits of the entries created by each test, to abort the runaway tests.
+    #[serde(default)]
+    pub budget: Option<BudgetConfig>,
+    /// Space left free on the file system under test (in bytes):
+    /// the tests which would use it are skipped.
+    #[serde(default)]
+    pub reserved_space: Option<u64>,
+    /// Semantics the file system is expected to conform to,
+    /// which determines the errors and timestamp updates accepted by the tests.
+    #[serde(default)]
+    pub conformance_profile: ConformanceProfile,
 }
 
-impl Default for SettingsConfig {
-    fn default() -> Self {
-        SettingsConfig {
-            naptime: default_naptime(),
-            allow_remount: false,
-        }
+/// Settings of the stress tests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StressConfig {
+    /// Number of threads running operations concurrently.
+    #[serde(default = "StressConfig::default_threads")]
+    pub threads: usize,
+    /// Number of operations run by each thread.
+    #[serde(default = "StressConfig::default_iterations")]
+    pub iterations: usize,
+}
+
+impl StressConfig {
+    fn default_threads() -> usize {
+        4
+    }
+
+    fn default_iterations() -> usize {
+        1000
     }
 }
 
-const fn default_naptime() -> f64 {
-    1.0
+/// Limits of the entries created by each test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// Maximum number of entries created by a test.
+    #[serde(default = "BudgetConfig::default_max_entries")]
+    pub max_entries: usize,
+    /// Maximum space used by the directory of a test (in bytes).
+    #[serde(default = "BudgetConfig::default_max_size")]
+    pub max_size: u64,
+}
+
+impl BudgetConfig {
+    fn default_max_entries() -> usize {
+        // Above the entries of the large tests
+        100_000
+    }
+
+    fn default_max_size() -> u64 {
+        // Above the large files, even when they cannot be sparse
+        8 << 30
+    }
+}
+
+/// Settings of the tests opening device nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DevicesConfig {
+    /// Character device whose device number is given to the created nodes.
+    /// Reads from it should always return the same data (e.g. `/dev/null` or `/dev/zero`),
+    /// and writes to it should be accepted.
+    #[serde(default = "DevicesConfig::default_char_device")]
+    pub char_device: PathBuf,
+    /// Block device whose device number is given to the created nodes.
+    /// It is only read, and the block device tests are skipped if it is unset.
+    #[serde(default)]
+    pub block_device: Option<PathBuf>,
+    /// Major number which is not assigned to any driver.
+    #[serde(default = "DevicesConfig::default_unassigned_major")]
+    pub unassigned_major: u64,
+}
+
+impl DevicesConfig {
+    fn default_char_device() -> PathBuf {
+        PathBuf::from("/dev/null")
+    }
+
+    fn default_unassigned_major() -> u64 {
+        // Reserved for local and experimental use on Linux, never allocated dynamically
+        120
+    }
+}
+
+/// Shell commands used to remount the file system under test.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemountConfig {
+    /// Command to unmount the file system.
+    pub unmount: String,
+    /// Command to mount the file system again, at the same place.
+    pub mount: String,
+}
+
+/// Settings overriding the declaration of the tests matching a pattern.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TestOverride {
+    /// Do not run the tests.
+    #[serde(default)]
+    pub disabled: bool,
+    /// Features required by the tests, in addition to the declared ones.
+    #[serde(default)]
+    pub features: Vec<FileSystemFeature>,
 }
 
 /// Configuration for the test suite.
@@ -74,4 +241,44 @@ pub struct Config {
     pub settings: SettingsConfig,
     /// Dummy authentication configuration.
     pub dummy_auth: DummyAuthConfig,
+    /// Tests which are expected to fail, as patterns matched against their names,
+    /// with the reason (e.g. the URL of the issue).
+    #[serde(default)]
+    pub expected_failures: HashMap<String, String>,
+    /// Overrides of the declaration of the tests, by patterns matched against their names.
+    #[serde(default)]
+    pub overrides: HashMap<String, TestOverride>,
+}
+
+impl Config {
+    /// Load the configuration from a TOML file, after checking it,
+    /// or the default configuration if there is none.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut figment = Figment::from(Serialized::defaults(Config::default()));
+        if let Some(path) = path {
+            validate_file(path)?;
+            figment = figment.merge(Toml::file(path))
+        }
+
+        Ok(figment.extract()?)
+    }
+
+    /// Return the reason why a test is expected to fail, if it is.
+    pub fn expected_failure(&self, test_name: &str) -> Option<&str> {
+        self.expected_failures
+            .iter()
+            .find(|(pattern, _)| test_name.contains(pattern.as_str()))
+            .map(|(_, reason)| reason.as_str())
+    }
+
+    /// Return the overrides applying to a test.
+    pub fn overrides_for<'a>(
+        &'a self,
+        test_name: &'a str,
+    ) -> impl Iterator<Item = &'a TestOverride> {
+        self.overrides
+            .iter()
+            .filter(move |(pattern, _)| test_name.contains(pattern.as_str()))
+            .map(|(_, test_override)| test_override)
+    }
 }
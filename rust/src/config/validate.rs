@@ -0,0 +1,533 @@
+//! Validation of the configuration file.
+//!
+//! The file is checked before being extracted, so that every problem is reported at once
+//! with its location, instead of a typo silently disabling tests
+//! (e.g. a misspelled feature or setting) or only the first error being reported.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use nix::unistd::{Group, User};
+use strum::IntoEnumIterator;
+use toml_edit::{ImDocument, Item, TableLike};
+
+use super::AuthEntrySerdeError;
+use crate::test::{FileFlags, FileSystemFeature};
+
+const ROOT_KEYS: &[&str] = &[
+    "features",
+    "settings",
+    "dummy_auth",
+    "expected_failures",
+    "overrides",
+];
+const FEATURES_KEYS: &[&str] = &[
+    "file_flags",
+    "unsupported_file_flags",
+    "secondary_fs",
+    "atime_policy",
+];
+const SETTINGS_KEYS: &[&str] = &[
+    "naptime",
+    "allow_remount",
+    "remount",
+    "pre_test_hook",
+    "post_test_hook",
+    "hook_timeout",
+    "seed",
+    "real_ids",
+    "stress",
+    "large_tests",
+    "interrupt_tests",
+    "devices",
+    "targets",
+    "syscall_tracer",
+    "budget",
+    "reserved_space",
+    "conformance_profile",
+];
+const REMOUNT_KEYS: &[&str] = &["unmount", "mount"];
+const STRESS_KEYS: &[&str] = &["threads", "iterations"];
+const DEVICES_KEYS: &[&str] = &["char_device", "block_device", "unassigned_major"];
+const BUDGET_KEYS: &[&str] = &["max_entries", "max_size"];
+const DUMMY_AUTH_KEYS: &[&str] = &["entries"];
+const OVERRIDE_KEYS: &[&str] = &["disabled", "features"];
+const ATIME_POLICIES: &[&str] = &["strict", "relatime", "noatime"];
+const CONFORMANCE_PROFILES: &[&str] = &["strict-posix", "nfs", "smb", "fuse-default-permissions"];
+
+/// A problem found in the configuration file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigProblem {
+    /// Line and column (starting at 1) of the problem, if it is known.
+    pub location: Option<(usize, usize)>,
+    pub message: String,
+}
+
+/// Problems found in a configuration file.
+#[derive(Debug)]
+pub struct ConfigValidationError {
+    pub path: PathBuf,
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl Display for ConfigValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} problem(s) found in the configuration file:",
+            self.problems.len()
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  {}", self.path.display())?;
+            if let Some((line, column)) = problem.location {
+                write!(f, ":{line}:{column}")?;
+            }
+            write!(f, ": {}", problem.message)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ConfigValidationError {}
+
+/// Check the configuration file, and return all the problems found in it.
+pub fn validate_file(path: &Path) -> Result<(), ConfigValidationError> {
+    let problems = match std::fs::read_to_string(path) {
+        Ok(source) => validate_str(&source),
+        Err(e) => vec![ConfigProblem {
+            location: None,
+            message: format!("cannot read the file: {e}"),
+        }],
+    };
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(ConfigValidationError {
+            path: path.to_owned(),
+            problems,
+        })
+    }
+}
+
+/// Check the content of a configuration file, and return all the problems found in it.
+pub fn validate_str(source: &str) -> Vec<ConfigProblem> {
+    let mut validator = Validator {
+        source,
+        problems: vec![],
+    };
+
+    match ImDocument::parse(source) {
+        Ok(document) => validator.root(document.as_table()),
+        Err(e) => validator.report(e.span(), e.message().to_owned()),
+    }
+
+    // Report the problems in the order of the file
+    validator.problems.sort_by_key(|problem| problem.location);
+    validator.problems
+}
+
+struct Validator<'a> {
+    source: &'a str,
+    problems: Vec<ConfigProblem>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, span: Option<Range<usize>>, message: String) {
+        let location = span.map(|span| {
+            let before = &self.source[..span.start];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            (line, column)
+        });
+
+        self.problems.push(ConfigProblem { location, message });
+    }
+
+    /// Report the keys of the section which are not part of `known`,
+    /// and return the known ones with their values.
+    fn known_keys<'t>(
+        &mut self,
+        section: &str,
+        table: &'t dyn TableLike,
+        known: &[&str],
+    ) -> Vec<(&'t str, &'t Item)> {
+        let mut entries = vec![];
+        for (name, item) in table.iter() {
+            if known.contains(&name) {
+                entries.push((name, item));
+            } else {
+                let message = match section {
+                    "" => format!("unknown key '{name}' at the top level"),
+                    _ => format!("unknown key '{name}' in [{section}]"),
+                };
+                self.report_unknown(table, name, message, known.iter().copied());
+            }
+        }
+
+        entries
+    }
+
+    fn report_unknown<'k>(
+        &mut self,
+        table: &dyn TableLike,
+        name: &str,
+        mut message: String,
+        candidates: impl Iterator<Item = &'k str>,
+    ) {
+        if let Some(suggestion) = closest(name, candidates) {
+            message += &format!(", did you mean '{suggestion}'?");
+        }
+        self.report(key_span(table, name), message);
+    }
+
+    /// Return the item as a table, or report it.
+    fn table<'t>(&mut self, section: &str, item: &'t Item) -> Option<&'t dyn TableLike> {
+        let table = item.as_table_like();
+        if table.is_none() {
+            self.report(item.span(), format!("[{section}] should be a table"));
+        }
+
+        table
+    }
+
+    /// Return the strings of an array, or report it.
+    fn strings(&mut self, key: &str, item: &Item) -> Vec<(String, Option<Range<usize>>)> {
+        let Some(array) = item.as_array() else {
+            self.report(item.span(), format!("'{key}' should be an array"));
+            return vec![];
+        };
+
+        array
+            .iter()
+            .filter_map(|value| match value.as_str() {
+                Some(s) => Some((s.to_owned(), value.span())),
+                None => {
+                    self.report(value.span(), format!("'{key}' should only contain strings"));
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn root(&mut self, root: &dyn TableLike) {
+        for (name, item) in self.known_keys("", root, ROOT_KEYS) {
+            let Some(table) = self.table(name, item) else {
+                continue;
+            };
+            match name {
+                "features" => self.features(table),
+                "settings" => self.settings(table),
+                "dummy_auth" => self.dummy_auth(table),
+                "overrides" => self.overrides(table),
+                _ => (),
+            }
+        }
+    }
+
+    fn features(&mut self, features: &dyn TableLike) {
+        let known_features: Vec<_> = FileSystemFeature::iter().map(|f| f.to_string()).collect();
+
+        for (name, item) in features.iter() {
+            match name {
+                "file_flags" | "unsupported_file_flags" => {
+                    for (flag, span) in self.strings(name, item) {
+                        if !FileFlags::iter().any(|f| f.to_string() == flag) {
+                            self.report(
+                                span,
+                                format!("unknown file flag '{flag}' for this platform"),
+                            );
+                        }
+                    }
+                }
+                "atime_policy" => match item.as_str() {
+                    Some(policy) if ATIME_POLICIES.contains(&policy) => (),
+                    _ => self.report(
+                        item.span(),
+                        format!(
+                            "'atime_policy' should be one of {}",
+                            ATIME_POLICIES.join(", ")
+                        ),
+                    ),
+                },
+                "secondary_fs" => (),
+                _ if known_features.iter().any(|f| f == name) => {
+                    if !item.is_table_like() {
+                        self.report(
+                            item.span(),
+                            format!("feature '{name}' should be enabled with '{name} = {{}}'"),
+                        );
+                    }
+                }
+                _ => {
+                    let candidates = known_features
+                        .iter()
+                        .map(String::as_str)
+                        .chain(FEATURES_KEYS.iter().copied());
+                    let message = format!("unknown feature '{name}' in [features]");
+                    self.report_unknown(features, name, message, candidates);
+                }
+            }
+        }
+    }
+
+    fn settings(&mut self, settings: &dyn TableLike) {
+        for (name, item) in self.known_keys("settings", settings, SETTINGS_KEYS) {
+            let known = match name {
+                "conformance_profile" => {
+                    match item.as_str() {
+                        Some(profile) if CONFORMANCE_PROFILES.contains(&profile) => (),
+                        _ => self.report(
+                            item.span(),
+                            format!(
+                                "'conformance_profile' should be one of {}",
+                                CONFORMANCE_PROFILES.join(", ")
+                            ),
+                        ),
+                    }
+                    continue;
+                }
+                "remount" => REMOUNT_KEYS,
+                "stress" => STRESS_KEYS,
+                "devices" => DEVICES_KEYS,
+                "budget" => BUDGET_KEYS,
+                _ => continue,
+            };
+            let section = format!("settings.{name}");
+            if let Some(table) = self.table(&section, item) {
+                self.known_keys(&section, table, known);
+            }
+        }
+    }
+
+    fn dummy_auth(&mut self, dummy_auth: &dyn TableLike) {
+        let Some((_, entries)) = self
+            .known_keys("dummy_auth", dummy_auth, DUMMY_AUTH_KEYS)
+            .into_iter()
+            .next()
+        else {
+            return;
+        };
+
+        let Some(entries) = entries.as_array() else {
+            self.report(entries.span(), "'entries' should be an array".to_owned());
+            return;
+        };
+        if entries.len() != 3 {
+            self.report(
+                entries.span(),
+                format!("'entries' should have 3 entries, not {}", entries.len()),
+            );
+        }
+
+        for entry in entries {
+            let names: Option<Vec<_>> = entry
+                .as_array()
+                .filter(|entry| entry.len() == 2)
+                .and_then(|entry| entry.iter().map(|name| name.as_str()).collect());
+            let Some([user, group]) = names.as_deref() else {
+                self.report(
+                    entry.span(),
+                    "an entry should be a pair of user and group names".to_owned(),
+                );
+                continue;
+            };
+
+            if let Err(e) = check_auth_entry(user, group) {
+                self.report(entry.span(), e.to_string());
+            }
+        }
+    }
+
+    fn overrides(&mut self, overrides: &dyn TableLike) {
+        let known_features: Vec<_> = FileSystemFeature::iter().map(|f| f.to_string()).collect();
+
+        for (pattern, item) in overrides.iter() {
+            let section = format!("overrides.\"{pattern}\"");
+            let Some(table) = self.table(&section, item) else {
+                continue;
+            };
+
+            for (name, item) in self.known_keys(&section, table, OVERRIDE_KEYS) {
+                if name != "features" {
+                    continue;
+                }
+                for (feature, span) in self.strings(name, item) {
+                    if !known_features.contains(&feature) {
+                        let mut message = format!("unknown feature '{feature}'");
+                        if let Some(suggestion) =
+                            closest(&feature, known_features.iter().map(String::as_str))
+                        {
+                            message += &format!(", did you mean '{suggestion}'?");
+                        }
+                        self.report(span, message);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Check that the user and the group exist, and that the user is part of the group.
+fn check_auth_entry(user: &str, group: &str) -> Result<(), AuthEntrySerdeError> {
+    let Some(user) = User::from_name(user).ok().flatten() else {
+        return Err(AuthEntrySerdeError::UserNotFound(user.to_owned()));
+    };
+    let Some(group) = Group::from_name(group).ok().flatten() else {
+        return Err(AuthEntrySerdeError::GroupNotFound(group.to_owned()));
+    };
+    if user.gid != group.gid {
+        return Err(AuthEntrySerdeError::UserNotInGroup(user.name, group.name));
+    }
+
+    Ok(())
+}
+
+/// Return the span of a key, or of its value if the key has none.
+fn key_span(table: &dyn TableLike, name: &str) -> Option<Range<usize>> {
+    let (key, item) = table.get_key_value(name)?;
+    key.span().or_else(|| item.span())
+}
+
+/// Return the candidate closest to the name, if it is likely to be a typo of it.
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= 2)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut row: Vec<_> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(previous + 1).min(row[j] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, validate_str};
+
+    fn messages(source: &str) -> Vec<(Option<(usize, usize)>, String)> {
+        validate_str(source)
+            .into_iter()
+            .map(|problem| (problem.location, problem.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        let source = r#"
+[features]
+atime_policy = "relatime"
+utimensat = {}
+posix_fallocate = {}
+
+[settings]
+naptime = 0.01
+stress = { threads = 2 }
+conformance_profile = "nfs"
+
+[expected_failures]
+"rmdir::open_dir" = "https://example.com/issue"
+
+[overrides."chmod::"]
+features = ["nsec_timestamps"]
+"#;
+
+        assert_eq!(messages(source), vec![]);
+    }
+
+    #[test]
+    fn all_problems_reported() {
+        let source = r#"[features]
+utimesat = {}
+atime_policy = "lazy"
+
+[settings]
+naptme = 1
+conformance_profile = "posix"
+
+[settings.devices]
+block = "/dev/loop0"
+
+[overrides."rename::"]
+features = ["reflnk", "nothing_like_a_feature"]
+
+[unknown]
+"#;
+
+        assert_eq!(
+            messages(source),
+            vec![
+                (
+                    Some((2, 1)),
+                    "unknown feature 'utimesat' in [features], did you mean 'utimensat'?"
+                        .to_owned()
+                ),
+                (
+                    Some((3, 16)),
+                    "'atime_policy' should be one of strict, relatime, noatime".to_owned()
+                ),
+                (
+                    Some((6, 1)),
+                    "unknown key 'naptme' in [settings], did you mean 'naptime'?".to_owned()
+                ),
+                (
+                    Some((7, 23)),
+                    "'conformance_profile' should be one of strict-posix, nfs, smb, fuse-default-permissions".to_owned()
+                ),
+                (
+                    Some((10, 1)),
+                    "unknown key 'block' in [settings.devices]".to_owned()
+                ),
+                (
+                    Some((13, 13)),
+                    "unknown feature 'reflnk', did you mean 'reflink'?".to_owned()
+                ),
+                (
+                    Some((13, 23)),
+                    "unknown feature 'nothing_like_a_feature'".to_owned()
+                ),
+                (
+                    Some((15, 2)),
+                    "unknown key 'unknown' at the top level".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax_error() {
+        let problems = messages("[features\nutimensat = {}\n");
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].0.map(|(line, _)| line), Some(1));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("utimensat", "utimensat"), 0);
+        assert_eq!(edit_distance("utimesat", "utimensat"), 1);
+        assert_eq!(edit_distance("naptime", "nap_time"), 1);
+        assert_eq!(edit_distance("abc", "cba"), 2);
+        assert_eq!(edit_distance("", "seed"), 4);
+    }
+}
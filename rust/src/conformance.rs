@@ -0,0 +1,142 @@
+//! Conformance profiles, which relax the assertions of the tests for the file systems
+//! deviating from POSIX by design (network file systems, FUSE...), selected with
+//! `conformance_profile` in the `[settings]` section of the configuration.
+//!
+//! A profile is a table of the errors accepted in place of the expected ones,
+//! and of the timestamps whose updates are not guaranteed.
+//! The assertion helpers of the tests consult it, rather than the tests themselves.
+
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+
+/// Semantics the file system under test is expected to conform to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, strum::Display, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum ConformanceProfile {
+    /// POSIX semantics, without any tolerance.
+    #[default]
+    StrictPosix,
+    /// File system mounted by an NFS client.
+    Nfs,
+    /// File system mounted by an SMB client.
+    Smb,
+    /// FUSE file system mounted with `default_permissions`,
+    /// the kernel checking the permissions and caching the attributes.
+    FuseDefaultPermissions,
+}
+
+/// Timestamp of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    Atime,
+    Ctime,
+    Mtime,
+}
+
+/// Tolerances of a profile.
+#[derive(Debug)]
+struct Tolerances {
+    /// Errors accepted in addition to an expected one.
+    errors: &'static [(Errno, &'static [Errno])],
+    /// Timestamps whose updates are not guaranteed, and therefore not checked.
+    unreliable_timestamps: &'static [Timestamp],
+}
+
+const STRICT_POSIX: Tolerances = Tolerances {
+    errors: &[],
+    unreliable_timestamps: &[],
+};
+
+const NFS: Tolerances = Tolerances {
+    // Some clients report the permission errors of the server as EACCES
+    errors: &[(Errno::EPERM, &[Errno::EACCES])],
+    // The attributes are cached by the client, and the access times are often not updated
+    unreliable_timestamps: &[Timestamp::Atime],
+};
+
+const SMB: Tolerances = Tolerances {
+    // The protocol has a single "access denied" status, reported as EACCES
+    errors: &[(Errno::EPERM, &[Errno::EACCES])],
+    // The change time is not provided by every server
+    unreliable_timestamps: &[Timestamp::Atime, Timestamp::Ctime],
+};
+
+const FUSE_DEFAULT_PERMISSIONS: Tolerances = Tolerances {
+    // The permissions are checked by the kernel, with the usual errors
+    errors: &[],
+    // The attributes are cached by the kernel until they time out
+    unreliable_timestamps: &[Timestamp::Atime, Timestamp::Ctime],
+};
+
+impl ConformanceProfile {
+    fn tolerances(self) -> &'static Tolerances {
+        match self {
+            ConformanceProfile::StrictPosix => &STRICT_POSIX,
+            ConformanceProfile::Nfs => &NFS,
+            ConformanceProfile::Smb => &SMB,
+            ConformanceProfile::FuseDefaultPermissions => &FUSE_DEFAULT_PERMISSIONS,
+        }
+    }
+
+    /// Return the errors accepted when `expected` is expected, starting with it.
+    pub fn accepted_errors(self, expected: Errno) -> Vec<Errno> {
+        let mut accepted = vec![expected];
+        for (errno, alternatives) in self.tolerances().errors {
+            if *errno == expected {
+                accepted.extend(alternatives.iter().filter(|alt| **alt != expected));
+            }
+        }
+        accepted
+    }
+
+    /// Return whether the updates of the timestamp are guaranteed.
+    pub fn guarantees(self, timestamp: Timestamp) -> bool {
+        !self.tolerances().unreliable_timestamps.contains(&timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+    use serde::Deserialize;
+
+    use super::{ConformanceProfile, Timestamp};
+
+    #[test]
+    fn strict_posix() {
+        let profile = ConformanceProfile::default();
+        assert_eq!(profile, ConformanceProfile::StrictPosix);
+        assert_eq!(profile.accepted_errors(Errno::EPERM), [Errno::EPERM]);
+        assert!(profile.guarantees(Timestamp::Atime));
+    }
+
+    #[test]
+    fn tolerances() {
+        let nfs = ConformanceProfile::Nfs;
+        assert_eq!(
+            nfs.accepted_errors(Errno::EPERM),
+            [Errno::EPERM, Errno::EACCES]
+        );
+        assert_eq!(nfs.accepted_errors(Errno::EACCES), [Errno::EACCES]);
+        assert!(!nfs.guarantees(Timestamp::Atime));
+        assert!(nfs.guarantees(Timestamp::Ctime));
+
+        let fuse = ConformanceProfile::FuseDefaultPermissions;
+        assert_eq!(fuse.accepted_errors(Errno::EPERM), [Errno::EPERM]);
+        assert!(!fuse.guarantees(Timestamp::Ctime));
+        assert!(fuse.guarantees(Timestamp::Mtime));
+    }
+
+    #[test]
+    fn names() {
+        #[derive(Deserialize)]
+        struct Settings {
+            profile: ConformanceProfile,
+        }
+
+        let settings: Settings = toml::from_str(r#"profile = "fuse-default-permissions""#).unwrap();
+        assert_eq!(settings.profile, ConformanceProfile::FuseDefaultPermissions);
+        assert_eq!(ConformanceProfile::StrictPosix.to_string(), "strict-posix");
+    }
+}
@@ -4,32 +4,45 @@
 //! Its [`SerializedTestContext`] counterpart allows to execute functions as another user/group(s) and with another umask.
 
 use nix::{
+    errno::Errno,
     fcntl::OFlag,
     sys::{
         socket::{bind, socket, SockFlag, UnixAddr},
-        stat::{lstat, mknod, mode_t, umask, Mode, SFlag},
+        stat::{mode_t, umask, Mode, SFlag},
+        time::TimeSpec,
     },
     unistd::{
-        getgroups, mkdir, mkfifo, pathconf, setegid, seteuid, setgroups, Gid, Group, Uid, User,
+        getgroups, pathconf, setegid, seteuid, setgid, setgroups, setuid, Gid, Group, Uid, User,
     },
 };
 
 use rand::distributions::{Alphanumeric, DistString};
 use std::{
     cell::Cell,
-    fs::create_dir_all,
     ops::{Deref, DerefMut},
-    os::fd::{AsRawFd, OwnedFd},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
     panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
     path::{Path, PathBuf},
+    sync::Barrier,
     thread,
     time::Duration,
 };
 use strum_macros::EnumIter;
 
+mod fifo;
+pub use fifo::{PendingOpen, FIFO_TIMEOUT};
+mod tree;
+pub use tree::{Tree, TreeEntry};
+
 use crate::{
-    config::{Config, DummyAuthEntry, FeaturesConfig},
-    utils::{chmod, lchmod, open, symlink},
+    artifacts, budget,
+    config::{Config, DevicesConfig, DummyAuthEntry, FeaturesConfig, StressConfig},
+    conformance::ConformanceProfile,
+    isolation::{self, ChildError},
+    recorder,
+    rng::{self, with_rng},
+    snapshot::Snapshot,
+    utils::{chmod, lchmod, lchown, link, lstat, mkdir, mkfifo, mknod, open, symlink},
 };
 
 /// File type, mainly used with [TestContext::create] and parameterized tests.
@@ -42,6 +55,13 @@ pub enum FileType {
     Char,
     Socket,
     Symlink(Option<PathBuf>),
+    /// Hard link to the given file, or to a new regular file.
+    HardLink(Option<PathBuf>),
+    /// Chain of the given number of symbolic links (at least one), each one pointing to the next,
+    /// the last one being dangling.
+    SymlinkChain(usize),
+    /// Symbolic link to a new directory.
+    SymlinkToDir,
 }
 
 impl FileType {
@@ -52,6 +72,24 @@ impl FileType {
 
 const NUM_RAND_CHARS: usize = 32;
 
+/// Number of random names tried to create a file, before giving up with `EEXIST`.
+const NAME_ATTEMPTS: usize = 8;
+
+/// Whether the entries of a test are made removable during the teardown of its context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Cleanup {
+    /// The entries are always made removable.
+    #[default]
+    Always,
+    /// The entries of the failed tests are kept as they are.
+    KeepFailed,
+    /// The entries are always kept as they are.
+    Never,
+}
+
+/// Duration used by [`TestContext::nap`] when no naptime has been configured nor calibrated.
+const DEFAULT_NAPTIME: Duration = Duration::from_secs(1);
+
 /// Auth entries which are composed of a [`User`] and its associated [`Group`].
 /// Allows to retrieve the auth entries.
 #[derive(Debug)]
@@ -116,8 +154,18 @@ pub struct TestContext<'a> {
     temp_dir: &'a Path,
     /// Features configuration, used to determine which features are enabled.
     features_config: &'a FeaturesConfig,
+    /// Settings of the stress tests, if they are enabled.
+    stress_config: Option<&'a StressConfig>,
+    /// Settings of the device node tests, if they are enabled.
+    devices_config: Option<&'a DevicesConfig>,
+    /// Semantics the file system is expected to conform to.
+    conformance_profile: ConformanceProfile,
     /// Auth entries which are composed of a [`User`] and its associated [`Group`].
     auth_entries: DummyAuthEntries<'a>,
+    /// Path where the test directory is captured if the test fails.
+    artifacts_path: Option<PathBuf>,
+    /// Whether the entries are made removable during teardown.
+    cleanup: Cleanup,
     /// Jail, used to isolate the test environment on FreeBSD.
     #[cfg(target_os = "freebsd")]
     jail: Option<jail::RunningJail>,
@@ -154,6 +202,8 @@ pub struct TestContext<'a> {
 ///
 pub struct SerializedTestContext<'a> {
     ctx: TestContext<'a>,
+    /// Whether [`SerializedTestContext::as_user`] changes the real IDs.
+    real_ids: bool,
 }
 
 impl<'a> Deref for SerializedTestContext<'a> {
@@ -174,6 +224,7 @@ impl<'a> SerializedTestContext<'a> {
     pub fn new(config: &'a Config, entries: &'a [DummyAuthEntry], base_dir: &'a Path) -> Self {
         Self {
             ctx: TestContext::new(config, entries, base_dir),
+            real_ids: config.settings.real_ids,
         }
     }
 
@@ -182,7 +233,24 @@ impl<'a> SerializedTestContext<'a> {
     /// and the effective [`Gid`] will be this one.
     /// Otherwise, the first provided [`Gid`] will be the effective one
     /// and the others will be added with `setgroups`.
+    ///
+    /// Only the effective IDs are changed, unless the `real_ids` setting is enabled
+    /// (see [`SerializedTestContext::as_real_user`]).
     pub fn as_user<F>(&self, user: &User, groups: Option<&[Gid]>, f: F)
+    where
+        F: FnOnce(),
+    {
+        if self.real_ids {
+            self.as_real_user(user, groups, f)
+        } else {
+            self.as_effective_user(user, groups, f)
+        }
+    }
+
+    /// Execute the function with the effective IDs of another user/group(s),
+    /// the real IDs are left unchanged.
+    /// See [`SerializedTestContext::as_user`] for the meaning of `groups`.
+    pub fn as_effective_user<F>(&self, user: &User, groups: Option<&[Gid]>, f: F)
     where
         F: FnOnce(),
     {
@@ -209,6 +277,42 @@ impl<'a> SerializedTestContext<'a> {
         }
     }
 
+    /// Execute the function as another user/group(s) in a forked child process,
+    /// after changing its real, effective and saved IDs with `setgid`/`setuid`.
+    /// The privileges cannot be regained, unlike with [`SerializedTestContext::as_effective_user`].
+    /// See [`SerializedTestContext::as_user`] for the meaning of `groups`.
+    ///
+    /// # Note
+    ///
+    /// Since the function is run in another process,
+    /// only its effects on the file system are visible after it returns,
+    /// along with the choices it recorded in the behavior profile.
+    pub fn as_real_user<F>(&self, user: &User, groups: Option<&[Gid]>, f: F)
+    where
+        F: FnOnce(),
+    {
+        let groups: Vec<_> = groups
+            .unwrap_or_else(|| std::slice::from_ref(&user.gid))
+            .to_vec();
+
+        // Serialized tests are run while the runner is single-threaded
+        let res = isolation::run_in_child(|| {
+            setgroups(&groups).unwrap();
+            setgid(groups[0]).unwrap();
+            setuid(user.uid).unwrap();
+            f();
+            recorder::take_all()
+        });
+
+        match res {
+            Ok(recorded) => {
+                recorder::record_all(&recorded);
+            }
+            Err(ChildError::Panicked(message)) => panic!("{message}"),
+            Err(e) => panic!("process running as {} {e}", user.name),
+        }
+    }
+
     /// Execute the function with another umask.
     pub fn with_umask<F>(&self, mask: mode_t, f: F)
     where
@@ -235,12 +339,20 @@ impl<'a> Drop for SerializedTestContext<'a> {
 impl<'a> TestContext<'a> {
     /// Create a new test context.
     pub fn new(config: &'a Config, entries: &'a [DummyAuthEntry], temp_dir: &'a Path) -> Self {
-        let naptime = Duration::from_secs_f64(config.settings.naptime);
+        let naptime = config
+            .settings
+            .naptime
+            .map_or(DEFAULT_NAPTIME, Duration::from_secs_f64);
         TestContext {
             naptime,
             temp_dir,
             features_config: &config.features,
+            stress_config: config.settings.stress.as_ref(),
+            devices_config: config.settings.devices.as_ref(),
+            conformance_profile: config.settings.conformance_profile,
             auth_entries: DummyAuthEntries::new(entries),
+            artifacts_path: None,
+            cleanup: Cleanup::default(),
             #[cfg(target_os = "freebsd")]
             jail: None,
         }
@@ -251,14 +363,47 @@ impl<'a> TestContext<'a> {
         self.temp_dir
     }
 
+    /// Open a file descriptor for the base path, to use with the `*at` syscalls
+    /// and [`FileBuilder::at`].
+    pub fn dirfd(&self) -> nix::Result<OwnedFd> {
+        open(
+            self.base_path(),
+            OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+            Mode::empty(),
+        )
+    }
+
     pub fn features_config(&self) -> &FeaturesConfig {
         self.features_config
     }
 
+    /// Return the settings of the stress tests, if they are enabled.
+    pub fn stress_config(&self) -> Option<&StressConfig> {
+        self.stress_config
+    }
+
+    /// Return the settings of the device node tests, if they are enabled.
+    pub fn devices_config(&self) -> Option<&DevicesConfig> {
+        self.devices_config
+    }
+
+    /// Return the conformance profile, which determines the errors
+    /// and timestamp updates accepted by the assertion helpers.
+    pub fn conformance_profile(&self) -> ConformanceProfile {
+        self.conformance_profile
+    }
+
+    /// Record the state of all the entries of the test directory,
+    /// to later verify that an operation had no side effects.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.base_path())
+    }
+
     /// Generate a random path.
     pub fn gen_path(&self) -> PathBuf {
-        self.base_path()
-            .join(Alphanumeric.sample_string(&mut rand::thread_rng(), NUM_RAND_CHARS))
+        self.base_path().join(with_rng(|rng| {
+            Alphanumeric.sample_string(rng, NUM_RAND_CHARS)
+        }))
     }
 
     /// Create a regular file and open it.
@@ -275,61 +420,82 @@ impl<'a> TestContext<'a> {
     }
 
     /// Return a file builder.
-    pub fn new_file(&self, ft: FileType) -> FileBuilder {
+    pub fn new_file<'fd>(&self, ft: FileType) -> FileBuilder<'fd> {
         FileBuilder::new(ft, &self.base_path())
     }
 
-    /// Create a file with a random name.
+    /// Create a file with a random name which is not taken by another entry,
+    /// drawing a new name when the file system reports that it exists (`EEXIST`).
     pub fn create(&self, f_type: FileType) -> Result<PathBuf, nix::Error> {
         self.new_file(f_type).create()
     }
 
+    /// Build a tree of entries in a new directory, following their description,
+    /// and return their paths.
+    /// The entries are created in the order of their description,
+    /// and the mode and owner of a directory are changed once its entries are created.
+    pub fn build_tree<I: IntoIterator<Item = TreeEntry>>(&self, entries: I) -> nix::Result<Tree> {
+        Tree::build(self, self.create(FileType::Dir)?, entries)
+    }
+
     /// Create a file whose name length is _PC_NAME_MAX.
     pub fn create_name_max(&self, f_type: FileType) -> Result<PathBuf, nix::Error> {
         let max_name_len =
             pathconf(self.base_path(), nix::unistd::PathconfVar::NAME_MAX)?.unwrap() as usize;
 
-        let file = self
-            .new_file(f_type)
-            .name(Alphanumeric.sample_string(&mut rand::thread_rng(), max_name_len));
+        let file = self.new_file(f_type).name(with_rng(|rng| {
+            Alphanumeric.sample_string(rng, max_name_len)
+        }));
 
         file.create()
     }
 
+    /// Create a file whose name is made of _PC_NAME_MAX times the character `c`,
+    /// and is then longer than _PC_NAME_MAX bytes if `c` is multibyte.
+    pub fn create_name_max_chars(&self, f_type: FileType, c: char) -> Result<PathBuf, nix::Error> {
+        let max_name_len =
+            pathconf(self.base_path(), nix::unistd::PathconfVar::NAME_MAX)?.unwrap() as usize;
+
+        self.new_file(f_type)
+            .name(c.to_string().repeat(max_name_len))
+            .create()
+    }
+
     /// Create a file whose path length is _PC_PATH_MAX.
     pub fn create_path_max(&self, f_type: FileType) -> Result<PathBuf, nix::Error> {
         let max_name_len =
             pathconf(self.base_path(), nix::unistd::PathconfVar::NAME_MAX)?.unwrap() as usize;
-        let component_len = max_name_len / 2;
 
         // - 1 for null char
         let max_path_len =
             pathconf(self.base_path(), nix::unistd::PathconfVar::PATH_MAX)?.unwrap() as usize - 1;
 
-        let mut path = self.base_path().to_owned();
-        let initial_path_len = path.to_string_lossy().len();
-        let remaining_chars = max_path_len - initial_path_len;
-
-        let parts: Vec<_> = (0..remaining_chars / component_len)
-            .map(|_| Alphanumeric.sample_string(&mut rand::thread_rng(), component_len - 1))
-            .collect();
-
-        let remaining_chars = remaining_chars % component_len - 1;
-        if remaining_chars > 0 {
-            path.extend(parts);
-
-            create_dir_all(&path).unwrap();
-
-            path.push(Alphanumeric.sample_string(&mut rand::thread_rng(), remaining_chars));
-        } else {
-            path.extend(&parts[..parts.len() - 1]);
+        let path = self.gen_path_with_len(max_path_len, max_name_len / 2)?;
+        self.new_file(f_type).name(&path).create()?;
 
-            create_dir_all(&path).unwrap();
+        Ok(path)
+    }
 
-            path.push(&parts[parts.len() - 1]);
+    /// Generate a path of `len` bytes in the test directory, made of components
+    /// of at most `component_len` bytes (at least 2), and create its parent directories.
+    /// Return `EINVAL` if `len` leaves no room for a component after the test directory.
+    pub fn gen_path_with_len(&self, len: usize, component_len: usize) -> nix::Result<PathBuf> {
+        let mut path = self.base_path().to_owned();
+        // Each component takes its length and a separator
+        let mut remaining = len
+            .checked_sub(path.as_os_str().len())
+            .filter(|remaining| *remaining >= 2)
+            .ok_or(Errno::EINVAL)?;
+        while remaining > component_len + 1 {
+            // Leave room for a last component of at least one byte
+            let dir_len = component_len.min(remaining - 3);
+            path.push(with_rng(|rng| Alphanumeric.sample_string(rng, dir_len)));
+            mkdir(&path, Mode::from_bits_truncate(0o755))?;
+            remaining -= dir_len + 1;
         }
-
-        self.new_file(f_type).name(&path).create()?;
+        path.push(with_rng(|rng| {
+            Alphanumeric.sample_string(rng, remaining - 1)
+        }));
 
         Ok(path)
     }
@@ -356,6 +522,139 @@ impl<'a> TestContext<'a> {
         thread::sleep(self.naptime)
     }
 
+    /// Execute the function concurrently in `threads` threads, which are released at the same time
+    /// to maximize contention, and return the results ordered by thread index.
+    /// A panic in any of the threads is propagated.
+    pub fn race<T, F>(&self, threads: usize, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize) -> T + Sync,
+    {
+        let barrier = Barrier::new(threads);
+
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..threads)
+                .map(|i| {
+                    let (barrier, f) = (&barrier, &f);
+                    s.spawn(move || {
+                        barrier.wait();
+                        f(i)
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or_else(|e| resume_unwind(e)))
+                .collect()
+        })
+    }
+
+    /// Execute the function `iterations` times in each of `threads` threads, with the index
+    /// of the thread and of the iteration, to stress the file system with concurrent operations.
+    /// Each thread has its own random generator, seeded from the one of the test,
+    /// so that the operations chosen randomly by a thread are the same for a given seed
+    /// (but not their interleaving).
+    /// A panic in any of the threads is propagated.
+    pub fn stress<F>(&self, threads: usize, iterations: usize, f: F)
+    where
+        F: Fn(usize, usize) + Sync,
+    {
+        let seeds: Vec<u64> = (0..threads).map(|_| rng::random()).collect();
+
+        self.race(threads, |i| {
+            rng::set_seed(seeds[i]);
+            for iteration in 0..iterations {
+                f(i, iteration);
+            }
+        });
+    }
+
+    /// Open `path` with `oflags` in another thread, as the open of a FIFO may block.
+    pub fn open_in_background(&self, path: &Path, oflags: OFlag) -> PendingOpen {
+        PendingOpen::new(path, oflags)
+    }
+
+    /// Open the FIFO at `path` concurrently for reading and for writing,
+    /// and return the reader and the writer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an open fails or does not complete within `timeout`.
+    pub fn fifo_pair(&self, path: &Path, timeout: Duration) -> (OwnedFd, OwnedFd) {
+        let mut reader = self.open_in_background(path, OFlag::O_RDONLY);
+        let mut writer = self.open_in_background(path, OFlag::O_WRONLY);
+        let complete = |pending: &mut PendingOpen, end: &str| {
+            pending
+                .wait(timeout)
+                .unwrap_or_else(|| panic!("opening the {end} of the FIFO did not complete"))
+                .unwrap_or_else(|e| panic!("cannot open the {end} of the FIFO: {e}"))
+        };
+
+        let reader = complete(&mut reader, "reader");
+        let writer = complete(&mut writer, "writer");
+        (reader, writer)
+    }
+
+    /// Execute the function in a forked child process, so that a fatal signal it raises
+    /// (e.g. SIGBUS when touching a truncated mapping) is returned as an error
+    /// instead of killing the runner.
+    ///
+    /// # Note
+    ///
+    /// Since the function is run in another process,
+    /// only its effects on the file system are visible after it returns,
+    /// along with the choices it recorded in the behavior profile.
+    pub fn in_child<F>(&self, f: F) -> Result<(), ChildError>
+    where
+        F: FnOnce(),
+    {
+        let recorded = isolation::run_in_child(|| {
+            f();
+            recorder::take_all()
+        })?;
+        recorder::record_all(&recorded);
+
+        Ok(())
+    }
+
+    /// Execute the function with the given capabilities dropped from the effective set.
+    /// Capabilities are per-thread, so other tests are not affected.
+    #[cfg(target_os = "linux")]
+    pub fn without_caps<F>(&self, caps: &[caps::Capability], f: F)
+    where
+        F: FnOnce(),
+    {
+        use caps::CapSet;
+
+        for cap in caps {
+            caps::drop(None, CapSet::Effective, *cap).unwrap();
+        }
+
+        let res = catch_unwind(AssertUnwindSafe(f));
+
+        for cap in caps {
+            caps::raise(None, CapSet::Effective, *cap).unwrap();
+        }
+
+        if let Err(e) = res {
+            resume_unwind(e)
+        }
+    }
+
+    /// Capture the test directory to `path` during teardown if the test fails,
+    /// before the permissions and flags preventing its removal are cleared.
+    pub fn capture_artifacts_to<P: Into<PathBuf>>(&mut self, path: P) {
+        self.artifacts_path = Some(path.into());
+    }
+
+    /// Set whether the entries are made removable during teardown.
+    /// The permissions and flags of the kept entries are left as they are,
+    /// which might prevent their removal.
+    pub fn set_cleanup(&mut self, cleanup: Cleanup) {
+        self.cleanup = cleanup;
+    }
+
     /// Set this Context's jail, so it will be destroyed during teardown.
     #[cfg(target_os = "freebsd")]
     pub fn set_jail(&mut self, jail: jail::RunningJail) {
@@ -367,6 +666,30 @@ impl<'a> TestContext<'a> {
 // search or write permission is denied, or a flag denying delete for a file.
 impl<'a> Drop for TestContext<'a> {
     fn drop(&mut self) {
+        if thread::panicking() {
+            if let Some(artifacts_path) = &self.artifacts_path {
+                if let Err(e) = artifacts::capture(self.base_path(), artifacts_path) {
+                    eprintln!(
+                        "Cannot capture artifacts to {}: {e}",
+                        artifacts_path.display()
+                    );
+                }
+            }
+        }
+
+        let disarmed = match self.cleanup {
+            Cleanup::Always => false,
+            Cleanup::KeepFailed => thread::panicking(),
+            Cleanup::Never => true,
+        };
+        if disarmed {
+            #[cfg(target_os = "freebsd")]
+            if let Some(jail) = self.jail.take() {
+                let _ = jail.kill();
+            }
+            return;
+        }
+
         let iter = walkdir::WalkDir::new(self.base_path()).into_iter();
         for entry in iter {
             let entry = match entry {
@@ -409,32 +732,63 @@ impl<'a> Drop for TestContext<'a> {
 
 /// Allows to create a file using builder pattern.
 #[derive(Debug)]
-pub struct FileBuilder {
+pub struct FileBuilder<'a> {
     file_type: FileType,
-    path: PathBuf,
-    random_name: bool,
+    base_path: PathBuf,
+    /// Name of the file, a random one is drawn if it is not given.
+    name: Option<PathBuf>,
     mode: Option<Mode>,
+    owner: Option<(Uid, Gid)>,
+    times: Option<(TimeSpec, TimeSpec)>,
+    #[cfg(chflags)]
+    flags: Option<nix::sys::stat::FileFlag>,
+    dirfd: Option<BorrowedFd<'a>>,
 }
 
-impl FileBuilder {
+impl<'a> FileBuilder<'a> {
     /// Create a file builder.
     pub fn new<P: AsRef<Path>>(file_type: FileType, base_path: &P) -> Self {
         Self {
-            path: base_path.as_ref().to_path_buf(),
-            random_name: true,
+            base_path: base_path.as_ref().to_path_buf(),
+            name: None,
             mode: None,
+            owner: None,
+            times: None,
+            #[cfg(chflags)]
+            flags: None,
+            dirfd: None,
             file_type,
         }
     }
 
-    /// [`Take`](std::mem::take) and return the path final form.
-    fn final_path(&mut self) -> PathBuf {
-        if self.random_name {
-            self.path
-                .push(Alphanumeric.sample_string(&mut rand::thread_rng(), NUM_RAND_CHARS))
+    /// Create the entry with `create`, at the given name or at a random one in the base path,
+    /// or relative to the directory file descriptor.
+    /// A random name is drawn again when it is already taken, so that a collision
+    /// (e.g. of names differing only by case on a case-insensitive file system)
+    /// does not fail the test.
+    fn create_with<T>(
+        &mut self,
+        mut create: impl FnMut(&Path) -> nix::Result<T>,
+    ) -> nix::Result<(PathBuf, T)> {
+        let base_path = match self.dirfd {
+            Some(_) => PathBuf::new(),
+            None => std::mem::take(&mut self.base_path),
+        };
+        if let Some(name) = self.name.take() {
+            let path = base_path.join(name);
+            return create(&path).map(|value| (path, value));
         }
 
-        std::mem::take(&mut self.path)
+        let mut attempts = 0;
+        loop {
+            let path = base_path.join(random_name());
+            attempts += 1;
+            match create(&path) {
+                // bind fails with EADDRINUSE when the name of a socket is taken
+                Err(Errno::EEXIST | Errno::EADDRINUSE) if attempts < NAME_ATTEMPTS => continue,
+                result => return result.map(|value| (path, value)),
+            }
+        }
     }
 
     /// Create the file according to the provided information.
@@ -443,42 +797,82 @@ impl FileBuilder {
             FileType::Dir => Mode::from_bits_truncate(0o755),
             _ => Mode::from_bits_truncate(0o644),
         });
-        let path = self.final_path();
-
-        match self.file_type {
-            FileType::Regular => open(&path, OFlag::O_CREAT, mode).map(drop),
-            FileType::Dir => mkdir(&path, mode),
-            FileType::Fifo => mkfifo(&path, mode),
-            FileType::Block => mknod(&path, SFlag::S_IFBLK, mode, 0),
-            FileType::Char => mknod(&path, SFlag::S_IFCHR, mode, 0),
+        // A random name must not reuse an existing file
+        let excl = if self.name.is_none() {
+            OFlag::O_EXCL
+        } else {
+            OFlag::empty()
+        };
+        let file_type = self.file_type.clone();
+        let chosen_mode = self.mode;
+        let loc = self.location();
+
+        let (path, ()) = self.create_with(|path| match &file_type {
+            FileType::Regular => loc.open(path, OFlag::O_CREAT | excl, mode).map(drop),
+            FileType::Dir => loc.mkdir(path, mode),
+            FileType::Fifo => loc.mknod(path, SFlag::S_IFIFO, mode),
+            FileType::Block => loc.mknod(path, SFlag::S_IFBLK, mode),
+            FileType::Char => loc.mknod(path, SFlag::S_IFCHR, mode),
             FileType::Socket => {
+                // There is no bindat
+                if let Location::At(_) = loc {
+                    return Err(Errno::EOPNOTSUPP);
+                }
                 let fd = socket(
                     nix::sys::socket::AddressFamily::Unix,
                     nix::sys::socket::SockType::Stream,
                     SockFlag::empty(),
                     None,
                 )?;
-                let sockaddr = UnixAddr::new(&path)?;
+                let sockaddr = UnixAddr::new(path)?;
                 bind(fd.as_raw_fd(), &sockaddr)?;
-                if let Some(mode) = self.mode {
-                    chmod(&path, mode)?;
+                budget::created(path);
+                if let Some(mode) = chosen_mode {
+                    chmod(path, mode)?;
                 }
                 Ok(())
             }
-            FileType::Symlink(target) => {
-                symlink(
-                    target.as_deref().unwrap_or_else(|| Path::new("test")),
-                    &path,
-                )?;
+            FileType::Symlink(_) | FileType::SymlinkChain(_) | FileType::SymlinkToDir => {
+                let target = match &file_type {
+                    FileType::Symlink(target) => target.clone().unwrap_or_else(|| "test".into()),
+                    FileType::SymlinkChain(depth) => {
+                        // From the dangling end of the chain, with targets relative to the links
+                        let mut target = PathBuf::from(random_name());
+                        for _ in 1..*depth {
+                            let name = random_name();
+                            loc.symlink(&target, &path.with_file_name(&name))?;
+                            target = name.into();
+                        }
+                        target
+                    }
+                    _ => {
+                        let name = random_name();
+                        loc.mkdir(&path.with_file_name(&name), Mode::from_bits_truncate(0o755))?;
+                        name.into()
+                    }
+                };
+                loc.symlink(&target, path)?;
 
                 #[cfg(lchmod)]
-                if let Some(mode) = self.mode {
-                    lchmod(&path, mode)?;
+                if let Some(mode) = chosen_mode {
+                    loc.lchmod(path, mode)?;
                 }
 
                 Ok(())
             }
-        }?;
+            FileType::HardLink(target) => {
+                let target = match target {
+                    Some(target) => target.clone(),
+                    None => {
+                        let target = path.with_file_name(random_name());
+                        loc.open(&target, OFlag::O_CREAT | OFlag::O_EXCL, mode)?;
+                        target
+                    }
+                };
+                loc.link(&target, path)
+            }
+        })?;
+        self.apply_attributes(loc, &path)?;
 
         Ok(path)
     }
@@ -486,19 +880,23 @@ impl FileBuilder {
     /// Create the file according to the provided information and open it.
     /// This function automatically adds [`O_CREAT`](nix::fcntl::OFlag::O_CREAT) to the [`open`] flags when creating a regular file.
     pub fn open(mut self, oflags: OFlag) -> nix::Result<(PathBuf, OwnedFd)> {
+        let loc = self.location();
         match self.file_type {
             FileType::Regular => {
-                let path = self.final_path();
-                open(
-                    &path,
-                    OFlag::O_CREAT | oflags,
-                    self.mode.unwrap_or_else(|| Mode::from_bits_truncate(0o644)),
-                )
-                .map(|fd| (path, fd))
+                let mode = self.mode.unwrap_or_else(|| Mode::from_bits_truncate(0o644));
+                let excl = if self.name.is_none() {
+                    OFlag::O_EXCL
+                } else {
+                    OFlag::empty()
+                };
+                let (path, fd) =
+                    self.create_with(|path| loc.open(path, OFlag::O_CREAT | excl | oflags, mode))?;
+                self.apply_attributes(loc, &path)?;
+                Ok((path, fd))
             }
             _ => self
                 .create()
-                .and_then(|p| open(&p, oflags, Mode::empty()).map(|fd| (p, fd))),
+                .and_then(|p| loc.open(&p, oflags, Mode::empty()).map(|fd| (p, fd))),
         }
     }
 
@@ -508,13 +906,224 @@ impl FileBuilder {
         self
     }
 
-    /// Join `name` to the base path.
+    /// Change the owner and group of the file once created.
+    /// The mode is applied again afterwards, as changing the owner may clear the SUID/SGID bits.
+    pub fn owner(mut self, uid: Uid, gid: Gid) -> Self {
+        self.owner = Some((uid, gid));
+        self
+    }
+
+    /// Set the access and modification times of the file once created.
+    pub fn times(mut self, atime: TimeSpec, mtime: TimeSpec) -> Self {
+        self.times = Some((atime, mtime));
+        self
+    }
+
+    /// Set the flags of the file once created, after its other attributes,
+    /// as flags such as `UF_IMMUTABLE` prevent them from being changed.
+    #[cfg(chflags)]
+    pub fn flags<F: Into<nix::sys::stat::FileFlag>>(mut self, flags: F) -> Self {
+        self.flags = Some(flags.into());
+        self
+    }
+
+    /// Create the file relative to a directory file descriptor, with the `*at` syscalls,
+    /// instead of the base path (e.g. in a directory which has been renamed or unlinked since).
+    /// The path returned on creation is then relative to the directory,
+    /// and has to be joined to the path of the directory to be used by path.
+    /// Sockets cannot be created relative to a directory file descriptor (`EOPNOTSUPP`).
+    pub fn at<Fd: AsFd>(mut self, dirfd: &'a Fd) -> Self {
+        self.dirfd = Some(dirfd.as_fd());
+        self
+    }
+
+    /// Join `name` to the base path, or to the directory file descriptor given with [`FileBuilder::at`].
     /// An absolute path can also be provided, in this case it completely replaces the path.
     pub fn name<P: AsRef<Path>>(mut self, name: P) -> Self {
-        self.path.push(name.as_ref());
-        self.random_name = false;
+        self.name = Some(match self.name.take() {
+            Some(previous) => previous.join(name),
+            None => name.as_ref().to_path_buf(),
+        });
         self
     }
+
+    fn location(&self) -> Location<'a> {
+        self.dirfd.map_or(Location::Path, Location::At)
+    }
+
+    /// Apply the owner, times and flags requested for the created file,
+    /// without following it if it is a symlink.
+    fn apply_attributes(&self, loc: Location<'a>, path: &Path) -> nix::Result<()> {
+        let is_symlink = matches!(
+            self.file_type,
+            FileType::Symlink(_) | FileType::SymlinkChain(_) | FileType::SymlinkToDir
+        );
+
+        if let Some((uid, gid)) = self.owner {
+            loc.lchown(path, uid, gid)?;
+            match self.mode {
+                Some(mode) if !is_symlink => loc.chmod(path, mode)?,
+                #[cfg(lchmod)]
+                Some(mode) => loc.lchmod(path, mode)?,
+                _ => (),
+            }
+        }
+
+        if let Some((atime, mtime)) = self.times {
+            nix::sys::stat::utimensat(
+                loc.dirfd(),
+                path,
+                &atime,
+                &mtime,
+                nix::sys::stat::UtimensatFlags::NoFollowSymlink,
+            )?;
+        }
+
+        #[cfg(chflags)]
+        if let Some(flags) = self.flags {
+            loc.lchflags(path, flags)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Return a random name, for the entries created along with the file of a [`FileBuilder`].
+fn random_name() -> String {
+    with_rng(|rng| Alphanumeric.sample_string(rng, NUM_RAND_CHARS))
+}
+
+/// Where the entries of a [`FileBuilder`] are created.
+#[derive(Debug, Clone, Copy)]
+enum Location<'a> {
+    /// At their path.
+    Path,
+    /// Relative to a directory file descriptor, see [`FileBuilder::at`].
+    At(BorrowedFd<'a>),
+}
+
+impl Location<'_> {
+    fn dirfd(self) -> Option<RawFd> {
+        match self {
+            Location::Path => None,
+            Location::At(dirfd) => Some(dirfd.as_raw_fd()),
+        }
+    }
+
+    fn open(self, path: &Path, oflags: OFlag, mode: Mode) -> nix::Result<OwnedFd> {
+        match self {
+            Location::Path => open(path, oflags, mode),
+            Location::At(_) => {
+                let fd = nix::fcntl::openat(self.dirfd(), path, oflags, mode)
+                    // SAFETY: The file descriptor was just returned by openat and isn't owned elsewhere
+                    .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })?;
+                if oflags.contains(OFlag::O_CREAT) {
+                    budget::created(path);
+                }
+                Ok(fd)
+            }
+        }
+    }
+
+    fn mkdir(self, path: &Path, mode: Mode) -> nix::Result<()> {
+        match self {
+            Location::Path => mkdir(path, mode),
+            Location::At(_) => {
+                nix::sys::stat::mkdirat(self.dirfd(), path, mode).map(|_| budget::created(path))
+            }
+        }
+    }
+
+    fn mknod(self, path: &Path, kind: SFlag, mode: Mode) -> nix::Result<()> {
+        match self {
+            Location::Path if kind == SFlag::S_IFIFO => mkfifo(path, mode),
+            Location::Path => mknod(path, kind, mode, 0),
+            #[cfg(not(target_vendor = "apple"))]
+            Location::At(_) => nix::sys::stat::mknodat(self.dirfd(), path, kind, mode, 0)
+                .map(|_| budget::created(path)),
+            // mknodat is not available on macOS
+            #[cfg(target_vendor = "apple")]
+            Location::At(_) => Err(Errno::EOPNOTSUPP),
+        }
+    }
+
+    fn symlink(self, target: &Path, path: &Path) -> nix::Result<()> {
+        match self {
+            Location::Path => symlink(target, path),
+            Location::At(_) => {
+                nix::unistd::symlinkat(target, self.dirfd(), path).map(|_| budget::created(path))
+            }
+        }
+    }
+
+    fn link(self, target: &Path, path: &Path) -> nix::Result<()> {
+        match self {
+            Location::Path => link(target, path),
+            Location::At(_) => nix::unistd::linkat(
+                self.dirfd(),
+                target,
+                self.dirfd(),
+                path,
+                nix::fcntl::AtFlags::empty(),
+            )
+            .map(|_| budget::created(path)),
+        }
+    }
+
+    fn chmod(self, path: &Path, mode: Mode) -> nix::Result<()> {
+        match self {
+            Location::Path => chmod(path, mode),
+            Location::At(_) => nix::sys::stat::fchmodat(
+                self.dirfd(),
+                path,
+                mode,
+                nix::sys::stat::FchmodatFlags::FollowSymlink,
+            ),
+        }
+    }
+
+    fn lchown(self, path: &Path, uid: Uid, gid: Gid) -> nix::Result<()> {
+        match self {
+            Location::Path => lchown(path, Some(uid), Some(gid)),
+            Location::At(_) => nix::unistd::fchownat(
+                self.dirfd(),
+                path,
+                Some(uid),
+                Some(gid),
+                nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW,
+            ),
+        }
+    }
+
+    #[cfg(chflags)]
+    fn lchflags(self, path: &Path, flags: nix::sys::stat::FileFlag) -> nix::Result<()> {
+        match self {
+            Location::Path => crate::utils::lchflags(path, flags),
+            #[cfg(chflagsat)]
+            Location::At(dirfd) => crate::utils::chflagsat(
+                dirfd.as_raw_fd(),
+                path,
+                flags,
+                nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW,
+            ),
+            // chflagsat is not available on macOS and NetBSD
+            #[cfg(not(chflagsat))]
+            Location::At(_) => Err(Errno::EOPNOTSUPP),
+        }
+    }
+
+    #[cfg(lchmod)]
+    fn lchmod(self, path: &Path, mode: Mode) -> nix::Result<()> {
+        match self {
+            Location::Path => lchmod(path, mode),
+            Location::At(_) => nix::sys::stat::fchmodat(
+                self.dirfd(),
+                path,
+                mode,
+                nix::sys::stat::FchmodatFlags::NoFollowSymlink,
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -570,6 +1179,223 @@ mod tests {
         }
     }
 
+    #[test]
+    fn create_links() {
+        use std::{fs, os::unix::fs::MetadataExt};
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        let file = ctx.create(FileType::Regular).unwrap();
+        let link = ctx.create(FileType::HardLink(Some(file.clone()))).unwrap();
+        assert_eq!(
+            fs::metadata(&link).unwrap().ino(),
+            fs::metadata(&file).unwrap().ino()
+        );
+        assert_eq!(fs::metadata(&link).unwrap().nlink(), 2);
+        let link = ctx.create(FileType::HardLink(None)).unwrap();
+        assert_eq!(fs::metadata(&link).unwrap().nlink(), 2);
+
+        let mut link = ctx.create(FileType::SymlinkChain(3)).unwrap();
+        for _ in 0..3 {
+            assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+            link = ctx.base_path().join(fs::read_link(&link).unwrap());
+        }
+        assert!(fs::symlink_metadata(&link).is_err());
+
+        let link = ctx.create(FileType::SymlinkToDir).unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert!(fs::metadata(&link).unwrap().is_dir());
+
+        // The targets are relative to the directory of the link
+        let dirfd = ctx.dirfd().unwrap();
+        let link = ctx
+            .new_file(FileType::SymlinkToDir)
+            .at(&dirfd)
+            .create()
+            .unwrap();
+        assert!(fs::metadata(ctx.base_path().join(link)).unwrap().is_dir());
+    }
+
+    #[test]
+    fn create_attributes() {
+        use nix::{
+            sys::{stat::lstat, time::TimeSpec},
+            unistd::{getgid, getuid},
+        };
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        let (atime, mtime) = (TimeSpec::new(1_000_000, 0), TimeSpec::new(2_000_000, 0));
+        let target = ctx.create(FileType::Regular).unwrap();
+        let target_stat = lstat(&target).unwrap();
+        for ft in [FileType::Regular, FileType::Symlink(Some(target.clone()))] {
+            let is_regular = ft == FileType::Regular;
+            let file = ctx
+                .new_file(ft)
+                .mode(0o640)
+                .owner(getuid(), getgid())
+                .times(atime, mtime)
+                .create()
+                .unwrap();
+            let file_stat = lstat(&file).unwrap();
+            assert_eq!(file_stat.st_uid, getuid().as_raw());
+            assert_eq!(file_stat.st_gid, getgid().as_raw());
+            assert_eq!(file_stat.st_atime, atime.tv_sec());
+            assert_eq!(file_stat.st_mtime, mtime.tv_sec());
+            if is_regular {
+                assert_eq!(file_stat.st_mode & 0o7777, 0o640);
+            }
+        }
+
+        // The target of the symlink is left untouched
+        let stat = lstat(&target).unwrap();
+        assert_eq!(
+            (stat.st_atime, stat.st_mtime),
+            (target_stat.st_atime, target_stat.st_mtime)
+        );
+    }
+
+    #[test]
+    fn build_tree() {
+        use std::{
+            fs,
+            os::unix::fs::{FileTypeExt, MetadataExt},
+        };
+
+        use super::TreeEntry;
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        let tree = ctx
+            .build_tree([
+                TreeEntry::dir(
+                    "dir",
+                    [
+                        TreeEntry::file("file").mode(0o600),
+                        TreeEntry::symlink("symlink", "file"),
+                        TreeEntry::dir("subdir", [TreeEntry::link("link", "dir/file")]),
+                    ],
+                )
+                .mode(0o500),
+                TreeEntry::new("fifo", FileType::Fifo),
+            ])
+            .unwrap();
+
+        let mode = |path| fs::symlink_metadata(path).unwrap().mode() & 0o7777;
+        assert_eq!(tree.path("dir"), tree.root().join("dir"));
+        assert_eq!(mode(tree.path("dir")), 0o500);
+        assert_eq!(mode(tree.path("dir/file")), 0o600);
+        assert_eq!(
+            fs::read_link(tree.path("dir/symlink")).unwrap(),
+            std::path::Path::new("file")
+        );
+        assert_eq!(
+            fs::metadata(tree.path("dir/subdir/link")).unwrap().ino(),
+            fs::metadata(tree.path("dir/file")).unwrap().ino()
+        );
+        assert!(fs::symlink_metadata(tree.path("fifo"))
+            .unwrap()
+            .file_type()
+            .is_fifo());
+
+        // Another tree with the same entries can be built
+        let other = ctx.build_tree([TreeEntry::dir("dir", [])]).unwrap();
+        assert_ne!(other.path("dir"), tree.path("dir"));
+    }
+
+    #[test]
+    fn open_in_background() {
+        use std::time::Duration;
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+        let fifo = ctx.create(FileType::Fifo).unwrap();
+
+        // The blocked threads are released when dropped
+        for oflags in [OFlag::O_RDONLY, OFlag::O_WRONLY] {
+            let mut pending = ctx.open_in_background(&fifo, oflags);
+            assert!(pending.wait(Duration::from_millis(50)).is_none());
+        }
+
+        let (reader, writer) = ctx.fifo_pair(&fifo, Duration::from_secs(5));
+        assert_eq!(nix::unistd::write(&writer, b"data"), Ok(4));
+        drop(reader);
+    }
+
+    #[test]
+    fn create_at() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        let base_fd = ctx.dirfd().unwrap();
+        for ft in [FileType::Regular, FileType::Dir, FileType::Symlink(None)] {
+            let name = ctx.new_file(ft).at(&base_fd).create().unwrap();
+            assert!(name.is_relative());
+            assert!(ctx.base_path().join(name).symlink_metadata().is_ok());
+        }
+
+        // The name is kept whether it is given before or after the descriptor
+        let name = ctx
+            .new_file(FileType::Regular)
+            .name("named")
+            .at(&base_fd)
+            .create()
+            .unwrap();
+        assert_eq!(name, std::path::Path::new("named"));
+
+        // The directory of the descriptor is renamed, then unlinked
+        let dir = ctx.create(FileType::Dir).unwrap();
+        let dirfd = std::fs::File::open(&dir).unwrap();
+        let renamed = ctx.gen_path();
+        std::fs::rename(&dir, &renamed).unwrap();
+        let (name, _) = ctx
+            .new_file(FileType::Regular)
+            .at(&dirfd)
+            .name("file")
+            .open(OFlag::O_WRONLY)
+            .unwrap();
+        assert!(renamed.join(name).is_file());
+
+        std::fs::remove_file(renamed.join("file")).unwrap();
+        std::fs::remove_dir(&renamed).unwrap();
+        assert_eq!(
+            ctx.new_file(FileType::Dir).at(&dirfd).create(),
+            Err(Errno::ENOENT)
+        );
+    }
+
+    #[test]
+    fn create_collision() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        // The same seed draws the same name again
+        for ft in [FileType::Regular, FileType::Dir, FileType::Socket] {
+            crate::rng::set_seed(1);
+            let first = ctx.create(ft.clone()).unwrap();
+            crate::rng::set_seed(1);
+            let second = ctx.create(ft).unwrap();
+            assert_ne!(first, second);
+            assert!(first.symlink_metadata().is_ok());
+            assert!(second.symlink_metadata().is_ok());
+        }
+
+        crate::rng::set_seed(2);
+        let (first, _) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
+        crate::rng::set_seed(2);
+        let (second, _) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
+        assert_ne!(first, second);
+    }
+
     #[test]
     fn name_max() {
         let tmpdir = TempDir::new().unwrap();
@@ -614,6 +1440,69 @@ mod tests {
         );
     }
 
+    #[test]
+    fn path_with_len() {
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+        let base_len = ctx.base_path().as_os_str().len();
+
+        let path = ctx.gen_path_with_len(base_len + 20, 4).unwrap();
+        assert_eq!(path.as_os_str().len(), base_len + 20);
+        assert!(path.parent().unwrap().is_dir());
+
+        assert_eq!(ctx.gen_path_with_len(base_len + 1, 4), Err(Errno::EINVAL));
+        assert_eq!(ctx.gen_path_with_len(0, 4), Err(Errno::EINVAL));
+    }
+
+    #[test]
+    fn race() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+        let started = AtomicUsize::new(0);
+
+        let results = ctx.race(4, |i| {
+            started.fetch_add(1, Ordering::SeqCst);
+            i * 2
+        });
+
+        assert_eq!(results, [0, 2, 4, 6]);
+        assert_eq!(started.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn stress() {
+        use std::sync::Mutex;
+
+        let tmpdir = TempDir::new().unwrap();
+        let config = Config::default();
+        let ctx = TestContext::new(&config, &[], tmpdir.path());
+
+        let run = || {
+            let calls = Mutex::new(Vec::new());
+            crate::rng::set_seed(42);
+            ctx.stress(3, 4, |i, iteration| {
+                calls
+                    .lock()
+                    .unwrap()
+                    .push((i, iteration, crate::rng::random::<u64>()));
+            });
+            let mut calls = calls.into_inner().unwrap();
+            calls.sort();
+            calls
+        };
+
+        let calls = run();
+        assert_eq!(calls.len(), 12);
+        for (n, (i, iteration, _)) in calls.iter().enumerate() {
+            assert_eq!((*i, *iteration), (n / 4, n % 4));
+        }
+        assert_eq!(run(), calls);
+    }
+
     #[test]
     fn new_file() {
         let current_umask = nix::sys::stat::umask(Mode::from_bits_truncate(ALLPERMS));
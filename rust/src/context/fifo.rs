@@ -0,0 +1,87 @@
+//! Opening of FIFOs in other threads with [`open_in_background`](super::TestContext::open_in_background),
+//! as the open of one of their ends blocks until the other end is opened.
+
+use std::{
+    os::fd::OwnedFd,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use nix::{fcntl::OFlag, sys::stat::Mode};
+
+use crate::utils::open;
+
+/// Time given to an open of a FIFO to complete,
+/// including when a [`PendingOpen`] is dropped.
+pub const FIFO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// An `open` executed in another thread, which may be blocked.
+/// If it is still blocked when dropped, the other end of the FIFO is opened to release the thread.
+#[derive(Debug)]
+pub struct PendingOpen {
+    path: PathBuf,
+    oflags: OFlag,
+    receiver: Receiver<nix::Result<OwnedFd>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PendingOpen {
+    pub(super) fn new(path: &Path, oflags: OFlag) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let thread_path = path.to_path_buf();
+        let handle = thread::spawn(move || {
+            let _ = sender.send(open(&thread_path, oflags, Mode::empty()));
+        });
+
+        Self {
+            path: path.to_path_buf(),
+            oflags,
+            receiver,
+            handle: Some(handle),
+        }
+    }
+
+    /// Wait up to `timeout` for the open to complete,
+    /// and return its result, or `None` if it is still blocked.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the result has already been returned.
+    pub fn wait(&mut self, timeout: Duration) -> Option<nix::Result<OwnedFd>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(res) => {
+                if let Some(handle) = self.handle.take() {
+                    let _ = handle.join();
+                }
+                Some(res)
+            }
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => panic!("the open has already completed"),
+        }
+    }
+}
+
+impl Drop for PendingOpen {
+    fn drop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+
+        if self.receiver.try_recv().is_err() {
+            // A blocked reader counts as one for a non-blocking writer, and the converse
+            let other_end = if self.oflags.contains(OFlag::O_WRONLY) {
+                OFlag::O_RDONLY
+            } else {
+                OFlag::O_WRONLY
+            };
+            let unblock = open(&self.path, other_end | OFlag::O_NONBLOCK, Mode::empty());
+            // Leave the thread blocked rather than hanging the test
+            if unblock.is_err() || self.receiver.recv_timeout(FIFO_TIMEOUT).is_err() {
+                return;
+            }
+        }
+        let _ = handle.join();
+    }
+}
@@ -0,0 +1,159 @@
+//! Declarative fixture trees, built with [`TestContext::build_tree`].
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    sys::stat::{mode_t, Mode},
+    unistd::{Gid, Uid},
+};
+
+use crate::utils::{chmod, lchown};
+
+use super::{FileType, TestContext};
+
+/// Description of an entry of a fixture tree.
+#[derive(Debug, Clone)]
+pub struct TreeEntry {
+    name: PathBuf,
+    file_type: FileType,
+    mode: Option<mode_t>,
+    owner: Option<(Uid, Gid)>,
+    children: Vec<TreeEntry>,
+}
+
+impl TreeEntry {
+    /// Describe an entry of the given type.
+    /// The target of a [`FileType::HardLink`] is relative to the root of the tree,
+    /// while the one of a [`FileType::Symlink`] is kept as is, that is relative to the link.
+    pub fn new<P: Into<PathBuf>>(name: P, file_type: FileType) -> Self {
+        Self {
+            name: name.into(),
+            file_type,
+            mode: None,
+            owner: None,
+            children: vec![],
+        }
+    }
+
+    /// Describe a regular file.
+    pub fn file<P: Into<PathBuf>>(name: P) -> Self {
+        Self::new(name, FileType::Regular)
+    }
+
+    /// Describe a directory and its entries.
+    pub fn dir<P: Into<PathBuf>, I: IntoIterator<Item = TreeEntry>>(name: P, children: I) -> Self {
+        Self {
+            children: children.into_iter().collect(),
+            ..Self::new(name, FileType::Dir)
+        }
+    }
+
+    /// Describe a symbolic link to `target`.
+    pub fn symlink<P: Into<PathBuf>, T: Into<PathBuf>>(name: P, target: T) -> Self {
+        Self::new(name, FileType::Symlink(Some(target.into())))
+    }
+
+    /// Describe a hard link to the entry at `target`, relative to the root of the tree,
+    /// which has to be described before the link.
+    pub fn link<P: Into<PathBuf>, T: Into<PathBuf>>(name: P, target: T) -> Self {
+        Self::new(name, FileType::HardLink(Some(target.into())))
+    }
+
+    /// Change the mode of the entry.
+    pub fn mode(mut self, mode: mode_t) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Change the owner and group of the entry.
+    pub fn owner(mut self, uid: Uid, gid: Gid) -> Self {
+        self.owner = Some((uid, gid));
+        self
+    }
+
+    /// Create the entry in `parent`, and record its path relative to `root`.
+    fn build(
+        &self,
+        ctx: &TestContext,
+        root: &Path,
+        parent: &Path,
+        paths: &mut HashMap<PathBuf, PathBuf>,
+    ) -> nix::Result<()> {
+        let path = parent.join(&self.name);
+        let file_type = match &self.file_type {
+            FileType::HardLink(Some(target)) => FileType::HardLink(Some(root.join(target))),
+            file_type => file_type.clone(),
+        };
+
+        if file_type == FileType::Dir {
+            ctx.new_file(FileType::Dir).name(&path).create()?;
+            for child in &self.children {
+                child.build(ctx, root, &path, paths)?;
+            }
+            // Once the entries are created, so that the mode or owner cannot prevent it
+            if let Some((uid, gid)) = self.owner {
+                lchown(&path, Some(uid), Some(gid))?;
+            }
+            if let Some(mode) = self.mode {
+                chmod(&path, Mode::from_bits_truncate(mode))?;
+            }
+        } else {
+            let mut file = ctx.new_file(file_type).name(&path);
+            if let Some(mode) = self.mode {
+                file = file.mode(mode);
+            }
+            if let Some((uid, gid)) = self.owner {
+                file = file.owner(uid, gid);
+            }
+            file.create()?;
+        }
+
+        paths.insert(path.strip_prefix(root).unwrap().to_path_buf(), path);
+        Ok(())
+    }
+}
+
+/// Fixture tree built with [`TestContext::build_tree`].
+#[derive(Debug)]
+pub struct Tree {
+    root: PathBuf,
+    paths: HashMap<PathBuf, PathBuf>,
+}
+
+impl Tree {
+    /// Build the entries in `root`.
+    pub(super) fn build<I: IntoIterator<Item = TreeEntry>>(
+        ctx: &TestContext,
+        root: PathBuf,
+        entries: I,
+    ) -> nix::Result<Self> {
+        let mut paths = HashMap::new();
+        for entry in entries {
+            entry.build(ctx, &root, &root, &mut paths)?;
+        }
+
+        Ok(Self { root, paths })
+    }
+
+    /// Return the directory containing the entries of the tree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Return the path of an entry of the tree, given as a path relative to its root
+    /// (e.g. `"dir/file"`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry has not been described.
+    pub fn path<P: AsRef<Path>>(&self, entry: P) -> PathBuf {
+        let entry = entry.as_ref();
+        self.paths
+            .get(entry)
+            .unwrap_or_else(|| panic!("no entry {} in the tree", entry.display()))
+            .clone()
+    }
+}
@@ -0,0 +1,148 @@
+//! Events of a run streamed to an external driver (a CI dashboard, an IDE plugin...)
+//! with `--report-socket`, to display the progress of the tests while they are run.
+//!
+//! The runner connects to the UNIX socket the driver listens on,
+//! and writes each event as a line of JSON, e.g.:
+//!
+//! ```text
+//! {"event":"run_started","path":"/mnt/test","seed":42,"tests":669}
+//! {"event":"test_started","test":"chmod::change_perm::regular"}
+//! {"event":"test_finished","test":"chmod::change_perm::regular","status":"passed","duration":0.002}
+//! {"event":"test_skipped","test":"chflags::set","reasons":["requires features: chflags"]}
+//! {"event":"run_finished","passed":593,"failed":0,"skipped":76,"expected_failures":0,"unexpected_passes":0,"interrupted":false}
+//! ```
+
+use std::{
+    io::Write,
+    os::unix::net::UnixStream,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::runner::TestStatus;
+
+/// Event of a run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    RunStarted {
+        /// Directory where the tests are run.
+        path: &'a Path,
+        seed: u64,
+        /// Number of tests to run.
+        tests: usize,
+    },
+    TestStarted {
+        test: &'a str,
+    },
+    TestFinished {
+        test: &'a str,
+        status: TestStatus,
+        /// Duration of the test, in seconds.
+        duration: f64,
+        /// Why the test failed.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<&'a str>,
+    },
+    TestSkipped {
+        test: &'a str,
+        reasons: &'a [String],
+    },
+    RunFinished {
+        passed: usize,
+        failed: usize,
+        skipped: usize,
+        expected_failures: usize,
+        unexpected_passes: usize,
+        /// Whether the run has been interrupted before all the tests have been run.
+        interrupted: bool,
+    },
+}
+
+/// Connection to the socket of a driver, where the events are sent.
+#[derive(Debug)]
+pub struct ReportSocket {
+    stream: UnixStream,
+    /// Whether the driver has gone away, after which the events are no longer sent.
+    closed: AtomicBool,
+}
+
+impl ReportSocket {
+    /// Connect to the socket a driver listens on.
+    pub fn connect(path: &Path) -> anyhow::Result<Self> {
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("cannot connect to the report socket {}", path.display()))?;
+
+        Ok(Self {
+            stream,
+            closed: AtomicBool::new(false),
+        })
+    }
+
+    /// Send an event to the driver.
+    /// The run goes on if it cannot be sent, without sending the following ones.
+    pub fn send(&self, event: &Event) {
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut line = serde_json::to_vec(event).unwrap();
+        line.push(b'\n');
+        if let Err(e) = (&self.stream).write_all(&line) {
+            println!("Cannot send the events to the report socket anymore: {e}");
+            self.closed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixListener,
+        path::Path,
+    };
+
+    use crate::runner::TestStatus;
+
+    use super::{Event, ReportSocket};
+
+    #[test]
+    fn events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("socket");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let socket = ReportSocket::connect(&path).unwrap();
+        socket.send(&Event::RunStarted {
+            path: Path::new("/mnt/test"),
+            seed: 42,
+            tests: 2,
+        });
+        socket.send(&Event::TestFinished {
+            test: "rmdir::open_dir",
+            status: TestStatus::Failed,
+            duration: 0.5,
+            message: Some("EBUSY"),
+        });
+        socket.send(&Event::TestSkipped {
+            test: "chflags::set",
+            reasons: &[String::from("requires features: chflags")],
+        });
+        drop(socket);
+
+        let (stream, _) = listener.accept().unwrap();
+        let lines: Vec<_> = BufReader::new(stream).lines().map(Result::unwrap).collect();
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"run_started","path":"/mnt/test","seed":42,"tests":2}"#,
+                r#"{"event":"test_finished","test":"rmdir::open_dir","status":"failed","duration":0.5,"message":"EBUSY"}"#,
+                r#"{"event":"test_skipped","test":"chflags::set","reasons":["requires features: chflags"]}"#,
+            ]
+        );
+    }
+}
@@ -25,16 +25,52 @@ pub enum FileSystemFeature {
     Chflags,
     /// NFSv4 style Access Control Lists are available
     Nfsv4Acls,
+    /// The file system is mounted by an NFS client, which provides close-to-open consistency (the errors it reports in place of the local ones are accepted with the `nfs` conformance profile)
+    NfsClient,
+    /// The server maps root to an anonymous user (`root_squash` on NFS), so root has no privileges on the file system and the tests relying on them are skipped
+    RootSquash,
+    /// Timestamps are stored with nanosecond precision
+    NsecTimestamps,
     /// The [`posix_fallocate`](https://pubs.opengroup.org/onlinepubs/007904975/functions/posix_fallocate.html) syscall is available
     PosixFallocate,
+    /// Holes can be punched in files, with [`fallocate`](https://man7.org/linux/man-pages/man2/fallocate.2.html) and `FALLOC_FL_PUNCH_HOLE` on Linux or [`fspacectl`](https://man.freebsd.org/cgi/man.cgi?fspacectl(2)) on FreeBSD
+    FallocatePunchHole,
+    /// Ranges of files can be zeroed with [`fallocate`](https://man7.org/linux/man-pages/man2/fallocate.2.html) and `FALLOC_FL_ZERO_RANGE`
+    FallocateZeroRange,
+    /// Ranges can be removed from files with [`fallocate`](https://man7.org/linux/man-pages/man2/fallocate.2.html) and `FALLOC_FL_COLLAPSE_RANGE`
+    FallocateCollapseRange,
+    /// Ranges can be inserted in files with [`fallocate`](https://man7.org/linux/man-pages/man2/fallocate.2.html) and `FALLOC_FL_INSERT_RANGE`
+    FallocateInsertRange,
     /// [`rename`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/rename.html) changes `st_ctime` on success (POSIX does not require a file system to update a file's ctime when it gets renamed, but some file systems choose to do it anyway)
     RenameCtime,
+    /// [`rmdir`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/rmdir.html) returns `EBUSY` for a directory which is open, as POSIX allows when the directory is in use
+    RmdirOpenDirBusy,
+    /// Writing to or truncating a regular file without privileges clears its SUID/SGID bits
+    ClearSuidSgidOnWrite,
+    /// The [`clonefile`](https://keith.github.io/xcode-man-pages/clonefile.2.html) syscall is supported by the file system (e.g. APFS)
+    Clonefile,
+    /// Data can be shared between files with the [`FICLONE`, `FICLONERANGE`](https://man7.org/linux/man-pages/man2/ioctl_ficlonerange.2.html) and [`FIDEDUPERANGE`](https://man7.org/linux/man-pages/man2/ioctl_fideduperange.2.html) ioctls (e.g. Btrfs, XFS)
+    Reflink,
+    /// File names which differ only by case refer to the same file, while the case used on creation is preserved (e.g. APFS, SMB, FAT)
+    CaseInsensitive,
+    /// File names which differ only by their Unicode normalization form (NFC or NFD) refer to the same file (e.g. APFS, HFS+)
+    UnicodeNormalization,
+    /// File names have to be valid UTF-8, and other names are rejected with `EILSEQ` (e.g. APFS, ZFS with `utf8only`)
+    Utf8OnlyNames,
+    /// `{NAME_MAX}` counts the characters of a name rather than its bytes, so names of `{NAME_MAX}` multibyte characters are accepted (e.g. APFS, HFS+)
+    NameMaxCharacters,
+    /// Regular files can be sparse, the holes left by writing past their end or by extending them not being allocated
+    SparseFiles,
     /// `struct stat` contains an [`st_birthtime`](https://man.freebsd.org/cgi/man.cgi?stat(2)) field
     StatStBirthtime,
     /// The [`SF_SNAPSHOT`](https://man.freebsd.org/cgi/man.cgi?chflags(2)) flag can be set with `chflags`
     ChflagsSfSnapshot,
+    /// The `O_SYNC`, `O_DSYNC` and `O_RSYNC` flags of [`open`](https://pubs.opengroup.org/onlinepubs/9699919799/functions/open.html) are supported, where the platform defines them
+    SyncFlags,
     /// The [`UTIME_NOW`](https://pubs.opengroup.org/onlinepubs/9699919799.orig/functions/futimens.html) constant is available
     UtimeNow,
     /// The [`utimensat`](https://pubs.opengroup.org/onlinepubs/9699919799.orig/functions/utimensat.html) syscall is available
     Utimensat,
+    /// Extended attributes of the user namespace can be set, with `setxattr` or `extattr_set_file`
+    UserXattrs,
 }
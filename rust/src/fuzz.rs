@@ -0,0 +1,421 @@
+//! Random sequences of file system operations checked against a model, for `pjdfstest fuzz`.
+//!
+//! The operations (create, rename, link, unlink, chmod and truncate) are run on a small set of names
+//! of a temporary directory, and applied to a model of the directory tracking
+//! the expected result of each operation and the expected state of the entries.
+//! The directory is compared with the model after each step,
+//! and a failing sequence is shrunk to the operations needed to reproduce the failure.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use gumdrop::Options;
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    libc::{ino_t, mode_t, off_t},
+    sys::stat::Mode,
+    unistd::Uid,
+};
+use rand::{seq::SliceRandom, Rng};
+use tempfile::tempdir_in;
+
+use crate::{
+    rng, setup,
+    utils::{chmod, link, lstat, open, rename, truncate, unlink},
+};
+
+/// Names the operations are run on, few enough for the operations to often collide.
+const NAMES: [&str; 4] = ["a", "b", "c", "d"];
+
+/// Number of sequences run, by default.
+const DEFAULT_SEQUENCES: usize = 100;
+
+/// Number of operations of each sequence, by default.
+const DEFAULT_LENGTH: usize = 50;
+
+/// Options of the `fuzz` subcommand.
+#[derive(Debug, Options)]
+pub struct FuzzOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(help = "Path of a directory on the file system to fuzz")]
+    path: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Seed of the random generator, to reproduce a previous run"
+    )]
+    seed: Option<u64>,
+
+    #[options(
+        short = "n",
+        help = "Number of sequences of operations to run (100 by default)"
+    )]
+    sequences: Option<usize>,
+
+    #[options(help = "Number of operations of each sequence (50 by default)")]
+    length: Option<usize>,
+}
+
+/// Operation on the entries of the directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Create(&'static str, mode_t),
+    Rename(&'static str, &'static str),
+    Link(&'static str, &'static str),
+    Unlink(&'static str),
+    Chmod(&'static str, mode_t),
+    Truncate(&'static str, off_t),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Create(name, mode) => write!(f, "create({name}, 0o{mode:o})"),
+            Op::Rename(from, to) => write!(f, "rename({from}, {to})"),
+            Op::Link(from, to) => write!(f, "link({from}, {to})"),
+            Op::Unlink(name) => write!(f, "unlink({name})"),
+            Op::Chmod(name, mode) => write!(f, "chmod({name}, 0o{mode:o})"),
+            Op::Truncate(name, size) => write!(f, "truncate({name}, {size})"),
+        }
+    }
+}
+
+impl Op {
+    /// Generate a random operation.
+    fn random(rng: &mut impl Rng) -> Self {
+        let mut name = || *NAMES.choose(rng).unwrap();
+        let (name, other) = (name(), name());
+        let mode = rng.gen_range(0..=0o777);
+        match rng.gen_range(0..6) {
+            0 => Op::Create(name, mode),
+            1 => Op::Rename(name, other),
+            2 => Op::Link(name, other),
+            3 => Op::Unlink(name),
+            4 => Op::Chmod(name, mode),
+            _ => Op::Truncate(name, rng.gen_range(0..=4096)),
+        }
+    }
+
+    /// Run the operation in the directory.
+    fn execute(&self, dir: &Path) -> Result<(), Errno> {
+        match *self {
+            Op::Create(name, mode) => open(
+                &dir.join(name),
+                OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+                Mode::from_bits_truncate(mode),
+            )
+            .map(drop),
+            Op::Rename(from, to) => rename(&dir.join(from), &dir.join(to)),
+            Op::Link(from, to) => link(&dir.join(from), &dir.join(to)),
+            Op::Unlink(name) => unlink(&dir.join(name)),
+            Op::Chmod(name, mode) => chmod(&dir.join(name), Mode::from_bits_truncate(mode)),
+            Op::Truncate(name, size) => truncate(&dir.join(name), size),
+        }
+    }
+}
+
+/// Expected state of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Inode {
+    mode: mode_t,
+    size: off_t,
+    nlink: u64,
+}
+
+impl fmt::Display for Inode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mode 0o{:o}, size {}, {} links",
+            self.mode, self.size, self.nlink
+        )
+    }
+}
+
+/// Expected state of the directory.
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    /// Inode of each entry.
+    entries: BTreeMap<&'static str, usize>,
+    /// Files, indexed by their inode, kept after their last link is removed.
+    inodes: Vec<Inode>,
+    /// Whether the operations are run as root, bypassing the permissions.
+    root: bool,
+}
+
+impl Model {
+    fn new(root: bool) -> Self {
+        Model {
+            root,
+            ..Default::default()
+        }
+    }
+
+    /// Return the inode of an entry.
+    fn inode(&self, name: &str) -> Result<usize, Errno> {
+        self.entries.get(name).copied().ok_or(Errno::ENOENT)
+    }
+
+    /// Remove a link to an inode.
+    fn unlink(&mut self, name: &str) {
+        if let Some(inode) = self.entries.remove(name) {
+            self.inodes[inode].nlink -= 1;
+        }
+    }
+
+    /// Apply the operation, and return its expected result.
+    pub fn apply(&mut self, op: &Op) -> Result<(), Errno> {
+        match *op {
+            Op::Create(name, mode) => {
+                if self.entries.contains_key(name) {
+                    return Err(Errno::EEXIST);
+                }
+                self.inodes.push(Inode {
+                    mode,
+                    size: 0,
+                    nlink: 1,
+                });
+                self.entries.insert(name, self.inodes.len() - 1);
+            }
+            Op::Rename(from, to) => {
+                let inode = self.inode(from)?;
+                // Renaming a link to another link of the same file does nothing
+                if self.entries.get(to) != Some(&inode) {
+                    self.unlink(to);
+                    self.entries.remove(from);
+                    self.entries.insert(to, inode);
+                }
+            }
+            Op::Link(from, to) => {
+                let inode = self.inode(from)?;
+                if self.entries.contains_key(to) {
+                    return Err(Errno::EEXIST);
+                }
+                self.inodes[inode].nlink += 1;
+                self.entries.insert(to, inode);
+            }
+            Op::Unlink(name) => {
+                self.inode(name)?;
+                self.unlink(name);
+            }
+            Op::Chmod(name, mode) => {
+                let inode = self.inode(name)?;
+                self.inodes[inode].mode = mode;
+            }
+            Op::Truncate(name, size) => {
+                let inode = self.inode(name)?;
+                if !self.root && self.inodes[inode].mode & 0o200 == 0 {
+                    return Err(Errno::EACCES);
+                }
+                self.inodes[inode].size = size;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compare the directory with the model.
+    fn check(&self, dir: &Path) -> Result<(), String> {
+        let names: BTreeSet<Vec<u8>> = fs::read_dir(dir)
+            .map_err(|e| format!("cannot read the directory: {e}"))?
+            .map(|entry| entry.map(|entry| entry.file_name().as_bytes().to_vec()))
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("cannot read the directory: {e}"))?;
+        let expected: BTreeSet<Vec<u8>> = self
+            .entries
+            .keys()
+            .map(|name| name.as_bytes().to_vec())
+            .collect();
+        if names != expected {
+            let names: Vec<_> = names.iter().map(|n| String::from_utf8_lossy(n)).collect();
+            return Err(format!(
+                "the directory contains {names:?}, expected {:?}",
+                self.entries.keys().collect::<Vec<_>>()
+            ));
+        }
+
+        // Inode numbers of the files of the model, which should be distinct
+        let mut inodes: HashMap<usize, ino_t> = HashMap::new();
+        for (name, inode) in &self.entries {
+            let expected = &self.inodes[*inode];
+            let file_stat = lstat(&dir.join(name)).map_err(|e| format!("lstat({name}): {e}"))?;
+            let actual = Inode {
+                mode: file_stat.st_mode & 0o7777,
+                size: file_stat.st_size,
+                nlink: file_stat.st_nlink as u64,
+            };
+            if actual != *expected {
+                return Err(format!("{name} has {actual}, expected {expected}"));
+            }
+
+            let ino = *inodes.entry(*inode).or_insert(file_stat.st_ino);
+            if ino != file_stat.st_ino {
+                return Err(format!(
+                    "{name} is not a link to the same file as its other links"
+                ));
+            }
+        }
+        if inodes.values().collect::<BTreeSet<_>>().len() != inodes.len() {
+            return Err(String::from("distinct files have the same inode number"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Failure of a sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Index of the operation after which the directory differs from the model.
+    pub step: usize,
+    pub message: String,
+}
+
+/// Run the sequence in a new temporary directory of `base_path`,
+/// comparing the directory with the model after each operation.
+pub fn replay(base_path: &Path, ops: &[Op]) -> anyhow::Result<Result<(), Failure>> {
+    let dir = tempdir_in(base_path)?;
+    let mut model = Model::new(Uid::effective().is_root());
+
+    for (step, op) in ops.iter().enumerate() {
+        let expected = model.apply(op);
+        let result = op.execute(dir.path());
+        let failure = |message| Ok(Err(Failure { step, message }));
+        if result != expected {
+            let describe = |result: Result<(), Errno>| match result {
+                Ok(()) => String::from("success"),
+                Err(errno) => format!("{errno:?}"),
+            };
+            return failure(format!(
+                "{op} returned {}, expected {}",
+                describe(result),
+                describe(expected)
+            ));
+        }
+        if let Err(message) = model.check(dir.path()) {
+            return failure(format!("after {op}, {message}"));
+        }
+    }
+
+    Ok(Ok(()))
+}
+
+/// Remove the operations which are not needed for the sequence to fail.
+pub fn shrink(mut ops: Vec<Op>, mut fails: impl FnMut(&[Op]) -> bool) -> Vec<Op> {
+    let mut i = 0;
+    while i < ops.len() {
+        let mut candidate = ops.clone();
+        candidate.remove(i);
+        if fails(&candidate) {
+            ops = candidate;
+        } else {
+            i += 1;
+        }
+    }
+
+    ops
+}
+
+/// Run the `fuzz` subcommand with its arguments.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let options = FuzzOptions::parse_args_default(args)?;
+    if options.help {
+        println!(
+            "Usage: pjdfstest fuzz [OPTIONS]\n\n{}",
+            FuzzOptions::usage()
+        );
+        return Ok(());
+    }
+
+    setup();
+    let path = match options.path {
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    let seed = options.seed.unwrap_or_else(rng::gen_seed);
+    println!("Using seed {seed}");
+    rng::set_seed(seed);
+
+    let sequences = options.sequences.unwrap_or(DEFAULT_SEQUENCES);
+    let length = options.length.unwrap_or(DEFAULT_LENGTH);
+    for sequence in 0..sequences {
+        let ops: Vec<_> = rng::with_rng(|rng| (0..length).map(|_| Op::random(rng)).collect());
+        let Err(failure) = replay(&path, &ops)? else {
+            continue;
+        };
+
+        println!(
+            "Sequence {} failed at step {}: {}",
+            sequence + 1,
+            failure.step + 1,
+            failure.message
+        );
+        let ops = shrink(ops[..=failure.step].to_vec(), |ops| {
+            matches!(replay(&path, ops), Ok(Err(_)))
+        });
+        let message = match replay(&path, &ops)? {
+            Err(shrunk_failure) => shrunk_failure.message,
+            Ok(()) => failure.message,
+        };
+        println!("Shrunk to {} operations:", ops.len());
+        for op in &ops {
+            println!("\t{op}");
+        }
+        println!("{message}");
+        println!("Seed: {seed} (reproduce with --seed {seed})");
+        anyhow::bail!("The file system differs from the model");
+    }
+
+    println!("{sequences} sequences of {length} operations passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use super::{shrink, Model, Op};
+
+    #[test]
+    fn model() {
+        let mut model = Model::new(false);
+        assert_eq!(model.apply(&Op::Unlink("a")), Err(Errno::ENOENT));
+        assert_eq!(model.apply(&Op::Create("a", 0o444)), Ok(()));
+        assert_eq!(model.apply(&Op::Create("a", 0o644)), Err(Errno::EEXIST));
+        assert_eq!(model.apply(&Op::Truncate("a", 1)), Err(Errno::EACCES));
+        assert_eq!(model.apply(&Op::Link("a", "b")), Ok(()));
+        assert_eq!(model.apply(&Op::Link("a", "b")), Err(Errno::EEXIST));
+        assert_eq!(model.inodes[0].nlink, 2);
+
+        // Renaming a link over another link of the same file leaves both
+        assert_eq!(model.apply(&Op::Rename("a", "b")), Ok(()));
+        assert_eq!(model.entries.len(), 2);
+
+        assert_eq!(model.apply(&Op::Create("c", 0o644)), Ok(()));
+        assert_eq!(model.apply(&Op::Rename("c", "b")), Ok(()));
+        assert_eq!(model.inodes[0].nlink, 1);
+        assert_eq!(model.entries.get("b"), Some(&1));
+    }
+
+    #[test]
+    fn shrinking() {
+        let ops = vec![
+            Op::Create("a", 0o644),
+            Op::Create("b", 0o644),
+            Op::Unlink("b"),
+            Op::Link("a", "c"),
+            Op::Chmod("c", 0o600),
+        ];
+        // Fails whenever a link is created
+        let fails = |ops: &[Op]| ops.iter().any(|op| matches!(op, Op::Link(..)));
+
+        assert_eq!(shrink(ops, fails), vec![Op::Link("a", "c")]);
+    }
+}
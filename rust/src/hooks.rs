@@ -0,0 +1,151 @@
+//! Execution of the hooks run around each test,
+//! allowing external tooling (snapshots, crash-consistency checkers...) to be driven per test.
+
+use std::{
+    io::Read,
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
+};
+
+use crate::config::SettingsConfig;
+
+/// Maximum duration of a hook when no timeout has been configured.
+const DEFAULT_HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Interval at which a running hook is checked for completion.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Hooks run before and after each test.
+#[derive(Debug)]
+pub struct Hooks<'a> {
+    pre_test: Option<&'a str>,
+    post_test: Option<&'a str>,
+    timeout: Duration,
+}
+
+impl<'a> Hooks<'a> {
+    /// Get the hooks from the settings.
+    pub fn new(settings: &'a SettingsConfig) -> Self {
+        Self {
+            pre_test: settings.pre_test_hook.as_deref(),
+            post_test: settings.post_test_hook.as_deref(),
+            timeout: settings
+                .hook_timeout
+                .map_or(DEFAULT_HOOK_TIMEOUT, Duration::from_secs_f64),
+        }
+    }
+
+    /// Run the hook configured to be run before the test, if any.
+    pub fn pre_test(&self, test_name: &str, path: &Path) -> anyhow::Result<()> {
+        self.pre_test.map_or(Ok(()), |command| {
+            run(command, self.timeout, test_name, path)
+        })
+    }
+
+    /// Run the hook configured to be run after the test, if any.
+    pub fn post_test(&self, test_name: &str, path: &Path) -> anyhow::Result<()> {
+        self.post_test.map_or(Ok(()), |command| {
+            run(command, self.timeout, test_name, path)
+        })
+    }
+}
+
+/// Run the command with `sh -c`, passing the test name and the path of its directory
+/// as positional arguments and with the `PJDFSTEST_TEST_NAME` and `PJDFSTEST_TEST_PATH`
+/// environment variables.
+/// The command runs in its own process group, which is killed with the processes
+/// it started if it doesn't complete before the timeout.
+fn run(command: &str, timeout: Duration, test_name: &str, path: &Path) -> anyhow::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .arg("sh")
+        .arg(test_name)
+        .arg(path)
+        .env("PJDFSTEST_TEST_NAME", test_name)
+        .env("PJDFSTEST_TEST_PATH", path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+
+    // Read the error output concurrently, so the hook doesn't block on a full pipe
+    let mut stderr = child.stderr.take().unwrap();
+    let stderr_reader = thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if start.elapsed() > timeout {
+            // The processes started by the hook would otherwise keep running,
+            // and keep the error output open
+            killpg(Pid::from_raw(child.id() as i32), Signal::SIGKILL)?;
+            child.wait()?;
+            let _ = stderr_reader.join();
+            anyhow::bail!("`{command}` timed out after {}s", timeout.as_secs_f64());
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let stderr = stderr_reader.join().unwrap_or_default();
+    if !status.success() {
+        anyhow::bail!("`{command}` failed ({status}): {}", stderr.trim());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use super::run;
+
+    #[test]
+    fn failure() {
+        let error = run(
+            "echo oops >&2; exit 3",
+            Duration::from_secs(10),
+            "t",
+            Path::new("/"),
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.ends_with(": oops"), "{error}");
+    }
+
+    #[test]
+    fn timeout_kills_group() {
+        let start = Instant::now();
+        let error = run(
+            "sleep 30 & sleep 30",
+            Duration::from_millis(100),
+            "t",
+            Path::new("/"),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        // The background process, which holds the error output, has been killed too
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,182 @@
+//! Invariants of the state of the process, which the tests must leave as they found it.
+//!
+//! The state is captured before and after each test, and a test which leaks file descriptors,
+//! or changes the working directory, the umask or the effective IDs fails with a diagnostic.
+//! The state is then restored, so that the following tests are not affected.
+
+use std::{collections::BTreeSet, env, os::fd::RawFd, path::PathBuf};
+
+use nix::{
+    dir::Dir,
+    fcntl::OFlag,
+    libc::mode_t,
+    sys::stat::{umask, Mode},
+    unistd::{close, getegid, geteuid, setegid, seteuid, Gid, Uid},
+};
+
+/// Directory listing the open file descriptors of the process.
+#[cfg(target_os = "linux")]
+const FD_DIR: &str = "/proc/self/fd";
+#[cfg(not(target_os = "linux"))]
+const FD_DIR: &str = "/dev/fd";
+
+/// State of the process which is shared by the tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessState {
+    pub fds: BTreeSet<RawFd>,
+    pub cwd: Option<PathBuf>,
+    pub umask: mode_t,
+    pub euid: Uid,
+    pub egid: Gid,
+}
+
+/// Return the open file descriptors of the process.
+fn open_fds() -> BTreeSet<RawFd> {
+    let Ok(mut dir) = Dir::open(FD_DIR, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()) else {
+        return BTreeSet::new();
+    };
+    let dir_fd = std::os::fd::AsRawFd::as_raw_fd(&dir);
+
+    dir.iter()
+        .filter_map(|entry| entry.ok()?.file_name().to_str().ok()?.parse().ok())
+        .filter(|fd| *fd != dir_fd)
+        .collect()
+}
+
+/// Describe a file descriptor, with the path of the file it refers to if it is known.
+fn describe_fd(fd: RawFd) -> String {
+    match std::fs::read_link(format!("{FD_DIR}/{fd}")) {
+        Ok(path) => format!("{fd} ({})", path.display()),
+        Err(_) => fd.to_string(),
+    }
+}
+
+impl ProcessState {
+    /// Capture the current state of the process.
+    pub fn capture() -> Self {
+        let mask = umask(Mode::empty());
+        umask(mask);
+
+        ProcessState {
+            fds: open_fds(),
+            cwd: env::current_dir().ok(),
+            umask: mask.bits(),
+            euid: geteuid(),
+            egid: getegid(),
+        }
+    }
+
+    /// Describe how the state differs from a previous one.
+    pub fn diff(&self, previous: &ProcessState) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        let leaked: Vec<_> = self
+            .fds
+            .difference(&previous.fds)
+            .map(|fd| describe_fd(*fd))
+            .collect();
+        if !leaked.is_empty() {
+            differences.push(format!("file descriptors left open: {}", leaked.join(", ")));
+        }
+        let closed: Vec<_> = previous
+            .fds
+            .difference(&self.fds)
+            .map(|fd| fd.to_string())
+            .collect();
+        if !closed.is_empty() {
+            differences.push(format!(
+                "file descriptors of the runner closed: {}",
+                closed.join(", ")
+            ));
+        }
+        if self.cwd != previous.cwd {
+            differences.push(format!(
+                "working directory changed from {:?} to {:?}",
+                previous.cwd, self.cwd
+            ));
+        }
+        if self.umask != previous.umask {
+            differences.push(format!(
+                "umask changed from {:#o} to {:#o}",
+                previous.umask, self.umask
+            ));
+        }
+        if self.euid != previous.euid {
+            differences.push(format!(
+                "effective user ID changed from {} to {}",
+                previous.euid, self.euid
+            ));
+        }
+        if self.egid != previous.egid {
+            differences.push(format!(
+                "effective group ID changed from {} to {}",
+                previous.egid, self.egid
+            ));
+        }
+
+        differences
+    }
+
+    /// Restore a previous state, closing the file descriptors which have been leaked.
+    pub fn restore(&self, previous: &ProcessState) {
+        for fd in self.fds.difference(&previous.fds) {
+            let _ = close(*fd);
+        }
+        if let Some(cwd) = previous.cwd.as_ref().filter(|_| self.cwd != previous.cwd) {
+            let _ = env::set_current_dir(cwd);
+        }
+        umask(Mode::from_bits_truncate(previous.umask));
+        // The user ID has to be restored first, to get the privileges to restore the group ID
+        if self.euid != previous.euid {
+            let _ = seteuid(previous.euid);
+        }
+        if self.egid != previous.egid {
+            let _ = setegid(previous.egid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, path::PathBuf};
+
+    use nix::unistd::{Gid, Uid};
+
+    use super::ProcessState;
+
+    fn state() -> ProcessState {
+        ProcessState {
+            fds: BTreeSet::from([0, 1, 2]),
+            cwd: Some(PathBuf::from("/")),
+            umask: 0,
+            euid: Uid::from_raw(0),
+            egid: Gid::from_raw(0),
+        }
+    }
+
+    #[test]
+    fn unchanged() {
+        assert!(state().diff(&state()).is_empty());
+    }
+
+    #[test]
+    fn changed() {
+        let mut after = state();
+        after.fds.insert(9);
+        after.fds.remove(&2);
+        after.cwd = Some(PathBuf::from("/tmp"));
+        after.umask = 0o22;
+        after.euid = Uid::from_raw(65534);
+
+        let differences = after.diff(&state());
+        assert_eq!(differences.len(), 5);
+        assert!(differences[0].starts_with("file descriptors left open: 9"));
+        assert_eq!(differences[1], "file descriptors of the runner closed: 2");
+        assert_eq!(
+            differences[2],
+            "working directory changed from Some(\"/\") to Some(\"/tmp\")"
+        );
+        assert_eq!(differences[3], "umask changed from 0o0 to 0o22");
+        assert_eq!(differences[4], "effective user ID changed from 0 to 65534");
+    }
+}
@@ -0,0 +1,165 @@
+//! Execution of functions in forked child processes, so that a fatal signal they raise
+//! (e.g. SIGSEGV or SIGBUS) is reported as an error instead of killing the runner.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{Read, Write},
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use nix::{
+    sys::{
+        signal::Signal,
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::{fork, pipe, ForkResult},
+};
+
+use crate::runner::panic_message;
+
+/// How a child process ended, when it did not return a result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChildError {
+    /// The function panicked with the given message.
+    Panicked(String),
+    /// The process was terminated by a signal.
+    Signaled { signal: Signal, core_dumped: bool },
+    /// The process exited without returning a result, with the given status.
+    Exited(i32),
+}
+
+impl Display for ChildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChildError::Panicked(message) => write!(f, "{message}"),
+            ChildError::Signaled {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "terminated by signal {signal} ({})", describe(*signal))?;
+                if *core_dumped {
+                    write!(f, ", core dumped")?;
+                }
+                Ok(())
+            }
+            ChildError::Exited(status) => {
+                write!(f, "exited with status {status} without returning a result")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChildError {}
+
+/// Return what a fatal signal usually means.
+fn describe(signal: Signal) -> &'static str {
+    match signal {
+        Signal::SIGSEGV => "invalid memory reference",
+        Signal::SIGBUS => "access to an undefined portion of a memory object",
+        Signal::SIGILL => "illegal instruction",
+        Signal::SIGFPE => "erroneous arithmetic operation",
+        Signal::SIGABRT => "abort",
+        Signal::SIGKILL => "killed",
+        _ => "unexpected signal",
+    }
+}
+
+/// Execute the function in a forked child process, and return the bytes it returned,
+/// or how the process ended otherwise (panic, signal...).
+///
+/// # Note
+///
+/// Since the function is run in another process, only its effects on the file system
+/// and the bytes it returns are visible afterwards.
+/// The process has to be single-threaded when it is called, which is the case of the runner
+/// when it runs the tests.
+pub fn run_in_child<F>(f: F) -> Result<Vec<u8>, ChildError>
+where
+    F: FnOnce() -> Vec<u8>,
+{
+    let (reader, writer) = pipe().unwrap();
+
+    // SAFETY: The runner is single-threaded when the tests are run
+    match unsafe { fork() }.unwrap() {
+        ForkResult::Child => {
+            drop(reader);
+            let bytes = match catch_unwind(AssertUnwindSafe(f)) {
+                Ok(mut bytes) => {
+                    bytes.insert(0, 0);
+                    bytes
+                }
+                Err(e) => {
+                    let mut bytes = vec![1];
+                    bytes.extend(panic_message(e).into_bytes());
+                    bytes
+                }
+            };
+            let _ = File::from(writer).write_all(&bytes);
+            // SAFETY: Exit without running the destructors of the state shared with the parent
+            unsafe { nix::libc::_exit(0) }
+        }
+        ForkResult::Parent { child } => {
+            drop(writer);
+            let mut bytes = Vec::new();
+            let _ = File::from(reader).read_to_end(&mut bytes);
+
+            match waitpid(child, None).unwrap() {
+                WaitStatus::Signaled(_, signal, core_dumped) => Err(ChildError::Signaled {
+                    signal,
+                    core_dumped,
+                }),
+                WaitStatus::Exited(_, 0) => match bytes.split_first() {
+                    Some((0, result)) => Ok(result.to_vec()),
+                    Some((1, message)) => Err(ChildError::Panicked(
+                        String::from_utf8_lossy(message).into_owned(),
+                    )),
+                    _ => Err(ChildError::Exited(0)),
+                },
+                WaitStatus::Exited(_, status) => Err(ChildError::Exited(status)),
+                status => panic!("unexpected status of the child process: {status:?}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::sys::signal::{self, raise, SigHandler, Signal};
+
+    use super::{run_in_child, ChildError};
+
+    #[test]
+    fn result() {
+        assert_eq!(run_in_child(|| b"result".to_vec()), Ok(b"result".to_vec()));
+        assert_eq!(
+            run_in_child(|| panic!("message")),
+            Err(ChildError::Panicked("message".to_owned()))
+        );
+        assert_eq!(
+            run_in_child(|| unsafe { nix::libc::_exit(3) }),
+            Err(ChildError::Exited(3))
+        );
+    }
+
+    #[test]
+    fn signal() {
+        let res = run_in_child(|| {
+            // The handler installed by the standard library only handles memory faults
+            unsafe { signal::signal(Signal::SIGBUS, SigHandler::SigDfl) }.unwrap();
+            raise(Signal::SIGBUS).unwrap();
+            vec![]
+        });
+        assert!(matches!(
+            res,
+            Err(ChildError::Signaled {
+                signal: Signal::SIGBUS,
+                ..
+            })
+        ));
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .starts_with("terminated by signal SIGBUS"));
+    }
+}
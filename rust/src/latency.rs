@@ -0,0 +1,205 @@
+//! Latency of the file system operations of the tests.
+//!
+//! When enabled, the wrappers of [`crate::utils`] measure the duration of each operation,
+//! and a histogram of the latencies of each syscall family is printed at the end of the run,
+//! so the test suite can double as a performance smoke test of a file system under development.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::recorder::{Record, Recorder};
+
+/// Families of the measured operations, named after their main syscall.
+const FAMILIES: [&str; 13] = [
+    "chmod", "chown", "link", "mkdir", "mknod", "open", "readlink", "rename", "rmdir", "stat",
+    "symlink", "truncate", "unlink",
+];
+
+/// Number of buckets of the histograms, the last one gathering the latencies above 2^BUCKETS µs.
+const BUCKETS: usize = 24;
+
+/// Width of the largest bar of the histograms.
+const BAR_WIDTH: u64 = 40;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Latencies recorded by the current test, taken by the runner after it returns.
+pub(crate) static RECORDER: Recorder<(&'static str, Duration)> = Recorder::new();
+
+/// Enable or disable the measurement of the latencies.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Run an operation of the family, measuring its latency if enabled.
+pub fn measure<T>(family: &'static str, operation: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return operation();
+    }
+
+    let start = Instant::now();
+    let result = operation();
+    RECORDER.record((family, start.elapsed()));
+
+    result
+}
+
+/// Take the latencies recorded since the last call.
+pub fn take() -> Vec<(&'static str, Duration)> {
+    RECORDER.take()
+}
+
+impl Record for (&'static str, Duration) {
+    fn to_line(&self) -> String {
+        format!("{}:{}", self.0, self.1.as_nanos())
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let (family, nanos) = line.split_once(':')?;
+        let family = FAMILIES.iter().find(|f| **f == family)?;
+        Some((*family, Duration::from_nanos(nanos.parse().ok()?)))
+    }
+}
+
+/// Distribution of the latencies of a family, in buckets of powers of two of microseconds.
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    fn add(&mut self, latency: Duration) {
+        let micros = latency.as_micros().max(1);
+        let bucket = (micros.ilog2() as usize).min(BUCKETS - 1);
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Latencies of the file system operations over a run.
+#[derive(Debug, Default)]
+pub struct Latencies {
+    histograms: BTreeMap<&'static str, Histogram>,
+}
+
+impl Latencies {
+    /// Add the latencies recorded by a test.
+    pub fn add(&mut self, latencies: Vec<(&'static str, Duration)>) {
+        for (family, latency) in latencies {
+            self.histograms.entry(family).or_default().add(latency);
+        }
+    }
+
+    /// Merge the latencies of another run.
+    pub fn merge(&mut self, other: Latencies) {
+        for (family, histogram) in other.histograms {
+            self.histograms.entry(family).or_default().merge(&histogram);
+        }
+    }
+
+    /// Return whether no latency has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.histograms.is_empty()
+    }
+}
+
+/// Round a duration to the microsecond, to print it.
+fn micros(duration: Duration) -> Duration {
+    Duration::from_micros(duration.as_micros() as u64)
+}
+
+impl fmt::Display for Latencies {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Latency of the file system operations:")?;
+        for (family, histogram) in &self.histograms {
+            let mean = histogram.total / histogram.count as u32;
+            let calls = match histogram.count {
+                1 => String::from("1 call"),
+                count => format!("{count} calls"),
+            };
+            writeln!(
+                f,
+                "\t{family}: {calls}, mean {:?}, max {:?}",
+                micros(mean),
+                micros(histogram.max)
+            )?;
+
+            let largest = histogram.buckets.iter().copied().max().unwrap_or(0);
+            let first = histogram.buckets.iter().position(|count| *count > 0);
+            let last = histogram.buckets.iter().rposition(|count| *count > 0);
+            let (Some(first), Some(last)) = (first, last) else {
+                continue;
+            };
+            for (bucket, count) in histogram
+                .buckets
+                .iter()
+                .enumerate()
+                .take(last + 1)
+                .skip(first)
+            {
+                let range = match bucket {
+                    0 => String::from("< 2µs"),
+                    _ if bucket == BUCKETS - 1 => format!(">= {}µs", 1u64 << bucket),
+                    _ => format!("{}-{}µs", 1u64 << bucket, 1u64 << (bucket + 1)),
+                };
+                let bar = "#".repeat((count * BAR_WIDTH).div_ceil(largest) as usize);
+                let line = format!("\t\t{range:>12} {count:>8} {bar}");
+                writeln!(f, "{}", line.trim_end())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Latencies;
+
+    #[test]
+    fn histogram() {
+        let mut latencies = Latencies::default();
+        assert!(latencies.is_empty());
+
+        latencies.add(vec![
+            ("mkdir", Duration::from_micros(1)),
+            ("mkdir", Duration::from_micros(5)),
+            ("mkdir", Duration::from_micros(6)),
+        ]);
+        let mut other = Latencies::default();
+        other.add(vec![("rmdir", Duration::from_micros(10))]);
+        latencies.merge(other);
+
+        assert_eq!(
+            latencies.to_string(),
+            "Latency of the file system operations:
+\tmkdir: 3 calls, mean 4µs, max 6µs
+\t\t       < 2µs        1 ####################
+\t\t       2-4µs        0
+\t\t       4-8µs        2 ########################################
+\trmdir: 1 call, mean 10µs, max 10µs
+\t\t      8-16µs        1 ########################################
+"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! pjdfstest is a test suite for POSIX file systems.
+//!
+//! The test suite is composed of a set of test cases, each of which is a function
+//! that takes a [`TestContext`] as an argument. The [`TestContext`] provides access to
+//! the configuration, the dummy authentication entries, and a temporary directory
+//! for the test to use.
+//!
+//! The test suite is built using the `inventory` crate, which
+//! allows test cases to be registered at compile time. The test suite is run by
+//! iterating over the registered test cases and running each one in turn.
+//!
+//! The [`TestContext`] is created for each test case, and the test case function is called
+//! with the [`TestContext`] as an argument. The test case function can then use the
+//! [`TestContext`] to access the configuration, the dummy authentication entries, and
+//! the temporary directory. The test case function can perform whatever tests are
+//! necessary, and panic if the test fails. The test suite catches the panic, prints
+//! an error message, and continues running the remaining test cases.
+//!
+//! Besides the `pjdfstest` runner, the test suite can be run from another harness
+//! (e.g. the integration tests of a file system) with [`run`]:
+//!
+//! ```no_run
+//! use pjdfstest::{config::Config, Filter};
+//!
+//! let mut config = Config::load(Some("pjdfstest.toml".as_ref())).unwrap();
+//! let filter = Filter {
+//!     patterns: vec![String::from("rename::")],
+//!     ..Default::default()
+//! };
+//! let summary = pjdfstest::run(&mut config, &filter, "/mnt/test".as_ref()).unwrap();
+//! assert_eq!(summary.failed, 0);
+//! ```
+//!
+//! [`TestContext`]: crate::context::TestContext
+
+use std::{backtrace::Backtrace, panic::set_hook, path::Path};
+
+use nix::sys::stat::{umask, Mode};
+use tempfile::tempdir_in;
+
+mod artifacts;
+pub mod baseline;
+pub mod bench;
+mod budget;
+pub mod cli;
+pub mod compare;
+pub mod config;
+pub mod conformance;
+pub mod context;
+pub mod events;
+mod features;
+mod flags;
+pub mod fuzz;
+mod hooks;
+mod invariants;
+pub mod isolation;
+pub mod latency;
+
+mod macros;
+pub(crate) use macros::*;
+
+pub mod merge;
+pub mod ops;
+pub mod probe;
+pub mod profile;
+mod recorder;
+pub mod remote;
+pub mod repeat;
+pub mod report;
+pub mod resume;
+pub mod rng;
+pub mod runner;
+pub mod sandbox;
+pub mod snapshot;
+pub mod space;
+pub mod syscall_trace;
+pub mod test;
+mod tests;
+pub mod trace;
+pub mod utils;
+
+// Used by register_test_case! in other crates
+#[doc(hidden)]
+pub use inventory;
+#[doc(hidden)]
+pub use paste;
+
+use config::Config;
+use context::Cleanup;
+use runner::{run_test_cases, RunOptions, RunSummary, BACKTRACE};
+use test::TestCase;
+use utils::timestamp_granularity;
+
+/// Selection of the tests to run, by their names.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    /// Patterns matched against the names of the tests, all the tests are selected if empty.
+    pub patterns: Vec<String>,
+    /// Match the names exactly, instead of the names containing a pattern.
+    pub exact: bool,
+}
+
+impl Filter {
+    /// Return whether the test is selected.
+    pub fn matches(&self, name: &str) -> bool {
+        self.patterns.is_empty()
+            || self.patterns.iter().any(|pat| {
+                if self.exact {
+                    name == pat
+                } else {
+                    name.contains(pat.as_str())
+                }
+            })
+    }
+}
+
+/// Return the registered test cases selected by the filter and not disabled in the configuration,
+/// with the features required by the overrides of the configuration.
+pub fn test_cases(config: &Config, filter: &Filter) -> Vec<TestCase> {
+    inventory::iter::<TestCase>
+        .into_iter()
+        .map(|tc: &TestCase| TestCase {
+            // Ideally trim_start_matches could be done in test_case!, but only
+            // const functions are allowed there.
+            name: tc.name.trim_start_matches("pjdfstest::tests::"),
+            description: tc.description,
+            require_root: tc.require_root,
+            fun: tc.fun,
+            required_features: tc.required_features,
+            guards: tc.guards,
+        })
+        .filter(|tc| filter.matches(tc.name))
+        .filter(|tc| !config.overrides_for(tc.name).any(|o| o.disabled))
+        .map(|mut tc| {
+            let mut required_features = tc.required_features.to_vec();
+            for feature in config.overrides_for(tc.name).flat_map(|o| &o.features) {
+                if !required_features.contains(feature) {
+                    required_features.push(feature.clone());
+                }
+            }
+            if required_features.len() > tc.required_features.len() {
+                // The test cases are kept until the end of the run
+                tc.required_features = Vec::leak(required_features);
+            }
+            tc
+        })
+        .collect()
+}
+
+/// Prepare the process to run the tests:
+/// clear the umask, and capture the backtraces of the failed tests.
+pub fn setup() {
+    umask(Mode::empty());
+
+    set_hook(Box::new(|_| {
+        *BACKTRACE.lock().unwrap() = Some(Backtrace::capture());
+    }));
+}
+
+/// Set the naptime from the timestamp granularity measured in `dir`, if it is not configured.
+pub fn measure_naptime(config: &mut Config, dir: &Path) -> anyhow::Result<()> {
+    if config.settings.naptime.is_none() {
+        // Leave some margin, timestamps should be strictly greater after a nap
+        let naptime = timestamp_granularity(dir)?.as_secs_f64() * 2.0;
+        println!("Measured timestamp granularity, using a naptime of {naptime}s");
+        config.settings.naptime = Some(naptime);
+    }
+
+    Ok(())
+}
+
+/// Run the tests selected by the filter in a new temporary directory of `path`,
+/// with the seed of the configuration (or a new one), and return their summary.
+pub fn run(config: &mut Config, filter: &Filter, path: &Path) -> anyhow::Result<RunSummary> {
+    setup();
+
+    let base_dir = tempdir_in(path)?;
+    measure_naptime(config, base_dir.path())?;
+
+    let test_cases = test_cases(config, filter);
+    let options = RunOptions {
+        verbose: false,
+        isolate: false,
+        remount: None,
+        seed: config.settings.seed.unwrap_or_else(rng::gen_seed),
+        artifacts_dir: None,
+        cleanup: Cleanup::Always,
+        trace: false,
+        syscall_tracer: None,
+        latency: false,
+        report_socket: None,
+        resume: None,
+    };
+
+    run_test_cases(&test_cases, config, base_dir, &options)
+}
@@ -1,9 +1,14 @@
 //! Macros for defining test cases.
 //!
-//! The `test_case` macro is used to define test cases for a test suite.
+//! The `register_test_case` macro is used to define test cases for a test suite.
+//! It is available as `test_case` inside of this crate.
 
 /// Macro for defining test cases, which are automatically registered with the test suite.
 ///
+/// It can be used by other crates (e.g. the tests of a specific file system) to add their own test cases,
+/// which are run along with the test cases of this crate by the runner of the binary linking them,
+/// such as [`cli::main`](crate::cli::main).
+///
 /// A test case can be serialized or non-serialized, require root privileges, and be run on specific file types.
 /// It can also require specific features to be enabled, and have guards which are run before the test case is executed to determine if conditions are met.
 ///
@@ -12,89 +17,110 @@
 ///
 /// ```rust
 /// // Non-serialized test case
-/// test_case! {
-///    /// description
-///   basic
+/// pjdfstest::register_test_case! {
+///     /// description
+///     basic
 /// }
-/// fn basic(_: &mut crate::test::TestContext) {}
+/// fn basic(_: &mut pjdfstest::test::TestContext) {}
 /// ```
 ///
 /// ```rust
+/// # fn guard_example(_: &pjdfstest::config::Config, _: &std::path::Path) -> anyhow::Result<()> {
+/// #     Ok(())
+/// # }
+/// use pjdfstest::test::FileSystemFeature;
+///
 /// // Non-serialized test case with required features, guards, and root privileges
-/// test_case! {
-///   /// description
-///  features, root, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate; guard_example
+/// pjdfstest::register_test_case! {
+///     /// description
+///     features, root, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate; guard_example
 /// }
-/// fn features(_: &mut crate::test::TestContext) {}
+/// fn features(_: &mut pjdfstest::test::TestContext) {}
 /// ```
 ///
 /// ```rust
 /// // Serialized test case with root privileges
-/// test_case! {
-///  /// description
-/// serialized, serialized, root
+/// pjdfstest::register_test_case! {
+///     /// description
+///     serialized, serialized, root
 /// }
-/// fn serialized(_: &mut crate::test::SerializedTestContext) {}
+/// fn serialized(_: &mut pjdfstest::test::SerializedTestContext) {}
 /// ```
 ///
 /// ```rust
+/// use pjdfstest::test::FileSystemFeature;
+///
 /// // Serialized test case with required features
-/// test_case! {
-/// /// description
-/// serialized_features, serialized, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate
+/// pjdfstest::register_test_case! {
+///     /// description
+///     serialized_features, serialized, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate
 /// }
-/// fn serialized_features(_: &mut crate::test::SerializedTestContext) {}
+/// fn serialized_features(_: &mut pjdfstest::test::SerializedTestContext) {}
 /// ```
 ///
 /// ```rust
+/// # fn guard_example(_: &pjdfstest::config::Config, _: &std::path::Path) -> anyhow::Result<()> {
+/// #     Ok(())
+/// # }
+/// use pjdfstest::{context::FileType, test::FileSystemFeature};
+///
 /// // Serialized test case with required features, guards, root privileges, and file types
-/// test_case! {
-/// /// description
-/// serialized_types, serialized, root, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate; guard_example, root => [Regular, Fifo]
+/// pjdfstest::register_test_case! {
+///     /// description
+///     serialized_types, serialized, root, FileSystemFeature::Chflags, FileSystemFeature::PosixFallocate; guard_example => [Regular, Fifo]
 /// }
-/// fn serialized_types(_: &mut crate::test::SerializedTestContext, _: crate::context::FileType) {}
+/// fn serialized_types(_: &mut pjdfstest::test::SerializedTestContext, _: FileType) {}
 /// ```
-macro_rules! test_case {
+#[macro_export]
+macro_rules! register_test_case {
+    (@privileged [$( $guard:expr ),*]) => {
+        &[$( $guard, )* $crate::test::root_privileges]
+    };
     ($(#[doc = $docs:expr])*
         $f:ident, serialized, root $(,)* $( $features:expr ),* $(,)* $(; $( $flags:expr ),+)? $(=> $guards: tt )?) => {
-        $crate::test_case! {@serialized $f, &[$( $features ),*], &[$( $( $flags ),+ )?], concat!($($docs),*), true $(=> $guards)?}
+        $crate::register_test_case! {@serialized $f, &[$( $features ),*], [$( $( $flags ),+ )?], concat!($($docs),*), true $(=> $guards)?}
     };
     ($(#[doc = $docs:expr])*
         $f:ident, serialized $(,)* $( $features:expr ),* $(,)* $(; $( $flags:expr ),+)? $(=> $guards: tt )?) => {
-        $crate::test_case! {@serialized $f, &[$( $features ),*], &[$( $( $flags ),+ )?], concat!($($docs),*), false $(=> $guards)?}
+        $crate::register_test_case! {@serialized $f, &[$( $features ),*], [$( $( $flags ),+ )?], concat!($($docs),*), false $(=> $guards)?}
     };
     ($(#[doc = $docs:expr])*
         $f:ident, root $(,)* $( $features:expr ),* $(,)* $(; $( $flags:expr ),+)? $(=> $guards: tt )?) => {
-        $crate::test_case! {@ $f, &[$( $features ),*], &[$( $( $flags ),+ )?], true, concat!($($docs),*) $(=> $guards)?}
+        $crate::register_test_case! {@ $f, &[$( $features ),*], [$( $( $flags ),+ )?], true, concat!($($docs),*) $(=> $guards)?}
     };
     ($(#[doc = $docs:expr])*
         $f:ident $(,)* $( $features:expr ),* $(,)* $(; $( $flags:expr ),+)? $(=> $guards: tt )?) => {
-        $crate::test_case! {@ $f, &[$( $features ),*], &[$( $( $flags ),+ )?], false, concat!($($docs),*) $(=> $guards)?}
+        $crate::register_test_case! {@ $f, &[$( $features ),*], [$( $( $flags ),+ )?], false, concat!($($docs),*) $(=> $guards)?}
     };
 
 
 
-    (@serialized $f:ident, $features:expr, $guards:expr, $desc:expr, $require_root:expr ) => {
-        ::inventory::submit! {
+    (@serialized $f:ident, $features:expr, $guards:tt, $desc:expr, $require_root:expr ) => {
+        $crate::inventory::submit! {
             $crate::test::TestCase {
                 name: concat!(module_path!(), "::", stringify!($f)),
                 description: $desc,
                 required_features: $features,
-                guards: $guards,
+                guards: &$guards,
                 require_root: $require_root,
                 fun: $crate::test::TestFn::Serialized($f),
             }
         }
     };
-    (@serialized $f:ident, $features:expr, $guards:expr, $desc:expr, $require_root:expr => [$( $file_type:tt $( ($ft_args: tt) )? ),+ $(,)*]) => {
+    (@serialized $f:ident, $features:expr, $guards:tt, $desc:expr, $require_root:expr => [$( $file_type:tt $( ($ft_args: tt) )? ),+ $(,)*]) => {
         $(
-            paste::paste! {
-                ::inventory::submit! {
+            $crate::paste::paste! {
+                $crate::inventory::submit! {
                     $crate::test::TestCase {
                         name: concat!(module_path!(), "::", stringify!($f), "::", stringify!([<$file_type:lower>])),
                         description: $desc,
                         required_features: $features,
-                        guards: $guards,
+                        // Device nodes cannot be created by a squashed root
+                        guards: if $crate::context::FileType::$file_type $( ($ft_args) )?.privileged() {
+                            $crate::register_test_case!(@privileged $guards)
+                        } else {
+                            &$guards
+                        },
                         require_root: $require_root || $crate::context::FileType::$file_type $( ($ft_args) )?.privileged(),
                         fun: $crate::test::TestFn::Serialized(|ctx| $f(ctx, $crate::context::FileType::$file_type $( ($ft_args) )?)),
                     }
@@ -103,27 +129,32 @@ macro_rules! test_case {
         )+
     };
 
-    (@ $f:ident, $features:expr, $guards:expr, $require_root:expr, $desc:expr ) => {
-        ::inventory::submit! {
+    (@ $f:ident, $features:expr, $guards:tt, $require_root:expr, $desc:expr ) => {
+        $crate::inventory::submit! {
             $crate::test::TestCase {
                 name: concat!(module_path!(), "::", stringify!($f)),
                 description: $desc,
                 required_features: $features,
-                guards: $guards,
+                guards: &$guards,
                 require_root: $require_root,
                 fun: $crate::test::TestFn::NonSerialized($f),
             }
         }
     };
-    (@ $f:ident, $features:expr, $guards:expr, $require_root:expr, $desc:expr => [$( $file_type:tt $( ($ft_args: tt) )? ),+ $(,)*]) => {
+    (@ $f:ident, $features:expr, $guards:tt, $require_root:expr, $desc:expr => [$( $file_type:tt $( ($ft_args: tt) )? ),+ $(,)*]) => {
         $(
-            paste::paste! {
-                ::inventory::submit! {
+            $crate::paste::paste! {
+                $crate::inventory::submit! {
                     $crate::test::TestCase {
                         name: concat!(module_path!(), "::", stringify!($f), "::", stringify!([<$file_type:lower>])),
                         description: $desc,
                         required_features: $features,
-                        guards: $guards,
+                        // Device nodes cannot be created by a squashed root
+                        guards: if $crate::context::FileType::$file_type $( ($ft_args) )?.privileged() {
+                            $crate::register_test_case!(@privileged $guards)
+                        } else {
+                            &$guards
+                        },
                         require_root: $require_root || $crate::context::FileType::$file_type $( ($ft_args) )?.privileged(),
                         fun: $crate::test::TestFn::NonSerialized(|ctx| $f(ctx, $crate::context::FileType::$file_type $( ($ft_args) )?)),
                     }
@@ -133,13 +164,13 @@ macro_rules! test_case {
     };
 }
 
-pub(crate) use test_case;
+pub(crate) use crate::register_test_case as test_case;
 
 #[cfg(test)]
 mod t {
     use crate::context::FileType;
     use crate::test::FileSystemFeature;
-    use crate::{SerializedTestContext, TestCase, TestContext, TestFn};
+    use crate::test::{SerializedTestContext, TestCase, TestContext, TestFn};
     use std::path::Path;
 
     crate::test_case! {
@@ -155,7 +186,9 @@ mod t {
         assert_eq!(" description", tc.description);
         assert!(!tc.require_root);
         assert!(tc.required_features.is_empty());
-        assert!(matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == basic as usize));
+        assert!(
+            matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == basic as *const () as usize)
+        );
         assert!(tc.guards.is_empty());
     }
 
@@ -178,7 +211,9 @@ mod t {
                 FileSystemFeature::PosixFallocate
             ]
         );
-        assert!(matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == features as usize));
+        assert!(
+            matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == features as *const () as usize)
+        );
         assert!(tc.guards.is_empty());
     }
 
@@ -200,9 +235,11 @@ mod t {
         assert!(!tc.require_root);
         assert_eq!(
             tc.guards.iter().map(|&g| g as usize).collect::<Vec<_>>(),
-            vec![guard_example as usize]
+            vec![guard_example as *const () as usize]
+        );
+        assert!(
+            matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == guard as *const () as usize)
         );
-        assert!(matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == guard as usize));
     }
 
     crate::test_case! {
@@ -218,7 +255,9 @@ mod t {
         assert_eq!(" description", tc.description);
         assert!(tc.require_root);
         assert!(tc.required_features.is_empty());
-        assert!(matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == root as usize));
+        assert!(
+            matches!(tc.fun, TestFn::NonSerialized(f) if f as usize == root as *const () as usize)
+        );
         assert!(tc.guards.is_empty());
     }
 
@@ -261,7 +300,9 @@ mod t {
         assert_eq!(" description", tc.description);
         assert!(!tc.require_root);
         assert!(tc.required_features.is_empty());
-        assert!(matches!(tc.fun, TestFn::Serialized(f) if f as usize == serialized as usize));
+        assert!(
+            matches!(tc.fun, TestFn::Serialized(f) if f as usize == serialized as *const () as usize)
+        );
         assert!(tc.guards.is_empty());
     }
 }
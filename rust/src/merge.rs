@@ -0,0 +1,468 @@
+//! Aggregation of the results of several runs, for `pjdfstest merge`.
+//!
+//! The baselines saved by the runs (e.g. one per file system or per OS in CI, with `--save-baseline`)
+//! are combined into a matrix of the features and the test statuses of each run,
+//! rendered as Markdown or as a self-contained HTML page.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Context;
+use gumdrop::Options;
+
+use crate::{
+    baseline::{errors_names, status_name, Baseline},
+    compare::{differs, Comparison},
+    report::{escape, html_header, STATUSES},
+    runner::TestStatus,
+};
+
+/// Format of the merged report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            _ => anyhow::bail!("unknown report format {s} (expected markdown or html)"),
+        }
+    }
+}
+
+/// Options of the `merge` subcommand.
+#[derive(Debug, Options)]
+pub struct MergeOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(help = "Format of the report, markdown (by default) or html")]
+    format: Option<ReportFormat>,
+
+    #[options(help = "File where the report is written, instead of the standard output")]
+    output: Option<PathBuf>,
+
+    #[options(
+        no_short,
+        help = "Only list the tests whose status differs between the runs"
+    )]
+    only_differences: bool,
+
+    #[options(free, help = "Baselines saved by the runs (TOML or JSON)")]
+    baselines: Vec<PathBuf>,
+}
+
+/// Results of several runs, each one named after its baseline.
+#[derive(Debug, Default)]
+pub struct Matrix {
+    names: Vec<String>,
+    runs: Comparison,
+    /// Only list the tests whose status differs between the runs.
+    pub only_differences: bool,
+}
+
+impl Matrix {
+    /// Add the results of a run.
+    pub fn add(&mut self, name: String, baseline: Baseline) {
+        self.runs.add(PathBuf::from(&name), baseline);
+        self.names.push(name);
+    }
+
+    /// Return the number of tests of each run with the given status.
+    fn counts(&self, status: TestStatus) -> Vec<usize> {
+        self.runs
+            .baselines()
+            .map(|baseline| baseline.tests.values().filter(|s| **s == status).count())
+            .collect()
+    }
+
+    /// Return the features enabled in any run, with whether each run enabled them.
+    fn features(&self) -> Vec<(&str, Vec<bool>)> {
+        let features: BTreeSet<_> = self
+            .runs
+            .baselines()
+            .flat_map(|baseline| &baseline.features)
+            .collect();
+
+        features
+            .into_iter()
+            .map(|feature| {
+                let enabled = self
+                    .runs
+                    .baselines()
+                    .map(|baseline| baseline.features.contains(feature))
+                    .collect();
+                (feature.as_str(), enabled)
+            })
+            .collect()
+    }
+
+    /// Return the tests listed in the report, with their status on each run
+    /// (`None` if the test was not run).
+    fn tests(&self) -> Vec<(&str, Vec<Option<TestStatus>>)> {
+        if self.only_differences {
+            self.runs.status_differences()
+        } else {
+            self.runs.statuses()
+        }
+    }
+
+    /// Return the errors which differ between the runs, for the tests which passed on all of them,
+    /// as their test, the description of the accepted errors and the errors returned by each run.
+    fn error_differences(&self) -> Vec<(String, String, Vec<String>)> {
+        self.runs
+            .error_differences()
+            .into_iter()
+            .map(|difference| {
+                (
+                    difference.test.to_owned(),
+                    difference.accepted.to_owned(),
+                    difference.returned.into_iter().map(errors_names).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Render the report in the given format.
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.markdown(),
+            ReportFormat::Html => self.html(),
+        }
+    }
+
+    /// Render the report as Markdown tables.
+    pub fn markdown(&self) -> String {
+        let row = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let header = |first: &str| {
+            let mut cells = vec![first.to_owned()];
+            cells.extend(self.names.iter().cloned());
+            let separator = vec!["---".to_owned(); cells.len()];
+            row(cells) + &row(separator)
+        };
+
+        let mut out = format!("# Results of {} runs\n\n", self.names.len());
+
+        out += &header("Status");
+        for status in STATUSES {
+            let mut cells = vec![status_name(status).to_owned()];
+            cells.extend(self.counts(status).iter().map(usize::to_string));
+            out += &row(cells);
+        }
+
+        let features = self.features();
+        if !features.is_empty() {
+            out += "\n## Features\n\n";
+            out += &header("Feature");
+            for (feature, enabled) in features {
+                let mut cells = vec![feature.to_owned()];
+                cells.extend(
+                    enabled
+                        .iter()
+                        .map(|e| if *e { "yes" } else { "no" }.to_owned()),
+                );
+                out += &row(cells);
+            }
+        }
+
+        let tests = self.tests();
+        if !tests.is_empty() {
+            out += if self.only_differences {
+                "\n## Tests whose status differs\n\n"
+            } else {
+                "\n## Tests\n\n"
+            };
+            out += &header("Test");
+            for (test, statuses) in tests {
+                let mut cells = vec![test.to_owned()];
+                cells.extend(
+                    statuses
+                        .iter()
+                        .map(|status| status.map_or("not run", status_name).to_owned()),
+                );
+                out += &row(cells);
+            }
+        }
+
+        let error_differences = self.error_differences();
+        if !error_differences.is_empty() {
+            out += "\n## Errors which differ\n\n";
+            out += &header("Test");
+            for (test, accepted, returned) in error_differences {
+                let mut cells = vec![format!("{test} ({accepted})")];
+                cells.extend(returned);
+                out += &row(cells);
+            }
+        }
+
+        out
+    }
+
+    /// Render the report as a self-contained HTML page.
+    pub fn html(&self) -> String {
+        let header = |first: &str| {
+            let mut out = format!("<tr><th>{}</th>", escape(first));
+            for name in &self.names {
+                let _ = write!(out, "<th>{}</th>", escape(name));
+            }
+            out + "</tr>\n"
+        };
+        let cell =
+            |class: &str, content: &str| format!("<td class=\"{class}\">{}</td>", escape(content));
+
+        let mut out = html_header("pjdfstest results");
+        let _ = writeln!(out, "<h1>Results of {} runs</h1>", self.names.len());
+
+        out += "<table>\n";
+        out += &header("Status");
+        for status in STATUSES {
+            let name = status_name(status);
+            out += &format!("<tr><td>{name}</td>");
+            for count in self.counts(status) {
+                out += &cell(name, &count.to_string());
+            }
+            out += "</tr>\n";
+        }
+        out += "</table>\n";
+
+        let features = self.features();
+        if !features.is_empty() {
+            out += "<h2>Features</h2>\n<table>\n";
+            out += &header("Feature");
+            for (feature, enabled) in features {
+                out += &format!("<tr><td>{}</td>", escape(feature));
+                for enabled in enabled {
+                    out += &if enabled {
+                        cell("passed", "yes")
+                    } else {
+                        cell("not_run", "no")
+                    };
+                }
+                out += "</tr>\n";
+            }
+            out += "</table>\n";
+        }
+
+        let tests = self.tests();
+        if !tests.is_empty() {
+            out += if self.only_differences {
+                "<h2>Tests whose status differs</h2>\n<table>\n"
+            } else {
+                "<h2>Tests</h2>\n<table>\n"
+            };
+            out += &header("Test");
+            for (test, statuses) in tests {
+                let class = if differs(&statuses) {
+                    " class=\"differs\""
+                } else {
+                    ""
+                };
+                out += &format!("<tr{class}><td>{}</td>", escape(test));
+                for status in statuses {
+                    out += &match status {
+                        Some(status) => cell(status_name(status), status_name(status)),
+                        None => cell("not_run", "not run"),
+                    };
+                }
+                out += "</tr>\n";
+            }
+            out += "</table>\n";
+        }
+
+        let error_differences = self.error_differences();
+        if !error_differences.is_empty() {
+            out += "<h2>Errors which differ</h2>\n<table>\n";
+            out += &header("Test");
+            for (test, accepted, returned) in error_differences {
+                out += &format!("<tr><td>{}</td>", escape(&format!("{test} ({accepted})")));
+                for errors in returned {
+                    out += &cell("errors", &errors);
+                }
+                out += "</tr>\n";
+            }
+            out += "</table>\n";
+        }
+
+        out + "</body>\n</html>\n"
+    }
+}
+
+/// Return the names of the runs, from the names of their baselines without extension,
+/// or their whole paths if several baselines have the same name.
+fn run_names(paths: &[PathBuf]) -> Vec<String> {
+    let stem = |path: &Path| {
+        path.file_stem().map_or_else(
+            || path.display().to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+    };
+    let mut stems: BTreeMap<String, usize> = BTreeMap::new();
+    for path in paths {
+        *stems.entry(stem(path)).or_default() += 1;
+    }
+
+    paths
+        .iter()
+        .map(|path| {
+            let name = stem(path);
+            if stems[&name] > 1 {
+                path.display().to_string()
+            } else {
+                name
+            }
+        })
+        .collect()
+}
+
+/// Run the `merge` subcommand with its arguments, and write the report.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let options = MergeOptions::parse_args_default(args)?;
+    if options.help {
+        println!(
+            "Usage: pjdfstest merge [OPTIONS] BASELINES...\n\n{}",
+            MergeOptions::usage()
+        );
+        return Ok(());
+    }
+    if options.baselines.is_empty() {
+        anyhow::bail!("at least one baseline has to be given");
+    }
+
+    let mut matrix = Matrix {
+        only_differences: options.only_differences,
+        ..Default::default()
+    };
+    for (path, name) in options.baselines.iter().zip(run_names(&options.baselines)) {
+        matrix.add(name, Baseline::load(path)?);
+    }
+
+    let report = matrix.render(options.format.unwrap_or_default());
+    match &options.output {
+        Some(path) => fs::write(path, report)
+            .with_context(|| format!("cannot write report {}", path.display()))?,
+        None => print!("{report}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        path::PathBuf,
+    };
+
+    use crate::{baseline::Baseline, runner::TestStatus};
+
+    use super::{run_names, Matrix};
+
+    fn baseline(open_dir: TestStatus, enotempty: &str) -> Baseline {
+        Baseline {
+            features: BTreeSet::from([String::from("chflags")]),
+            tests: BTreeMap::from([
+                (String::from("rmdir::open_dir"), open_dir),
+                (String::from("rmdir::enotempty"), TestStatus::Passed),
+            ]),
+            errors: BTreeMap::from([(
+                String::from("rmdir::enotempty"),
+                BTreeMap::from([(
+                    String::from("EEXIST or ENOTEMPTY"),
+                    BTreeSet::from([String::from(enotempty)]),
+                )]),
+            )]),
+        }
+    }
+
+    fn matrix() -> Matrix {
+        let mut matrix = Matrix::default();
+        matrix.add(
+            String::from("zfs"),
+            baseline(TestStatus::Passed, "ENOTEMPTY"),
+        );
+        matrix.add(String::from("ext4"), baseline(TestStatus::Failed, "EEXIST"));
+        matrix
+    }
+
+    #[test]
+    fn markdown() {
+        assert_eq!(
+            matrix().markdown(),
+            "# Results of 2 runs
+
+| Status | zfs | ext4 |
+| --- | --- | --- |
+| passed | 2 | 1 |
+| failed | 0 | 1 |
+| skipped | 0 | 0 |
+| expected_failure | 0 | 0 |
+| unexpected_pass | 0 | 0 |
+
+## Features
+
+| Feature | zfs | ext4 |
+| --- | --- | --- |
+| chflags | yes | yes |
+
+## Tests
+
+| Test | zfs | ext4 |
+| --- | --- | --- |
+| rmdir::enotempty | passed | passed |
+| rmdir::open_dir | passed | failed |
+
+## Errors which differ
+
+| Test | zfs | ext4 |
+| --- | --- | --- |
+| rmdir::enotempty (EEXIST or ENOTEMPTY) | ENOTEMPTY | EEXIST |
+"
+        );
+    }
+
+    #[test]
+    fn only_differences() {
+        let mut matrix = matrix();
+        matrix.only_differences = true;
+        let report = matrix.markdown();
+
+        assert!(report.contains("| rmdir::open_dir | passed | failed |"));
+        assert!(!report.contains("| rmdir::enotempty | passed | passed |"));
+    }
+
+    #[test]
+    fn html() {
+        let report = matrix().html();
+
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains(
+            "<tr class=\"differs\"><td>rmdir::open_dir</td><td class=\"passed\">passed</td><td class=\"failed\">failed</td></tr>"
+        ));
+    }
+
+    #[test]
+    fn names() {
+        let paths = [
+            PathBuf::from("ci/zfs.toml"),
+            PathBuf::from("linux/ext4.json"),
+            PathBuf::from("freebsd/ext4.json"),
+        ];
+
+        assert_eq!(
+            run_names(&paths),
+            ["zfs", "linux/ext4.json", "freebsd/ext4.json"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Backend of the file system operations used by the tests.
+//!
+//! The tests call the path-based syscalls through the wrappers of [`crate::utils`],
+//! which use the backend of the process: by default [`NixOps`], which calls the syscalls directly.
+//! Another backend can be set with [`set_backend`] before running the tests
+//! (e.g. a client library of a network file system, or the in-process API of a FUSE file system),
+//! to test a file system without mounting it.
+//!
+//! The operations on file descriptors (e.g. `read`, `write` or `fstat`)
+//! are done directly on the descriptors returned by [`FsOps::open`].
+
+use std::{
+    ffi::{CStr, OsString},
+    os::fd::{FromRawFd, OwnedFd},
+    sync::OnceLock,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{readlink, renameat, AtFlags, OFlag},
+    libc::{dev_t, off_t},
+    sys::stat::{fchmodat, lstat, mknod, stat, FchmodatFlags, FileStat, Mode, SFlag},
+    unistd::{chown, fchownat, linkat, mkdir, mkfifo, symlinkat, truncate, unlink, Gid, Uid},
+};
+
+use crate::trace::{self, TracedOps};
+
+/// Path-based file system operations, with the semantics of the syscalls of the same name.
+/// The paths are either absolute or relative to the current working directory.
+pub trait FsOps: Send + Sync {
+    fn open(&self, path: &CStr, oflag: OFlag, mode: Mode) -> nix::Result<OwnedFd>;
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat>;
+    fn lstat(&self, path: &CStr) -> nix::Result<FileStat>;
+    fn mkdir(&self, path: &CStr, mode: Mode) -> nix::Result<()>;
+    fn rmdir(&self, path: &CStr) -> nix::Result<()>;
+    fn unlink(&self, path: &CStr) -> nix::Result<()>;
+    fn rename(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()>;
+    fn link(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()>;
+    fn symlink(&self, target: &CStr, path: &CStr) -> nix::Result<()>;
+    fn readlink(&self, path: &CStr) -> nix::Result<OsString>;
+    fn chmod(&self, path: &CStr, mode: Mode) -> nix::Result<()>;
+    /// Change the mode of a symlink itself.
+    fn lchmod(&self, path: &CStr, mode: Mode) -> nix::Result<()>;
+    fn chown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()>;
+    fn lchown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()>;
+    fn truncate(&self, path: &CStr, len: off_t) -> nix::Result<()>;
+    fn mknod(&self, path: &CStr, kind: SFlag, perm: Mode, dev: dev_t) -> nix::Result<()>;
+    fn mkfifo(&self, path: &CStr, mode: Mode) -> nix::Result<()>;
+}
+
+/// Backend calling the syscalls of the operating system.
+#[derive(Debug, Default)]
+pub struct NixOps;
+
+impl FsOps for NixOps {
+    fn open(&self, path: &CStr, oflag: OFlag, mode: Mode) -> nix::Result<OwnedFd> {
+        // SAFETY: The file descriptor was initialized only by open and isn't used anywhere else,
+        // leaving the ownership to the caller.
+        nix::fcntl::open(path, oflag, mode).map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat> {
+        stat(path)
+    }
+
+    fn lstat(&self, path: &CStr) -> nix::Result<FileStat> {
+        lstat(path)
+    }
+
+    fn mkdir(&self, path: &CStr, mode: Mode) -> nix::Result<()> {
+        mkdir(path, mode)
+    }
+
+    fn rmdir(&self, path: &CStr) -> nix::Result<()> {
+        // SAFETY: The path is a valid C string.
+        let res = unsafe { nix::libc::rmdir(path.as_ptr()) };
+        Errno::result(res).map(drop)
+    }
+
+    fn unlink(&self, path: &CStr) -> nix::Result<()> {
+        unlink(path)
+    }
+
+    fn rename(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()> {
+        renameat(None, old_path, None, new_path)
+    }
+
+    fn link(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()> {
+        linkat(None, old_path, None, new_path, AtFlags::empty())
+    }
+
+    fn symlink(&self, target: &CStr, path: &CStr) -> nix::Result<()> {
+        symlinkat(target, None, path)
+    }
+
+    fn readlink(&self, path: &CStr) -> nix::Result<OsString> {
+        readlink(path)
+    }
+
+    fn chmod(&self, path: &CStr, mode: Mode) -> nix::Result<()> {
+        fchmodat(None, path, mode, FchmodatFlags::FollowSymlink)
+    }
+
+    fn lchmod(&self, path: &CStr, mode: Mode) -> nix::Result<()> {
+        fchmodat(None, path, mode, FchmodatFlags::NoFollowSymlink)
+    }
+
+    fn chown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()> {
+        chown(path, owner, group)
+    }
+
+    fn lchown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()> {
+        fchownat(None, path, owner, group, AtFlags::AT_SYMLINK_NOFOLLOW)
+    }
+
+    fn truncate(&self, path: &CStr, len: off_t) -> nix::Result<()> {
+        truncate(path, len)
+    }
+
+    fn mknod(&self, path: &CStr, kind: SFlag, perm: Mode, dev: dev_t) -> nix::Result<()> {
+        mknod(path, kind, perm, dev)
+    }
+
+    fn mkfifo(&self, path: &CStr, mode: Mode) -> nix::Result<()> {
+        mkfifo(path, mode)
+    }
+}
+
+static BACKEND: OnceLock<Box<dyn FsOps>> = OnceLock::new();
+
+/// Set the backend of the file system operations, before running the tests.
+/// It can only be set once, and the backend is returned if it was already set.
+pub fn set_backend(ops: Box<dyn FsOps>) -> Result<(), Box<dyn FsOps>> {
+    BACKEND.set(ops)
+}
+
+/// Return the backend of the file system operations,
+/// which records them when they are traced (see [`crate::trace`]).
+pub fn backend() -> &'static dyn FsOps {
+    if trace::enabled() {
+        &TracedOps
+    } else {
+        configured_backend()
+    }
+}
+
+/// Return the backend which was set, or the default one.
+pub(crate) fn configured_backend() -> &'static dyn FsOps {
+    BACKEND.get_or_init(|| Box::new(NixOps)).as_ref()
+}
@@ -0,0 +1,425 @@
+//! Probing of the features supported by a file system, for `pjdfstest probe`.
+//!
+//! Each feature is probed by using it in a temporary directory of the file system,
+//! and the supported ones are printed as a `[features]` section of the configuration file.
+
+use std::{
+    fmt,
+    fs::File,
+    io::Read,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use gumdrop::Options;
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        stat::{lstat, stat, utimensat, Mode, UtimensatFlags},
+        time::TimeSpec,
+    },
+};
+use tempfile::tempdir_in;
+
+use crate::{
+    config::AtimePolicy,
+    test::{FileFlags, FileSystemFeature},
+    utils::{open, rename, timestamp_granularity},
+};
+
+/// Options of the `probe` subcommand.
+#[derive(Debug, Options)]
+pub struct ProbeOptions {
+    #[options(help = "print help message")]
+    help: bool,
+
+    #[options(help = "Path of a directory on the file system to probe")]
+    path: Option<PathBuf>,
+}
+
+/// Features found to be supported by a file system.
+#[derive(Debug, Default)]
+pub struct ProbedFeatures {
+    /// Access time update policy.
+    pub atime_policy: Option<AtimePolicy>,
+    /// File flags which can be set and cleared.
+    pub file_flags: Vec<FileFlags>,
+    /// File flags which cannot be set, with `EOPNOTSUPP`.
+    pub unsupported_file_flags: Vec<FileFlags>,
+    /// Features which do not require any additional configuration.
+    pub fs_features: Vec<FileSystemFeature>,
+}
+
+impl fmt::Display for ProbedFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = |flags: &[FileFlags]| {
+            flags
+                .iter()
+                .map(|flag| format!("\"{flag}\""))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        writeln!(f, "[features]")?;
+        if let Some(policy) = self.atime_policy {
+            let policy = match policy {
+                AtimePolicy::Strict => "strict",
+                AtimePolicy::Relatime => "relatime",
+                AtimePolicy::Noatime => "noatime",
+            };
+            writeln!(f, "atime_policy = \"{policy}\"")?;
+        }
+        if !self.file_flags.is_empty() {
+            writeln!(f, "file_flags = [{}]", flags(&self.file_flags))?;
+        }
+        if !self.unsupported_file_flags.is_empty() {
+            writeln!(
+                f,
+                "unsupported_file_flags = [{}]",
+                flags(&self.unsupported_file_flags)
+            )?;
+        }
+        for feature in &self.fs_features {
+            writeln!(f, "{feature} = {{}}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Probe which returns whether a feature is supported, in a temporary directory.
+type Probe = fn(&Path) -> nix::Result<bool>;
+
+/// Create a regular file.
+fn create(path: &Path) -> nix::Result<PathBuf> {
+    open(
+        path,
+        OFlag::O_CREAT | OFlag::O_WRONLY,
+        Mode::from_bits_truncate(0o644),
+    )?;
+    Ok(path.to_path_buf())
+}
+
+/// Return whether two paths refer to the same file.
+fn same_file(a: &Path, b: &Path) -> nix::Result<bool> {
+    Ok(lstat(a)?.st_ino == lstat(b)?.st_ino)
+}
+
+/// Create a file filled with 4 blocks of data, and return it with its block size.
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+fn create_with_blocks(path: &Path) -> nix::Result<(File, i64)> {
+    use crate::utils::write_blocks;
+
+    let file = File::from(open(
+        path,
+        OFlag::O_CREAT | OFlag::O_RDWR,
+        Mode::from_bits_truncate(0o644),
+    )?);
+    let block_size = write_blocks(&file, 4).map_err(|_| Errno::EIO)?;
+
+    Ok((file, block_size as i64))
+}
+
+/// Probe a mode of fallocate on a file of 4 blocks.
+#[cfg(target_os = "linux")]
+fn fallocate_mode(dir: &Path, name: &str, flags: nix::fcntl::FallocateFlags) -> nix::Result<bool> {
+    let (file, block_size) = create_with_blocks(&dir.join(name))?;
+    nix::fcntl::fallocate(file.as_raw_fd(), flags, block_size, block_size).map(|_| true)
+}
+
+const PROBES: &[(FileSystemFeature, Probe)] = &[
+    (FileSystemFeature::Utimensat, |dir| {
+        let time = TimeSpec::new(1_000_000_000, 0);
+        let file = create(&dir.join("utimensat"))?;
+        utimensat(None, &file, &time, &time, UtimensatFlags::FollowSymlink)?;
+        Ok(stat(&file)?.st_mtime == 1_000_000_000)
+    }),
+    (FileSystemFeature::UtimeNow, |dir| {
+        let now = TimeSpec::new(0, nix::libc::UTIME_NOW);
+        let file = create(&dir.join("utime_now"))?;
+        utimensat(None, &file, &now, &now, UtimensatFlags::FollowSymlink).map(|_| true)
+    }),
+    (FileSystemFeature::NsecTimestamps, |dir| {
+        let time = TimeSpec::new(1_000_000_000, 123_456_789);
+        let file = create(&dir.join("nsec_timestamps"))?;
+        utimensat(None, &file, &time, &time, UtimensatFlags::FollowSymlink)?;
+        Ok(stat(&file)?.st_mtime_nsec == 123_456_789)
+    }),
+    #[cfg(birthtime)]
+    (FileSystemFeature::StatStBirthtime, |dir| {
+        Ok(stat(&create(&dir.join("birthtime"))?)?.st_birthtime > 0)
+    }),
+    (FileSystemFeature::RenameCtime, |dir| {
+        let from = create(&dir.join("rename_ctime"))?;
+        let to = dir.join("rename_ctime_to");
+        let before = stat(&from)?;
+        let granularity = timestamp_granularity(dir).map_err(|_| Errno::EIO)?;
+        thread::sleep(granularity * 2);
+        rename(&from, &to)?;
+        let after = stat(&to)?;
+        Ok((after.st_ctime, after.st_ctime_nsec) != (before.st_ctime, before.st_ctime_nsec))
+    }),
+    #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+    (FileSystemFeature::PosixFallocate, |dir| {
+        let file = File::create(dir.join("posix_fallocate")).map_err(|_| Errno::EIO)?;
+        nix::fcntl::posix_fallocate(file.as_raw_fd(), 0, 4096).map(|_| true)
+    }),
+    #[cfg(target_os = "linux")]
+    (FileSystemFeature::FallocatePunchHole, |dir| {
+        use nix::fcntl::FallocateFlags;
+
+        fallocate_mode(
+            dir,
+            "punch_hole",
+            FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE,
+        )
+    }),
+    #[cfg(target_os = "freebsd")]
+    (FileSystemFeature::FallocatePunchHole, |dir| {
+        let (file, block_size) = create_with_blocks(&dir.join("punch_hole"))?;
+        crate::utils::fspacectl_dealloc(file.as_raw_fd(), block_size, block_size).map(|_| true)
+    }),
+    #[cfg(target_os = "linux")]
+    (FileSystemFeature::FallocateZeroRange, |dir| {
+        fallocate_mode(
+            dir,
+            "zero_range",
+            nix::fcntl::FallocateFlags::FALLOC_FL_ZERO_RANGE,
+        )
+    }),
+    #[cfg(target_os = "linux")]
+    (FileSystemFeature::FallocateCollapseRange, |dir| {
+        fallocate_mode(
+            dir,
+            "collapse_range",
+            nix::fcntl::FallocateFlags::FALLOC_FL_COLLAPSE_RANGE,
+        )
+    }),
+    #[cfg(target_os = "linux")]
+    (FileSystemFeature::FallocateInsertRange, |dir| {
+        fallocate_mode(
+            dir,
+            "insert_range",
+            nix::fcntl::FallocateFlags::FALLOC_FL_INSERT_RANGE,
+        )
+    }),
+    #[cfg(target_os = "linux")]
+    (FileSystemFeature::Reflink, |dir| {
+        let src = File::create(dir.join("reflink_src")).map_err(|_| Errno::EIO)?;
+        let dest = File::create(dir.join("reflink_dest")).map_err(|_| Errno::EIO)?;
+        crate::tests::reflink::ficlone(dest.as_raw_fd(), src.as_raw_fd()).map(|_| true)
+    }),
+    #[cfg(target_os = "macos")]
+    (FileSystemFeature::Clonefile, |dir| {
+        let src = create(&dir.join("clonefile_src"))?;
+        crate::tests::clonefile::clonefile_wrapper(&src, &dir.join("clonefile_dest")).map(|_| true)
+    }),
+    (FileSystemFeature::SparseFiles, |dir| {
+        let file = open(
+            &dir.join("sparse"),
+            OFlag::O_CREAT | OFlag::O_WRONLY,
+            Mode::from_bits_truncate(0o644),
+        )?;
+        let offset = crate::tests::blocks::SPARSE_OFFSET;
+        nix::sys::uio::pwrite(&file, b"sparse", offset as nix::libc::off_t)?;
+        nix::unistd::fsync(file.as_raw_fd())?;
+        nix::unistd::sync();
+        let allocated = crate::tests::blocks::allocated(&file);
+        Ok(allocated < offset / 2)
+    }),
+    (FileSystemFeature::SyncFlags, |dir| {
+        let file = create(&dir.join("sync_flags"))?;
+        for (_, flag) in crate::tests::o_sync::sync_flags() {
+            open(&file, OFlag::O_WRONLY | flag, Mode::empty())?;
+        }
+        Ok(true)
+    }),
+    #[cfg(chflags)]
+    (FileSystemFeature::Chflags, |dir| {
+        let file = create(&dir.join("chflags"))?;
+        nix::unistd::chflags(&file, nix::sys::stat::FileFlag::empty()).map(|_| true)
+    }),
+    #[cfg(target_os = "freebsd")]
+    (FileSystemFeature::Nfsv4Acls, |dir| {
+        let res = nix::NixPath::with_nix_path(dir, |path| unsafe {
+            nix::libc::pathconf(path.as_ptr(), nix::libc::_PC_ACL_NFS4)
+        })?;
+        Ok(res == 1)
+    }),
+    #[cfg(target_os = "macos")]
+    (FileSystemFeature::Nfsv4Acls, |dir| {
+        let res = nix::NixPath::with_nix_path(dir, |path| unsafe {
+            nix::libc::pathconf(path.as_ptr(), nix::libc::_PC_EXTENDED_SECURITY_NP)
+        })?;
+        Ok(res == 1)
+    }),
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+    (FileSystemFeature::UserXattrs, |dir| {
+        let file = create(&dir.join("user_xattrs"))?;
+        crate::utils::set_user_xattr(&file, c"pjdfstest", b"value").map(|_| true)
+    }),
+    (FileSystemFeature::CaseInsensitive, |dir| {
+        let file = create(&dir.join("case"))?;
+        Ok(lstat(&dir.join("CASE")).is_ok() && same_file(&file, &dir.join("CASE"))?)
+    }),
+    (FileSystemFeature::UnicodeNormalization, |dir| {
+        let nfc = create(&dir.join("\u{e9}"))?;
+        let nfd = dir.join("e\u{301}");
+        Ok(lstat(&nfd).is_ok() && same_file(&nfc, &nfd)?)
+    }),
+    (FileSystemFeature::Utf8OnlyNames, |dir| {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = std::ffi::OsStr::from_bytes(b"invalid_\xff");
+        Ok(create(&dir.join(name)) == Err(Errno::EILSEQ))
+    }),
+    (FileSystemFeature::NameMaxCharacters, |dir| {
+        let name_max = nix::unistd::pathconf(dir, nix::unistd::PathconfVar::NAME_MAX)?;
+        let Some(name_max) = name_max else {
+            return Ok(false);
+        };
+        Ok(create(&dir.join("\u{e9}".repeat(name_max as usize))).is_ok())
+    }),
+];
+
+/// Probe the access time update policy, by reading a file whose access time
+/// is older than its modification time, and then reading it again.
+fn atime_policy(dir: &Path) -> nix::Result<AtimePolicy> {
+    let path = dir.join("atime");
+    std::fs::write(&path, b"atime").map_err(|_| Errno::EIO)?;
+    let old = TimeSpec::new(1_000_000_000, 0);
+    let now = TimeSpec::new(0, nix::libc::UTIME_NOW);
+    utimensat(None, &path, &old, &now, UtimensatFlags::FollowSymlink)?;
+
+    let read = || -> nix::Result<i64> {
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut Vec::new()))
+            .map_err(|_| Errno::EIO)?;
+        Ok(stat(&path)?.st_atime)
+    };
+
+    if read()? == old.tv_sec() {
+        return Ok(AtimePolicy::Noatime);
+    }
+    let first = stat(&path)?;
+    let granularity = timestamp_granularity(dir).map_err(|_| Errno::EIO)?;
+    thread::sleep(granularity * 2);
+    read()?;
+    let second = stat(&path)?;
+
+    if (second.st_atime, second.st_atime_nsec) != (first.st_atime, first.st_atime_nsec) {
+        Ok(AtimePolicy::Strict)
+    } else {
+        Ok(AtimePolicy::Relatime)
+    }
+}
+
+/// Probe the file flags which can be set and cleared on their own,
+/// and the ones which are not supported.
+#[cfg(file_flags)]
+fn file_flags(dir: &Path, features: &mut ProbedFeatures) -> nix::Result<()> {
+    use nix::{sys::stat::FileFlag, unistd::chflags};
+    use strum::IntoEnumIterator;
+
+    let file = create(&dir.join("file_flags"))?;
+
+    // Masks such as UF_SETTABLE are not flags on their own
+    for flag in FileFlags::iter().filter(|flag| FileFlag::from(*flag).bits().count_ones() == 1) {
+        match chflags(&file, flag.into()) {
+            Ok(()) => {
+                let set = FileFlag::from_bits_truncate(lstat(&file)?.st_flags.into());
+                chflags(&file, FileFlag::empty())?;
+                if set.contains(flag.into()) {
+                    features.file_flags.push(flag);
+                }
+            }
+            Err(Errno::EOPNOTSUPP) => features.unsupported_file_flags.push(flag),
+            Err(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(file_flags))]
+fn file_flags(_: &Path, _: &mut ProbedFeatures) -> nix::Result<()> {
+    Ok(())
+}
+
+/// Run the `probe` subcommand with its arguments, and print the `[features]` section.
+pub fn run(args: &[String]) -> anyhow::Result<()> {
+    let options = ProbeOptions::parse_args_default(args)?;
+    if options.help {
+        println!(
+            "Usage: pjdfstest probe [OPTIONS]\n\n{}",
+            ProbeOptions::usage()
+        );
+        return Ok(());
+    }
+
+    let path = match options.path {
+        Some(path) => path,
+        None => std::env::current_dir()?,
+    };
+    print!("{}", probe(&path)?);
+
+    Ok(())
+}
+
+/// Probe the features supported by the file system of `base_path`, in a temporary directory.
+pub fn probe(base_path: &Path) -> anyhow::Result<ProbedFeatures> {
+    let dir = tempdir_in(base_path)?;
+    let mut features = ProbedFeatures {
+        atime_policy: atime_policy(dir.path()).ok(),
+        ..Default::default()
+    };
+
+    file_flags(dir.path(), &mut features)?;
+
+    for (feature, probe) in PROBES {
+        if let Ok(true) = probe(dir.path()) {
+            features.fs_features.push(feature.clone());
+        }
+    }
+    features
+        .fs_features
+        .sort_by_key(|feature| feature.to_string());
+
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::AtimePolicy, test::FileSystemFeature};
+
+    use super::ProbedFeatures;
+
+    #[test]
+    fn features_section() {
+        let features = ProbedFeatures {
+            atime_policy: Some(AtimePolicy::Relatime),
+            fs_features: vec![
+                FileSystemFeature::NsecTimestamps,
+                FileSystemFeature::PosixFallocate,
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            features.to_string(),
+            "[features]\natime_policy = \"relatime\"\nnsec_timestamps = {}\nposix_fallocate = {}\n"
+        );
+    }
+
+    #[test]
+    fn probe_temporary_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let features = super::probe(dir.path()).unwrap();
+
+        assert!(features.atime_policy.is_some());
+        assert!(features.fs_features.contains(&FileSystemFeature::Utimensat));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Behavior profile of the file system.
+//!
+//! When POSIX leaves the choice between several errors (e.g. EEXIST or ENOTEMPTY),
+//! the tests record which one the file system returned,
+//! and the choices are summarized at the end of the run.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use nix::errno::Errno;
+
+use crate::recorder::{Record, Recorder};
+
+/// Choices recorded by the current test, taken by the runner after it returns.
+pub(crate) static RECORDER: Recorder<Choice> = Recorder::new();
+
+/// Error returned by the file system among the accepted ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    /// Errors accepted by the test.
+    pub accepted: Vec<Errno>,
+    /// Error returned by the file system.
+    pub returned: Errno,
+}
+
+impl Record for Choice {
+    fn to_line(&self) -> String {
+        let accepted = self
+            .accepted
+            .iter()
+            .map(|errno| (*errno as i32).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        format!("{accepted}:{}", self.returned as i32)
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let (accepted, returned) = line.split_once(':')?;
+        let accepted = accepted
+            .split(',')
+            .map(|errno| errno.parse().ok().map(Errno::from_raw))
+            .collect::<Option<_>>()?;
+
+        Some(Choice {
+            accepted,
+            returned: Errno::from_raw(returned.parse().ok()?),
+        })
+    }
+}
+
+/// Record that the file system returned `returned` when any of `accepted` was expected.
+pub fn record(accepted: &[Errno], returned: Errno) {
+    let mut accepted = accepted.to_vec();
+    accepted.sort_by_key(|errno| *errno as i32);
+    accepted.dedup();
+
+    RECORDER.record(Choice { accepted, returned });
+}
+
+/// Take the choices recorded since the last call.
+pub fn take() -> Vec<Choice> {
+    RECORDER.take()
+}
+
+/// Summary of the choices of the file system over a run.
+#[derive(Debug, Default)]
+pub struct Profile {
+    /// Tests which recorded each returned error, for each set of accepted errors.
+    choices: BTreeMap<String, BTreeMap<String, BTreeSet<&'static str>>>,
+}
+
+impl Profile {
+    /// Add the choices recorded by a test.
+    pub fn add(&mut self, test_name: &'static str, choices: Vec<Choice>) {
+        for choice in choices {
+            let accepted = choice
+                .accepted
+                .iter()
+                .map(|errno| format!("{errno:?}"))
+                .collect::<Vec<_>>()
+                .join(" or ");
+
+            self.choices
+                .entry(accepted)
+                .or_default()
+                .entry(format!("{:?}", choice.returned))
+                .or_default()
+                .insert(test_name);
+        }
+    }
+
+    /// Merge the choices of another run.
+    pub fn merge(&mut self, other: Profile) {
+        for (accepted, returned) in other.choices {
+            let current = self.choices.entry(accepted).or_default();
+            for (errno, tests) in returned {
+                current.entry(errno).or_default().extend(tests);
+            }
+        }
+    }
+
+    /// Return the errors returned for each set of accepted errors, by test.
+    pub fn by_test(&self) -> BTreeMap<String, BTreeMap<String, BTreeSet<String>>> {
+        let mut by_test: BTreeMap<String, BTreeMap<String, BTreeSet<String>>> = BTreeMap::new();
+        for (accepted, returned) in &self.choices {
+            for (errno, tests) in returned {
+                for test in tests {
+                    by_test
+                        .entry(test.to_string())
+                        .or_default()
+                        .entry(accepted.clone())
+                        .or_default()
+                        .insert(errno.clone());
+                }
+            }
+        }
+
+        by_test
+    }
+
+    /// Return whether no choice has been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.choices.is_empty()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Behavior profile:")?;
+        for (accepted, returned) in &self.choices {
+            let returned = returned
+                .iter()
+                .map(|(errno, tests)| match tests.len() {
+                    1 => format!("{errno} (1 test)"),
+                    count => format!("{errno} ({count} tests)"),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "\t{accepted}: {returned}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use crate::recorder::Record;
+
+    use super::{Choice, Profile};
+
+    #[test]
+    fn serialization_round_trip() {
+        let choice = Choice {
+            accepted: vec![Errno::EEXIST, Errno::ENOTEMPTY],
+            returned: Errno::ENOTEMPTY,
+        };
+
+        assert_eq!(Choice::from_line(&choice.to_line()), Some(choice));
+        assert_eq!(Choice::from_line("garbage"), None);
+    }
+
+    #[test]
+    fn summary() {
+        let choice = |returned| Choice {
+            accepted: vec![Errno::EPERM, Errno::EACCES],
+            returned,
+        };
+        let mut profile = Profile::default();
+        assert!(profile.is_empty());
+
+        profile.add("a", vec![choice(Errno::EACCES), choice(Errno::EACCES)]);
+        profile.add("b", vec![choice(Errno::EACCES), choice(Errno::EPERM)]);
+
+        assert_eq!(
+            profile.to_string(),
+            "Behavior profile:\n\tEPERM or EACCES: EACCES (2 tests), EPERM (1 test)\n"
+        );
+    }
+}
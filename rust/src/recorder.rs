@@ -0,0 +1,117 @@
+//! Recording of the observations of the tests (behavior profile, traced operations, latencies).
+//!
+//! Each kind of observation is kept in a global [`Recorder`] until the runner takes it
+//! after the test returns. When the test runs in a forked child process,
+//! the observations are sent back to the parent with [`take_all`] and [`record_all`].
+
+use std::sync::Mutex;
+
+use crate::{latency, profile, trace};
+
+/// Observation which can be sent from a forked child process, one per line.
+pub trait Record: Sized {
+    /// Serialize the observation, without newline.
+    fn to_line(&self) -> String;
+
+    /// Deserialize an observation, returning `None` if the line is malformed.
+    fn from_line(line: &str) -> Option<Self>;
+}
+
+/// Observations recorded by the current test.
+#[derive(Debug)]
+pub struct Recorder<T> {
+    recorded: Mutex<Vec<T>>,
+}
+
+impl<T: Record> Recorder<T> {
+    pub const fn new() -> Self {
+        Self {
+            recorded: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record an observation.
+    pub fn record(&self, value: T) {
+        self.recorded.lock().unwrap().push(value);
+    }
+
+    /// Take the observations recorded since the last call.
+    pub fn take(&self) -> Vec<T> {
+        std::mem::take(&mut *self.recorded.lock().unwrap())
+    }
+
+    /// Take the observations recorded since the last call, serialized.
+    fn take_serialized(&self) -> String {
+        self.take()
+            .iter()
+            .map(|value| format!("{}\n", value.to_line()))
+            .collect()
+    }
+
+    /// Record the observations serialized by [`Recorder::take_serialized`].
+    fn record_serialized(&self, serialized: &str) {
+        self.recorded
+            .lock()
+            .unwrap()
+            .extend(serialized.lines().filter_map(T::from_line));
+    }
+}
+
+/// Take the observations of every recorder, serialized to be sent from a forked child process.
+/// They are framed by NUL bytes, and may be followed by other data.
+pub fn take_all() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for serialized in [
+        profile::RECORDER.take_serialized(),
+        trace::RECORDER.take_serialized(),
+        latency::RECORDER.take_serialized(),
+    ] {
+        bytes.extend(serialized.into_bytes());
+        bytes.push(0);
+    }
+
+    bytes
+}
+
+/// Record the observations serialized by [`take_all`] in a forked child process,
+/// and return the data which follows them.
+pub fn record_all(mut bytes: &[u8]) -> &[u8] {
+    let recorders: [&dyn Fn(&str); 3] = [
+        &|serialized| profile::RECORDER.record_serialized(serialized),
+        &|serialized| trace::RECORDER.record_serialized(serialized),
+        &|serialized| latency::RECORDER.record_serialized(serialized),
+    ];
+    for record_serialized in recorders {
+        if let Some(end) = bytes.iter().position(|b| *b == 0) {
+            record_serialized(&String::from_utf8_lossy(&bytes[..end]));
+            bytes = &bytes[end + 1..];
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use nix::errno::Errno;
+
+    use crate::{latency, profile, trace};
+
+    use super::{record_all, take_all};
+
+    #[test]
+    fn framing() {
+        profile::record(&[Errno::EEXIST, Errno::ENOTEMPTY], Errno::EEXIST);
+        latency::RECORDER.record(("mkdir", Duration::from_micros(3)));
+        let mut bytes = take_all();
+        bytes.extend(b"outcome");
+
+        assert!(profile::take().is_empty());
+        assert_eq!(record_all(&bytes), b"outcome");
+        assert_eq!(profile::take().len(), 1);
+        assert!(trace::take().is_empty());
+        assert_eq!(latency::take(), [("mkdir", Duration::from_micros(3))]);
+    }
+}
@@ -0,0 +1,295 @@
+//! Execution of the test suite on a remote machine over SSH,
+//! to drive the tests on a virtual machine or an embedded target from a workstation.
+//!
+//! The runner (or a prebuilt binary for the remote platform) and the configuration file
+//! are copied to a temporary directory of the remote machine with `scp`, and the runner is run there with `ssh`.
+//! The local scripts run by the commands of the configuration (hooks, syscall tracer, remount)
+//! are copied along, and the commands are rewritten to run the copies.
+//! Its output is streamed as it runs, and the results are brought back
+//! as a baseline (see [`crate::baseline`]) once the run is over.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use toml_edit::DocumentMut;
+
+use crate::baseline::Baseline;
+
+/// Name of the results file in the remote directory.
+const RESULTS: &str = "results.toml";
+
+/// Settings of the configuration whose value is a shell command, which may run a local script.
+const COMMAND_SETTINGS: [&[&str]; 5] = [
+    &["settings", "pre_test_hook"],
+    &["settings", "post_test_hook"],
+    &["settings", "syscall_tracer"],
+    &["settings", "remount", "unmount"],
+    &["settings", "remount", "mount"],
+];
+
+/// Directory of a remote machine, written `[user@]host:/path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteTarget {
+    /// Destination given to `ssh`.
+    pub destination: String,
+    /// Path where the test suite is executed on the remote machine.
+    pub path: PathBuf,
+}
+
+impl FromStr for RemoteTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((destination, path)) if !destination.is_empty() && path.starts_with('/') => {
+                Ok(RemoteTarget {
+                    destination: destination.to_owned(),
+                    path: PathBuf::from(path),
+                })
+            }
+            _ => Err(format!(
+                "invalid remote target `{s}`, expected [user@]host:/absolute/path"
+            )),
+        }
+    }
+}
+
+/// Run of the test suite on a remote machine.
+pub struct RemoteRun<'a> {
+    pub target: &'a RemoteTarget,
+    /// Binary copied to the remote machine, the current runner if unset.
+    pub binary: Option<&'a Path>,
+    pub configuration_file: Option<&'a Path>,
+    /// Arguments given to the remote runner, in addition to its path, configuration file and results file.
+    pub args: Vec<String>,
+}
+
+impl RemoteRun<'_> {
+    /// Run the test suite on the remote machine, and return its results.
+    pub fn run(&self) -> anyhow::Result<Baseline> {
+        let ssh = Ssh {
+            destination: &self.target.destination,
+        };
+        let dir = ssh
+            .output("mktemp -d")
+            .context("cannot create a temporary directory on the remote machine")?;
+        let dir = dir.trim();
+
+        let results = self.run_in(&ssh, dir);
+
+        if let Err(e) = ssh.output(&format!("rm -rf {}", quote(dir))) {
+            eprintln!("Cannot remove the remote directory {dir}: {e}");
+        }
+
+        results
+    }
+
+    fn run_in(&self, ssh: &Ssh, dir: &str) -> anyhow::Result<Baseline> {
+        let binary = match self.binary {
+            Some(binary) => binary.to_owned(),
+            None => std::env::current_exe()?,
+        };
+        let remote_binary = format!("{dir}/pjdfstest");
+        ssh.copy(&binary, &remote_binary)?;
+
+        let mut command = vec![remote_binary];
+        if let Some(configuration_file) = self.configuration_file {
+            let content = std::fs::read_to_string(configuration_file).with_context(|| {
+                format!(
+                    "cannot read the configuration file {}",
+                    configuration_file.display()
+                )
+            })?;
+            let mut configuration: DocumentMut = content.parse()?;
+            for (script, remote_script) in relocate_scripts(&mut configuration, dir) {
+                ssh.copy(&script, &remote_script)?;
+            }
+
+            let mut local_configuration = tempfile::NamedTempFile::new()?;
+            local_configuration.write_all(configuration.to_string().as_bytes())?;
+            let remote_configuration_file = format!("{dir}/pjdfstest.toml");
+            ssh.copy(local_configuration.path(), &remote_configuration_file)?;
+            command.extend(["-c".to_owned(), remote_configuration_file]);
+        }
+        let results = format!("{dir}/{RESULTS}");
+        command.extend([
+            "-p".to_owned(),
+            self.target.path.to_string_lossy().into_owned(),
+            "--save-baseline".to_owned(),
+            results.clone(),
+        ]);
+        command.extend(self.args.iter().cloned());
+        let command = command
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // The output is streamed, and the status is known from the results
+        let status = ssh.command(&command).status()?;
+
+        let content = ssh
+            .output(&format!("cat {}", quote(&results)))
+            .with_context(|| {
+                format!("the remote runner exited with {status} without saving its results")
+            })?;
+        Baseline::parse(&content).context("invalid results from the remote runner")
+    }
+}
+
+/// Rewrite the commands of the configuration running a local script to run a copy of it
+/// in the remote directory, and return the scripts to copy with their remote paths.
+/// A command runs a local script if its first word is the path of an existing file.
+fn relocate_scripts(configuration: &mut DocumentMut, dir: &str) -> Vec<(PathBuf, String)> {
+    let mut scripts = Vec::new();
+    for setting in COMMAND_SETTINGS {
+        let item = setting
+            .iter()
+            .try_fold(configuration.as_item_mut(), |item, key| item.get_mut(key));
+        let Some(item) = item else {
+            continue;
+        };
+        let Some(command) = item.as_str().map(str::to_owned) else {
+            continue;
+        };
+
+        let (program, args) = command
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .unwrap_or((command.trim_start(), ""));
+        let script = Path::new(program);
+        if !program.contains('/') || !script.is_file() {
+            continue;
+        }
+
+        let name = script.file_name().unwrap_or_default().to_string_lossy();
+        let remote_script = format!("{dir}/{}-{name}", scripts.len());
+        *item = toml_edit::value(format!("{} {args}", quote(&remote_script)).trim_end());
+        scripts.push((script.to_owned(), remote_script));
+    }
+
+    scripts
+}
+
+/// Transport to the remote machine, with the `ssh` and `scp` commands.
+struct Ssh<'a> {
+    destination: &'a str,
+}
+
+impl Ssh<'_> {
+    /// Options of `ssh` and `scp`, which should never prompt during a run.
+    const OPTIONS: [&'static str; 2] = ["-o", "BatchMode=yes"];
+
+    /// Return a command running a shell command on the remote machine.
+    fn command(&self, command: &str) -> Command {
+        let mut ssh = Command::new("ssh");
+        ssh.args(Self::OPTIONS)
+            .arg("--")
+            .arg(self.destination)
+            .arg(command);
+
+        ssh
+    }
+
+    /// Run a shell command on the remote machine, and return its output.
+    fn output(&self, command: &str) -> anyhow::Result<String> {
+        let output = self.command(command).output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "`{command}` failed on {}: {}",
+                self.destination,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    /// Copy a local file to the remote machine.
+    fn copy(&self, local: &Path, remote: &str) -> anyhow::Result<()> {
+        let output = Command::new("scp")
+            .args(Self::OPTIONS)
+            .arg("-q")
+            .arg(local)
+            .arg(format!("{}:{remote}", self.destination))
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "cannot copy {} to {}: {}",
+                local.display(),
+                self.destination,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Quote an argument for the remote shell.
+fn quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use toml_edit::DocumentMut;
+
+    use super::{quote, relocate_scripts, RemoteTarget};
+
+    #[test]
+    fn parse_target() {
+        assert_eq!(
+            "root@freebsd-vm:/mnt/test".parse(),
+            Ok(RemoteTarget {
+                destination: String::from("root@freebsd-vm"),
+                path: PathBuf::from("/mnt/test"),
+            })
+        );
+        assert_eq!(
+            "board:/data".parse::<RemoteTarget>().unwrap().destination,
+            "board"
+        );
+        assert!("host".parse::<RemoteTarget>().is_err());
+        assert!("host:relative".parse::<RemoteTarget>().is_err());
+        assert!(":/mnt/test".parse::<RemoteTarget>().is_err());
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("rmdir::"), "'rmdir::'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn scripts() {
+        let mut script = tempfile::NamedTempFile::new().unwrap();
+        writeln!(script, "#!/bin/sh").unwrap();
+        let script_path = script.path().to_str().unwrap();
+        let mut configuration: DocumentMut = format!(
+            "[settings]\npre_test_hook = \"{script_path} --before\"\npost_test_hook = \"logger done\"\n"
+        )
+        .parse()
+        .unwrap();
+
+        let scripts = relocate_scripts(&mut configuration, "/tmp/remote");
+        let name = script.path().file_name().unwrap().to_str().unwrap();
+        let remote_script = format!("/tmp/remote/0-{name}");
+        assert_eq!(scripts, [(script.path().to_owned(), remote_script.clone())]);
+        assert_eq!(
+            configuration["settings"]["pre_test_hook"].as_str(),
+            Some(format!("'{remote_script}' --before").as_str())
+        );
+        assert_eq!(
+            configuration["settings"]["post_test_hook"].as_str(),
+            Some("logger done")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Repetition of the runs with different seeds, to soak the file system
+//! and detect the tests whose outcome varies between runs.
+
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{baseline::status_name, runner::RunSummary};
+
+/// Parse a duration in seconds, optionally followed by a `s`, `m` or `h` unit.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (value, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+
+    let factor = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        _ => {
+            return Err(format!(
+                "invalid unit in duration `{s}`, expected s, m or h"
+            ))
+        }
+    };
+    value
+        .checked_mul(factor)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration `{s}` is too long"))
+}
+
+/// Statuses of the tests over the repeated runs.
+#[derive(Debug, Default)]
+pub struct FlakeStats {
+    /// Number of runs.
+    runs: usize,
+    /// Number of runs which ended with each status, for each test.
+    statuses: BTreeMap<String, BTreeMap<&'static str, usize>>,
+}
+
+impl FlakeStats {
+    /// Add the statuses of a run.
+    pub fn add(&mut self, summary: &RunSummary) {
+        self.runs += 1;
+        for (name, status) in &summary.statuses {
+            *self
+                .statuses
+                .entry(name.clone())
+                .or_default()
+                .entry(status_name(*status))
+                .or_default() += 1;
+        }
+    }
+
+    /// Return the number of runs.
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Return the tests whose status varied between the runs,
+    /// with the number of runs for each status.
+    pub fn flaky_tests(&self) -> impl Iterator<Item = (&str, &BTreeMap<&'static str, usize>)> {
+        self.statuses
+            .iter()
+            .filter(|(_, statuses)| statuses.len() > 1)
+            .map(|(name, statuses)| (name.as_str(), statuses))
+    }
+}
+
+impl fmt::Display for FlakeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Flaky tests over {} runs:", self.runs)?;
+        for (name, statuses) in self.flaky_tests() {
+            let statuses = statuses
+                .iter()
+                .map(|(status, count)| format!("{count} {status}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "\t{name}: {statuses}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::runner::{RunSummary, TestStatus};
+
+    use super::{parse_duration, FlakeStats};
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_duration("1d").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn flaky_tests() {
+        let summary = |statuses: &[(&str, TestStatus)]| RunSummary {
+            statuses: statuses
+                .iter()
+                .map(|(name, status)| (name.to_string(), *status))
+                .collect(),
+            ..Default::default()
+        };
+        let mut stats = FlakeStats::default();
+
+        stats.add(&summary(&[
+            ("stable", TestStatus::Passed),
+            ("flaky", TestStatus::Passed),
+        ]));
+        stats.add(&summary(&[
+            ("stable", TestStatus::Passed),
+            ("flaky", TestStatus::Failed),
+        ]));
+
+        assert_eq!(stats.runs(), 2);
+        assert_eq!(
+            stats.to_string(),
+            "Flaky tests over 2 runs:\n\tflaky: 1 failed, 1 passed\n"
+        );
+    }
+}
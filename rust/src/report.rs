@@ -0,0 +1,289 @@
+//! Reports of the results of a run, written with `--html-report`.
+//!
+//! The HTML report is a single self-contained file, with the summary of the run,
+//! the results of each group of tests and the details of each test
+//! (duration, skip reasons, and the message, backtrace and operations of the failures),
+//! which can be expanded.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    fs,
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::{
+    baseline::status_name,
+    runner::{test_group, RunSummary, TestDetails, TestStatus},
+    test::TestCase,
+};
+
+/// Statuses counted in the reports, in their order.
+pub(crate) const STATUSES: [TestStatus; 5] = [
+    TestStatus::Passed,
+    TestStatus::Failed,
+    TestStatus::Skipped,
+    TestStatus::ExpectedFailure,
+    TestStatus::UnexpectedPass,
+];
+
+/// Style of the HTML reports, with a class for each status.
+const STYLE: &str = "
+body { font-family: sans-serif; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; vertical-align: top; }
+tr.differs td:first-child { font-weight: bold; }
+summary { cursor: pointer; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.passed { background: #d4f4d4; }
+.failed, .unexpected_pass { background: #f8d0d0; }
+.skipped, .not_run { background: #eee; }
+.expected_failure { background: #f8ecc8; }
+";
+
+/// Return the beginning of an HTML report, up to its body,
+/// with its style so that it can be opened on its own.
+pub(crate) fn html_header(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n",
+        escape(title)
+    )
+}
+
+/// Escape the characters of a text which have a meaning in HTML.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a duration in seconds, with millisecond precision.
+fn seconds(duration: Duration) -> String {
+    format!("{:.3}s", duration.as_secs_f64())
+}
+
+/// HTML report of the run of the tests on a target directory.
+pub struct HtmlReport<'a> {
+    /// Directory where the tests have been run.
+    pub path: &'a Path,
+    pub seed: u64,
+    pub summary: &'a RunSummary,
+    /// Test cases of the run, for their description.
+    pub test_cases: &'a [TestCase],
+}
+
+impl HtmlReport<'_> {
+    /// Render the report as a self-contained HTML page.
+    pub fn render(&self) -> String {
+        let descriptions: HashMap<_, _> = self
+            .test_cases
+            .iter()
+            .map(|tc| (tc.name, tc.description))
+            .collect();
+        let mut groups: BTreeMap<&str, Vec<(&str, TestStatus)>> = BTreeMap::new();
+        for (name, status) in &self.summary.statuses {
+            groups
+                .entry(test_group(name))
+                .or_default()
+                .push((name, *status));
+        }
+        let no_details = TestDetails::default();
+        let details = |name: &str| self.summary.details.get(name).unwrap_or(&no_details);
+        let duration = |tests: &[(&str, TestStatus)]| {
+            tests
+                .iter()
+                .map(|(name, _)| details(name).duration)
+                .sum::<Duration>()
+        };
+        let counts = |tests: &[(&str, TestStatus)]| {
+            STATUSES.map(|status| tests.iter().filter(|(_, s)| *s == status).count())
+        };
+        let header = |first: &str| {
+            let mut out = format!("<tr><th>{first}</th>");
+            for status in STATUSES {
+                let _ = write!(out, "<th>{}</th>", status_name(status));
+            }
+            out + "<th>duration</th></tr>\n"
+        };
+
+        let mut out = html_header("pjdfstest report");
+        let _ = writeln!(
+            out,
+            "<h1>pjdfstest report</h1>\n<p>Tests run in {} with seed {} (reproduce with <code>--seed {}</code>)</p>",
+            escape(&self.path.display().to_string()),
+            self.seed,
+            self.seed
+        );
+
+        let all: Vec<_> = groups.values().flatten().copied().collect();
+        out += "<table>\n";
+        out += &header("");
+        out += "<tr><td>total</td>";
+        for (status, count) in STATUSES.iter().zip(counts(&all)) {
+            let _ = write!(out, "<td class=\"{}\">{count}</td>", status_name(*status));
+        }
+        let _ = writeln!(out, "<td>{}</td></tr>\n</table>", seconds(duration(&all)));
+
+        out += "<h2>Groups</h2>\n<table>\n";
+        out += &header("group");
+        for (group, tests) in &groups {
+            let _ = write!(out, "<tr><td><a href=\"#{0}\">{0}</a></td>", escape(group));
+            for count in counts(tests) {
+                let _ = write!(out, "<td>{count}</td>");
+            }
+            let _ = writeln!(out, "<td>{}</td></tr>", seconds(duration(tests)));
+        }
+        out += "</table>\n";
+
+        out += "<h2>Tests</h2>\n";
+        for (group, tests) in &groups {
+            // The groups with failures are expanded
+            let open = if tests.iter().any(|(_, status)| status.is_failure()) {
+                " open"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "<details id=\"{0}\"{open}><summary>{0} ({1} tests)</summary>\n<table>",
+                escape(group),
+                tests.len()
+            );
+            out += "<tr><th>test</th><th>status</th><th>duration</th><th>details</th></tr>\n";
+            for (name, status) in tests {
+                let details = details(name);
+                let description = descriptions.get(name).copied().unwrap_or_default();
+                let _ = write!(
+                    out,
+                    "<tr><td title=\"{}\">{}</td><td class=\"{2}\">{2}</td><td>{3}</td><td>",
+                    escape(description),
+                    escape(name),
+                    status_name(*status),
+                    seconds(details.duration)
+                );
+                out += &render_details(details);
+                out += "</td></tr>\n";
+            }
+            out += "</table>\n</details>\n";
+        }
+
+        out + "</body>\n</html>\n"
+    }
+
+    /// Write the report to a file.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, self.render())
+            .with_context(|| format!("cannot write report {}", path.display()))
+    }
+}
+
+/// Render the skip reasons, and the details of a failure which can be expanded.
+fn render_details(details: &TestDetails) -> String {
+    let mut out = String::new();
+    for reason in &details.skip_reasons {
+        let _ = write!(out, "{}<br>", escape(reason));
+    }
+
+    let Some(message) = &details.message else {
+        return out;
+    };
+    let _ = write!(
+        out,
+        "<details><summary>{}</summary>",
+        escape(message.lines().next().unwrap_or_default())
+    );
+    let _ = write!(out, "<pre>{}</pre>", escape(message));
+    if let Some(backtrace) = &details.backtrace {
+        let _ = write!(
+            out,
+            "<details><summary>backtrace</summary><pre>{}</pre></details>",
+            escape(backtrace)
+        );
+    }
+    if !details.operations.is_empty() {
+        let _ = write!(
+            out,
+            "<details><summary>operations</summary><pre>{}</pre></details>",
+            escape(&details.operations.join("\n"))
+        );
+    }
+    out + "</details>"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use crate::runner::{RunSummary, TestDetails, TestStatus};
+
+    use super::{escape, HtmlReport};
+
+    #[test]
+    fn html() {
+        let mut summary = RunSummary::default();
+        summary
+            .statuses
+            .insert(String::from("rmdir::enotempty"), TestStatus::Passed);
+        summary
+            .statuses
+            .insert(String::from("rmdir::open_dir"), TestStatus::Failed);
+        summary
+            .statuses
+            .insert(String::from("chflags::set"), TestStatus::Skipped);
+        summary.details.insert(
+            String::from("rmdir::open_dir"),
+            TestDetails {
+                duration: Duration::from_millis(1500),
+                message: Some(String::from(
+                    "assertion `left == right` failed\n left: <EBUSY>",
+                )),
+                backtrace: Some(String::from("0: rmdir::open_dir")),
+                operations: vec![String::from("rmdir(\"dir\") = -1 EBUSY")],
+                ..Default::default()
+            },
+        );
+        summary.details.insert(
+            String::from("chflags::set"),
+            TestDetails {
+                skip_reasons: vec![String::from("requires features: chflags")],
+                ..Default::default()
+            },
+        );
+
+        let report = HtmlReport {
+            path: Path::new("/mnt/test"),
+            seed: 42,
+            summary: &summary,
+            test_cases: &[],
+        }
+        .render();
+
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<tr><td>total</td><td class=\"passed\">1</td><td class=\"failed\">1</td><td class=\"skipped\">1</td>"));
+        assert!(report.contains("<details id=\"rmdir\" open><summary>rmdir (2 tests)</summary>"));
+        assert!(report.contains("<details id=\"chflags\"><summary>chflags (1 tests)</summary>"));
+        assert!(report.contains("requires features: chflags<br>"));
+        assert!(report.contains("<td>1.500s</td>"));
+        assert!(report.contains("<summary>assertion `left == right` failed</summary>"));
+        assert!(report.contains("left: &lt;EBUSY&gt;"));
+        assert!(report.contains("<pre>0: rmdir::open_dir</pre>"));
+        assert!(report.contains("rmdir(&quot;dir&quot;) = -1 EBUSY"));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+}
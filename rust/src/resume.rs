@@ -0,0 +1,142 @@
+//! Resumable runs with `--resume`, for long runs which may be interrupted
+//! (e.g. on slow network file systems).
+//!
+//! The status of each test is saved to a JSON state file as soon as it has been run.
+//! A subsequent run with the same state file and the same selection of tests
+//! does not run again the tests which have passed, and runs the other ones
+//! with the seed of the first run.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::runner::TestStatus;
+
+/// Content of the state file.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct State {
+    /// Seed of the first run.
+    seed: Option<u64>,
+    /// Names of the selected tests, sorted as their order may change between runs.
+    selection: Vec<String>,
+    /// Status of the tests which have been run.
+    tests: BTreeMap<String, TestStatus>,
+}
+
+/// State of a resumable run, saved to a file.
+#[derive(Debug)]
+pub struct ResumeState {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+impl ResumeState {
+    /// Load the state of a previous run with the same selection of tests from `path`,
+    /// or start a new one if the file doesn't exist.
+    pub fn open(path: &Path, selection: &[&str]) -> anyhow::Result<Self> {
+        let mut selection: Vec<_> = selection.iter().map(|name| name.to_string()).collect();
+        selection.sort();
+        let state = if path.exists() {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("cannot read state file {}", path.display()))?;
+            let state: State = serde_json::from_str(&content)
+                .with_context(|| format!("invalid state file {}", path.display()))?;
+            if state.selection != selection {
+                anyhow::bail!(
+                    "the state file {} has been saved for another selection of tests, remove it to start over",
+                    path.display()
+                );
+            }
+            state
+        } else {
+            State {
+                selection,
+                ..Default::default()
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Return the seed of the run being resumed, if any.
+    pub fn seed(&self) -> Option<u64> {
+        self.state.lock().unwrap().seed
+    }
+
+    /// Return the number of tests which have passed in the previous runs.
+    pub fn passed_count(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .tests
+            .values()
+            .filter(|status| **status == TestStatus::Passed)
+            .count()
+    }
+
+    /// Return whether the test has passed in a previous run.
+    pub fn passed(&self, name: &str) -> bool {
+        self.state.lock().unwrap().tests.get(name) == Some(&TestStatus::Passed)
+    }
+
+    /// Record the seed of the run, and save the state.
+    pub fn start(&self, seed: u64) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.seed = Some(seed);
+        self.save(&state)
+    }
+
+    /// Record the status of a test, and save the state.
+    pub fn record(&self, name: &str, status: TestStatus) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.tests.insert(name.to_owned(), status);
+        self.save(&state)
+    }
+
+    /// Save the state, replacing the file at once so that it cannot be left truncated.
+    fn save(&self, state: &State) -> anyhow::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(state)?)
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .with_context(|| format!("cannot write state file {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::runner::TestStatus;
+
+    use super::ResumeState;
+
+    #[test]
+    fn resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let selection = ["chmod::change_perm", "rmdir::open_dir"];
+
+        let state = ResumeState::open(&path, &selection).unwrap();
+        assert_eq!(state.seed(), None);
+        state.start(42).unwrap();
+        state
+            .record("chmod::change_perm", TestStatus::Passed)
+            .unwrap();
+        state.record("rmdir::open_dir", TestStatus::Failed).unwrap();
+
+        let state = ResumeState::open(&path, &selection).unwrap();
+        assert_eq!(state.seed(), Some(42));
+        assert_eq!(state.passed_count(), 1);
+        assert!(state.passed("chmod::change_perm"));
+        assert!(!state.passed("rmdir::open_dir"));
+
+        assert!(ResumeState::open(&path, &selection[..1]).is_err());
+    }
+}
@@ -0,0 +1,49 @@
+//! Random generator used for the names and the data of the tests.
+//!
+//! It is seeded again before each test with the seed of the run and the name of the test,
+//! so that a test always uses the same names for a given seed,
+//! whichever other tests are run.
+
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Generate a seed for a run.
+pub fn gen_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+/// Seed the generator for the test with the given name.
+pub fn reseed(seed: u64, test_name: &str) {
+    // FNV-1a, which is stable across Rust versions unlike `DefaultHasher`
+    let name_hash = test_name.bytes().fold(0xcbf29ce484222325, |hash: u64, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+
+    set_seed(seed ^ name_hash);
+}
+
+/// Seed the generator of the current thread.
+pub fn set_seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Call the function with the generator.
+pub fn with_rng<F, T>(f: F) -> T
+where
+    F: FnOnce(&mut StdRng) -> T,
+{
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Generate a random value.
+pub fn random<T>() -> T
+where
+    rand::distributions::Standard: rand::distributions::Distribution<T>,
+{
+    with_rng(|rng| rng.gen())
+}
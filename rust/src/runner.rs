@@ -0,0 +1,836 @@
+//! Execution of the test cases and report of their results.
+
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    collections::{BTreeMap, HashSet},
+    io::{stdout, Write},
+    panic::catch_unwind,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use nix::{
+    libc::c_int,
+    sys::{
+        signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+        stat::Mode,
+    },
+    unistd::Uid,
+};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    rngs::StdRng,
+    seq::SliceRandom,
+    SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+use crate::{
+    budget,
+    config::{Config, RemountConfig},
+    context::Cleanup,
+    events::{Event, ReportSocket},
+    hooks::Hooks,
+    invariants::ProcessState,
+    isolation,
+    latency::{self, Latencies},
+    profile::{self, Profile},
+    recorder,
+    resume::ResumeState,
+    rng, sandbox,
+    snapshot::Snapshot,
+    space,
+    syscall_trace::SyscallTracer,
+    test::{SerializedTestContext, TestCase, TestContext, TestFn},
+    trace,
+    utils::chmod,
+};
+
+/// Backtrace of the last panic, captured by the panic hook.
+pub static BACKTRACE: Mutex<Option<Backtrace>> = Mutex::new(None);
+
+/// Whether the run has been interrupted by SIGINT.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn interrupt(_: c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Stop the run after the current test when SIGINT is received (e.g. Ctrl-C),
+/// so that the directories of the tests are cleaned up and the partial summary is reported.
+/// A second SIGINT terminates the process.
+pub fn handle_interruption() {
+    // The operations of the current test are restarted instead of failing with EINTR
+    let action = SigAction::new(
+        SigHandler::Handler(interrupt),
+        SaFlags::SA_RESTART | SaFlags::SA_RESETHAND,
+        SigSet::empty(),
+    );
+    // SAFETY: The handler only sets an atomic flag, which is async-signal-safe
+    unsafe { sigaction(Signal::SIGINT, &action) }.unwrap();
+}
+
+/// Return whether the run has been interrupted by SIGINT.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Options changing how the test cases are run.
+#[derive(Debug, Default)]
+pub struct RunOptions<'a> {
+    /// Print the description of the tests.
+    pub verbose: bool,
+    /// Run each test in a forked child process.
+    pub isolate: bool,
+    /// Remount the file system after each group of tests to check the durability of the metadata.
+    pub remount: Option<&'a RemountConfig>,
+    /// Seed of the random generator.
+    pub seed: u64,
+    /// Directory where the directories of the failed tests are captured.
+    pub artifacts_dir: Option<&'a Path>,
+    /// Whether the directories of the tests are removed.
+    pub cleanup: Cleanup,
+    /// Record the file system operations of the tests, and report those of the failed tests.
+    pub trace: bool,
+    /// Run the failed tests again under a syscall tracer, saving the trace with their artifacts.
+    pub syscall_tracer: Option<&'a SyscallTracer>,
+    /// Measure the latency of the file system operations of the tests.
+    pub latency: bool,
+    /// Socket where the events of the run are streamed.
+    pub report_socket: Option<&'a ReportSocket>,
+    /// State of a resumable run, where the status of the tests is saved.
+    pub resume: Option<&'a ResumeState>,
+}
+
+/// Status of a test at the end of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+    /// The test failed, as expected by the configuration.
+    ExpectedFailure,
+    /// The test passed, but was expected to fail by the configuration.
+    UnexpectedPass,
+}
+
+impl TestStatus {
+    /// Return whether the status fails the run.
+    pub fn is_failure(self) -> bool {
+        matches!(self, TestStatus::Failed | TestStatus::UnexpectedPass)
+    }
+}
+
+/// What happened to a test, reported with its status.
+#[derive(Debug, Default, Clone)]
+pub struct TestDetails {
+    /// Duration of the execution of the test.
+    pub duration: Duration,
+    /// Why the test was skipped.
+    pub skip_reasons: Vec<String>,
+    /// Why the test failed.
+    pub message: Option<String>,
+    pub backtrace: Option<String>,
+    /// File system operations of the failed test, if traced.
+    pub operations: Vec<String>,
+}
+
+/// Results of a run.
+#[derive(Debug, Default)]
+pub struct RunSummary {
+    pub failed: usize,
+    pub skipped: usize,
+    pub passed: usize,
+    pub expected_failures: usize,
+    pub unexpected_passes: usize,
+    /// Status of each test.
+    pub statuses: BTreeMap<String, TestStatus>,
+    /// Details of each test, for the reports.
+    pub details: BTreeMap<String, TestDetails>,
+    /// Errors chosen by the file system when the tests accept several.
+    pub profile: Profile,
+    /// Latency of the file system operations, if measured.
+    pub latencies: Latencies,
+    /// Whether the run has been interrupted before all the tests have been run.
+    pub interrupted: bool,
+}
+
+impl RunSummary {
+    /// Record the status of a test, with its details.
+    fn record_details(&mut self, name: &str, status: TestStatus, details: TestDetails) {
+        self.details.insert(name.to_owned(), details);
+        self.record(name, status);
+    }
+
+    /// Record the status of a test.
+    fn record(&mut self, name: impl Into<String>, status: TestStatus) {
+        match status {
+            TestStatus::Passed => self.passed += 1,
+            TestStatus::Failed => self.failed += 1,
+            TestStatus::Skipped => self.skipped += 1,
+            TestStatus::ExpectedFailure => self.expected_failures += 1,
+            TestStatus::UnexpectedPass => self.unexpected_passes += 1,
+        }
+        self.statuses.insert(name.into(), status);
+    }
+
+    /// Merge the results of another run of the tests.
+    /// The status of a test is the failing one if it failed in either run,
+    /// with the details of the failure.
+    pub fn merge(&mut self, mut other: RunSummary) {
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.passed += other.passed;
+        self.expected_failures += other.expected_failures;
+        self.unexpected_passes += other.unexpected_passes;
+        self.interrupted |= other.interrupted;
+        for (name, status) in other.statuses {
+            let details = other.details.remove(&name);
+            let current = self.statuses.entry(name.clone()).or_insert(status);
+            if status.is_failure() {
+                *current = status;
+                if let Some(details) = details {
+                    self.details.insert(name, details);
+                }
+            }
+        }
+        self.profile.merge(other.profile);
+        self.latencies.merge(other.latencies);
+    }
+}
+
+/// Outcome of the execution of a test.
+#[derive(Debug)]
+enum TestOutcome {
+    Passed,
+    Failed {
+        message: String,
+        backtrace: Option<String>,
+    },
+}
+
+impl TestOutcome {
+    /// Serialize the outcome to send it from an isolated child process.
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            TestOutcome::Passed => vec![0],
+            TestOutcome::Failed { message, backtrace } => {
+                let mut bytes = vec![1];
+                bytes.extend(message.as_bytes());
+                if let Some(backtrace) = backtrace {
+                    bytes.push(0);
+                    bytes.extend(backtrace.as_bytes());
+                }
+                bytes
+            }
+        }
+    }
+
+    /// Deserialize the outcome sent by an isolated child process.
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes.split_first()? {
+            (0, _) => Some(TestOutcome::Passed),
+            (1, rest) => {
+                let mut parts = rest.splitn(2, |b| *b == 0);
+                let message = String::from_utf8_lossy(parts.next().unwrap_or_default());
+                let backtrace = parts
+                    .next()
+                    .map(|bt| String::from_utf8_lossy(bt).into_owned());
+                Some(TestOutcome::Failed {
+                    message: message.into_owned(),
+                    backtrace,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Return the group of a test, which is the module (usually the syscall) it belongs to.
+pub fn test_group(name: &str) -> &str {
+    name.split("::").next().unwrap_or(name)
+}
+
+/// Shuffle the non-serialized test cases with the seed, to reveal dependencies between tests
+/// hidden by their usual order.
+/// The serialized test cases are kept in place.
+pub fn shuffle_test_cases(test_cases: &mut Vec<TestCase>, seed: u64) {
+    let slots: Vec<_> = test_cases
+        .iter()
+        .enumerate()
+        .filter(|(_, tc)| matches!(tc.fun, TestFn::NonSerialized(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let mut order = slots.clone();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+
+    let mut cases: Vec<_> = test_cases.drain(..).map(Some).collect();
+    let shuffled: Vec<_> = order.iter().map(|i| cases[*i].take().unwrap()).collect();
+    for (slot, tc) in slots.into_iter().zip(shuffled) {
+        cases[slot] = Some(tc);
+    }
+    test_cases.extend(cases.into_iter().map(Option::unwrap));
+}
+
+/// Extract the message from a panic payload.
+pub fn panic_message(e: Box<dyn Any + Send>) -> String {
+    match e.downcast::<String>() {
+        Ok(v) => *v,
+        Err(e) => match e.downcast::<&str>() {
+            Ok(v) => v.to_string(),
+            _ => "Unknown Source of Error".to_owned(),
+        },
+    }
+}
+
+/// Return the path where the directory of a failed test is captured.
+fn artifacts_path(artifacts_dir: &Path, test_case: &TestCase) -> PathBuf {
+    artifacts_dir.join(test_case.name.replace("::", "."))
+}
+
+/// Execute the test in the current process.
+/// The test fails if it does not leave the state of the process as it found it.
+fn execute_test(
+    test_case: &TestCase,
+    config: &Config,
+    path: &Path,
+    options: &RunOptions,
+) -> TestOutcome {
+    let entries = &config.dummy_auth.entries;
+    let setup = |context: &mut TestContext| {
+        context.set_cleanup(options.cleanup);
+        if let Some(artifacts_dir) = options.artifacts_dir {
+            context.capture_artifacts_to(artifacts_path(artifacts_dir, test_case));
+        }
+    };
+
+    let state = ProcessState::capture();
+    budget::start(config.settings.budget.as_ref(), path);
+    let result = catch_unwind(|| match test_case.fun {
+        TestFn::NonSerialized(fun) => {
+            let mut context = TestContext::new(config, entries, path);
+            setup(&mut context);
+
+            (fun)(&mut context)
+        }
+        TestFn::Serialized(fun) => {
+            let mut context = SerializedTestContext::new(config, entries, path);
+            setup(&mut context);
+
+            (fun)(&mut context)
+        }
+    });
+    let exceeded_budget = budget::finish();
+
+    let final_state = ProcessState::capture();
+    let differences = final_state.diff(&state);
+    final_state.restore(&state);
+
+    match (result, exceeded_budget) {
+        (Ok(_), Some(message)) => TestOutcome::Failed {
+            message,
+            backtrace: None,
+        },
+        (Ok(_), None) if differences.is_empty() => TestOutcome::Passed,
+        (Ok(_), None) => TestOutcome::Failed {
+            message: format!(
+                "the test did not restore the state of the process: {}",
+                differences.join("; ")
+            ),
+            backtrace: None,
+        },
+        (Err(e), _) => {
+            let backtrace = BACKTRACE
+                .lock()
+                .unwrap()
+                .take()
+                .filter(|bt| bt.status() == BacktraceStatus::Captured)
+                .map(|bt| bt.to_string());
+
+            TestOutcome::Failed {
+                message: panic_message(e),
+                backtrace,
+            }
+        }
+    }
+}
+
+/// Execute the test in a forked child process, so it can neither corrupt
+/// the state of the runner nor crash it.
+/// The outcome is sent back by the child, after the choices recorded for the behavior profile,
+/// the traced operations and the measured latencies.
+fn execute_isolated(
+    test_case: &TestCase,
+    config: &Config,
+    path: &Path,
+    options: &RunOptions,
+) -> TestOutcome {
+    let res = isolation::run_in_child(|| {
+        let outcome = execute_test(test_case, config, path, options);
+        let mut bytes = recorder::take_all();
+        bytes.extend(outcome.to_bytes());
+        bytes
+    });
+
+    let bytes = match res {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return TestOutcome::Failed {
+                message: format!("test process {e}"),
+                backtrace: None,
+            }
+        }
+    };
+    TestOutcome::from_bytes(recorder::record_all(&bytes)).unwrap_or(TestOutcome::Failed {
+        message: String::from("test process exited without reporting its result"),
+        backtrace: None,
+    })
+}
+
+/// Run a shell command used to remount the file system.
+fn run_remount_command(command: &str) -> Result<(), String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| format!("cannot run `{command}`: {e}"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "`{command}` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Remount the file system and check that the entries left by the tests of the group
+/// have not been modified, then remove them.
+/// The check is recorded in the summary as the `remount_durability` test of the group.
+fn report_remount_durability(
+    group: &str,
+    remount: &RemountConfig,
+    group_dirs: &mut Vec<(Option<TempDir>, Snapshot)>,
+    summary: &mut RunSummary,
+    options: &RunOptions,
+) {
+    let name = format!("{group}::remount_durability");
+    send_event(options, Event::TestStarted { test: &name });
+    let start = Instant::now();
+
+    let result = run_remount_command(&remount.unmount)
+        .and_then(|_| run_remount_command(&remount.mount))
+        .and_then(|_| {
+            catch_unwind(|| {
+                for (_, snapshot) in group_dirs.iter() {
+                    snapshot.verify_unchanged();
+                }
+            })
+            .map_err(panic_message)
+        });
+    group_dirs.clear();
+
+    let (status, message) = match result {
+        Ok(_) => {
+            println!("{name:77} ok");
+            (TestStatus::Passed, None)
+        }
+        Err(e) => {
+            BACKTRACE.lock().unwrap().take();
+            println!("{name:73} FAILED\n\t{e}");
+            (TestStatus::Failed, Some(e))
+        }
+    };
+    let details = TestDetails {
+        duration: start.elapsed(),
+        message,
+        ..Default::default()
+    };
+    send_finished(options, &name, status, &details);
+    summary.record_details(&name, status, details);
+}
+
+/// Send an event of the run to the report socket, if any.
+fn send_event(options: &RunOptions, event: Event) {
+    if let Some(socket) = options.report_socket {
+        socket.send(&event);
+    }
+}
+
+/// Send the end of a test to the report socket, if any.
+fn send_finished(options: &RunOptions, name: &str, status: TestStatus, details: &TestDetails) {
+    send_event(
+        options,
+        Event::TestFinished {
+            test: name,
+            status,
+            duration: details.duration.as_secs_f64(),
+            message: details.message.as_deref(),
+        },
+    );
+}
+
+/// Save the status of a test to the state of a resumable run, if any.
+fn record_resume(options: &RunOptions, name: &str, status: TestStatus) -> anyhow::Result<()> {
+    match options.resume {
+        Some(resume) => resume.record(name, status),
+        None => Ok(()),
+    }
+}
+
+/// Run provided test cases and filter according to features and flags availability.
+//TODO: Refactor this function
+pub fn run_test_cases(
+    test_cases: &[TestCase],
+    config: &Config,
+    base_dir: TempDir,
+    options: &RunOptions,
+) -> Result<RunSummary, anyhow::Error> {
+    let mut summary = RunSummary::default();
+
+    let is_root = Uid::current().is_root();
+
+    let enabled_features: HashSet<_> = config.features.fs_features.keys().collect();
+
+    let hooks = Hooks::new(&config.settings);
+
+    trace::set_enabled(options.trace);
+    latency::set_enabled(options.latency);
+
+    send_event(
+        options,
+        Event::RunStarted {
+            path: base_dir.path().parent().unwrap_or(base_dir.path()),
+            seed: options.seed,
+            tests: test_cases.len(),
+        },
+    );
+
+    // Directories of the tests of the current group, kept until the file system is remounted
+    let mut group_dirs = Vec::new();
+    let mut previous_group = None;
+    // Whether the directory of a test has been kept, in which case the base directory is kept too
+    let mut kept_dirs = false;
+
+    for test_case in test_cases {
+        if interrupted() {
+            println!("\nInterrupted, the remaining tests are not run");
+            summary.interrupted = true;
+            break;
+        }
+
+        let group = test_group(test_case.name);
+        if let Some(remount) = options.remount {
+            if previous_group.is_some_and(|g| g != group) && !group_dirs.is_empty() {
+                report_remount_durability(
+                    previous_group.unwrap(),
+                    remount,
+                    &mut group_dirs,
+                    &mut summary,
+                    options,
+                );
+            }
+            previous_group = Some(group);
+        }
+
+        if options
+            .resume
+            .is_some_and(|resume| resume.passed(test_case.name))
+        {
+            println!("{:67} ok (resumed)", test_case.name);
+            summary.record(test_case.name, TestStatus::Passed);
+            continue;
+        }
+
+        //TODO: There's probably a better way to do this...
+        let mut should_skip = test_case.require_root && !is_root;
+        let mut skip_reasons = Vec::<String>::new();
+
+        if should_skip {
+            skip_reasons.push(String::from("requires root privileges"));
+        }
+
+        let features: HashSet<_> = test_case.required_features.iter().collect();
+        let missing_features: Vec<_> = features.difference(&enabled_features).collect();
+        if !missing_features.is_empty() {
+            should_skip = true;
+
+            let features = &missing_features
+                .iter()
+                .map(|feature| format!("{}", feature))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            skip_reasons.push(format!("requires features: {}", features));
+        }
+
+        // The name of the test directory is also derived from the seed
+        rng::reseed(options.seed, test_case.name);
+        let temp_dir = tempfile::Builder::new()
+            .prefix(&rng::with_rng(|rng| Alphanumeric.sample_string(rng, 10)))
+            .rand_bytes(0)
+            .tempdir_in(base_dir.path())
+            .unwrap();
+        // FIX: some tests need a 0o755 base dir
+        chmod(temp_dir.path(), Mode::from_bits_truncate(0o755)).unwrap();
+
+        if test_case
+            .guards
+            .iter()
+            .any(|guard| guard(config, temp_dir.path()).is_err())
+        {
+            should_skip = true;
+            skip_reasons.extend(
+                test_case
+                    .guards
+                    .iter()
+                    .filter_map(|guard| guard(config, base_dir.path()).err())
+                    .map(|err| err.to_string()),
+            );
+        }
+
+        // The tests which need more space check it in their guards
+        if !should_skip {
+            if let Err(e) = space::require(config, temp_dir.path(), space::BASE_REQUIREMENT) {
+                should_skip = true;
+                skip_reasons.push(e.to_string());
+            }
+        }
+
+        if let Some(reason) = sandbox::restrictions(base_dir.path())
+            .and_then(|restrictions| restrictions.skip_reason(test_case.name))
+        {
+            should_skip = true;
+            skip_reasons.push(reason);
+        }
+
+        // TODO: ;decide what to do about verbose
+        if options.verbose && !test_case.description.is_empty() {
+            print!("\n\t{}\t\t", test_case.description);
+        }
+
+        stdout().lock().flush()?;
+
+        if should_skip {
+            println!("{:72} skipped", test_case.name);
+            for reason in &skip_reasons {
+                println!("\t{}", reason);
+            }
+            send_event(
+                options,
+                Event::TestSkipped {
+                    test: test_case.name,
+                    reasons: &skip_reasons,
+                },
+            );
+            let details = TestDetails {
+                skip_reasons,
+                ..Default::default()
+            };
+            summary.record_details(test_case.name, TestStatus::Skipped, details);
+            record_resume(options, test_case.name, TestStatus::Skipped)?;
+            continue;
+        }
+
+        send_event(
+            options,
+            Event::TestStarted {
+                test: test_case.name,
+            },
+        );
+        if let Err(e) = hooks.pre_test(test_case.name, temp_dir.path()) {
+            let message = format!("pre-test hook failed: {e}");
+            println!("{:73} FAILED\n\t{message}", test_case.name);
+            let details = TestDetails {
+                message: Some(message),
+                ..Default::default()
+            };
+            send_finished(options, test_case.name, TestStatus::Failed, &details);
+            summary.record_details(test_case.name, TestStatus::Failed, details);
+            record_resume(options, test_case.name, TestStatus::Failed)?;
+            continue;
+        }
+
+        // Only the operations of the test are reported
+        trace::take();
+        latency::take();
+        let start = Instant::now();
+        let outcome = if options.isolate {
+            execute_isolated(test_case, config, temp_dir.path(), options)
+        } else {
+            execute_test(test_case, config, temp_dir.path(), options)
+        };
+        let mut details = TestDetails {
+            duration: start.elapsed(),
+            ..Default::default()
+        };
+
+        summary.profile.add(test_case.name, profile::take());
+        let operations = trace::take();
+        summary.latencies.add(latency::take());
+        let post_hook_result = hooks.post_test(test_case.name, temp_dir.path());
+
+        let expected_failure = config.expected_failure(test_case.name);
+        let status = match (&outcome, &post_hook_result, expected_failure) {
+            (TestOutcome::Passed, Ok(_), None) => TestStatus::Passed,
+            (TestOutcome::Passed, Ok(_), Some(_)) => TestStatus::UnexpectedPass,
+            (_, _, None) => TestStatus::Failed,
+            (_, _, Some(_)) => TestStatus::ExpectedFailure,
+        };
+
+        match status {
+            TestStatus::Passed => println!("{:77} ok", test_case.name),
+            TestStatus::Failed => println!("{:73} FAILED", test_case.name),
+            TestStatus::ExpectedFailure => println!("{:74} XFAIL", test_case.name),
+            TestStatus::UnexpectedPass => println!("{:74} XPASS", test_case.name),
+            TestStatus::Skipped => unreachable!(),
+        }
+        if let Some(reason) = expected_failure {
+            println!("\texpected to fail: {reason}");
+        }
+        if let TestOutcome::Failed { message, backtrace } = &outcome {
+            println!("\t{}", message);
+            if let (Some(backtrace), TestStatus::Failed) = (backtrace, status) {
+                println!("Backtrace:\n{}", backtrace);
+            }
+            if options.trace {
+                println!("\tOperations:");
+                for operation in &operations {
+                    println!("\t\t{operation}");
+                }
+            }
+            if let Some(artifacts_dir) = options.artifacts_dir {
+                let path = artifacts_path(artifacts_dir, test_case);
+                if let (Some(tracer), TestStatus::Failed) = (options.syscall_tracer, status) {
+                    match tracer.trace(test_case.name, base_dir.path(), options.seed, &path) {
+                        Ok(trace) => println!("\tsyscalls traced in {}", trace.display()),
+                        Err(e) => println!("\tcannot trace the syscalls: {e}"),
+                    }
+                }
+                if path.exists() {
+                    println!("\tartifacts captured in {}", path.display());
+                }
+            }
+        }
+        if let Err(e) = &post_hook_result {
+            println!("\tpost-test hook failed: {e}");
+        }
+        if let TestOutcome::Failed { message, backtrace } = &outcome {
+            details.message = Some(message.clone());
+            details.backtrace = backtrace.clone();
+            details.operations = operations;
+        } else if let Err(e) = post_hook_result {
+            details.message = Some(format!("post-test hook failed: {e}"));
+        }
+        send_finished(options, test_case.name, status, &details);
+        summary.record_details(test_case.name, status, details);
+        record_resume(options, test_case.name, status)?;
+
+        let keep = match options.cleanup {
+            Cleanup::Always => false,
+            Cleanup::KeepFailed => !matches!(outcome, TestOutcome::Passed),
+            Cleanup::Never => true,
+        };
+        // The context has been dropped, the entries won't be modified anymore
+        let snapshot = options.remount.map(|_| Snapshot::new(temp_dir.path()));
+        let temp_dir = if keep {
+            let path = temp_dir.into_path();
+            println!("\tkept in {}", path.display());
+            kept_dirs = true;
+            None
+        } else {
+            Some(temp_dir)
+        };
+        if let Some(snapshot) = snapshot {
+            group_dirs.push((temp_dir, snapshot));
+        }
+    }
+
+    if let (Some(remount), Some(group)) = (options.remount, previous_group) {
+        if !group_dirs.is_empty() && !summary.interrupted {
+            report_remount_durability(group, remount, &mut group_dirs, &mut summary, options);
+        }
+    }
+
+    send_event(
+        options,
+        Event::RunFinished {
+            passed: summary.passed,
+            failed: summary.failed,
+            skipped: summary.skipped,
+            expected_failures: summary.expected_failures,
+            unexpected_passes: summary.unexpected_passes,
+            interrupted: summary.interrupted,
+        },
+    );
+
+    if kept_dirs {
+        let _ = base_dir.into_path();
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test::{SerializedTestContext, TestCase, TestContext, TestFn};
+
+    use super::shuffle_test_cases;
+
+    fn non_serialized(_: &mut TestContext) {}
+
+    fn serialized(_: &mut SerializedTestContext) {}
+
+    fn test_case(name: &'static str, fun: TestFn) -> TestCase {
+        TestCase {
+            name,
+            description: "",
+            require_root: false,
+            fun,
+            required_features: &[],
+            guards: &[],
+        }
+    }
+
+    #[test]
+    fn shuffle() {
+        let names = ["a", "b", "serialized", "c", "d", "e", "f", "g", "h"];
+        let test_cases = || {
+            names
+                .iter()
+                .map(|name| {
+                    let fun = if *name == "serialized" {
+                        TestFn::Serialized(serialized)
+                    } else {
+                        TestFn::NonSerialized(non_serialized)
+                    };
+                    test_case(name, fun)
+                })
+                .collect::<Vec<_>>()
+        };
+        let shuffled = |seed| {
+            let mut test_cases = test_cases();
+            shuffle_test_cases(&mut test_cases, seed);
+            test_cases.iter().map(|tc| tc.name).collect::<Vec<_>>()
+        };
+
+        let order = shuffled(42);
+        assert_eq!(order, shuffled(42));
+        assert_ne!(order, names);
+        assert_eq!(order[2], "serialized");
+        let mut sorted = order.clone();
+        sorted.sort();
+        let mut expected = names.to_vec();
+        expected.sort();
+        assert_eq!(sorted, expected);
+    }
+}
@@ -0,0 +1,360 @@
+//! Detection of sandboxes (Landlock, seccomp, pledge, unveil) restricting file system operations.
+//!
+//! The sandboxes are detected from the state of the process: `/proc/self/status`
+//! (with the Landlock ABI) on Linux, the pledge flag and the visibility of the root directory
+//! on OpenBSD. When the process may be sandboxed, the syscalls are probed in a throwaway
+//! directory of the file system under test, since Landlock and unveil restrict by path.
+//! Only the errors the detected sandboxes deny with are taken as denials, the other ones
+//! being the file system's (they make the tests fail instead). Tests for a denied syscall
+//! are then skipped instead of failing with confusing errors.
+
+use std::{
+    fmt::{self, Display},
+    os::fd::OwnedFd,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        signal::Signal,
+        stat::{lstat, mknod, utimensat, Mode, SFlag, UtimensatFlags},
+        time::TimeSpec,
+    },
+    unistd::{chown, getegid, geteuid, mkdir, mkfifo, truncate, unlink},
+};
+use tempfile::{Builder, TempDir};
+
+use crate::{
+    isolation::{run_in_child, ChildError},
+    utils::{chmod, link, open, rename, rmdir, symlink},
+};
+
+/// Syscalls which are required to create the files used by the tests.
+const CREATION_SYSCALLS: [&str; 2] = ["open", "mkdir"];
+
+/// Sandbox which may restrict the file system operations of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    Landlock,
+    Seccomp,
+    Pledge,
+    Unveil,
+}
+
+impl Sandbox {
+    /// Errors returned by the operations denied by the sandbox.
+    pub fn denial_errors(self) -> &'static [Errno] {
+        match self {
+            Sandbox::Landlock => &[Errno::EACCES],
+            // The error is chosen by the filter
+            Sandbox::Seccomp => &[Errno::EPERM, Errno::EACCES, Errno::ENOSYS],
+            // Only with the `error` promise, the process is killed otherwise
+            Sandbox::Pledge => &[Errno::ENOSYS],
+            // The paths which are not unveiled are hidden
+            Sandbox::Unveil => &[Errno::EACCES, Errno::ENOENT],
+        }
+    }
+}
+
+impl Display for Sandbox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Sandbox::Landlock => "Landlock",
+            Sandbox::Seccomp => "seccomp",
+            Sandbox::Pledge => "pledge",
+            Sandbox::Unveil => "unveil",
+        })
+    }
+}
+
+/// How a syscall is denied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denial {
+    /// The syscall fails with an error.
+    Error(Errno),
+    /// The process is killed by a signal.
+    Killed(Signal),
+}
+
+impl Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Denial::Error(errno) => write!(f, "{errno}"),
+            Denial::Killed(signal) => write!(f, "killed by {signal}"),
+        }
+    }
+}
+
+/// Restrictions detected on the file system operations.
+#[derive(Debug)]
+pub struct Restrictions {
+    /// Sandboxes detected on the process, one of which enforces the restrictions.
+    pub sandboxes: Vec<Sandbox>,
+    /// Syscalls which are denied, with how they are denied.
+    pub denied: Vec<(&'static str, Denial)>,
+}
+
+impl Restrictions {
+    /// Return the name of the sandboxes which may enforce the restrictions.
+    pub fn sandbox(&self) -> String {
+        self.sandboxes
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(" or ")
+    }
+
+    /// Return the reason why the test should be skipped, if the syscall it tests is denied.
+    /// The syscall is derived from the module of the test.
+    pub fn skip_reason(&self, test_name: &str) -> Option<String> {
+        let module = test_name.split("::").next()?;
+        if module == "sandbox" {
+            return None;
+        }
+
+        self.denied
+            .iter()
+            .find(|(syscall, _)| CREATION_SYSCALLS.contains(syscall) || *syscall == module)
+            .map(|(syscall, denial)| {
+                format!("{syscall} is denied by {} ({denial})", self.sandbox())
+            })
+    }
+}
+
+/// Return the restrictions on the file system operations, if any.
+/// The probe is only done once, subsequent calls return the same result.
+pub fn restrictions(base_path: &Path) -> Option<&'static Restrictions> {
+    static RESTRICTIONS: OnceLock<Option<Restrictions>> = OnceLock::new();
+
+    RESTRICTIONS.get_or_init(|| probe(base_path)).as_ref()
+}
+
+/// Return the sandboxes which may restrict the process.
+#[cfg(target_os = "linux")]
+fn detect() -> Vec<Sandbox> {
+    // Only reports a version if Landlock is enabled in the kernel
+    const LANDLOCK_CREATE_RULESET_VERSION: nix::libc::c_uint = 1 << 0;
+
+    let mut sandboxes = Vec::new();
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+
+    // The Landlock domain of a process cannot be queried, but entering one requires
+    // no_new_privs (which the sandboxing tools set), and an enabled Landlock
+    if field("NoNewPrivs") == Some("1") {
+        // SAFETY: The version query doesn't read any ruleset attribute
+        let abi = unsafe {
+            nix::libc::syscall(
+                nix::libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<nix::libc::c_void>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        if abi > 0 {
+            sandboxes.push(Sandbox::Landlock);
+        }
+    }
+
+    // Mode 2 is the filter mode, the strict mode only allows read, write and exit
+    if field("Seccomp") == Some("2") {
+        sandboxes.push(Sandbox::Seccomp);
+    }
+
+    sandboxes
+}
+
+/// Return the sandboxes which may restrict the process.
+#[cfg(target_os = "openbsd")]
+fn detect() -> Vec<Sandbox> {
+    const PS_PLEDGE: u32 = 0x0010_0000;
+
+    let mut sandboxes = Vec::new();
+    // The sysctl may be denied by pledge, which kills the process
+    let pledged = run_in_child(|| {
+        let mib = [
+            nix::libc::CTL_KERN,
+            nix::libc::KERN_PROC,
+            nix::libc::KERN_PROC_PID,
+            std::process::id() as nix::libc::c_int,
+            std::mem::size_of::<nix::libc::kinfo_proc>() as nix::libc::c_int,
+            1,
+        ];
+        let mut info = std::mem::MaybeUninit::<nix::libc::kinfo_proc>::zeroed();
+        let mut len = std::mem::size_of::<nix::libc::kinfo_proc>();
+        // SAFETY: The buffer is as large as the requested structure, which is zeroed
+        let res = unsafe {
+            nix::libc::sysctl(
+                mib.as_ptr(),
+                mib.len() as nix::libc::c_uint,
+                info.as_mut_ptr().cast(),
+                &mut len,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        // SAFETY: The structure is zeroed, and filled by the sysctl if it succeeds
+        let flags = unsafe { info.assume_init() }.p_psflags;
+        vec![(res == 0 && flags & PS_PLEDGE != 0) as u8]
+    });
+    if !matches!(pledged.as_deref(), Ok([0])) {
+        sandboxes.push(Sandbox::Pledge);
+    }
+
+    // Once unveil has been called, the root directory is hidden unless it is unveiled itself
+    if nix::unistd::access("/", nix::unistd::AccessFlags::F_OK) == Err(Errno::ENOENT) {
+        sandboxes.push(Sandbox::Unveil);
+    }
+
+    sandboxes
+}
+
+/// Return the sandboxes which may restrict the process.
+#[cfg(not(any(target_os = "linux", target_os = "openbsd")))]
+fn detect() -> Vec<Sandbox> {
+    Vec::new()
+}
+
+/// Create a throwaway directory in `base_path`, where the syscalls are probed.
+fn scratch_dir(base_path: &Path) -> nix::Result<TempDir> {
+    Builder::new()
+        .prefix(".sandbox-probe")
+        .tempdir_in(base_path)
+        .map_err(|e| Errno::from_raw(e.raw_os_error().unwrap_or(0)))
+}
+
+/// Return whether the throwaway directory can still be used to probe the syscalls.
+fn usable(dir: &Path) -> bool {
+    lstat(dir)
+        .is_ok_and(|stat| SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFDIR)
+}
+
+type Probe = fn(&Path) -> nix::Result<()>;
+
+fn create(path: &Path) -> nix::Result<OwnedFd> {
+    open(
+        path,
+        OFlag::O_CREAT | OFlag::O_WRONLY,
+        Mode::from_bits_truncate(0o644),
+    )
+}
+
+fn existing_file(dir: &Path, name: &str) -> nix::Result<PathBuf> {
+    let path = dir.join(name);
+    create(&path)?;
+    Ok(path)
+}
+
+const PROBES: [(&str, Probe); 13] = [
+    ("open", |dir| create(&dir.join("open")).map(drop)),
+    ("mkdir", |dir| mkdir(&dir.join("mkdir"), Mode::S_IRWXU)),
+    ("mkfifo", |dir| mkfifo(&dir.join("mkfifo"), Mode::S_IRWXU)),
+    ("mknod", |dir| {
+        mknod(&dir.join("mknod"), SFlag::S_IFIFO, Mode::S_IRWXU, 0)
+    }),
+    ("symlink", |dir| {
+        symlink(Path::new("target"), &dir.join("symlink"))
+    }),
+    ("link", |dir| {
+        link(&*existing_file(dir, "link_src")?, &*dir.join("link"))
+    }),
+    ("rename", |dir| {
+        rename(&*existing_file(dir, "rename_src")?, &*dir.join("rename"))
+    }),
+    ("truncate", |dir| {
+        truncate(&existing_file(dir, "truncate")?, 1)
+    }),
+    ("chmod", |dir| {
+        chmod(&existing_file(dir, "chmod")?, Mode::S_IRWXU)
+    }),
+    ("chown", |dir| {
+        chown(
+            &existing_file(dir, "chown")?,
+            Some(geteuid()),
+            Some(getegid()),
+        )
+    }),
+    ("utimensat", |dir| {
+        let now = TimeSpec::new(0, nix::libc::UTIME_NOW);
+        utimensat(
+            None,
+            &existing_file(dir, "utimensat")?,
+            &now,
+            &now,
+            UtimensatFlags::FollowSymlink,
+        )
+    }),
+    ("unlink", |dir| unlink(&existing_file(dir, "unlink")?)),
+    ("rmdir", |dir| {
+        let path = dir.join("rmdir");
+        mkdir(&path, Mode::S_IRWXU)?;
+        rmdir(&path)
+    }),
+];
+
+/// Probe the syscalls in a throwaway directory of `base_path`, if the process may be sandboxed.
+///
+/// A failure is a denial if the process is killed, or if the error is one
+/// the detected sandboxes deny with, the other errors are the file system's.
+/// The syscalls are probed in a child process, since some sandboxes kill the process,
+/// and the directory is checked before and after each of them, so that the failures
+/// of the following probes aren't caused by a directory which is gone.
+fn probe(base_path: &Path) -> Option<Restrictions> {
+    let sandboxes = detect();
+    if sandboxes.is_empty() {
+        return None;
+    }
+    let is_denial = |errno: Errno| {
+        sandboxes
+            .iter()
+            .any(|sandbox| sandbox.denial_errors().contains(&errno))
+    };
+
+    let dir = match scratch_dir(base_path) {
+        Ok(dir) => dir,
+        // Nothing can be created in the file system under test
+        Err(errno) if is_denial(errno) => {
+            let denied = vec![("mkdir", Denial::Error(errno))];
+            return Some(Restrictions { sandboxes, denied });
+        }
+        Err(_) => return None,
+    };
+
+    let mut denied = Vec::new();
+    for (syscall, probe) in PROBES {
+        if !usable(dir.path()) {
+            break;
+        }
+        let result = run_in_child(|| {
+            let errno = probe(dir.path()).err().map_or(0, |errno| errno as i32);
+            errno.to_ne_bytes().to_vec()
+        });
+        if !usable(dir.path()) {
+            break;
+        }
+
+        let denial = match result {
+            Ok(bytes) => match bytes
+                .try_into()
+                .map(i32::from_ne_bytes)
+                .map(Errno::from_raw)
+            {
+                Ok(errno) if is_denial(errno) => Denial::Error(errno),
+                _ => continue,
+            },
+            Err(ChildError::Signaled { signal, .. }) => Denial::Killed(signal),
+            Err(_) => continue,
+        };
+        denied.push((syscall, denial));
+    }
+
+    (!denied.is_empty()).then_some(Restrictions { sandboxes, denied })
+}
@@ -0,0 +1,91 @@
+//! Snapshots of the state of a directory tree, allowing tests to check
+//! that a syscall had no side effects besides the expected ones.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use nix::sys::{
+    stat::{lstat, mode_t},
+    time::TimeSpec,
+};
+
+/// State of an entry recorded in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct EntryState {
+    mode: mode_t,
+    uid: u32,
+    gid: u32,
+    size: i64,
+    mtime: TimeSpec,
+    ctime: TimeSpec,
+    #[cfg(file_flags)]
+    flags: u64,
+}
+
+/// Recursive state (modes, owners, sizes, flags, modification and change times)
+/// of all the entries of a directory tree.
+#[derive(Debug)]
+pub struct Snapshot {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, EntryState>,
+}
+
+impl Snapshot {
+    /// Record the state of the tree rooted at `root`, without following symlinks.
+    /// Entries which cannot be accessed are not recorded.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        let root = root.as_ref().to_path_buf();
+        let entries = walkdir::WalkDir::new(&root)
+            .into_iter()
+            .filter_map(|entry| {
+                let path = entry.ok()?.into_path();
+                let file_stat = lstat(&path).ok()?;
+                let state = EntryState {
+                    mode: file_stat.st_mode,
+                    uid: file_stat.st_uid,
+                    gid: file_stat.st_gid,
+                    size: file_stat.st_size,
+                    mtime: TimeSpec::new(file_stat.st_mtime, file_stat.st_mtime_nsec),
+                    ctime: TimeSpec::new(file_stat.st_ctime, file_stat.st_ctime_nsec),
+                    #[cfg(file_flags)]
+                    flags: file_stat.st_flags.into(),
+                };
+
+                Some((path, state))
+            })
+            .collect();
+
+        Self { root, entries }
+    }
+
+    /// Assert that no entry of the tree has been created, removed or modified since the snapshot,
+    /// except for `paths`.
+    pub fn verify_unchanged_except(&self, paths: &[&Path]) {
+        let current = Snapshot::new(&self.root);
+        let excluded = |path: &Path| paths.contains(&path);
+
+        for (path, state) in self.entries.iter().filter(|(p, _)| !excluded(p)) {
+            match current.entries.get(path) {
+                Some(current_state) => {
+                    assert_eq!(state, current_state, "{} has been modified", path.display())
+                }
+                None => panic!("{} has been removed", path.display()),
+            }
+        }
+
+        for path in current.entries.keys().filter(|p| !excluded(p)) {
+            assert!(
+                self.entries.contains_key(path),
+                "{} has been created",
+                path.display()
+            );
+        }
+    }
+
+    /// Assert that no entry of the tree has been created, removed or modified since the snapshot.
+    pub fn verify_unchanged(&self) {
+        self.verify_unchanged_except(&[]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Check of the space available on the file system under test.
+//!
+//! Before each test, the runner checks that the file system has enough space
+//! for the few entries it creates, and the guards of the tests which need more
+//! (large files, large directories) check their own estimate.
+//! The tests which cannot fit are skipped, rather than failing with ENOSPC
+//! or filling the file system, which may be the one of the host.
+//! The `reserved_space` setting keeps a margin free on the file system.
+
+use std::path::Path;
+
+use nix::sys::statvfs::statvfs;
+
+use crate::config::Config;
+
+/// Space required by any test, for its directory and the entries it creates.
+pub const BASE_REQUIREMENT: u64 = 1 << 20;
+
+/// Return the space available on the file system of `path`, in bytes.
+pub fn available(path: &Path) -> nix::Result<u64> {
+    let stat = statvfs(path)?;
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+/// Check that a test can use `required` bytes on the file system of `path`,
+/// leaving the space reserved by the configuration free.
+pub fn require(config: &Config, path: &Path, required: u64) -> anyhow::Result<()> {
+    let available = available(path)?;
+    let reserved = config.settings.reserved_space.unwrap_or(0);
+    if available < required.saturating_add(reserved) {
+        let reserved = if reserved > 0 {
+            format!(" ({} reserved)", format_size(reserved))
+        } else {
+            String::new()
+        };
+        anyhow::bail!(
+            "not enough space: requires {}, {} available{reserved}",
+            format_size(required),
+            format_size(available)
+        );
+    }
+
+    Ok(())
+}
+
+/// Format a size with a binary unit.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    use super::{available, format_size, require};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size((1 << 32) + 123), "4.0 GiB");
+    }
+
+    #[test]
+    fn requirement() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        assert!(require(&config, dir.path(), 1).is_ok());
+
+        let available = available(dir.path()).unwrap();
+        let error = require(&config, dir.path(), available * 2).unwrap_err();
+        assert!(error.to_string().starts_with("not enough space: requires "));
+
+        config.settings.reserved_space = Some(available * 2);
+        let error = require(&config, dir.path(), 1).unwrap_err();
+        assert!(error.to_string().ends_with(" reserved)"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Trace of the syscalls of the failed tests, with the tracer of the platform (strace, truss...).
+//!
+//! A failed test is run again in a child runner under the tracer, with the same seed,
+//! and the trace is saved with the artifacts of the test.
+
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Name of the file where the trace is written, in the artifacts of the test.
+const TRACE: &str = "syscalls.trace";
+
+/// Name of the file where the output of the traced run is written, in the artifacts of the test.
+const LOG: &str = "syscalls.log";
+
+/// Tracer command of the platform, if it has one.
+pub fn default_command() -> Option<&'static str> {
+    if cfg!(target_os = "linux") {
+        Some(r#"strace -f -o "$PJDFSTEST_TRACE_FILE" "$@""#)
+    } else if cfg!(any(target_os = "freebsd", target_os = "illumos")) {
+        Some(r#"truss -f -o "$PJDFSTEST_TRACE_FILE" "$@""#)
+    } else {
+        None
+    }
+}
+
+/// Tracing of the syscalls of the failed tests.
+#[derive(Debug, Clone)]
+pub struct SyscallTracer {
+    /// Shell command running the command given as positional arguments under the tracer,
+    /// writing the trace to `$PJDFSTEST_TRACE_FILE`.
+    pub command: String,
+    /// Arguments given to the child runner, in addition to the path, the seed and the test name
+    /// (e.g. the configuration file).
+    pub args: Vec<String>,
+}
+
+impl SyscallTracer {
+    /// Run the test again under the tracer, in a new directory of `path` and with the same seed,
+    /// and return the path of the trace saved in `artifacts_dir`.
+    pub fn trace(
+        &self,
+        test_name: &str,
+        path: &Path,
+        seed: u64,
+        artifacts_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(artifacts_dir)?;
+        let trace = artifacts_dir.join(TRACE);
+        let log = File::create(artifacts_dir.join(LOG))?;
+
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .arg("sh")
+            .arg(std::env::current_exe()?)
+            .args(&self.args)
+            .arg("-p")
+            .arg(path)
+            .args(["--seed", &seed.to_string(), "--exact", "--", test_name])
+            .env("PJDFSTEST_TRACE_FILE", &trace)
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .status()?;
+
+        // The traced run fails with the test, only the trace tells whether the tracer worked
+        if !trace.exists() {
+            anyhow::bail!(
+                "`{}` did not write a trace ({status}), see {}",
+                self.command,
+                artifacts_dir.join(LOG).display()
+            );
+        }
+
+        Ok(trace)
+    }
+}
@@ -10,6 +10,21 @@ pub use crate::flags::*;
 /// Function which indicates if the test should be skipped by returning an error.
 pub type Guard = fn(&Config, &Path) -> Result<(), anyhow::Error>;
 
+/// Guard to check that root has its privileges on the file system (changing owners,
+/// overriding permissions, creating device nodes...), which it doesn't when the server
+/// maps it to an anonymous user (the `root_squash` feature).
+pub fn root_privileges(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::RootSquash)
+    {
+        anyhow::bail!("root privileges are squashed by the server (root_squash)")
+    }
+
+    Ok(())
+}
+
 /// Function which runs the test.
 /// The function is passed a context object which can be used to interact with the filesystem.
 #[derive(Clone, Copy)]
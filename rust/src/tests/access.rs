@@ -0,0 +1,83 @@
+//! Tests for the IDs used by access(2) and faccessat(2) to check permissions.
+
+use nix::{
+    errno::Errno,
+    fcntl::AtFlags,
+    unistd::{access, faccessat, getuid, seteuid, AccessFlags, Uid},
+};
+
+use super::assert_errno;
+use crate::{
+    context::{FileType, SerializedTestContext},
+    test::root_privileges,
+    utils::chown,
+};
+
+crate::test_case! {
+    /// access checks the permissions with the real IDs,
+    /// while faccessat with AT_EACCESS uses the effective ones
+    real_ids_effective_ids, serialized, root
+}
+fn real_ids_effective_ids(ctx: &mut SerializedTestContext) {
+    let file = ctx
+        .new_file(FileType::Regular)
+        .mode(0o600)
+        .create()
+        .unwrap();
+    let user = ctx.get_new_user();
+
+    ctx.as_effective_user(user, None, || {
+        assert!(access(&file, AccessFlags::R_OK | AccessFlags::W_OK).is_ok());
+        assert_errno(
+            ctx,
+            faccessat(None, &file, AccessFlags::R_OK, AtFlags::AT_EACCESS),
+            Errno::EACCES,
+        );
+    });
+
+    ctx.as_real_user(user, None, || {
+        assert_errno(ctx, access(&file, AccessFlags::R_OK), Errno::EACCES);
+        assert_errno(
+            ctx,
+            faccessat(None, &file, AccessFlags::R_OK, AtFlags::AT_EACCESS),
+            Errno::EACCES,
+        );
+    });
+}
+
+crate::test_case! {
+    /// access grants the permissions of the owner class to the real user ID
+    real_ids_owner, serialized, root; root_privileges
+}
+fn real_ids_owner(ctx: &mut SerializedTestContext) {
+    let user = ctx.get_new_user();
+    let file = ctx
+        .new_file(FileType::Regular)
+        .mode(0o400)
+        .create()
+        .unwrap();
+    chown(&file, Some(user.uid), Some(user.gid)).unwrap();
+
+    ctx.as_real_user(user, None, || {
+        assert!(access(&file, AccessFlags::R_OK).is_ok());
+        assert_errno(ctx, access(&file, AccessFlags::W_OK), Errno::EACCES);
+        assert_errno(ctx, access(&file, AccessFlags::X_OK), Errno::EACCES);
+    });
+}
+
+crate::test_case! {
+    /// Privileges cannot be regained once the real IDs have been changed
+    real_ids_dropped, serialized, root
+}
+fn real_ids_dropped(ctx: &mut SerializedTestContext) {
+    let user = ctx.get_new_user();
+
+    ctx.as_effective_user(user, None, || {
+        assert!(getuid().is_root());
+    });
+
+    ctx.as_real_user(user, None, || {
+        assert_eq!(getuid(), user.uid);
+        assert_errno(ctx, seteuid(Uid::from_raw(0)), Errno::EPERM);
+    });
+}
@@ -0,0 +1,136 @@
+//! Tests for the data path of files with an append-only flag (`UF_APPEND`/`SF_APPEND`).
+
+use std::{fs, os::fd::AsFd, path::Path};
+
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        stat::{FileFlag, Mode},
+        uio::pwrite,
+    },
+    unistd::{chflags, ftruncate, write},
+};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::{FileFlags, FileSystemFeature},
+    utils::{lstat, open, truncate},
+};
+
+use super::assert_errno_for;
+use super::chflags::declared_flags;
+
+const APPEND_FLAGS: [FileFlags; 2] = [FileFlags::UF_APPEND, FileFlags::SF_APPEND];
+
+/// Guard to check that an append-only flag is supported.
+fn has_append_flags(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if declared_flags(&config.features, &APPEND_FLAGS).is_empty() {
+        anyhow::bail!("neither UF_APPEND nor SF_APPEND is declared in file_flags")
+    }
+
+    Ok(())
+}
+
+/// Call `f` for each supported append-only flag with a regular file containing data,
+/// after setting the flag on the file.
+fn for_each_append_flag<F>(ctx: &TestContext, f: F)
+where
+    F: Fn(FileFlags, &Path),
+{
+    for flag in declared_flags(ctx.features_config(), &APPEND_FLAGS) {
+        let file = ctx.create(FileType::Regular).unwrap();
+        fs::write(&file, b"data").unwrap();
+        chflags(&file, flag.into()).unwrap();
+
+        f(flag, &file);
+
+        chflags(&file, FileFlag::empty()).unwrap();
+    }
+}
+
+crate::test_case! {
+    /// open returns EPERM when opening an append-only file for writing without O_APPEND, or with O_TRUNC
+    open_for_writing_eperm, root, FileSystemFeature::Chflags; has_append_flags
+}
+fn open_for_writing_eperm(ctx: &mut TestContext) {
+    for_each_append_flag(ctx, |flag, file| {
+        for oflag in [
+            OFlag::O_WRONLY,
+            OFlag::O_RDWR,
+            OFlag::O_WRONLY | OFlag::O_TRUNC,
+            OFlag::O_WRONLY | OFlag::O_APPEND | OFlag::O_TRUNC,
+        ] {
+            assert_errno_for(
+                ctx,
+                open(file, oflag, Mode::empty()).map(drop),
+                Errno::EPERM,
+                format_args!("{oflag:?} with {flag}"),
+            );
+        }
+        assert_eq!(fs::read(file).unwrap(), b"data");
+    });
+}
+
+crate::test_case! {
+    /// Writes to an append-only file succeed when it is opened with O_APPEND
+    append_write, root, FileSystemFeature::Chflags; has_append_flags
+}
+fn append_write(ctx: &mut TestContext) {
+    for_each_append_flag(ctx, |flag, file| {
+        let fd = open(file, OFlag::O_WRONLY | OFlag::O_APPEND, Mode::empty()).unwrap();
+        assert_eq!(write(fd.as_fd(), b" appended"), Ok(9), "{flag}");
+        assert_eq!(fs::read(file).unwrap(), b"data appended");
+    });
+}
+
+crate::test_case! {
+    /// write returns EPERM on an append-only file when not writing at the end of the file,
+    /// even through a descriptor opened before the flag was set
+    write_before_end_eperm, root, FileSystemFeature::Chflags; has_append_flags
+}
+fn write_before_end_eperm(ctx: &mut TestContext) {
+    for flag in declared_flags(ctx.features_config(), &APPEND_FLAGS) {
+        let file = ctx.create(FileType::Regular).unwrap();
+        fs::write(&file, b"data").unwrap();
+        let fd = open(&file, OFlag::O_WRONLY, Mode::empty()).unwrap();
+        chflags(&file, flag.into()).unwrap();
+
+        assert_errno_for(
+            ctx,
+            pwrite(fd.as_fd(), b"x", 0),
+            Errno::EPERM,
+            format_args!("{flag}"),
+        );
+        assert_errno_for(
+            ctx,
+            pwrite(fd.as_fd(), b"x", 2),
+            Errno::EPERM,
+            format_args!("{flag}"),
+        );
+        assert_eq!(fs::read(&file).unwrap(), b"data");
+
+        chflags(&file, FileFlag::empty()).unwrap();
+    }
+}
+
+crate::test_case! {
+    /// truncate and ftruncate return EPERM on an append-only file
+    truncate_eperm, root, FileSystemFeature::Chflags; has_append_flags
+}
+fn truncate_eperm(ctx: &mut TestContext) {
+    for_each_append_flag(ctx, |flag, file| {
+        assert_errno_for(ctx, truncate(file, 0), Errno::EPERM, format_args!("{flag}"));
+        assert_errno_for(
+            ctx,
+            truncate(file, 123),
+            Errno::EPERM,
+            format_args!("{flag}"),
+        );
+
+        let fd = open(file, OFlag::O_WRONLY | OFlag::O_APPEND, Mode::empty()).unwrap();
+        assert_errno_for(ctx, ftruncate(&fd, 0), Errno::EPERM, format_args!("{flag}"));
+        assert_eq!(lstat(file).unwrap().st_size, 4);
+    });
+}
@@ -0,0 +1,89 @@
+//! Tests for access time updates, according to the policy declared in the configuration.
+
+use std::{
+    fs::{read, read_dir, write},
+    path::Path,
+};
+
+use crate::{
+    config::{AtimePolicy, Config},
+    context::{FileType, TestContext},
+};
+
+use super::{assert_times_changed, assert_times_unchanged, ATIME};
+
+/// Guard to check that the access time policy has been declared in the configuration.
+fn has_atime_policy(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config.features.atime_policy.is_none() {
+        anyhow::bail!("No access time policy (atime_policy) has been configured")
+    }
+
+    Ok(())
+}
+
+/// Assert that two consecutive accesses done by `f` update the access time of `path`
+/// according to the declared policy.
+fn assert_atime_policy<F>(ctx: &TestContext, path: &Path, f: F)
+where
+    F: Fn(),
+{
+    match ctx.features_config().atime_policy.unwrap() {
+        AtimePolicy::Strict => {
+            assert_times_changed()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+            assert_times_changed()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+        }
+        // The access time of a new file is not newer than its modification time,
+        // so only the first access should update it
+        AtimePolicy::Relatime => {
+            assert_times_changed()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+            assert_times_unchanged()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+        }
+        AtimePolicy::Noatime => {
+            assert_times_unchanged()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+            assert_times_unchanged()
+                .path(path, ATIME)
+                .execute(ctx, false, &f);
+        }
+    }
+}
+
+crate::test_case! {
+    /// read updates the access time of a file according to the access time policy
+    read_file; has_atime_policy
+}
+fn read_file(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+    write(&file, b"data").unwrap();
+
+    assert_atime_policy(ctx, &file, || {
+        read(&file).unwrap();
+    });
+}
+
+crate::test_case! {
+    /// readdir updates the access time of a directory according to the access time policy
+    readdir; has_atime_policy
+}
+fn readdir(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    ctx.new_file(FileType::Regular)
+        .name(dir.join("file"))
+        .create()
+        .unwrap();
+
+    assert_atime_policy(ctx, &dir, || {
+        read_dir(&dir).unwrap().for_each(|entry| {
+            entry.unwrap();
+        });
+    });
+}
@@ -0,0 +1,183 @@
+//! Tests for the block accounting of regular files, that is `st_blocks`,
+//! with and without holes, and its consistency with the free blocks reported by statvfs.
+
+use std::{
+    os::fd::{AsFd, AsRawFd, OwnedFd},
+    thread,
+    time::Duration,
+};
+
+use nix::{
+    fcntl::OFlag,
+    libc::off_t,
+    sys::{stat::fstat, statvfs::statvfs, uio::pwrite},
+    unistd::{fsync, ftruncate, sync},
+};
+use rand::Rng;
+
+use crate::{context::TestContext, rng::with_rng, test::FileSystemFeature};
+
+/// Unit of `st_blocks`, which POSIX leaves unspecified but is 512 bytes on every supported platform.
+const BLOCK_UNIT: u64 = 512;
+
+/// Size of the data written by the tests.
+const DATA_SIZE: usize = 1024 * 1024;
+
+/// Size of the data written to check the consistency with statvfs,
+/// large enough for the metadata updated meanwhile to be negligible.
+const STATVFS_SIZE: usize = 16 * 1024 * 1024;
+
+/// Offset of the data written after a hole.
+pub(crate) const SPARSE_OFFSET: u64 = 64 * 1024 * 1024;
+
+/// Return the space allocated to an open file, in bytes.
+pub(crate) fn allocated<F: AsFd>(file: &F) -> u64 {
+    fstat(file.as_fd().as_raw_fd()).unwrap().st_blocks as u64 * BLOCK_UNIT
+}
+
+/// Write random data, so that the space is actually used on compressing file systems.
+fn write_random(file: &OwnedFd, offset: u64, len: usize) {
+    let mut data = vec![0; len];
+    with_rng(|rng| rng.fill(&mut data[..]));
+    assert_eq!(pwrite(file, &data, offset as off_t).unwrap(), len);
+}
+
+/// Flush the file, and the file system as some (e.g. ZFS) update `st_blocks`
+/// only once the data has been written out.
+fn flush(file: &OwnedFd) {
+    fsync(file.as_raw_fd()).unwrap();
+    sync();
+}
+
+/// Return the slack allowed over the size of the data for the space allocated to a file,
+/// which covers the rounding to the block size and the blocks of metadata.
+fn slack(file: &OwnedFd) -> u64 {
+    64 * fstat(file.as_raw_fd()).unwrap().st_blksize as u64
+}
+
+/// Assert that the space allocated to the file is in the bounds expected for `size` bytes of data.
+/// Half of the size is accepted, as some file systems account the data after deduplication
+/// or do not count their redundancy.
+fn assert_allocated(file: &OwnedFd, size: u64) {
+    let allocated = allocated(file);
+    assert!(
+        allocated >= size / 2 && allocated <= 2 * size + slack(file),
+        "{allocated} bytes are allocated for {size} bytes of data"
+    );
+}
+
+crate::test_case! {
+    /// st_blocks accounts for the data written to a file
+    blocks_written_data
+}
+fn blocks_written_data(ctx: &mut TestContext) {
+    let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    flush(&file);
+    let empty = allocated(&file);
+    assert!(
+        empty <= slack(&file),
+        "{empty} bytes are allocated to an empty file"
+    );
+
+    write_random(&file, 0, DATA_SIZE);
+    flush(&file);
+    assert_allocated(&file, DATA_SIZE as u64);
+}
+
+crate::test_case! {
+    /// st_blocks grows when a file is extended with data, and shrinks when it is truncated
+    blocks_extension_truncation
+}
+fn blocks_extension_truncation(ctx: &mut TestContext) {
+    let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    write_random(&file, 0, DATA_SIZE);
+    flush(&file);
+    let written = allocated(&file);
+
+    write_random(&file, DATA_SIZE as u64, DATA_SIZE);
+    flush(&file);
+    let extended = allocated(&file);
+    assert!(
+        extended > written,
+        "st_blocks did not grow when the file was extended ({written} bytes allocated, then {extended})"
+    );
+    assert_allocated(&file, 2 * DATA_SIZE as u64);
+
+    ftruncate(&file, DATA_SIZE as off_t / 2).unwrap();
+    flush(&file);
+    let truncated = allocated(&file);
+    assert!(
+        truncated < extended,
+        "st_blocks did not shrink when the file was truncated ({extended} bytes allocated, then {truncated})"
+    );
+    assert_allocated(&file, DATA_SIZE as u64 / 2);
+
+    ftruncate(&file, 0).unwrap();
+    flush(&file);
+    let emptied = allocated(&file);
+    assert!(
+        emptied <= slack(&file),
+        "{emptied} bytes are still allocated after truncating the file to 0"
+    );
+}
+
+crate::test_case! {
+    /// The holes of a sparse file are not accounted in st_blocks
+    blocks_holes, FileSystemFeature::SparseFiles
+}
+fn blocks_holes(ctx: &mut TestContext) {
+    let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+
+    // Data, then a hole and data again
+    write_random(&file, 0, DATA_SIZE);
+    write_random(&file, SPARSE_OFFSET, DATA_SIZE);
+    flush(&file);
+    assert_allocated(&file, 2 * DATA_SIZE as u64);
+    let written = allocated(&file);
+
+    // A hole at the end of the file
+    ftruncate(&file, 2 * SPARSE_OFFSET as off_t).unwrap();
+    flush(&file);
+    let extended = allocated(&file);
+    assert!(
+        extended <= written + slack(&file),
+        "extending the file to {} bytes allocated {} bytes",
+        2 * SPARSE_OFFSET,
+        extended - written
+    );
+}
+
+crate::test_case! {
+    /// The space allocated to a file according to st_blocks is consistent
+    /// with the change of the free blocks reported by statvfs
+    blocks_statvfs
+}
+fn blocks_statvfs(ctx: &mut TestContext) {
+    let free_space = || {
+        let stat = statvfs(ctx.base_path()).unwrap();
+        stat.blocks_free() as u64 * stat.fragment_size() as u64
+    };
+
+    let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    flush(&file);
+    let before = free_space();
+    write_random(&file, 0, STATVFS_SIZE);
+    flush(&file);
+    let allocated = allocated(&file);
+    assert_allocated(&file, STATVFS_SIZE as u64);
+
+    // Some file systems update their free blocks asynchronously
+    let mut used = 0;
+    for _ in 0..50 {
+        used = before.saturating_sub(free_space());
+        if used >= allocated / 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+        sync();
+    }
+    assert!(
+        used >= allocated / 2 && used <= 2 * allocated + slack(&file),
+        "{used} bytes were used on the file system for a file of {allocated} allocated bytes"
+    );
+}
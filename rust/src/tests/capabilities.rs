@@ -0,0 +1,95 @@
+//! Privilege checks relying on Linux capabilities rather than on the user ID,
+//! by dropping specific capabilities of the super-user.
+
+use std::path::Path;
+
+use caps::{has_cap, CapSet, Capability};
+use nix::{errno::Errno, sys::stat::Mode};
+
+use super::assert_errno;
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    utils::{chmod, chown, lstat, unlink},
+};
+
+/// Guard to check that the process has the capabilities which are dropped by the tests.
+fn has_caps(_: &Config, _: &Path) -> anyhow::Result<()> {
+    for cap in [Capability::CAP_CHOWN, Capability::CAP_FOWNER] {
+        if !has_cap(None, CapSet::Effective, cap)? {
+            anyhow::bail!("process doesn't have the {cap} capability")
+        }
+    }
+
+    Ok(())
+}
+
+crate::test_case! {
+    /// chown returns EPERM for the super-user without CAP_CHOWN
+    chown_without_cap_chown, root; has_caps => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn chown_without_cap_chown(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+    let (user, group) = ctx.get_new_entry();
+
+    ctx.without_caps(&[Capability::CAP_CHOWN], || {
+        assert_errno(ctx, chown(&file, Some(user.uid), None), Errno::EPERM);
+        assert_errno(ctx, chown(&file, None, Some(group.gid)), Errno::EPERM);
+    });
+
+    let file_stat = lstat(&file).unwrap();
+    assert_eq!(file_stat.st_uid, 0);
+    assert_eq!(file_stat.st_gid, 0);
+
+    chown(&file, Some(user.uid), Some(group.gid)).unwrap();
+}
+
+crate::test_case! {
+    /// chmod returns EPERM for the super-user without CAP_FOWNER if it doesn't own the file
+    chmod_without_cap_fowner, root; has_caps => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn chmod_without_cap_fowner(ctx: &mut TestContext, ft: FileType) {
+    let user = ctx.get_new_user();
+    let file = ctx.new_file(ft).owner(user.uid, user.gid).create().unwrap();
+    let mode = lstat(&file).unwrap().st_mode;
+
+    ctx.without_caps(&[Capability::CAP_FOWNER], || {
+        assert_errno(
+            ctx,
+            chmod(&file, Mode::from_bits_truncate(0o700)),
+            Errno::EPERM,
+        );
+    });
+    assert_eq!(lstat(&file).unwrap().st_mode, mode);
+
+    chmod(&file, Mode::from_bits_truncate(0o700)).unwrap();
+}
+
+crate::test_case! {
+    /// CAP_FOWNER allows the super-user to unlink a file it doesn't own in a sticky directory
+    /// it doesn't own either
+    sticky_cap_fowner, root; has_caps
+}
+fn sticky_cap_fowner(ctx: &mut TestContext) {
+    let (dir_owner, file_owner) = (ctx.get_new_user(), ctx.get_new_user());
+    let dir = ctx
+        .new_file(FileType::Dir)
+        .mode(0o1777)
+        .owner(dir_owner.uid, dir_owner.gid)
+        .create()
+        .unwrap();
+
+    let file = ctx
+        .new_file(FileType::Regular)
+        .name(dir.join("file"))
+        .owner(file_owner.uid, file_owner.gid)
+        .create()
+        .unwrap();
+
+    ctx.without_caps(&[Capability::CAP_FOWNER], || {
+        assert_errno(ctx, unlink(&file), Errno::EPERM);
+    });
+    assert!(lstat(&file).is_ok());
+
+    unlink(&file).unwrap();
+}
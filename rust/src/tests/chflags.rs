@@ -1,23 +1,19 @@
-use std::{collections::HashSet, iter::once, sync::OnceLock};
+use std::{collections::HashSet, iter::once, path::Path, sync::OnceLock};
 
-use nix::{
-    errno::Errno,
-    libc::fflags_t,
-    sys::stat::{lstat, stat, FileFlag},
-    unistd::chflags,
-};
+use nix::{errno::Errno, libc::fflags_t, sys::stat::FileFlag, unistd::chflags};
 
 #[cfg(lchflags)]
-use crate::utils::lchflags;
+use crate::utils::{lchflags, lstat, stat};
 use crate::{
+    config::{Config, FeaturesConfig},
     context::{FileType, SerializedTestContext, TestContext},
     test::{FileFlags, FileSystemFeature},
 };
 
 use super::{
-    assert_ctime_changed, assert_ctime_unchanged,
+    assert_ctime_changed, assert_ctime_unchanged, assert_errno, assert_errno_for,
     errors::efault::efault_path_test_case,
-    errors::eloop::eloop_comp_test_case,
+    errors::eloop::{eloop_comp_test_case, eloop_symloop_max_test_case},
     errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case},
     errors::enoent::{enoent_comp_test_case, enoent_named_file_test_case},
     errors::enotdir::enotdir_comp_test_case,
@@ -77,6 +73,29 @@ fn get_flags(ctx: &TestContext) -> (FileFlag, FileFlag, FileFlag) {
     (allflags, user_flags, system_flags)
 }
 
+/// Return the flags declared as supported in the configuration.
+pub(super) fn supported_flags(ctx: &TestContext) -> Vec<FileFlags> {
+    ctx.features_config().file_flags.iter().copied().collect()
+}
+
+/// Return the flags among `flags` which are declared as supported in the configuration.
+pub(super) fn declared_flags(config: &FeaturesConfig, flags: &[FileFlags]) -> Vec<FileFlags> {
+    flags
+        .iter()
+        .filter(|flag| config.file_flags.contains(flag))
+        .copied()
+        .collect()
+}
+
+/// Guard to check that some flags are declared as unsupported.
+fn has_unsupported_flags(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config.features.unsupported_file_flags.is_empty() {
+        anyhow::bail!("no flag declared as unsupported in unsupported_file_flags")
+    }
+
+    Ok(())
+}
+
 crate::test_case! {
     /// chflags(2) set the flags provided for the file.
     // chflags/00.t
@@ -109,6 +128,46 @@ fn set_flags(ctx: &mut TestContext, ft: FileType) {
     }
 }
 
+crate::test_case! {
+    /// chflags sets and clears each supported flag individually
+    flags_round_trip, root, FileSystemFeature::Chflags => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn flags_round_trip(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+
+    for flag in supported_flags(ctx) {
+        assert!(chflags(&file, flag.into()).is_ok());
+        let file_flags = stat(&file).unwrap().st_flags;
+        assert_eq!(
+            file_flags,
+            FileFlag::from(flag).bits() as fflags_t,
+            "{flag} has not been set"
+        );
+
+        assert!(chflags(&file, FileFlag::empty()).is_ok());
+        let file_flags = stat(&file).unwrap().st_flags;
+        assert_eq!(file_flags, 0, "{flag} has not been cleared");
+    }
+}
+
+crate::test_case! {
+    /// chflags returns EOPNOTSUPP when setting a flag which is not supported by the file system
+    unsupported_flags, root, FileSystemFeature::Chflags; has_unsupported_flags => [Regular, Dir]
+}
+fn unsupported_flags(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+
+    for flag in ctx.features_config().unsupported_file_flags.iter().copied() {
+        assert_errno_for(
+            ctx,
+            chflags(&file, flag.into()),
+            Errno::EOPNOTSUPP,
+            format_args!("{flag} should not be supported"),
+        );
+        assert_eq!(stat(&file).unwrap().st_flags, 0);
+    }
+}
+
 crate::test_case! {
     /// chflags changes flags while following symlinks
     // chflags/00.t
@@ -209,7 +268,7 @@ fn unchanged_ctime_failed(ctx: &mut SerializedTestContext, ft: FileType) {
     for flag in allflags.iter().chain(once(&FileFlag::empty())) {
         assert_ctime_unchanged(ctx, &file, || {
             ctx.as_user(user, None, || {
-                assert_eq!(chflags(&file, *flag), Err(Errno::EPERM));
+                assert_errno(ctx, chflags(&file, *flag), Errno::EPERM);
             })
         });
     }
@@ -220,7 +279,7 @@ fn unchanged_ctime_failed(ctx: &mut SerializedTestContext, ft: FileType) {
     for flag in allflags.into_iter().chain(once(FileFlag::empty())) {
         assert_ctime_unchanged(ctx, &file, || {
             ctx.as_user(user, None, || {
-                assert_eq!(lchflags(&file, flag), Err(Errno::EPERM));
+                assert_errno(ctx, lchflags(&file, flag), Errno::EPERM);
             })
         });
     }
@@ -243,6 +302,7 @@ enoent_comp_test_case!(chflags(~path, FileFlag::empty()));
 
 // chflags/06.t
 eloop_comp_test_case!(chflags(~path, FileFlag::empty()));
+eloop_symloop_max_test_case!(chflags(~path, FileFlag::empty()));
 
 // chflags/09.t
 #[cfg(target_os = "freebsd")]
@@ -270,8 +330,7 @@ fn securelevel(ctx: &mut TestContext, ft: FileType) {
         FileFlags::SF_APPEND,
         FileFlags::SF_NOUNLINK,
     ] {
-        let file = ctx.create(ft.clone()).unwrap();
-        lchflags(&file, flag.into()).unwrap();
+        let file = ctx.new_file(ft.clone()).flags(flag).create().unwrap();
 
         // Since this is a multithreaded application, we can't simply fork and chflags().  Instead,
         // execute a child process to test the operation.
@@ -0,0 +1,170 @@
+//! Tests for chflagsat(2), the `*at` variant of chflags(2).
+
+use std::os::fd::AsRawFd;
+
+use nix::{
+    errno::Errno,
+    fcntl::{AtFlags, OFlag},
+    libc::fflags_t,
+    sys::stat::FileFlag,
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    utils::{chflagsat, lstat, stat},
+};
+
+use super::{
+    assert_ctime_changed, assert_errno, chflags::supported_flags, mksyscalls::with_parent_dirfd,
+};
+
+crate::test_case! {
+    /// chflagsat sets and clears each supported flag of a file relative to a directory descriptor
+    set_flags, root, FileSystemFeature::Chflags => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn set_flags(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+
+    for flag in supported_flags(ctx) {
+        with_parent_dirfd(&file, |dirfd, name| {
+            chflagsat(dirfd, name, flag.into(), AtFlags::empty())
+        })
+        .unwrap();
+        let file_flags = stat(&file).unwrap().st_flags;
+        assert_eq!(
+            file_flags,
+            FileFlag::from(flag).bits() as fflags_t,
+            "{flag} has not been set"
+        );
+
+        with_parent_dirfd(&file, |dirfd, name| {
+            chflagsat(dirfd, name, FileFlag::empty(), AtFlags::empty())
+        })
+        .unwrap();
+        assert_eq!(
+            stat(&file).unwrap().st_flags,
+            0,
+            "{flag} has not been cleared"
+        );
+    }
+}
+
+crate::test_case! {
+    /// chflagsat follows symlinks without AT_SYMLINK_NOFOLLOW
+    follow_symlink, root, FileSystemFeature::Chflags
+}
+fn follow_symlink(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(file.clone()))).unwrap();
+    let original_link_flags = lstat(&link).unwrap().st_flags;
+
+    for flag in supported_flags(ctx) {
+        with_parent_dirfd(&link, |dirfd, name| {
+            chflagsat(dirfd, name, flag.into(), AtFlags::empty())
+        })
+        .unwrap();
+        assert_eq!(
+            stat(&file).unwrap().st_flags,
+            FileFlag::from(flag).bits() as fflags_t
+        );
+        assert_eq!(lstat(&link).unwrap().st_flags, original_link_flags);
+
+        with_parent_dirfd(&link, |dirfd, name| {
+            chflagsat(dirfd, name, FileFlag::empty(), AtFlags::empty())
+        })
+        .unwrap();
+    }
+}
+
+crate::test_case! {
+    /// chflagsat changes the flags of the symlink itself with AT_SYMLINK_NOFOLLOW
+    no_follow_symlink, root, FileSystemFeature::Chflags
+}
+fn no_follow_symlink(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(file.clone()))).unwrap();
+    let original_file_flags = stat(&file).unwrap().st_flags;
+
+    for flag in supported_flags(ctx) {
+        with_parent_dirfd(&link, |dirfd, name| {
+            chflagsat(dirfd, name, flag.into(), AtFlags::AT_SYMLINK_NOFOLLOW)
+        })
+        .unwrap();
+        assert_eq!(
+            lstat(&link).unwrap().st_flags,
+            FileFlag::from(flag).bits() as fflags_t
+        );
+        assert_eq!(stat(&file).unwrap().st_flags, original_file_flags);
+
+        with_parent_dirfd(&link, |dirfd, name| {
+            chflagsat(dirfd, name, FileFlag::empty(), AtFlags::AT_SYMLINK_NOFOLLOW)
+        })
+        .unwrap();
+    }
+}
+
+crate::test_case! {
+    /// successful chflagsat updates ctime
+    changed_ctime, root, FileSystemFeature::Chflags => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn changed_ctime(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+
+    for flag in supported_flags(ctx)
+        .into_iter()
+        .map(FileFlag::from)
+        .chain([FileFlag::empty()])
+    {
+        assert_ctime_changed(ctx, &file, || {
+            with_parent_dirfd(&file, |dirfd, name| {
+                chflagsat(dirfd, name, flag, AtFlags::empty())
+            })
+            .unwrap();
+        });
+    }
+}
+
+crate::test_case! {
+    /// chflagsat returns ENOTDIR if the path is relative
+    /// and the descriptor is not associated with a directory
+    enotdir_dirfd, FileSystemFeature::Chflags
+}
+fn enotdir_dirfd(ctx: &mut TestContext) {
+    let (_, fd) = ctx.create_file(OFlag::O_RDONLY, None).unwrap();
+
+    assert_errno(
+        ctx,
+        chflagsat(fd.as_raw_fd(), "file", FileFlag::empty(), AtFlags::empty()),
+        Errno::ENOTDIR,
+    );
+}
+
+crate::test_case! {
+    /// chflagsat returns EBADF if the path is relative
+    /// and the descriptor is neither valid nor AT_FDCWD
+    ebadf, FileSystemFeature::Chflags
+}
+fn ebadf(_ctx: &mut TestContext) {
+    assert_errno(
+        _ctx,
+        chflagsat(i32::MAX, "file", FileFlag::empty(), AtFlags::empty()),
+        Errno::EBADF,
+    );
+}
+
+crate::test_case! {
+    /// chflagsat returns EINVAL if the value of the flag argument is not valid
+    einval_atflag, FileSystemFeature::Chflags
+}
+fn einval_atflag(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+
+    assert_errno(
+        ctx,
+        with_parent_dirfd(&file, |dirfd, name| {
+            chflagsat(dirfd, name, FileFlag::empty(), AtFlags::AT_REMOVEDIR)
+        }),
+        Errno::EINVAL,
+    );
+}
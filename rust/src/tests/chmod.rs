@@ -1,26 +1,23 @@
 use crate::{
     context::{FileType, SerializedTestContext},
-    test::TestContext,
+    test::{root_privileges, TestContext},
     tests::{assert_ctime_changed, assert_ctime_unchanged},
-    utils::{chmod, ALLPERMS},
+    utils::{chmod, chown, lstat, stat, ALLPERMS},
 };
 
 #[cfg(lchmod)]
 use crate::utils::lchmod;
 
-use nix::{
-    sys::stat::{lstat, stat, Mode},
-    unistd::chown,
-};
+use nix::sys::stat::Mode;
 
 use super::errors::{
     efault::efault_path_test_case,
-    eloop::{eloop_comp_test_case, eloop_final_comp_test_case},
+    eloop::{eloop_comp_test_case, eloop_final_comp_test_case, eloop_symloop_max_test_case},
     enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case},
     enoent::{
         enoent_comp_test_case, enoent_named_file_test_case, enoent_symlink_named_file_test_case,
     },
-    enotdir::enotdir_comp_test_case,
+    enotdir::{enotdir_comp_test_case, enotdir_trailing_slash_test_case},
     erofs::erofs_named_test_case,
 };
 
@@ -78,11 +75,13 @@ crate::test_case! {
 fn failed_chmod_unchanged_ctime(ctx: &mut SerializedTestContext, f_type: FileType) {
     let path = ctx.create(f_type).unwrap();
     let user = ctx.get_new_user();
+    let snapshot = ctx.snapshot();
     assert_ctime_unchanged(ctx, &path, || {
         ctx.as_user(user, None, || {
             assert!(chmod(&path, Mode::from_bits_truncate(0o111)).is_err());
         });
     });
+    snapshot.verify_unchanged();
 }
 
 // chmod/00.t:L119
@@ -91,7 +90,7 @@ crate::test_case! {
     /// if the calling process does not have appropriate privileges, and if
     /// the group ID of the file does not match the effective group ID or one of the
     /// supplementary group IDs
-    clear_isgid_bit, serialized, root
+    clear_isgid_bit, serialized, root; root_privileges
 }
 fn clear_isgid_bit(ctx: &mut SerializedTestContext) {
     let path = ctx.create(FileType::Regular).unwrap();
@@ -121,6 +120,7 @@ fn clear_isgid_bit(ctx: &mut SerializedTestContext) {
 
 // chmod/01.t
 enotdir_comp_test_case!(chmod(~path, Mode::empty()));
+enotdir_trailing_slash_test_case!(chmod(~path, Mode::empty()));
 
 // chmod/02.t
 enametoolong_comp_test_case!(chmod(~path, Mode::empty()));
@@ -135,6 +135,7 @@ enoent_symlink_named_file_test_case!(chmod(~path, Mode::empty()));
 
 // chmod/06.t
 eloop_comp_test_case!(chmod(~path, Mode::empty()));
+eloop_symloop_max_test_case!(chmod(~path, Mode::empty()));
 
 // chmod/06.t
 eloop_final_comp_test_case!(chmod(~path, Mode::empty()));
@@ -145,6 +146,28 @@ erofs_named_test_case!(chmod(~path, Mode::empty()));
 // chmod/10.t
 efault_path_test_case!(chmod, |ptr| nix::libc::chmod(ptr, 0));
 
+// The macOS libc changes the mode of symlinks with fchmodat, without providing lchmod
+#[cfg(not(any(lchmod, target_vendor = "apple")))]
+crate::test_case! {
+    /// fchmodat with AT_SYMLINK_NOFOLLOW returns EOPNOTSUPP on a symlink
+    /// when the system does not support changing the mode of symlinks
+    symlink_nofollow_eopnotsupp
+}
+#[cfg(not(any(lchmod, target_vendor = "apple")))]
+fn symlink_nofollow_eopnotsupp(ctx: &mut TestContext) {
+    use nix::errno::Errno;
+
+    use super::assert_errno;
+
+    let link = ctx.create(FileType::Symlink(None)).unwrap();
+
+    assert_errno(
+        ctx,
+        crate::utils::lchmod(&link, Mode::from_bits_truncate(0o644)),
+        Errno::EOPNOTSUPP,
+    );
+}
+
 #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
 crate::test_case! {
     /// chmod returns EFTYPE if the effective user ID is not the super-user,
@@ -157,6 +180,8 @@ crate::test_case! {
 fn eftype(ctx: &mut SerializedTestContext, ft: FileType) {
     use nix::errno::Errno;
 
+    use super::assert_errno;
+
     let user = ctx.get_new_user();
 
     let original_mode = Mode::from_bits_truncate(0o640);
@@ -170,15 +195,16 @@ fn eftype(ctx: &mut SerializedTestContext, ft: FileType) {
     let link = ctx.create(FileType::Symlink(Some(file.clone()))).unwrap();
 
     ctx.as_user(user, None, || {
-        assert_eq!(chmod(&file, new_mode | Mode::S_ISVTX), Err(Errno::EFTYPE));
+        assert_errno(ctx, chmod(&file, new_mode | Mode::S_ISVTX), Errno::EFTYPE);
     });
     let file_stat = stat(&file).unwrap();
     assert_eq!(file_stat.st_mode & ALLPERMS_STICKY, original_mode.bits());
 
     ctx.as_user(user, None, || {
-        assert_eq!(
+        assert_errno(
+            ctx,
             chmod(&link, original_mode | Mode::S_ISVTX),
-            Err(Errno::EFTYPE)
+            Errno::EFTYPE,
         );
     });
     let file_stat = stat(&link).unwrap();
@@ -188,39 +214,9 @@ fn eftype(ctx: &mut SerializedTestContext, ft: FileType) {
 
     let mode = Mode::from_bits_truncate(0o621) | Mode::S_ISVTX;
     ctx.as_user(user, None, || {
-        assert_eq!(lchmod(&file, mode), Err(Errno::EFTYPE));
+        assert_errno(ctx, lchmod(&file, mode), Errno::EFTYPE);
     });
 
     let file_stat = lstat(&file).unwrap();
     assert_eq!(file_stat.st_mode & ALLPERMS_STICKY, original_mode.bits());
 }
-
-#[cfg(lchmod)]
-mod lchmod {
-    use super::*;
-
-    // chmod/01.t
-    enotdir_comp_test_case!(lchmod(~path, Mode::empty()));
-
-    // chmod/04.t
-    enoent_named_file_test_case!(lchmod(~path, Mode::empty()));
-    enoent_comp_test_case!(lchmod(~path, Mode::empty()));
-
-    // chmod/06.t#L25
-    eloop_comp_test_case!(lchmod(~path, Mode::empty()));
-
-    enametoolong_comp_test_case!(lchmod(~path, Mode::empty()));
-    enametoolong_path_test_case!(lchmod(~path, Mode::empty()));
-
-    // chmod/09.t
-    erofs_named_test_case!(lchmod(~path, Mode::empty()));
-
-    // chmod/10.t
-    // TODO: lchmod is missing in libc
-    efault_path_test_case!(lchmod, |ptr| nix::libc::fchmodat(
-        0,
-        ptr,
-        0,
-        nix::libc::AT_SYMLINK_NOFOLLOW
-    ));
-}
@@ -1,23 +1,46 @@
-use nix::unistd::chown;
+use std::path::Path;
 
-use crate::{context::TestContext, utils::lchown};
+use nix::{
+    errno::Errno,
+    unistd::{pathconf, PathconfVar},
+};
+
+use crate::{
+    config::Config,
+    context::{FileType, SerializedTestContext, TestContext},
+    test::root_privileges,
+    utils::{chown, stat},
+};
 
 use super::errors::efault::efault_path_test_case;
-use super::errors::eloop::{eloop_comp_test_case, eloop_final_comp_test_case};
+use super::errors::eloop::{
+    eloop_comp_test_case, eloop_final_comp_test_case, eloop_symloop_max_test_case,
+};
 use super::errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case};
 use super::errors::enoent::{
     enoent_comp_test_case, enoent_named_file_test_case, enoent_symlink_named_file_test_case,
 };
-use super::errors::enotdir::enotdir_comp_test_case;
+use super::errors::enotdir::{enotdir_comp_test_case, enotdir_trailing_slash_test_case};
 use super::errors::erofs::erofs_named_test_case;
+use super::{assert_errno, assert_times_changed, CTIME};
+
+/// Guard to check that only privileged processes can change the owner of a file,
+/// and only to a group they belong to for the others (_POSIX_CHOWN_RESTRICTED).
+pub(super) fn chown_restricted(_: &Config, base_path: &Path) -> anyhow::Result<()> {
+    match pathconf(base_path, PathconfVar::_POSIX_CHOWN_RESTRICTED)? {
+        Some(_) => Ok(()),
+        None => anyhow::bail!("_POSIX_CHOWN_RESTRICTED is not in effect"),
+    }
+}
 
-fn chown_wrapper(ctx: &mut TestContext, path: &std::path::Path) -> nix::Result<()> {
+fn chown_wrapper(ctx: &mut TestContext, path: &Path) -> nix::Result<()> {
     let user = ctx.get_new_user();
     chown(path, Some(user.uid), None)
 }
 
 // chown/01.t
 enotdir_comp_test_case!(chown, chown_wrapper);
+enotdir_trailing_slash_test_case!(chown, chown_wrapper);
 
 // chown/02.t
 enametoolong_comp_test_case!(chown, chown_wrapper);
@@ -36,6 +59,7 @@ enoent_symlink_named_file_test_case!(chown, chown_wrapper);
 
 // chown/06.t
 eloop_comp_test_case!(chown, chown_wrapper);
+eloop_symloop_max_test_case!(chown, chown_wrapper);
 
 // chown/06.t
 eloop_final_comp_test_case!(chown, chown_wrapper);
@@ -46,36 +70,83 @@ erofs_named_test_case!(chown, chown_wrapper);
 // chown/10.t
 efault_path_test_case!(chown, |ptr| nix::libc::chown(ptr, 0, 0));
 
-mod lchown {
-    use std::path::Path;
-
-    use super::*;
-
-    fn lchown_wrapper<P: AsRef<Path>>(ctx: &mut TestContext, path: P) -> nix::Result<()> {
-        let path = path.as_ref();
-        let user = ctx.get_new_user();
-        lchown(path, Some(user.uid), Some(user.gid))
-    }
-
-    // chown/01.t
-    enotdir_comp_test_case!(lchown, lchown_wrapper);
-
-    // chown/04.t
-    enoent_named_file_test_case!(lchown, lchown_wrapper);
-    enoent_comp_test_case!(lchown, lchown_wrapper);
-
-    // chown/06.t#L25
-    eloop_comp_test_case!(lchown, lchown_wrapper);
-
-    // chown/02.t
-    enametoolong_comp_test_case!(lchown, lchown_wrapper);
-
-    // chown/03.t
-    enametoolong_path_test_case!(lchown, lchown_wrapper);
+crate::test_case! {
+    /// chown leaves the user or group ID unchanged when it is None,
+    /// but still updates ctime
+    // chown/00.t
+    none_id_updates_ctime, root; root_privileges => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn none_id_updates_ctime(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+    let (user, group) = ctx.get_new_entry();
+    let original_stat = stat(&file).unwrap();
+
+    assert_times_changed()
+        .path(&file, CTIME)
+        .execute(ctx, false, || {
+            chown(&file, None, Some(group.gid)).unwrap();
+        });
+    let file_stat = stat(&file).unwrap();
+    assert_eq!(file_stat.st_uid, original_stat.st_uid);
+    assert_eq!(file_stat.st_gid, group.gid.as_raw());
+
+    assert_times_changed()
+        .path(&file, CTIME)
+        .execute(ctx, false, || {
+            chown(&file, Some(user.uid), None).unwrap();
+        });
+    let file_stat = stat(&file).unwrap();
+    assert_eq!(file_stat.st_uid, user.uid.as_raw());
+    assert_eq!(file_stat.st_gid, group.gid.as_raw());
+}
 
-    // chown/09.t
-    erofs_named_test_case!(lchown, lchown_wrapper);
+crate::test_case! {
+    /// chown succeeds when a non-privileged owner sets the owner to itself
+    // chown/00.t
+    same_owner_non_root, serialized, root; root_privileges
+}
+fn same_owner_non_root(ctx: &mut SerializedTestContext) {
+    let (user, group) = ctx.get_new_entry();
+    let file = ctx
+        .new_file(FileType::Regular)
+        .owner(user.uid, group.gid)
+        .create()
+        .unwrap();
+
+    ctx.as_user(user, None, || {
+        assert_times_changed()
+            .path(&file, CTIME)
+            .execute(ctx, false, || {
+                chown(&file, Some(user.uid), None).unwrap();
+            });
+        assert_times_changed()
+            .path(&file, CTIME)
+            .execute(ctx, false, || {
+                chown(&file, Some(user.uid), Some(group.gid)).unwrap();
+            });
+    });
+    let file_stat = stat(&file).unwrap();
+    assert_eq!(file_stat.st_uid, user.uid.as_raw());
+    assert_eq!(file_stat.st_gid, group.gid.as_raw());
+}
 
-    // chown/10.t
-    efault_path_test_case!(lchown, |ptr| nix::libc::lchown(ptr, 0, 0));
+crate::test_case! {
+    /// chown returns EPERM when a non-privileged owner gives the file away
+    /// and _POSIX_CHOWN_RESTRICTED is in effect
+    // chown/00.t
+    give_away_non_root, serialized, root; chown_restricted, root_privileges
+}
+fn give_away_non_root(ctx: &mut SerializedTestContext) {
+    let (user, group) = ctx.get_new_entry();
+    let other_user = ctx.get_new_user();
+    let file = ctx
+        .new_file(FileType::Regular)
+        .owner(user.uid, group.gid)
+        .create()
+        .unwrap();
+
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, chown(&file, Some(other_user.uid), None), Errno::EPERM);
+    });
+    assert_eq!(stat(&file).unwrap().st_uid, user.uid.as_raw());
 }
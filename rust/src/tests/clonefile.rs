@@ -0,0 +1,141 @@
+//! Tests for clonefile(2) and clonefileat(2), which create copy-on-write clones on APFS.
+
+use std::{fs, path::Path};
+
+use nix::{errno::Errno, libc::AT_FDCWD};
+
+use crate::{
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    utils::{clonefile, clonefileat, lstat, stat},
+};
+
+use super::assert_errno;
+use super::errors::exdev::exdev_target_test_case;
+use super::mksyscalls::with_parent_dirfd;
+
+/// Do not follow the source if it is a symbolic link.
+const CLONE_NOFOLLOW: u32 = 0x0001;
+
+pub(crate) fn clonefile_wrapper(src: &Path, dst: &Path) -> nix::Result<()> {
+    clonefile(src, dst, 0)
+}
+
+crate::test_case! {
+    /// clonefile creates a distinct inode sharing the data of the source
+    distinct_inode, FileSystemFeature::Clonefile
+}
+fn distinct_inode(ctx: &mut TestContext) {
+    let src = ctx.create(FileType::Regular).unwrap();
+    fs::write(&src, b"pjdfstest clonefile").unwrap();
+    let dst = ctx.gen_path();
+
+    assert!(clonefile(&src, &dst, 0).is_ok());
+
+    let src_stat = stat(&src).unwrap();
+    let dst_stat = stat(&dst).unwrap();
+    assert_ne!(src_stat.st_ino, dst_stat.st_ino);
+    assert_eq!(src_stat.st_nlink, 1);
+    assert_eq!(dst_stat.st_nlink, 1);
+    assert_eq!(src_stat.st_mode, dst_stat.st_mode);
+    assert_eq!(src_stat.st_size, dst_stat.st_size);
+    assert_eq!(fs::read(&src).unwrap(), fs::read(&dst).unwrap());
+}
+
+crate::test_case! {
+    /// Writes to a clone or its source are not visible in the other file
+    copy_on_write, FileSystemFeature::Clonefile
+}
+fn copy_on_write(ctx: &mut TestContext) {
+    let src = ctx.create(FileType::Regular).unwrap();
+    fs::write(&src, b"original").unwrap();
+    let dst = ctx.gen_path();
+    clonefile(&src, &dst, 0).unwrap();
+
+    fs::write(&dst, b"modified clone").unwrap();
+    assert_eq!(fs::read(&src).unwrap(), b"original");
+    assert_eq!(fs::read(&dst).unwrap(), b"modified clone");
+
+    fs::write(&src, b"modified source").unwrap();
+    assert_eq!(fs::read(&src).unwrap(), b"modified source");
+    assert_eq!(fs::read(&dst).unwrap(), b"modified clone");
+}
+
+crate::test_case! {
+    /// clonefile clones a directory hierarchy
+    clone_dir, FileSystemFeature::Clonefile
+}
+fn clone_dir(ctx: &mut TestContext) {
+    let src = ctx.create(FileType::Dir).unwrap();
+    fs::write(src.join("file"), b"pjdfstest").unwrap();
+    let dst = ctx.gen_path();
+
+    assert!(clonefile(&src, &dst, 0).is_ok());
+    assert_ne!(stat(&src).unwrap().st_ino, stat(&dst).unwrap().st_ino);
+    assert_eq!(fs::read(dst.join("file")).unwrap(), b"pjdfstest");
+}
+
+crate::test_case! {
+    /// clonefile clones the target of a symlink, unless CLONE_NOFOLLOW is given
+    symlink_source, FileSystemFeature::Clonefile
+}
+fn symlink_source(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(file.clone()))).unwrap();
+
+    let dst = ctx.gen_path();
+    assert!(clonefile(&link, &dst, 0).is_ok());
+    assert!(lstat(&dst).unwrap().st_mode & nix::libc::S_IFMT == nix::libc::S_IFREG);
+
+    let dst = ctx.gen_path();
+    assert!(clonefile(&link, &dst, CLONE_NOFOLLOW).is_ok());
+    assert!(lstat(&dst).unwrap().st_mode & nix::libc::S_IFMT == nix::libc::S_IFLNK);
+    assert_eq!(fs::read_link(&dst).unwrap(), file);
+}
+
+crate::test_case! {
+    /// clonefileat resolves relative paths from the directory descriptors
+    clonefileat_dirfd, FileSystemFeature::Clonefile
+}
+fn clonefileat_dirfd(ctx: &mut TestContext) {
+    let src = ctx.create(FileType::Regular).unwrap();
+    fs::write(&src, b"pjdfstest").unwrap();
+    let dst = ctx.gen_path();
+
+    with_parent_dirfd(&src, |src_dirfd, src_name| {
+        with_parent_dirfd(&dst, |dst_dirfd, dst_name| {
+            clonefileat(src_dirfd, src_name, dst_dirfd, dst_name, 0)
+        })
+    })
+    .unwrap();
+    assert_eq!(fs::read(&dst).unwrap(), b"pjdfstest");
+
+    let dst = ctx.gen_path();
+    assert!(clonefileat(AT_FDCWD, &src, AT_FDCWD, &dst, 0).is_ok());
+    assert_eq!(fs::read(&dst).unwrap(), b"pjdfstest");
+}
+
+crate::test_case! {
+    /// clonefile returns EEXIST if the destination already exists
+    eexist, FileSystemFeature::Clonefile => [Regular, Dir, Fifo, Socket, Symlink(None)]
+}
+fn eexist(ctx: &mut TestContext, ft: FileType) {
+    let src = ctx.create(FileType::Regular).unwrap();
+    let dst = ctx.create(ft).unwrap();
+
+    assert_errno(ctx, clonefile(&src, &dst, 0), Errno::EEXIST);
+}
+
+crate::test_case! {
+    /// clonefile returns EINVAL if the flags argument has an invalid value
+    einval_flags, FileSystemFeature::Clonefile
+}
+fn einval_flags(ctx: &mut TestContext) {
+    let src = ctx.create(FileType::Regular).unwrap();
+    let dst = ctx.gen_path();
+
+    assert_errno(ctx, clonefile(&src, &dst, 0x8000_0000), Errno::EINVAL);
+}
+
+// clonefile returns EXDEV if the source and the destination are on different file systems
+exdev_target_test_case!(clonefile_wrapper);
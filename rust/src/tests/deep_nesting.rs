@@ -0,0 +1,164 @@
+//! Tests for directory trees deeper than `{PATH_MAX}`, which can only be reached
+//! with paths relative to directory file descriptors (openat, mkdirat...).
+
+use std::{
+    ffi::OsStr,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    path::Path,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{openat, readlinkat, renameat, AtFlags, OFlag},
+    sys::stat::{fstat, fstatat, mkdirat, Mode, SFlag},
+    unistd::{linkat, pathconf, symlinkat, unlinkat, PathconfVar, UnlinkatFlags},
+};
+use rand::distributions::{Alphanumeric, DistString};
+
+use super::assert_errno;
+use crate::{
+    context::{FileType, TestContext},
+    rng::with_rng,
+    utils::{lstat, mkdir, open, unlink},
+};
+
+/// Open a directory relative to a directory file descriptor.
+fn open_dir_at(dirfd: &OwnedFd, name: &str) -> nix::Result<OwnedFd> {
+    let fd = openat(
+        Some(dirfd.as_raw_fd()),
+        name,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+        Mode::empty(),
+    )?;
+    // SAFETY: The file descriptor was just returned by openat and isn't owned elsewhere
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Return the file type of an entry relative to a directory file descriptor.
+fn file_type_at<P: ?Sized + nix::NixPath>(dirfd: &OwnedFd, path: &P) -> nix::Result<SFlag> {
+    let stat = fstatat(Some(dirfd.as_raw_fd()), path, AtFlags::AT_SYMLINK_NOFOLLOW)?;
+    Ok(SFlag::from_bits_truncate(
+        stat.st_mode & SFlag::S_IFMT.bits(),
+    ))
+}
+
+crate::test_case! {
+    /// A directory tree deeper than {PATH_MAX} can be built, used and removed through directory file descriptors,
+    /// while the absolute paths of its entries return ENAMETOOLONG
+    deeper_than_path_max
+}
+fn deeper_than_path_max(ctx: &mut TestContext) {
+    let name_max = pathconf(ctx.base_path(), PathconfVar::NAME_MAX)
+        .unwrap()
+        .unwrap() as usize;
+    // {PATH_MAX} includes the terminating null byte
+    let path_max = pathconf(ctx.base_path(), PathconfVar::PATH_MAX)
+        .unwrap()
+        .unwrap() as usize
+        - 1;
+
+    let root = ctx.create(FileType::Dir).unwrap();
+    let mut fds = vec![open(&root, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()).unwrap()];
+    let mut names = vec![];
+    let mut path = root.clone();
+
+    // Components of {NAME_MAX} bytes, until the tree is two levels deeper than {PATH_MAX}
+    let depth = path_max / (name_max + 1) + 2;
+    for _ in 0..depth {
+        let name = with_rng(|rng| Alphanumeric.sample_string(rng, name_max));
+        let parent = fds.last().unwrap();
+        mkdirat(
+            Some(parent.as_raw_fd()),
+            name.as_str(),
+            Mode::from_bits_truncate(0o755),
+        )
+        .unwrap();
+        fds.push(open_dir_at(parent, &name).unwrap());
+        path.push(&name);
+        names.push(name);
+    }
+    assert!(path.as_os_str().len() > path_max);
+
+    let bottom = fds.last().unwrap();
+    let bottom_fd = Some(bottom.as_raw_fd());
+
+    // The operations relative to the deepest directory succeed
+    let file = openat(
+        bottom_fd,
+        "file",
+        OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+        Mode::from_bits_truncate(0o644),
+    )
+    .unwrap();
+    // SAFETY: The file descriptor was just returned by openat and isn't owned elsewhere
+    drop(unsafe { OwnedFd::from_raw_fd(file) });
+    assert_eq!(file_type_at(bottom, "file"), Ok(SFlag::S_IFREG));
+    renameat(bottom_fd, "file", bottom_fd, "renamed").unwrap();
+    assert_errno(ctx, file_type_at(bottom, "file"), Errno::ENOENT);
+    linkat(bottom_fd, "renamed", bottom_fd, "link", AtFlags::empty()).unwrap();
+    assert_eq!(
+        fstatat(bottom_fd, "link", AtFlags::empty())
+            .unwrap()
+            .st_nlink,
+        2
+    );
+    symlinkat("renamed", bottom_fd, "symlink").unwrap();
+    assert_eq!(
+        readlinkat(bottom_fd, "symlink").unwrap(),
+        OsStr::new("renamed")
+    );
+    mkdirat(bottom_fd, "dir", Mode::from_bits_truncate(0o755)).unwrap();
+    assert_eq!(file_type_at(bottom, "dir"), Ok(SFlag::S_IFDIR));
+
+    // ".." resolves to the parent directory
+    let parent = fstat(fds[fds.len() - 2].as_raw_fd()).unwrap();
+    let dotdot = fstat(open_dir_at(bottom, "..").unwrap().as_raw_fd()).unwrap();
+    assert_eq!(
+        (dotdot.st_dev, dotdot.st_ino),
+        (parent.st_dev, parent.st_ino)
+    );
+
+    // A relative path shorter than {PATH_MAX} from a directory halfway up reaches the bottom
+    let middle = depth / 2;
+    let relative = Path::new(&names[middle..].join("/")).join("renamed");
+    assert!(relative.as_os_str().len() <= path_max);
+    assert_eq!(file_type_at(&fds[middle], &relative), Ok(SFlag::S_IFREG));
+
+    // The absolute paths are too long
+    assert_errno(
+        ctx,
+        lstat(&path.join("renamed")).map(drop),
+        Errno::ENAMETOOLONG,
+    );
+    assert_errno(
+        ctx,
+        open(
+            &path.join("new"),
+            OFlag::O_CREAT | OFlag::O_WRONLY,
+            Mode::from_bits_truncate(0o644),
+        )
+        .map(drop),
+        Errno::ENAMETOOLONG,
+    );
+    assert_errno(
+        ctx,
+        mkdir(&path.join("new"), Mode::from_bits_truncate(0o755)),
+        Errno::ENAMETOOLONG,
+    );
+    assert_errno(ctx, unlink(&path.join("renamed")), Errno::ENAMETOOLONG);
+
+    // The tree can be removed from the bottom up
+    for name in ["renamed", "link", "symlink"] {
+        unlinkat(bottom_fd, name, UnlinkatFlags::NoRemoveDir).unwrap();
+    }
+    unlinkat(bottom_fd, "dir", UnlinkatFlags::RemoveDir).unwrap();
+    for (parent, name) in fds.iter().zip(&names).rev() {
+        unlinkat(
+            Some(parent.as_raw_fd()),
+            name.as_str(),
+            UnlinkatFlags::RemoveDir,
+        )
+        .unwrap_or_else(|e| panic!("cannot remove a directory of the tree: {e}"));
+    }
+    assert_eq!(std::fs::read_dir(&root).unwrap().count(), 0);
+}
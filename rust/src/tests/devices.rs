@@ -0,0 +1,162 @@
+//! Tests opening device nodes created on the file system under test,
+//! which should pass the operations through to the device driver.
+//!
+//! They are opt-in, and enabled by the `[settings.devices]` section of the configuration.
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+use std::os::fd::AsRawFd;
+use std::{
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+use nix::unistd::{lseek, read, write, Whence};
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::stat::{Mode, SFlag},
+};
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+use super::assert_errno;
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+use crate::context::FileType;
+use crate::{
+    config::Config,
+    context::TestContext,
+    utils::{mknod, open},
+};
+
+/// Number of bytes read from the devices.
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+const LEN: usize = 4096;
+
+fn devices_enabled(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config.settings.devices.is_none() {
+        anyhow::bail!(
+            "Device node tests are not enabled in the configuration ([settings.devices])"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+fn block_device_configured(config: &Config, path: &Path) -> anyhow::Result<()> {
+    devices_enabled(config, path)?;
+    if config
+        .settings
+        .devices
+        .as_ref()
+        .unwrap()
+        .block_device
+        .is_none()
+    {
+        anyhow::bail!("No block device is set in the configuration (block_device)");
+    }
+
+    Ok(())
+}
+
+/// Create a node with the device number of `device`.
+fn mknod_like(ctx: &TestContext, kind: SFlag, device: &Path) -> PathBuf {
+    let rdev = std::fs::metadata(device).unwrap().rdev();
+    let node = ctx.gen_path();
+    mknod(&node, kind, Mode::from_bits_truncate(0o600), rdev as _).unwrap();
+
+    node
+}
+
+/// Read up to [`LEN`] bytes at the start of the file.
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+fn read_start(path: &Path) -> Vec<u8> {
+    let fd = open(path, OFlag::O_RDONLY, Mode::empty()).unwrap();
+    let mut buf = vec![0; LEN];
+    let len = read(fd.as_raw_fd(), &mut buf).unwrap();
+    buf.truncate(len);
+
+    buf
+}
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+crate::test_case! {
+    /// Reads from and writes to a character device node are passed through to the device
+    passthrough_char, root; devices_enabled
+}
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+fn passthrough_char(ctx: &mut TestContext) {
+    let device = ctx.devices_config().unwrap().char_device.clone();
+    let node = mknod_like(ctx, SFlag::S_IFCHR, &device);
+
+    assert_eq!(read_start(&node), read_start(&device));
+
+    let fd = open(&node, OFlag::O_WRONLY, Mode::empty()).unwrap();
+    assert_eq!(write(&fd, &[0; LEN]).unwrap(), LEN);
+}
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+crate::test_case! {
+    /// Reads from a block device node are passed through to the device
+    passthrough_block, root; block_device_configured
+}
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+fn passthrough_block(ctx: &mut TestContext) {
+    let device = ctx.devices_config().unwrap().block_device.clone().unwrap();
+    let node = mknod_like(ctx, SFlag::S_IFBLK, &device);
+
+    assert_eq!(read_start(&node), read_start(&device));
+
+    let size = |path: &Path| {
+        let fd = open(path, OFlag::O_RDONLY, Mode::empty()).unwrap();
+        lseek(fd.as_raw_fd(), 0, Whence::SeekEnd).unwrap()
+    };
+    assert_eq!(size(&node), size(&device));
+}
+
+#[cfg(target_os = "freebsd")]
+crate::test_case! {
+    /// Device nodes can only be opened on devfs, and opening one created
+    /// on the file system returns EOPNOTSUPP or ENXIO
+    no_passthrough, root; devices_enabled
+}
+#[cfg(target_os = "freebsd")]
+fn no_passthrough(ctx: &mut TestContext) {
+    let device = ctx.devices_config().unwrap().char_device.clone();
+    let node = mknod_like(ctx, SFlag::S_IFCHR, &device);
+
+    super::assert_errno_choice(
+        ctx,
+        open(&node, OFlag::O_RDONLY, Mode::empty()),
+        &[Errno::EOPNOTSUPP, Errno::ENXIO],
+    );
+}
+
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+crate::test_case! {
+    /// open returns ENXIO for a device node whose major number is not assigned to any driver
+    unassigned_major, root; devices_enabled => [Block, Char]
+}
+#[cfg(any(target_os = "linux", target_os = "illumos"))]
+fn unassigned_major(ctx: &mut TestContext, ft: FileType) {
+    let kind = match ft {
+        FileType::Block => SFlag::S_IFBLK,
+        FileType::Char => SFlag::S_IFCHR,
+        _ => unreachable!(),
+    };
+    let major = ctx.devices_config().unwrap().unassigned_major;
+    let node = ctx.gen_path();
+    mknod(
+        &node,
+        kind,
+        Mode::from_bits_truncate(0o600),
+        crate::utils::dev::makedev(major as _, 0),
+    )
+    .unwrap();
+
+    assert_errno(
+        ctx,
+        open(&node, OFlag::O_RDONLY, Mode::empty()),
+        Errno::ENXIO,
+    );
+}
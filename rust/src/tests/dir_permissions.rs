@@ -0,0 +1,129 @@
+//! Tests for the permissions of a directory required to create and remove entries,
+//! which need both the write and the search permissions.
+
+use std::path::Path;
+
+use nix::{
+    errno::Errno,
+    fcntl::{open, OFlag},
+    libc::mode_t,
+    sys::stat::Mode,
+    unistd::close,
+};
+
+use super::assert_errno_for;
+use crate::{
+    context::{FileType, SerializedTestContext, TreeEntry},
+    test::root_privileges,
+    utils::{link, mkdir, mkfifo, rename, rmdir, symlink, unlink},
+};
+
+/// Permissions of the directory owner, along with whether they allow
+/// to create and remove entries.
+const DIR_MODES: [(mode_t, bool); 4] = [
+    // No permissions
+    (0o000, false),
+    // Write only
+    (0o200, false),
+    // Search only
+    (0o100, false),
+    // Write and search
+    (0o300, true),
+];
+
+/// Name of the entry which exists in the directory before the operation.
+const ENTRY: &str = "entry";
+/// Name of the entry created by the operation.
+const NEW_ENTRY: &str = "new_entry";
+
+/// An operation on the entries of a directory, with the type of the entry
+/// which has to exist before executing it.
+struct DirOperation {
+    name: &'static str,
+    existing: Option<FileType>,
+    op: fn(&Path) -> nix::Result<()>,
+}
+
+const OPERATIONS: &[DirOperation] = &[
+    DirOperation {
+        name: "unlink",
+        existing: Some(FileType::Regular),
+        op: |dir| unlink(&dir.join(ENTRY)),
+    },
+    DirOperation {
+        name: "rmdir",
+        existing: Some(FileType::Dir),
+        op: |dir| rmdir(&dir.join(ENTRY)),
+    },
+    DirOperation {
+        name: "rename",
+        existing: Some(FileType::Regular),
+        op: |dir| rename(&dir.join(ENTRY), &dir.join(NEW_ENTRY)),
+    },
+    DirOperation {
+        name: "link",
+        existing: Some(FileType::Regular),
+        op: |dir| link(&dir.join(ENTRY), &dir.join(NEW_ENTRY)),
+    },
+    DirOperation {
+        name: "open",
+        existing: None,
+        op: |dir| {
+            open(
+                &dir.join(NEW_ENTRY),
+                OFlag::O_CREAT | OFlag::O_WRONLY,
+                Mode::from_bits_truncate(0o644),
+            )
+            .and_then(close)
+        },
+    },
+    DirOperation {
+        name: "mkdir",
+        existing: None,
+        op: |dir| mkdir(&dir.join(NEW_ENTRY), Mode::from_bits_truncate(0o755)),
+    },
+    DirOperation {
+        name: "mkfifo",
+        existing: None,
+        op: |dir| mkfifo(&dir.join(NEW_ENTRY), Mode::from_bits_truncate(0o644)),
+    },
+    DirOperation {
+        name: "symlink",
+        existing: None,
+        op: |dir| symlink(Path::new("test"), &dir.join(NEW_ENTRY)),
+    },
+];
+
+crate::test_case! {
+    /// Creating or removing an entry succeeds only if the user has both write
+    /// and search permissions on the directory, and returns EACCES otherwise
+    permission_matrix, serialized, root; root_privileges
+}
+fn permission_matrix(ctx: &mut SerializedTestContext) {
+    let user = ctx.get_new_user();
+
+    for operation in OPERATIONS {
+        for (mode, allowed) in DIR_MODES {
+            let entries = operation
+                .existing
+                .iter()
+                .map(|ft| TreeEntry::new(ENTRY, ft.clone()).owner(user.uid, user.gid));
+            let tree = ctx
+                .build_tree([TreeEntry::dir("dir", entries)
+                    .mode(mode)
+                    .owner(user.uid, user.gid)])
+                .unwrap();
+            let dir = tree.path("dir");
+
+            ctx.as_user(user, None, || {
+                let res = (operation.op)(&dir);
+                let case = format!("{} in a directory with mode {mode:#o}", operation.name);
+                if allowed {
+                    assert!(res.is_ok(), "{case}: {res:?}");
+                } else {
+                    assert_errno_for(ctx, res, Errno::EACCES, case);
+                }
+            });
+        }
+    }
+}
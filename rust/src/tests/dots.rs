@@ -0,0 +1,127 @@
+//! Tests for the "." and ".." entries of directories, which cannot be created, removed
+//! or linked to, and which resolve to the directory itself and its parent.
+
+use std::fs;
+
+use nix::{
+    errno::Errno,
+    fcntl::{open, OFlag},
+    sys::stat::{fstat, Mode},
+    unistd::close,
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    utils::{get_mountpoint, link, lstat, mkdir, rename, stat, unlink},
+};
+
+use super::assert_errno;
+use super::assert_errno_choice;
+
+crate::test_case! {
+    /// open resolves "." to the directory itself and ".." to its parent
+    open_dots
+}
+fn open_dots(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let parent = ctx.base_path();
+
+    for (path, expected) in [(dir.join("."), dir.as_path()), (dir.join(".."), parent)] {
+        let fd = open(&path, OFlag::O_RDONLY, Mode::empty()).unwrap();
+        assert_eq!(fstat(fd).unwrap().st_ino, stat(expected).unwrap().st_ino);
+        close(fd).unwrap();
+
+        assert_errno(
+            ctx,
+            open(&path, OFlag::O_WRONLY, Mode::empty()),
+            Errno::EISDIR,
+        );
+    }
+}
+
+crate::test_case! {
+    /// unlink returns EISDIR, EPERM, EINVAL or EBUSY if the last component of the path is "." or ".."
+    unlink_dots
+}
+fn unlink_dots(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    for path in [dir.join("."), dir.join("..")] {
+        assert_errno_choice(
+            ctx,
+            unlink(&path),
+            &[Errno::EISDIR, Errno::EPERM, Errno::EINVAL, Errno::EBUSY],
+        );
+    }
+    assert!(dir.is_dir());
+}
+
+crate::test_case! {
+    /// mkdir returns EEXIST if the last component of the path is "." or ".."
+    mkdir_dots_eexist
+}
+fn mkdir_dots_eexist(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_errno(ctx, mkdir(&dir.join("."), Mode::empty()), Errno::EEXIST);
+    assert_errno(ctx, mkdir(&dir.join(".."), Mode::empty()), Errno::EEXIST);
+}
+
+crate::test_case! {
+    /// link returns EPERM if the source is "." or "..", and EEXIST if the destination is
+    link_dots
+}
+fn link_dots(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let file = ctx.create(FileType::Regular).unwrap();
+
+    for dot in [dir.join("."), dir.join("..")] {
+        assert_errno(ctx, link(&dot, &ctx.gen_path()), Errno::EPERM);
+        assert_errno(ctx, link(&file, &dot), Errno::EEXIST);
+    }
+    assert_eq!(lstat(&file).unwrap().st_nlink, 1);
+}
+
+crate::test_case! {
+    /// rename returns EINVAL, EBUSY, EISDIR, EEXIST or ENOTEMPTY if the last component
+    /// of the destination is "." or ".."
+    rename_to_dots => [Regular, Dir]
+}
+fn rename_to_dots(ctx: &mut TestContext, ft: FileType) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let file = ctx.create(ft).unwrap();
+
+    for dot in [dir.join("."), dir.join("..")] {
+        assert_errno_choice(
+            ctx,
+            rename(&file, &dot),
+            &[
+                Errno::EINVAL,
+                Errno::EBUSY,
+                Errno::EISDIR,
+                Errno::EEXIST,
+                Errno::ENOTEMPTY,
+            ],
+        );
+    }
+    assert!(file.symlink_metadata().is_ok());
+    assert!(dir.is_dir());
+}
+
+crate::test_case! {
+    /// ".." at the root of the mounted file system resolves to the parent of the mount point,
+    /// or to the root itself if the file system is mounted on "/"
+    mount_root_dotdot
+}
+fn mount_root_dotdot(ctx: &mut TestContext) {
+    let base_path = fs::canonicalize(ctx.base_path()).unwrap();
+    let mountpoint = get_mountpoint(&base_path).unwrap();
+    let expected = mountpoint.parent().unwrap_or(mountpoint);
+
+    let dotdot_stat = stat(&mountpoint.join("..")).unwrap();
+    let expected_stat = stat(expected).unwrap();
+    assert_eq!(
+        (dotdot_stat.st_dev, dotdot_stat.st_ino),
+        (expected_stat.st_dev, expected_stat.st_ino)
+    );
+}
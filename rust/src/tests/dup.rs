@@ -0,0 +1,118 @@
+//! Tests for the descriptors duplicated with dup and dup2,
+//! which refer to the same open file description as the original one.
+
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    sys::stat::{fstat, Mode},
+    unistd::{dup, dup2, lseek, read, write, Whence},
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    utils::open,
+};
+
+use super::fcntl::{fd_flags, status_flags};
+
+/// Return the duplicates of the descriptor made with dup and dup2, with the syscall name.
+fn duplicates(fd: &OwnedFd, other: OwnedFd) -> [(&'static str, OwnedFd); 2] {
+    // SAFETY: The descriptors have just been returned by dup and dup2,
+    // which closed `other` before reusing its number
+    let dup = unsafe { OwnedFd::from_raw_fd(dup(fd.as_raw_fd()).unwrap()) };
+    let dup2 = unsafe { OwnedFd::from_raw_fd(dup2(fd.as_raw_fd(), other.into_raw_fd()).unwrap()) };
+
+    [("dup", dup), ("dup2", dup2)]
+}
+
+/// Open another descriptor, to be replaced by dup2.
+fn other_fd(ctx: &mut TestContext) -> OwnedFd {
+    let (_, fd) = ctx.create_file(OFlag::O_RDONLY, None).unwrap();
+    fd
+}
+
+crate::test_case! {
+    /// Duplicated descriptors share the file offset,
+    /// and what is written through one can be read through the other
+    shared_offset
+}
+fn shared_offset(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let fd = open(&path, OFlag::O_RDWR, Mode::empty()).unwrap();
+    let other = other_fd(ctx);
+
+    for (name, dup) in duplicates(&fd, other) {
+        assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekSet), Ok(0));
+        assert_eq!(write(dup.as_fd(), b"data"), Ok(4), "{name}");
+        assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekCur), Ok(4), "{name}");
+
+        assert_eq!(lseek(dup.as_raw_fd(), 1, Whence::SeekSet), Ok(1));
+        let mut buf = [0; 4];
+        assert_eq!(read(fd.as_raw_fd(), &mut buf), Ok(3), "{name}");
+        assert_eq!(&buf[..3], b"ata", "{name}");
+        assert_eq!(lseek(dup.as_raw_fd(), 0, Whence::SeekCur), Ok(4), "{name}");
+    }
+}
+
+crate::test_case! {
+    /// Duplicated descriptors share the status flags, but not FD_CLOEXEC,
+    /// which is cleared on the duplicate
+    shared_status_flags
+}
+fn shared_status_flags(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let fd = open(&path, OFlag::O_WRONLY | OFlag::O_CLOEXEC, Mode::empty()).unwrap();
+    let other = other_fd(ctx);
+
+    for (name, dup) in duplicates(&fd, other) {
+        assert!(!fd_flags(&dup).contains(FdFlag::FD_CLOEXEC), "{name}");
+        assert!(fd_flags(&fd).contains(FdFlag::FD_CLOEXEC), "{name}");
+
+        fcntl(dup.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_APPEND)).unwrap();
+        assert!(status_flags(&fd).contains(OFlag::O_APPEND), "{name}");
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::empty())).unwrap();
+        assert!(!status_flags(&dup).contains(OFlag::O_APPEND), "{name}");
+
+        fcntl(dup.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).unwrap();
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty())).unwrap();
+        assert!(fd_flags(&dup).contains(FdFlag::FD_CLOEXEC), "{name}");
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).unwrap();
+    }
+}
+
+crate::test_case! {
+    /// Closing a descriptor does not invalidate its duplicates
+    close_original
+}
+fn close_original(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let other = other_fd(ctx);
+
+    let fd = open(&path, OFlag::O_RDWR, Mode::empty()).unwrap();
+    let dups = duplicates(&fd, other);
+    drop(fd);
+
+    // The duplicates share the offset, so they write one after the other
+    for (i, (name, dup)) in dups.iter().enumerate() {
+        assert_eq!(write(dup.as_fd(), b"data"), Ok(4), "{name}");
+        assert_eq!(
+            fstat(dup.as_raw_fd()).unwrap().st_size,
+            4 * (i as i64 + 1),
+            "{name}"
+        );
+    }
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"datadata");
+}
+
+crate::test_case! {
+    /// dup2 returns the descriptor without closing it when both arguments are the same
+    dup2_same_fd
+}
+fn dup2_same_fd(ctx: &mut TestContext) {
+    let (_, fd) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
+
+    assert_eq!(dup2(fd.as_raw_fd(), fd.as_raw_fd()), Ok(fd.as_raw_fd()));
+    assert_eq!(write(fd.as_fd(), b"data"), Ok(4));
+}
@@ -9,3 +9,4 @@ pub(super) mod enotdir;
 pub(super) mod erofs;
 pub(super) mod etxtbsy;
 pub(super) mod exdev;
+pub(super) mod sticky;
@@ -6,7 +6,7 @@
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// eloop_comp_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -18,7 +18,7 @@
 ///   with the context and the path as arguments, for syscalls
 ///   requiring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// eloop_comp_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -34,7 +34,10 @@ macro_rules! eexist_file_exists_test_case {
         fn eexist_file_exists(ctx: &mut crate::context::TestContext,
             ft: crate::context::FileType) {
             let path = ctx.create(ft).unwrap();
-            $( assert_eq!($f(ctx, &path), Err(nix::errno::Errno::EEXIST)); )+
+            $(
+                let res = $f(ctx, &path);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::EEXIST);
+            )+
         }
     };
 
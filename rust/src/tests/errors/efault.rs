@@ -1,13 +1,137 @@
+use std::{
+    ffi::{c_char, c_void},
+    num::NonZeroUsize,
+    ptr::NonNull,
+};
+
+use nix::{
+    errno::{Errno, ErrnoSentinel},
+    sys::mman::{mmap_anonymous, mprotect, munmap, MapFlags, ProtFlags},
+    unistd::{sysconf, SysconfVar},
+};
+
+use crate::isolation::run_in_child;
+
+/// Length of the path which runs into the guard page without being terminated.
+const UNTERMINATED_LEN: usize = 8;
+
+/// An accessible page followed by an inaccessible guard page,
+/// to build pointers which are outside the address space accessible by the process.
+pub(crate) struct GuardPage {
+    addr: NonNull<c_void>,
+    page_size: usize,
+}
+
+impl GuardPage {
+    /// Map the pages.
+    pub fn new() -> Self {
+        let page_size = sysconf(SysconfVar::PAGE_SIZE).unwrap().unwrap() as usize;
+        let len = NonZeroUsize::new(2 * page_size).unwrap();
+
+        // SAFETY: The mapping is anonymous and only accessed through the pointers built here
+        let addr = unsafe {
+            mmap_anonymous(
+                None,
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE,
+            )
+        }
+        .unwrap();
+        let guard = GuardPage { addr, page_size };
+
+        let guard_page = NonNull::new(guard.guard().cast_mut().cast()).unwrap();
+        // SAFETY: The guard page is part of the mapping
+        unsafe { mprotect(guard_page, page_size, ProtFlags::PROT_NONE) }.unwrap();
+
+        // SAFETY: The bytes before the guard page are part of the accessible page
+        unsafe {
+            guard
+                .unterminated()
+                .cast_mut()
+                .write_bytes(b'a' as _, UNTERMINATED_LEN)
+        };
+
+        guard
+    }
+
+    /// Return a pointer to the start of the guard page.
+    pub fn guard(&self) -> *const c_char {
+        // SAFETY: The offset stays in the mapping
+        unsafe { self.addr.as_ptr().cast::<c_char>().add(self.page_size) }
+    }
+
+    /// Return a pointer to a path which is not terminated before the guard page.
+    pub fn unterminated(&self) -> *const c_char {
+        // SAFETY: The offset stays in the mapping
+        unsafe { self.guard().sub(UNTERMINATED_LEN) }
+    }
+
+    /// Return the pointers outside the accessible address space, with their description.
+    pub fn invalid_pointers(&self) -> [(&'static str, *const c_char); 3] {
+        let [guard, unterminated] = self.non_null_pointers();
+        [("NULL pointer", std::ptr::null()), guard, unterminated]
+    }
+
+    /// Return the pointers outside the accessible address space other than NULL,
+    /// with their description.
+    pub fn non_null_pointers(&self) -> [(&'static str, *const c_char); 2] {
+        [
+            ("pointer to the guard page", self.guard()),
+            ("path running into the guard page", self.unterminated()),
+        ]
+    }
+}
+
+impl Drop for GuardPage {
+    fn drop(&mut self) {
+        // SAFETY: The mapping is not used after being dropped
+        let _ = unsafe { munmap(self.addr, 2 * self.page_size) };
+    }
+}
+
+/// Call the syscall in a forked child process, and return its result.
+/// The test fails if the child is killed by a signal (e.g. SIGSEGV),
+/// rather than the whole runner.
+pub(crate) fn call_in_child<T, F>(f: F) -> nix::Result<()>
+where
+    T: ErrnoSentinel + PartialEq,
+    F: FnOnce() -> T,
+{
+    let res = run_in_child(|| match Errno::result(f()) {
+        Ok(_) => vec![],
+        Err(errno) => (errno as i32).to_ne_bytes().to_vec(),
+    });
+
+    match res {
+        Ok(errno) if errno.is_empty() => Ok(()),
+        Ok(errno) => Err(Errno::from_raw(i32::from_ne_bytes(
+            errno.try_into().unwrap(),
+        ))),
+        Err(e) => panic!("the syscall was {e}"),
+    }
+}
+
 /// Create a test case which asserts that the sycall
 /// returns EFAULT if the path argument points
 /// outside the process's allocated address space.
-/// It takes a function with the path pointer as argument.
+/// It takes a function with the path pointer as argument,
+/// which is called in a forked child process with each pointer of [`GuardPage::invalid_pointers`].
+///
+/// The NULL pointer is left out with `non_null`, for the syscalls which give it a meaning.
 ///
 /// ```ignore
-/// efault_path_test_case!(|ptr| nix::libc::mkdir(ptr, 0o755))
+/// efault_path_test_case!(mkdir, |ptr| nix::libc::mkdir(ptr, 0o755))
+/// efault_path_test_case!(utimensat, non_null, |ptr| nix::libc::utimensat(AT_FDCWD, ptr, null(), 0))
 /// ````
 macro_rules! efault_path_test_case {
+    ($syscall: ident, non_null, $fn: expr) => {
+        $crate::tests::errors::efault::efault_path_test_case!(@ $syscall, non_null_pointers, $fn);
+    };
     ($syscall: ident, $fn: expr) => {
+        $crate::tests::errors::efault::efault_path_test_case!(@ $syscall, invalid_pointers, $fn);
+    };
+    (@ $syscall: ident, $pointers: ident, $fn: expr) => {
         crate::test_case! {
             #[doc = concat!(stringify!($syscall),
             " returns EFAULT if the path argument points",
@@ -15,24 +139,19 @@ macro_rules! efault_path_test_case {
             )]
             efault_path
         }
-        fn efault_path(_: &mut crate::context::TestContext) {
-            let f = |ptr| unsafe { $fn(ptr) };
+        fn efault_path(ctx: &mut crate::context::TestContext) {
+            use $crate::tests::errors::efault::{call_in_child, GuardPage};
 
-            let null_ptr = std::ptr::null();
-            // TODO: This theorically could be a valid pointer, but
-            // it's unlikely to be the case in practice. Should we
-            // use a more robust way to get a pointer outside the
-            // process's allocated address space, though?
-            let invalid_ptr = usize::MAX as *const _;
+            let guard = GuardPage::new();
 
-            assert_eq!(
-                nix::errno::Errno::result(f(null_ptr)),
-                Err(nix::errno::Errno::EFAULT)
-            );
-            assert_eq!(
-                nix::errno::Errno::result(f(invalid_ptr)),
-                Err(nix::errno::Errno::EFAULT)
-            );
+            for (description, ptr) in guard.$pointers() {
+                crate::tests::assert_errno_for(
+                    ctx,
+                    call_in_child(|| unsafe { $fn(ptr) }),
+                    nix::errno::Errno::EFAULT,
+                    description,
+                );
+            }
         }
     };
 }
@@ -42,6 +161,9 @@ pub(crate) use efault_path_test_case;
 /// Create a test case which asserts that the sycall
 /// returns EFAULT if one of the pathnames specified
 /// is outside the process's allocated address space.
+/// It takes a function with both path pointers as arguments,
+/// which is called in a forked child process with each pointer of [`GuardPage::invalid_pointers`]
+/// in place of either pathname, or both.
 ///
 /// ```ignore
 /// efault_error_test_case!(link, nix::libc::link)
@@ -57,50 +179,68 @@ macro_rules! efault_either_test_case {
         }
         fn efault_either(ctx: &mut crate::context::TestContext) {
             use nix::NixPath;
+            use $crate::tests::errors::efault::{call_in_child, GuardPage};
 
             let file = ctx.create(crate::context::FileType::Regular).unwrap();
-
-            let null_ptr = std::ptr::null();
-
-            // TODO: This theorically could be a valid pointer, but
-            // it's unlikely to be the case in practice. Should we
-            // use a more robust way to get a pointer outside the
-            // process's allocated address space, though?
-            let invalid_ptr = usize::MAX as *const _;
+            let guard = GuardPage::new();
 
             file.with_nix_path(|cstr| {
-                let ptr = cstr.as_ptr();
+                let valid_ptr = cstr.as_ptr();
 
-                assert_eq!(
-                    nix::errno::Errno::result(unsafe { $fn(null_ptr, ptr) }),
-                    Err(nix::errno::Errno::EFAULT)
-                );
-                assert_eq!(
-                    nix::errno::Errno::result(unsafe { $fn(invalid_ptr, ptr) }),
-                    Err(nix::errno::Errno::EFAULT)
-                );
-
-                assert_eq!(
-                    nix::errno::Errno::result(unsafe { $fn(ptr, null_ptr) }),
-                    Err(nix::errno::Errno::EFAULT)
-                );
-                assert_eq!(
-                    nix::errno::Errno::result(unsafe { $fn(ptr, invalid_ptr) }),
-                    Err(nix::errno::Errno::EFAULT)
-                );
+                for (description, ptr) in guard.invalid_pointers() {
+                    crate::tests::assert_errno_for(
+                        ctx,
+                        call_in_child(|| unsafe { $fn(ptr, valid_ptr) }),
+                        nix::errno::Errno::EFAULT,
+                        format_args!("{description} as first pathname"),
+                    );
+                    crate::tests::assert_errno_for(
+                        ctx,
+                        call_in_child(|| unsafe { $fn(valid_ptr, ptr) }),
+                        nix::errno::Errno::EFAULT,
+                        format_args!("{description} as second pathname"),
+                    );
+                    for (other_description, other_ptr) in guard.invalid_pointers() {
+                        crate::tests::assert_errno_for(
+                            ctx,
+                            call_in_child(|| unsafe { $fn(ptr, other_ptr) }),
+                            nix::errno::Errno::EFAULT,
+                            format_args!("{description} and {other_description}"),
+                        );
+                    }
+                }
             })
             .unwrap();
-
-            assert_eq!(
-                nix::errno::Errno::result(unsafe { $fn(invalid_ptr, null_ptr) }),
-                Err(nix::errno::Errno::EFAULT)
-            );
-            assert_eq!(
-                nix::errno::Errno::result(unsafe { $fn(null_ptr, invalid_ptr) }),
-                Err(nix::errno::Errno::EFAULT)
-            );
         }
     };
 }
 
 pub(crate) use efault_either_test_case;
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use super::{call_in_child, GuardPage};
+
+    #[test]
+    fn invalid_pointers() {
+        let guard = GuardPage::new();
+
+        for (description, ptr) in guard.invalid_pointers() {
+            assert_eq!(
+                call_in_child(|| unsafe { nix::libc::access(ptr, nix::libc::F_OK) }),
+                Err(Errno::EFAULT),
+                "{description}"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "terminated by signal")]
+    fn signal_is_reported() {
+        let guard = GuardPage::new();
+
+        let _ = call_in_child(|| unsafe { guard.guard().read_volatile() as nix::libc::c_int });
+    }
+}
@@ -1,6 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::context::{FileType, TestContext};
+use nix::unistd::{sysconf, SysconfVar};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+};
 
 /// Create a loop between two symbolic links and return them.
 pub fn create_loop_symlinks(ctx: &mut TestContext) -> (PathBuf, PathBuf) {
@@ -19,6 +24,47 @@ pub fn create_loop_symlinks(ctx: &mut TestContext) -> (PathBuf, PathBuf) {
     )
 }
 
+/// Return the maximum number of symbolic links which can be followed while resolving a path.
+/// It is taken from `sysconf(_SC_SYMLOOP_MAX)`, and from the kernel constant
+/// on Linux where it is indeterminate.
+pub fn symloop_max() -> Option<usize> {
+    match sysconf(SysconfVar::SYMLOOP_MAX) {
+        Ok(Some(max)) => Some(max as usize),
+        // MAXSYMLINKS in include/linux/namei.h
+        #[cfg(target_os = "linux")]
+        _ => Some(40),
+        #[cfg(not(target_os = "linux"))]
+        _ => None,
+    }
+}
+
+/// Guard to check that the maximum number of symbolic links followed in a path is known.
+pub fn symloop_max_known(_: &Config, _: &Path) -> anyhow::Result<()> {
+    if symloop_max().is_none() {
+        anyhow::bail!("{{SYMLOOP_MAX}} is indeterminate")
+    }
+
+    Ok(())
+}
+
+/// Create a chain of `len` symbolic links ending at a directory containing a regular file named `test`,
+/// and return the first link of the chain along with a link to it,
+/// which starts a chain one link longer.
+pub fn create_symlink_chain(ctx: &mut TestContext, len: usize) -> (PathBuf, PathBuf) {
+    let target = ctx.create(FileType::Dir).unwrap();
+    ctx.new_file(FileType::Regular)
+        .name(target.join("test"))
+        .create()
+        .unwrap();
+
+    let head = (0..len).fold(target, |next, _| {
+        ctx.create(FileType::Symlink(Some(next))).unwrap()
+    });
+    let longer_head = ctx.create(FileType::Symlink(Some(head.clone()))).unwrap();
+
+    (head, longer_head)
+}
+
 /// Create a test case which asserts that the sycall
 /// returns ELOOP if too many symbolic links were encountered in translating
 /// a component of the pathname which is not the last one.
@@ -28,7 +74,7 @@ pub fn create_loop_symlinks(ctx: &mut TestContext) -> (PathBuf, PathBuf) {
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// eloop_comp_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -40,7 +86,7 @@ pub fn create_loop_symlinks(ctx: &mut TestContext) -> (PathBuf, PathBuf) {
 ///   with the context and the path as arguments, for syscalls
 ///   requiring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// eloop_comp_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -61,8 +107,10 @@ macro_rules! eloop_comp_test_case {
             loop2.push("test");
 
             $(
-                assert_eq!($f(ctx, &loop1).unwrap_err(), nix::errno::Errno::ELOOP);
-                assert_eq!($f(ctx, &loop2).unwrap_err(), nix::errno::Errno::ELOOP);
+                let res = $f(ctx, &loop1);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ELOOP);
+                let res = $f(ctx, &loop2);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ELOOP);
             )+
         }
     };
@@ -77,6 +125,49 @@ macro_rules! eloop_comp_test_case {
 
 pub(crate) use eloop_comp_test_case;
 
+/// Create a test case which asserts that the syscall succeeds on an existing file
+/// when exactly `{SYMLOOP_MAX}` symbolic links are encountered in translating
+/// the components of the pathname which are not the last one,
+/// and returns ELOOP with one more link.
+/// It has the same forms as [`eloop_comp_test_case`].
+///
+/// ```ignore
+/// eloop_symloop_max_test_case!(unlink);
+/// eloop_symloop_max_test_case!(chmod(~path, Mode::empty()));
+/// ```
+macro_rules! eloop_symloop_max_test_case {
+    ($syscall: ident, $($f: expr),+) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+            " succeeds if exactly {SYMLOOP_MAX} symbolic links are encountered in translating",
+            " the pathname, and returns ELOOP with one more")]
+            eloop_symloop_max; $crate::tests::errors::eloop::symloop_max_known
+        }
+        fn eloop_symloop_max(ctx: &mut crate::context::TestContext) {
+            use $crate::tests::errors::eloop::{create_symlink_chain, symloop_max};
+
+            let max = symloop_max().unwrap();
+
+            $(
+                let (head, longer_head) = create_symlink_chain(ctx, max);
+                let result = $f(ctx, &head.join("test"));
+                assert!(result.is_ok(), "{} links: {:?}", max, result.err());
+                let res = $f(ctx, &longer_head.join("test"));
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ELOOP);
+            )+
+        }
+    };
+
+    ($syscall: ident $( ($( $($before:expr),* ,)? ~path $(, $($after:expr),*)?) )?) => {
+        eloop_symloop_max_test_case!($syscall, |_: &mut $crate::context::TestContext,
+            path: &std::path::Path| {
+            $syscall($( $($($before),* ,)? )? path $( $(, $($after),*)? )?)
+        });
+    };
+}
+
+pub(crate) use eloop_symloop_max_test_case;
+
 /// Create a test case which asserts that the sycall
 /// returns ELOOP if too many symbolic links were encountered in translating
 /// a component of either pathname which is not the last one.
@@ -98,21 +189,25 @@ macro_rules! eloop_either_test_case {
             loop2.push("test");
             let valid_path = ctx.create(crate::context::FileType::Regular).unwrap();
 
-            assert_eq!(
-                $syscall(&loop1.join("test"), &valid_path).unwrap_err(),
-                Errno::ELOOP
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&loop1.join("test"), &valid_path),
+                nix::errno::Errno::ELOOP,
             );
-            assert_eq!(
-                $syscall(&loop2.join("test"), &valid_path).unwrap_err(),
-                Errno::ELOOP
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&loop2.join("test"), &valid_path),
+                nix::errno::Errno::ELOOP,
             );
-            assert_eq!(
-                $syscall(&valid_path, &loop1.join("test")).unwrap_err(),
-                Errno::ELOOP
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&valid_path, &loop1.join("test")),
+                nix::errno::Errno::ELOOP,
             );
-            assert_eq!(
-                $syscall(&valid_path, &loop2.join("test")).unwrap_err(),
-                Errno::ELOOP
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&valid_path, &loop2.join("test")),
+                nix::errno::Errno::ELOOP,
             );
         }
     };
@@ -129,7 +224,7 @@ pub(crate) use eloop_either_test_case;
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// eloop_final_comp_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -141,7 +236,7 @@ pub(crate) use eloop_either_test_case;
 ///   with the context and the path as arguments, for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// eloop_final_comp_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -160,8 +255,10 @@ macro_rules! eloop_final_comp_test_case {
             let (loop1, loop2) = $crate::tests::errors::eloop::create_loop_symlinks(ctx);
 
             $(
-                assert_eq!($f(ctx, &loop1).unwrap_err(), nix::errno::Errno::ELOOP);
-                assert_eq!($f(ctx, &loop2).unwrap_err(), nix::errno::Errno::ELOOP);
+                let res = $f(ctx, &loop1);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ELOOP);
+                let res = $f(ctx, &loop2);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ELOOP);
             )+
         }
     };
@@ -39,7 +39,10 @@ macro_rules! enametoolong_comp_test_case {
 
             let mut invalid_path = ctx.create_name_max(FileType::Regular).unwrap();
             invalid_path.set_extension("x");
-            $(assert_eq!($f(ctx, &invalid_path), Err(Errno::ENAMETOOLONG));)+
+            $(
+                let res = $f(ctx, &invalid_path);
+                crate::tests::assert_errno(ctx, res, Errno::ENAMETOOLONG);
+            )+
         }
     };
 
@@ -75,13 +78,15 @@ macro_rules! enametoolong_either_comp_test_case {
             let mut invalid_path = ctx.create_name_max(FileType::Regular).unwrap();
             invalid_path.set_extension("x");
             let valid_path = ctx.create_name_max(FileType::Regular).unwrap();
-            assert_eq!(
+            crate::tests::assert_errno(
+                ctx,
                 $syscall(&valid_path, &invalid_path),
-                Err(Errno::ENAMETOOLONG)
+                Errno::ENAMETOOLONG,
             );
-            assert_eq!(
+            crate::tests::assert_errno(
+                ctx,
                 $syscall(&invalid_path, &valid_path),
-                Err(Errno::ENAMETOOLONG)
+                Errno::ENAMETOOLONG,
             );
         }
     };
@@ -130,7 +135,10 @@ macro_rules! enametoolong_path_test_case {
 
             let mut invalid_path = ctx.create_path_max(FileType::Regular).unwrap();
             invalid_path.set_extension("x");
-            $(assert_eq!($f(ctx, &invalid_path), Err(Errno::ENAMETOOLONG));)+
+            $(
+                let res = $f(ctx, &invalid_path);
+                crate::tests::assert_errno(ctx, res, Errno::ENAMETOOLONG);
+            )+
         }
     };
 
@@ -166,13 +174,15 @@ macro_rules! enametoolong_either_path_test_case {
             let mut invalid_path = ctx.create_path_max(FileType::Regular).unwrap();
             invalid_path.set_extension("x");
             let valid_path = ctx.create_path_max(FileType::Regular).unwrap();
-            assert_eq!(
+            crate::tests::assert_errno(
+                ctx,
                 $syscall(&invalid_path, &valid_path),
-                Err(Errno::ENAMETOOLONG)
+                Errno::ENAMETOOLONG,
             );
-            assert_eq!(
+            crate::tests::assert_errno(
+                ctx,
                 $syscall(&invalid_path, &valid_path),
-                Err(Errno::ENAMETOOLONG)
+                Errno::ENAMETOOLONG,
             );
         }
     };
@@ -6,7 +6,7 @@
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// enoent_named_file_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -18,7 +18,7 @@
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// enoent_named_file_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -35,7 +35,10 @@ macro_rules! enoent_named_file_test_case {
             let dir = ctx.create(crate::context::FileType::Dir).unwrap();
             let path = dir.join("not_existent");
 
-            $( assert_eq!($f(ctx, &path).unwrap_err(), nix::errno::Errno::ENOENT) );+
+            $(
+                let res = $f(ctx, &path);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ENOENT);
+            )+
         }
     };
 
@@ -51,7 +54,7 @@ pub(crate) use enoent_named_file_test_case;
 
 /// Create a test case which asserts that the sycall
 /// returns ENOENT if either of the named file does not exist.
-/// ```
+/// ```ignore
 /// enoent_either_named_file_test_case!(rename);
 /// ```
 macro_rules! enoent_either_named_file_test_case {
@@ -82,7 +85,7 @@ pub(crate) use enoent_either_named_file_test_case;
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// enoent_symlink_named_file_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -94,7 +97,7 @@ pub(crate) use enoent_either_named_file_test_case;
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// enoent_symlink_named_file_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -114,7 +117,8 @@ macro_rules! enoent_symlink_named_file_test_case {
                 .create(crate::context::FileType::Symlink(Some(path.to_path_buf())))
                 .unwrap();
 
-            assert_eq!($f(ctx, &link).unwrap_err(), nix::errno::Errno::ENOENT)
+            let res = $f(ctx, &link);
+            crate::tests::assert_errno(ctx, res, nix::errno::Errno::ENOENT)
         }
     };
 
@@ -136,7 +140,7 @@ pub(crate) use enoent_symlink_named_file_test_case;
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// enotdir_comp_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -148,7 +152,7 @@ pub(crate) use enoent_symlink_named_file_test_case;
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// enotdir_comp_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -165,7 +169,10 @@ macro_rules! enoent_comp_test_case {
             let dir = ctx.create(crate::context::FileType::Dir).unwrap();
             let path = dir.join("not_existent").join("test");
 
-            $( assert_eq!($f(ctx, &path).unwrap_err(), nix::errno::Errno::ENOENT) );+
+            $(
+                let res = $f(ctx, &path);
+                crate::tests::assert_errno(ctx, res, nix::errno::Errno::ENOENT);
+            )+
         }
     };
 
@@ -6,7 +6,7 @@
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// enotdir_comp_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -18,7 +18,7 @@
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// enotdir_comp_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -36,7 +36,8 @@ macro_rules! enotdir_comp_test_case {
             let base_path = ctx.create(ft.clone()).unwrap();
             let path = base_path.join("previous_not_dir");
 
-            assert_eq!($f(ctx, &path).unwrap_err(), nix::errno::Errno::ENOTDIR)
+            let res = $f(ctx, &path);
+            crate::tests::assert_errno(ctx, res, nix::errno::Errno::ENOTDIR)
         }
     };
 
@@ -51,7 +52,7 @@ macro_rules! enotdir_comp_test_case {
 /// Create a test case which asserts that the syscall returns ENOTDIR
 /// if a component of either path prefix is not a directory.
 /// It takes the syscall as its only argument.
-/// ```
+/// ```ignore
 /// enotdir_comp_either_test_case!(rename);
 /// ```
 macro_rules! enotdir_comp_either_test_case {
@@ -69,15 +70,68 @@ macro_rules! enotdir_comp_either_test_case {
             let path = file.join("previous_not_dir");
             let new_path = ctx.gen_path();
 
-            assert_eq!($syscall(&*path, &*new_path).unwrap_err(), Errno::ENOTDIR);
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&*path, &*new_path),
+                nix::errno::Errno::ENOTDIR,
+            );
 
             let new_base_path = ctx.create(ft.clone()).unwrap();
             let new_path = new_base_path.join("previous_not_dir");
 
-            assert_eq!($syscall(&*file, &*new_path).unwrap_err(), Errno::ENOTDIR);
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&*file, &*new_path),
+                nix::errno::Errno::ENOTDIR,
+            );
+        }
+    };
+}
+
+/// Create a test case which asserts that the syscall returns ENOTDIR
+/// if the path names an existing file which is not a directory,
+/// and is followed by a trailing slash.
+/// It has the same forms as [`enotdir_comp_test_case`].
+///
+/// ```ignore
+/// // `unlink` accepts only a path as argument.
+/// enotdir_trailing_slash_test_case!(unlink);
+/// // `truncate` takes a path and the new size as arguments.
+/// enotdir_trailing_slash_test_case!(truncate(~path, 0));
+/// // `rename` needs a new path to be generated.
+/// enotdir_trailing_slash_test_case!(rename, |ctx: &mut TestContext, path: &Path| {
+///   rename(path, &*ctx.gen_path())
+/// })
+/// ```
+macro_rules! enotdir_trailing_slash_test_case {
+    ($syscall: ident, $f: expr) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+                 " returns ENOTDIR if the path names a file which is not a directory",
+                 " and has a trailing slash")]
+            enotdir_trailing_slash => [Regular, Fifo, Block, Char, Socket]
+        }
+        fn enotdir_trailing_slash(ctx: &mut crate::context::TestContext,
+                                  ft: crate::context::FileType) {
+            let file = ctx.create(ft.clone()).unwrap();
+            let mut path = file.clone().into_os_string();
+            path.push("/");
+            let path = std::path::PathBuf::from(path);
+
+            let res = $f(ctx, &path);
+            crate::tests::assert_errno(ctx, res, nix::errno::Errno::ENOTDIR);
+            assert!(file.symlink_metadata().is_ok());
         }
     };
+
+    ($syscall: ident $( ($( $($before:expr),* ,)? ~path $(, $($after:expr),*)?) )?) => {
+        enotdir_trailing_slash_test_case!($syscall, |_ctx: &mut crate::context::TestContext,
+                                             path: &std::path::Path| {
+                $syscall($( $($($before),* ,)? )? path $( $(, $($after),*)? )?)
+        });
+    };
 }
 
 pub(crate) use enotdir_comp_either_test_case;
 pub(crate) use enotdir_comp_test_case;
+pub(crate) use enotdir_trailing_slash_test_case;
@@ -84,7 +84,7 @@ where
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// erofs_new_file_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -96,7 +96,7 @@ where
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// erofs_new_file_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -114,7 +114,10 @@ macro_rules! erofs_new_file_test_case {
             let path = ctx.base_path().to_owned();
             let file = ctx.gen_path();
             with_readonly_fs(path, || {
-                $( assert_eq!($f(ctx, &file), Err(nix::errno::Errno::EROFS)); )+
+                $(
+                    let res = $f(&mut *ctx, &file);
+                    crate::tests::assert_errno(ctx, res, nix::errno::Errno::EROFS);
+                )+
             });
         }
     };
@@ -136,7 +139,7 @@ pub(crate) use erofs_new_file_test_case;
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// erofs_test_case!(unlink);
 /// // `chflags` takes a path and the flags to set as arguments.
@@ -148,7 +151,7 @@ pub(crate) use erofs_new_file_test_case;
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// erofs_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -167,7 +170,10 @@ macro_rules! erofs_named_test_case {
             let path = ctx.base_path().to_owned();
             let file = ctx.new_file(FileType::Regular).name(path.join("file")).create().unwrap();
             with_readonly_fs(path, || {
-                $( assert_eq!($f(ctx, &file), Err(nix::errno::Errno::EROFS)); )+
+                $(
+                    let res = $f(&mut *ctx, &file);
+                    crate::tests::assert_errno(ctx, res, nix::errno::Errno::EROFS);
+                )+
             });
         }
     };
@@ -6,7 +6,7 @@
 ///   to indicate where the `path` argument should be substituted if the path
 ///   is not the only argument taken by the syscall.
 ///
-/// ```
+/// ```ignore
 /// // `unlink` accepts only a path as argument.
 /// etxtbsy_test_case!(unlink);
 /// // `truncate` takes a path and the flags to set as arguments.
@@ -18,7 +18,7 @@
 ///   with the context and the path as arguments for syscalls
 ///   requring to compute other arguments.
 ///
-/// ```
+/// ```ignore
 /// etxtbsy_test_case!(chown, |ctx: &mut TestContext, path: &Path| {
 ///   let user = ctx.get_new_user();
 ///   chown(path, Some(user.uid), None)
@@ -31,30 +31,13 @@ macro_rules! etxtbsy_test_case {
                  " returns ETXTBSY when the file is a pure procedure (shared text) file that is being executed.")]
             etxtbsy; $crate::tests::errors::etxtbsy::exec_mounted
         }
-        fn etxtbsy (ctx: &mut crate::context::TestContext) {
-            use std::{fs::File, process::Command};
-
-            use nix::{errno::Errno, sys::stat::Mode};
-
-            use crate::utils::chmod;
-
-            let sleep_path =
-                String::from_utf8(Command::new("which").arg("sleep").output().unwrap().stdout).unwrap();
-            let sleep_path = sleep_path.trim();
-
-            let exec_path = ctx.gen_path();
-            std::io::copy(
-                &mut File::open(sleep_path).unwrap(),
-                &mut File::create(&exec_path).unwrap(),
-            )
-            .unwrap();
-
-            chmod(&exec_path, Mode::from_bits_truncate(0o755)).unwrap();
+        fn etxtbsy(ctx: &mut crate::context::TestContext) {
+            use nix::errno::Errno;
 
-            let mut sleep_process = Command::new(&exec_path).arg("10").spawn().unwrap();
-            $( assert_eq!($f(&exec_path).unwrap_err(), Errno::ETXTBSY); )+
-
-            sleep_process.kill().unwrap();
+            let executable = $crate::tests::errors::etxtbsy::RunningExecutable::spawn(ctx);
+            $(
+                crate::tests::assert_errno(ctx, $f(executable.path()), Errno::ETXTBSY);
+            )+
         }
     };
 
@@ -67,8 +50,67 @@ macro_rules! etxtbsy_test_case {
 
 pub(crate) use etxtbsy_test_case;
 
-use crate::config::Config;
-use std::path::Path;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+};
+
+use nix::sys::stat::Mode;
+
+use crate::{config::Config, context::TestContext, utils::chmod};
+
+/// Copy the `sleep` executable to a new file of the file system under test, and return its path.
+pub(crate) fn copy_executable(ctx: &TestContext) -> PathBuf {
+    let sleep_path =
+        String::from_utf8(Command::new("which").arg("sleep").output().unwrap().stdout).unwrap();
+    let sleep_path = sleep_path.trim();
+
+    let exec_path = ctx.gen_path();
+    std::io::copy(
+        &mut File::open(sleep_path).unwrap(),
+        &mut File::create(&exec_path).unwrap(),
+    )
+    .unwrap();
+
+    chmod(&exec_path, Mode::from_bits_truncate(0o755)).unwrap();
+
+    exec_path
+}
+
+/// Executable of the file system under test which is being executed,
+/// until it is dropped.
+pub(crate) struct RunningExecutable {
+    path: PathBuf,
+    process: Child,
+}
+
+impl RunningExecutable {
+    /// Copy the `sleep` executable to a new file and run it.
+    pub fn spawn(ctx: &TestContext) -> Self {
+        let path = copy_executable(ctx);
+        let process = Command::new(&path).arg("10").spawn().unwrap();
+
+        RunningExecutable { path, process }
+    }
+
+    /// Return the path of the executable.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return whether the executable is still running.
+    pub fn is_running(&mut self) -> bool {
+        self.process.try_wait().unwrap().is_none()
+    }
+}
+
+impl Drop for RunningExecutable {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
 
 /// Guard which checks if a file system is mounted with the `noexec` flag.
 pub fn exec_mounted(_: &Config, base_path: &Path) -> anyhow::Result<()> {
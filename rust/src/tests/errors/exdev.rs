@@ -27,7 +27,7 @@ macro_rules! exdev_target_test_case {
             " returns EXDEV when the target is on a different file-system")]
             exdev_target; crate::tests::errors::exdev::secondary_fs_available
         }
-        fn exdev_target(ctx: &mut crate::TestContext) {
+        fn exdev_target(ctx: &mut crate::context::TestContext) {
             let path = ctx.create(crate::context::FileType::Regular).unwrap();
             let other_fs_path = ctx
                 .features_config()
@@ -36,9 +36,119 @@ macro_rules! exdev_target_test_case {
                 .unwrap()
                 .join("file");
 
-            assert_eq!($syscall(&path, &other_fs_path), Err(Errno::EXDEV));
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&path, &other_fs_path),
+                nix::errno::Errno::EXDEV,
+            );
         }
     };
 }
 
 pub(crate) use exdev_target_test_case;
+
+/// Create a test-case for an `*at` syscall which returns `EXDEV` when the directory file descriptors
+/// refer to directories on different file-systems, with the names relative to them.
+/// The test-case will be skipped if no secondary file system has been configured.
+///
+/// ```rust,ignore
+/// exdev_dirfd_test_case!(linkat, |from_fd, from, to_fd, to| {
+///     linkat(Some(from_fd), from, Some(to_fd), to, AtFlags::empty())
+/// });
+/// ```
+macro_rules! exdev_dirfd_test_case {
+    ($syscall: ident, $f: expr) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+            " returns EXDEV when the directory file descriptors are on different file-systems")]
+            exdev_dirfd; crate::tests::errors::exdev::secondary_fs_available
+        }
+        fn exdev_dirfd(ctx: &mut crate::context::TestContext) {
+            use std::{
+                ffi::OsStr,
+                os::fd::{AsRawFd, RawFd},
+            };
+
+            let path = ctx.create(crate::context::FileType::Regular).unwrap();
+            let open_dir = |path: &std::path::Path| {
+                crate::utils::open(
+                    path,
+                    nix::fcntl::OFlag::O_DIRECTORY | nix::fcntl::OFlag::O_RDONLY,
+                    nix::sys::stat::Mode::empty(),
+                )
+                .unwrap()
+            };
+            let from_fd = open_dir(ctx.base_path());
+            let to_fd = open_dir(ctx.features_config().secondary_fs.as_ref().unwrap());
+
+            let f: fn(RawFd, &OsStr, RawFd, &OsStr) -> nix::Result<()> = $f;
+            let res = f(
+                from_fd.as_raw_fd(),
+                path.file_name().unwrap(),
+                to_fd.as_raw_fd(),
+                OsStr::new("file"),
+            );
+            crate::tests::assert_errno(ctx, res, nix::errno::Errno::EXDEV);
+            assert!(path.is_file());
+        }
+    };
+}
+
+pub(crate) use exdev_dirfd_test_case;
+
+/// Create a test-case for a syscall which returns `EXDEV` when a directory tree
+/// is moved to a different file-system, and checks that nothing has been partially moved.
+/// The test-case will be skipped if no secondary file system has been configured.
+///
+/// ```rust,ignore
+/// exdev_dir_tree_test_case!(rename);
+/// ```
+macro_rules! exdev_dir_tree_test_case {
+    ($syscall: ident) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+            " returns EXDEV when a directory tree is moved to a different file-system,",
+            " and leaves both trees unchanged")]
+            exdev_dir_tree; crate::tests::errors::exdev::secondary_fs_available
+        }
+        fn exdev_dir_tree(ctx: &mut crate::context::TestContext) {
+            use crate::context::FileType;
+
+            let dir = ctx.create(FileType::Dir).unwrap();
+            let subdir = ctx
+                .new_file(FileType::Dir)
+                .name(dir.join("subdir"))
+                .create()
+                .unwrap();
+            for parent in [&dir, &subdir] {
+                for (name, ft) in [
+                    ("regular", FileType::Regular),
+                    ("fifo", FileType::Fifo),
+                    ("symlink", FileType::Symlink(None)),
+                ] {
+                    ctx.new_file(ft).name(parent.join(name)).create().unwrap();
+                }
+            }
+            std::fs::write(dir.join("regular"), b"data").unwrap();
+
+            let other_fs_path = ctx
+                .features_config()
+                .secondary_fs
+                .as_ref()
+                .unwrap()
+                .join("dir");
+            let snapshot = ctx.snapshot();
+
+            crate::tests::assert_errno(
+                ctx,
+                $syscall(&dir, &other_fs_path),
+                nix::errno::Errno::EXDEV,
+            );
+
+            snapshot.verify_unchanged();
+            assert!(std::fs::symlink_metadata(&other_fs_path).is_err());
+        }
+    };
+}
+
+pub(crate) use exdev_dir_tree_test_case;
@@ -0,0 +1,303 @@
+//! Framework for testing operations in sticky directories, where an entry can be removed
+//! or replaced only by the owner of the entry or of the directory.
+//!
+//! The test cases are generated with [`sticky_test_case`] for syscalls taking one path,
+//! and with [`sticky_either_test_case`] for syscalls taking two paths.
+
+use std::path::{Path, PathBuf};
+
+use nix::{errno::Errno, sys::stat::Mode, unistd::User};
+
+use crate::{
+    context::{FileType, SerializedTestContext, TestContext},
+    utils::{chmod, lchown},
+};
+
+/// Owner of a file, relative to the user executing the operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Owner {
+    /// The user executing the operation.
+    User,
+    /// Another user.
+    Other,
+}
+
+/// Ownership of a sticky directory and of the entry on which the operation is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StickyCase {
+    pub dir: Owner,
+    pub entry: Owner,
+}
+
+impl StickyCase {
+    /// All the combinations of ownership.
+    pub const ALL: [StickyCase; 4] = [
+        StickyCase {
+            dir: Owner::User,
+            entry: Owner::User,
+        },
+        StickyCase {
+            dir: Owner::User,
+            entry: Owner::Other,
+        },
+        StickyCase {
+            dir: Owner::Other,
+            entry: Owner::User,
+        },
+        StickyCase {
+            dir: Owner::Other,
+            entry: Owner::Other,
+        },
+    ];
+
+    /// Return whether the user is allowed to remove or replace the entry.
+    pub fn allowed(self) -> bool {
+        self.dir == Owner::User || self.entry == Owner::User
+    }
+
+    /// Assert that the result of the operation matches the case,
+    /// that is success if it is allowed, and EACCES or EPERM otherwise.
+    pub fn assert_result<T: std::fmt::Debug>(self, ctx: &TestContext, res: nix::Result<T>) {
+        if self.allowed() {
+            assert!(res.is_ok(), "{self:?}: {res:?}");
+        } else if let Err(mismatch) =
+            crate::tests::check_errno(ctx, res, &[Errno::EACCES, Errno::EPERM])
+        {
+            panic!("{self:?}: {mismatch}");
+        }
+    }
+}
+
+/// Create a sticky directory owned by `owner`.
+pub fn create_sticky_dir(ctx: &SerializedTestContext, owner: &User) -> PathBuf {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    chmod(&dir, Mode::from_bits_truncate(0o1777)).unwrap();
+    lchown(&dir, Some(owner.uid), Some(owner.gid)).unwrap();
+
+    dir
+}
+
+/// Create an entry of type `ft` named `name` in `dir` and owned by `owner`.
+pub fn create_owned_entry(
+    ctx: &SerializedTestContext,
+    dir: &Path,
+    name: &str,
+    ft: FileType,
+    owner: &User,
+) -> PathBuf {
+    let entry = ctx.new_file(ft).name(dir.join(name)).create().unwrap();
+    lchown(&entry, Some(owner.uid), Some(owner.gid)).unwrap();
+
+    entry
+}
+
+/// Execute an operation on an entry of type `ft` in a sticky directory,
+/// for each combination of ownership of the entry and the directory,
+/// and assert that it succeeds only when the user owns either of them.
+///
+/// `setup` is executed with privileges, with the sticky directory and the user,
+/// and its result is given to `op`, which is executed as the user on the entry.
+pub fn assert_sticky_matrix<T, S, F>(ctx: &mut SerializedTestContext, ft: FileType, setup: S, op: F)
+where
+    S: Fn(&SerializedTestContext, &Path, &User) -> T,
+    F: Fn(&T, &Path) -> nix::Result<()>,
+{
+    let user = ctx.get_new_user();
+    let other_user = ctx.get_new_user();
+    let owner = |owner| match owner {
+        Owner::User => user,
+        Owner::Other => other_user,
+    };
+
+    for case in StickyCase::ALL {
+        let dir = create_sticky_dir(ctx, owner(case.dir));
+        let entry = create_owned_entry(ctx, &dir, "entry", ft.clone(), owner(case.entry));
+        let arg = setup(ctx, &dir, user);
+
+        ctx.as_user(user, None, || {
+            case.assert_result(ctx, op(&arg, &entry));
+        });
+        if !case.allowed() {
+            assert!(entry.symlink_metadata().is_ok(), "{case:?}: entry removed");
+        }
+    }
+}
+
+/// Create a test case which asserts that the syscall succeeds on an entry of a sticky directory
+/// only if the user owns the entry or the directory, and returns EACCES or EPERM otherwise.
+/// It takes the syscall, and optionally a function with the path as argument
+/// for syscalls requiring other arguments, followed by the file types to test.
+///
+/// ```ignore
+/// sticky_test_case!(unlink => [Regular, Fifo]);
+/// sticky_test_case!(rmdir => [Dir]);
+/// ```
+macro_rules! sticky_test_case {
+    ($syscall: ident $(, $f: expr)? => $file_types: tt) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+                " on an entry of a sticky directory succeeds only if the user owns",
+                " the entry or the directory, and returns EACCES or EPERM otherwise")]
+            sticky, serialized, root; $crate::test::root_privileges => $file_types
+        }
+        fn sticky(ctx: &mut crate::context::SerializedTestContext, ft: crate::context::FileType) {
+            $crate::tests::errors::sticky::assert_sticky_matrix(
+                ctx,
+                ft,
+                |_, _, _| (),
+                |_, path| sticky_test_case!(@call $syscall $(, $f)?)(path),
+            );
+        }
+    };
+
+    (@call $syscall: ident, $f: expr) => { $f };
+    (@call $syscall: ident) => { $syscall };
+}
+
+pub(crate) use sticky_test_case;
+
+/// Create test cases which assert that a syscall taking two paths succeeds
+/// on an entry of a sticky directory given as either path only if the user owns
+/// the entry or the directory, and returns EACCES or EPERM otherwise.
+/// The entry given as the other path is owned by the user,
+/// and is a new path for the first argument, or an entry of the same type for the second.
+///
+/// ```ignore
+/// sticky_either_test_case!(rename => [Regular, Dir]);
+/// ```
+macro_rules! sticky_either_test_case {
+    ($syscall: ident => $file_types: tt) => {
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+                " from an entry of a sticky directory succeeds only if the user owns",
+                " the entry or the directory, and returns EACCES or EPERM otherwise")]
+            sticky_from, serialized, root; $crate::test::root_privileges => $file_types
+        }
+        fn sticky_from(
+            ctx: &mut crate::context::SerializedTestContext,
+            ft: crate::context::FileType,
+        ) {
+            $crate::tests::errors::sticky::assert_sticky_matrix(
+                ctx,
+                ft,
+                |_, dir, _| dir.join("new"),
+                |new_path, path| $syscall(path, new_path.as_path()),
+            );
+        }
+
+        crate::test_case! {
+            #[doc = concat!(stringify!($syscall),
+                " to an existing entry of a sticky directory succeeds only if the user owns",
+                " the entry or the directory, and returns EACCES or EPERM otherwise")]
+            sticky_to, serialized, root; $crate::test::root_privileges => $file_types
+        }
+        fn sticky_to(
+            ctx: &mut crate::context::SerializedTestContext,
+            ft: crate::context::FileType,
+        ) {
+            $crate::tests::errors::sticky::assert_sticky_matrix(
+                ctx,
+                ft.clone(),
+                |ctx, dir, user| {
+                    $crate::tests::errors::sticky::create_owned_entry(
+                        ctx,
+                        dir,
+                        "source",
+                        ft.clone(),
+                        user,
+                    )
+                },
+                |source, path| $syscall(source.as_path(), path),
+            );
+        }
+    };
+}
+
+pub(crate) use sticky_either_test_case;
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use crate::{config::Config, context::TestContext};
+
+    use super::{Owner, StickyCase};
+
+    /// Run `f` with the context of a test in a temporary directory.
+    fn with_context(f: impl FnOnce(&TestContext)) {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default();
+        f(&TestContext::new(&config, &[], dir.path()));
+    }
+
+    #[test]
+    fn all_cases_distinct() {
+        for (i, case) in StickyCase::ALL.iter().enumerate() {
+            assert!(!StickyCase::ALL[i + 1..].contains(case));
+        }
+    }
+
+    #[test]
+    fn allowed() {
+        let allowed: Vec<_> = StickyCase::ALL
+            .into_iter()
+            .filter(|case| case.allowed())
+            .collect();
+
+        assert_eq!(allowed.len(), 3);
+        assert!(!allowed.contains(&StickyCase {
+            dir: Owner::Other,
+            entry: Owner::Other
+        }));
+    }
+
+    #[test]
+    fn assert_result_accepts_expected() {
+        with_context(|ctx| {
+            for case in StickyCase::ALL {
+                if case.allowed() {
+                    case.assert_result(ctx, Ok(()));
+                } else {
+                    case.assert_result(ctx, Err::<(), _>(Errno::EACCES));
+                    case.assert_result(ctx, Err::<(), _>(Errno::EPERM));
+                }
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_result_rejects_success() {
+        with_context(|ctx| {
+            StickyCase {
+                dir: Owner::Other,
+                entry: Owner::Other,
+            }
+            .assert_result(ctx, Ok(()));
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_result_rejects_other_errors() {
+        with_context(|ctx| {
+            StickyCase {
+                dir: Owner::Other,
+                entry: Owner::Other,
+            }
+            .assert_result(ctx, Err::<(), _>(Errno::ENOENT));
+        });
+    }
+
+    #[test]
+    #[should_panic]
+    fn assert_result_rejects_failure() {
+        with_context(|ctx| {
+            StickyCase {
+                dir: Owner::User,
+                entry: Owner::Other,
+            }
+            .assert_result(ctx, Err::<(), _>(Errno::EPERM));
+        });
+    }
+}
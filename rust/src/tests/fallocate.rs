@@ -0,0 +1,273 @@
+//! Tests for the native fallocate(2) modes of Linux, and fspacectl(2) on FreeBSD,
+//! which go beyond the allocation provided by posix_fallocate.
+
+use std::{
+    fs::{File, OpenOptions},
+    os::{fd::AsRawFd, unix::fs::FileExt},
+    path::PathBuf,
+};
+
+#[cfg(target_os = "linux")]
+use nix::{
+    errno::Errno,
+    fcntl::{fallocate, FallocateFlags},
+};
+use nix::{
+    libc::off_t,
+    sys::stat::{fstat, FileStat},
+};
+
+#[cfg(target_os = "linux")]
+use super::assert_errno;
+use crate::{
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    tests::assert_ctime_changed,
+    utils::write_blocks,
+};
+
+/// Number of blocks of the files used by the tests.
+const BLOCKS: u8 = 4;
+
+/// Create a regular file with [`BLOCKS`] blocks of data synced to the disk,
+/// each block being filled with its number (from 1), and return it with its block size.
+fn create_with_blocks(ctx: &TestContext) -> (PathBuf, File, usize) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .unwrap();
+    let block_size = write_blocks(&file, BLOCKS).unwrap() as usize;
+    file.sync_all().unwrap();
+
+    (path, file, block_size)
+}
+
+/// Return the content of a file, block by block.
+fn read_blocks(file: &File, block_size: usize) -> Vec<Vec<u8>> {
+    let size = file.metadata().unwrap().len() as usize;
+    let mut data = vec![0; size];
+    file.read_exact_at(&mut data, 0).unwrap();
+
+    data.chunks(block_size).map(<[u8]>::to_vec).collect()
+}
+
+/// Return the expected content of a file whose blocks have the given values.
+fn blocks(values: &[u8], block_size: usize) -> Vec<Vec<u8>> {
+    values.iter().map(|v| vec![*v; block_size]).collect()
+}
+
+/// Return the status of an open file.
+fn file_stat(file: &File) -> FileStat {
+    fstat(file.as_raw_fd()).unwrap()
+}
+
+/// Deallocate a range of a file, keeping its size.
+fn punch_hole(file: &File, offset: off_t, len: off_t) -> nix::Result<()> {
+    #[cfg(target_os = "linux")]
+    return fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE,
+        offset,
+        len,
+    );
+    #[cfg(target_os = "freebsd")]
+    return crate::utils::fspacectl_dealloc(file.as_raw_fd(), offset, len);
+}
+
+crate::test_case! {
+    /// Punching a hole deallocates the range, which reads back as zeroes,
+    /// without changing the size of the file
+    punch_hole_dealloc, FileSystemFeature::FallocatePunchHole
+}
+fn punch_hole_dealloc(ctx: &mut TestContext) {
+    let (_, file, block_size) = create_with_blocks(ctx);
+    let original_stat = file_stat(&file);
+
+    assert!(punch_hole(&file, block_size as off_t, 2 * block_size as off_t).is_ok());
+    file.sync_all().unwrap();
+
+    let new_stat = file_stat(&file);
+    assert_eq!(new_stat.st_size, original_stat.st_size);
+    assert!(new_stat.st_blocks < original_stat.st_blocks);
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 0, 0, 4], block_size)
+    );
+}
+
+crate::test_case! {
+    /// Punching a hole updates ctime
+    punch_hole_ctime, FileSystemFeature::FallocatePunchHole
+}
+fn punch_hole_ctime(ctx: &mut TestContext) {
+    let (path, file, block_size) = create_with_blocks(ctx);
+
+    assert_ctime_changed(ctx, &path, || {
+        assert!(punch_hole(&file, 0, block_size as off_t).is_ok());
+    });
+}
+
+#[cfg(target_os = "linux")]
+crate::test_case! {
+    /// fallocate returns EOPNOTSUPP when FALLOC_FL_PUNCH_HOLE is not combined with FALLOC_FL_KEEP_SIZE
+    punch_hole_without_keep_size, FileSystemFeature::FallocatePunchHole
+}
+#[cfg(target_os = "linux")]
+fn punch_hole_without_keep_size(ctx: &mut TestContext) {
+    let (_, file, block_size) = create_with_blocks(ctx);
+
+    assert_errno(
+        ctx,
+        fallocate(
+            file.as_raw_fd(),
+            FallocateFlags::FALLOC_FL_PUNCH_HOLE,
+            0,
+            block_size as off_t,
+        ),
+        Errno::EOPNOTSUPP,
+    );
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 2, 3, 4], block_size)
+    );
+}
+
+#[cfg(target_os = "linux")]
+crate::test_case! {
+    /// FALLOC_FL_ZERO_RANGE zeroes a range, and extends the file unless FALLOC_FL_KEEP_SIZE is given
+    zero_range, FileSystemFeature::FallocateZeroRange
+}
+#[cfg(target_os = "linux")]
+fn zero_range(ctx: &mut TestContext) {
+    let (_, file, block_size) = create_with_blocks(ctx);
+    let bs = block_size as off_t;
+
+    assert!(fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_ZERO_RANGE,
+        bs,
+        bs
+    )
+    .is_ok());
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 0, 3, 4], block_size)
+    );
+
+    let flags = FallocateFlags::FALLOC_FL_ZERO_RANGE | FallocateFlags::FALLOC_FL_KEEP_SIZE;
+    assert!(fallocate(file.as_raw_fd(), flags, 3 * bs, 2 * bs).is_ok());
+    assert_eq!(file_stat(&file).st_size, 4 * bs);
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 0, 3, 0], block_size)
+    );
+
+    assert!(fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_ZERO_RANGE,
+        4 * bs,
+        bs
+    )
+    .is_ok());
+    assert_eq!(file_stat(&file).st_size, 5 * bs);
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 0, 3, 0, 0], block_size)
+    );
+}
+
+#[cfg(target_os = "linux")]
+crate::test_case! {
+    /// FALLOC_FL_COLLAPSE_RANGE removes a block-aligned range and shifts the following data
+    collapse_range, FileSystemFeature::FallocateCollapseRange
+}
+#[cfg(target_os = "linux")]
+fn collapse_range(ctx: &mut TestContext) {
+    let (_, file, block_size) = create_with_blocks(ctx);
+    let bs = block_size as off_t;
+
+    assert!(fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_COLLAPSE_RANGE,
+        bs,
+        bs
+    )
+    .is_ok());
+    assert_eq!(file_stat(&file).st_size, 3 * bs);
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 3, 4], block_size)
+    );
+
+    // The range has to be aligned on the block size of the file system
+    assert_errno(
+        ctx,
+        fallocate(
+            file.as_raw_fd(),
+            FallocateFlags::FALLOC_FL_COLLAPSE_RANGE,
+            1,
+            bs,
+        ),
+        Errno::EINVAL,
+    );
+    // The range cannot reach the end of the file
+    assert_errno(
+        ctx,
+        fallocate(
+            file.as_raw_fd(),
+            FallocateFlags::FALLOC_FL_COLLAPSE_RANGE,
+            2 * bs,
+            bs,
+        ),
+        Errno::EINVAL,
+    );
+}
+
+#[cfg(target_os = "linux")]
+crate::test_case! {
+    /// FALLOC_FL_INSERT_RANGE inserts a block-aligned hole and shifts the following data
+    insert_range, FileSystemFeature::FallocateInsertRange
+}
+#[cfg(target_os = "linux")]
+fn insert_range(ctx: &mut TestContext) {
+    let (_, file, block_size) = create_with_blocks(ctx);
+    let bs = block_size as off_t;
+
+    assert!(fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_INSERT_RANGE,
+        bs,
+        bs
+    )
+    .is_ok());
+    assert_eq!(file_stat(&file).st_size, 5 * bs);
+    assert_eq!(
+        read_blocks(&file, block_size),
+        blocks(&[1, 0, 2, 3, 4], block_size)
+    );
+
+    // The range has to be aligned on the block size of the file system
+    assert_errno(
+        ctx,
+        fallocate(
+            file.as_raw_fd(),
+            FallocateFlags::FALLOC_FL_INSERT_RANGE,
+            1,
+            bs,
+        ),
+        Errno::EINVAL,
+    );
+    // The offset has to be within the file
+    assert_errno(
+        ctx,
+        fallocate(
+            file.as_raw_fd(),
+            FallocateFlags::FALLOC_FL_INSERT_RANGE,
+            5 * bs,
+            bs,
+        ),
+        Errno::EINVAL,
+    );
+}
@@ -0,0 +1,179 @@
+//! Tests for the descriptor and status flags of fcntl, and for F_DUPFD.
+
+use std::{
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::CommandExt,
+    },
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    sys::stat::Mode,
+    unistd::{dup2, lseek, read, write, Whence},
+};
+
+use super::assert_errno;
+use crate::{
+    context::{FileType, TestContext},
+    utils::open,
+};
+
+/// Return the status flags of the descriptor.
+pub(super) fn status_flags(fd: &OwnedFd) -> OFlag {
+    OFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFL).unwrap())
+}
+
+/// Return the descriptor flags of the descriptor.
+pub(super) fn fd_flags(fd: &OwnedFd) -> FdFlag {
+    FdFlag::from_bits_truncate(fcntl(fd.as_raw_fd(), FcntlArg::F_GETFD).unwrap())
+}
+
+/// Descriptor to which the shell of [`inherited_by_child`] writes,
+/// as it can only redirect to the descriptors 0 to 9.
+const CHILD_FD: RawFd = 9;
+
+/// Return whether a child process inherits the descriptor,
+/// by writing to the file through it from a shell.
+fn inherited_by_child(fd: &OwnedFd, path: &Path) -> bool {
+    let fd = fd.as_raw_fd();
+    std::fs::write(path, b"").unwrap();
+    lseek(fd, 0, Whence::SeekSet).unwrap();
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(format!("echo inherited >&{CHILD_FD}"))
+        .stderr(Stdio::null());
+    if fd != CHILD_FD {
+        // SAFETY: Only async-signal-safe functions are called between fork and exec
+        unsafe {
+            command.pre_exec(move || {
+                // The duplicate keeps the close-on-exec flag of the descriptor,
+                // which dup2 would clear otherwise
+                let flags = fcntl(fd, FcntlArg::F_GETFD)?;
+                dup2(fd, CHILD_FD)?;
+                fcntl(
+                    CHILD_FD,
+                    FcntlArg::F_SETFD(FdFlag::from_bits_truncate(flags)),
+                )?;
+                Ok(())
+            });
+        }
+    }
+    let status = command.status().unwrap();
+    let inherited = std::fs::read(path).unwrap() == b"inherited\n";
+    assert_eq!(status.success(), inherited);
+
+    inherited
+}
+
+crate::test_case! {
+    /// F_SETFL toggles O_APPEND and O_NONBLOCK, and F_GETFL reports them
+    setfl_toggle
+}
+fn setfl_toggle(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&path, b"data").unwrap();
+    let fd = open(&path, OFlag::O_WRONLY, Mode::empty()).unwrap();
+
+    for flag in [OFlag::O_APPEND, OFlag::O_NONBLOCK] {
+        assert!(!status_flags(&fd).contains(flag));
+
+        let flags = status_flags(&fd) | flag;
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags)).unwrap();
+        assert!(status_flags(&fd).contains(flag), "{flag:?} was not set");
+
+        let flags = status_flags(&fd) - flag;
+        fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(flags)).unwrap();
+        assert!(
+            !status_flags(&fd).contains(flag),
+            "{flag:?} was not cleared"
+        );
+    }
+
+    // The offset is at the beginning of the file, but writes append with O_APPEND
+    fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_APPEND)).unwrap();
+    assert_eq!(write(fd.as_fd(), b"more"), Ok(4));
+    assert_eq!(std::fs::read(&path).unwrap(), b"datamore");
+
+    fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::empty())).unwrap();
+    assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekSet), Ok(0));
+    assert_eq!(write(fd.as_fd(), b"DATA"), Ok(4));
+    assert_eq!(std::fs::read(&path).unwrap(), b"DATAmore");
+}
+
+crate::test_case! {
+    /// F_SETFL does not change the access mode of the descriptor
+    setfl_access_mode
+}
+fn setfl_access_mode(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&path, b"data").unwrap();
+    let fd = open(&path, OFlag::O_WRONLY, Mode::empty()).unwrap();
+
+    fcntl(
+        fd.as_raw_fd(),
+        FcntlArg::F_SETFL(OFlag::O_RDWR | OFlag::O_APPEND),
+    )
+    .unwrap();
+
+    let flags = status_flags(&fd);
+    assert!(flags.contains(OFlag::O_APPEND));
+    assert_eq!(flags & OFlag::O_ACCMODE, OFlag::O_WRONLY);
+    assert_errno(ctx, read(fd.as_raw_fd(), &mut [0; 4]), Errno::EBADF);
+}
+
+crate::test_case! {
+    /// F_DUPFD returns the lowest available descriptor greater than or equal to its argument,
+    /// which shares the offset and the status flags of the original descriptor
+    dupfd
+}
+fn dupfd(ctx: &mut TestContext) {
+    const MIN_FD: RawFd = 64;
+
+    let path = ctx.create(FileType::Regular).unwrap();
+    let fd = open(&path, OFlag::O_RDWR, Mode::empty()).unwrap();
+
+    let dup = fcntl(fd.as_raw_fd(), FcntlArg::F_DUPFD(MIN_FD)).unwrap();
+    // SAFETY: The descriptor has just been returned by F_DUPFD
+    let dup = unsafe { OwnedFd::from_raw_fd(dup) };
+    assert!(dup.as_raw_fd() >= MIN_FD);
+
+    assert_eq!(write(fd.as_fd(), b"data"), Ok(4));
+    assert_eq!(lseek(dup.as_raw_fd(), 0, Whence::SeekCur), Ok(4));
+    assert_eq!(lseek(dup.as_raw_fd(), 1, Whence::SeekSet), Ok(1));
+    assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekCur), Ok(1));
+
+    fcntl(fd.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_APPEND)).unwrap();
+    assert!(status_flags(&dup).contains(OFlag::O_APPEND));
+}
+
+crate::test_case! {
+    /// FD_CLOEXEC, set by open with O_CLOEXEC or by F_SETFD,
+    /// closes the descriptor in a spawned child process
+    cloexec
+}
+fn cloexec(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+
+    let fd = open(&path, OFlag::O_WRONLY, Mode::empty()).unwrap();
+    assert!(!fd_flags(&fd).contains(FdFlag::FD_CLOEXEC));
+    assert!(inherited_by_child(&fd, &path));
+
+    fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).unwrap();
+    assert!(fd_flags(&fd).contains(FdFlag::FD_CLOEXEC));
+    assert!(!inherited_by_child(&fd, &path));
+    drop(fd);
+
+    let fd = open(&path, OFlag::O_WRONLY | OFlag::O_CLOEXEC, Mode::empty()).unwrap();
+    assert!(fd_flags(&fd).contains(FdFlag::FD_CLOEXEC));
+    assert!(!inherited_by_child(&fd, &path));
+
+    fcntl(fd.as_raw_fd(), FcntlArg::F_SETFD(FdFlag::empty())).unwrap();
+    assert!(!fd_flags(&fd).contains(FdFlag::FD_CLOEXEC));
+    assert!(inherited_by_child(&fd, &path));
+}
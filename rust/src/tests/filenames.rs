@@ -0,0 +1,218 @@
+//! Tests for file names which contain other characters than the alphanumeric ones
+//! produced by the context (arbitrary bytes, trailing spaces and dots, multibyte sequences).
+
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    os::unix::{
+        ffi::{OsStrExt, OsStringExt},
+        fs::MetadataExt,
+    },
+    path::Path,
+};
+
+use nix::{errno::Errno, unistd::pathconf};
+
+use super::{assert_errno_for, entries};
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+};
+
+/// Guard to check that the file system has not been declared as accepting only UTF-8 names.
+fn non_utf8_names(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::Utf8OnlyNames)
+    {
+        anyhow::bail!("the file system has been declared as accepting only UTF-8 names")
+    }
+
+    Ok(())
+}
+
+/// Guard to check that `{NAME_MAX}` has not been declared as counting characters rather than bytes.
+fn name_max_counts_bytes(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::NameMaxCharacters)
+    {
+        anyhow::bail!("{{NAME_MAX}} has been declared as counting characters")
+    }
+
+    Ok(())
+}
+
+/// Return `{NAME_MAX}` for the test directory.
+fn name_max(ctx: &TestContext) -> usize {
+    pathconf(ctx.base_path(), nix::unistd::PathconfVar::NAME_MAX)
+        .unwrap()
+        .unwrap() as usize
+}
+
+/// Assert that a file named `name` can be created, looked up, listed,
+/// renamed and unlinked with its name kept byte for byte.
+fn assert_round_trip(dir: &Path, name: &OsStr) {
+    let path = dir.join(name);
+    File::create(&path).unwrap_or_else(|e| panic!("cannot create {name:?}: {e}"));
+    let ino = fs::symlink_metadata(&path).unwrap().ino();
+    assert_eq!(entries(dir), [name], "{name:?} has not been listed as is");
+
+    let mut new_name = name.to_owned();
+    new_name.push("~");
+    let new_path = dir.join(&new_name);
+    fs::rename(&path, &new_path).unwrap();
+    assert!(!path.exists(), "{name:?} still exists after rename");
+    assert_eq!(fs::symlink_metadata(&new_path).unwrap().ino(), ino);
+    assert_eq!(entries(dir), [new_name.as_os_str()]);
+
+    fs::remove_file(&new_path).unwrap();
+    assert!(
+        entries(dir).is_empty(),
+        "{new_name:?} has not been unlinked"
+    );
+}
+
+/// Return all the bytes which can be part of a name, that is all of them but NUL and '/'.
+fn name_bytes() -> impl Iterator<Item = u8> {
+    (1..=u8::MAX).filter(|b| *b != b'/')
+}
+
+crate::test_case! {
+    /// Names containing any byte but NUL and '/' round-trip through create, stat, readdir, rename and unlink
+    arbitrary_bytes; non_utf8_names
+}
+fn arbitrary_bytes(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    for b in name_bytes() {
+        assert_round_trip(&dir, OsStr::from_bytes(&[b, b'-', b]));
+    }
+
+    let all_bytes: Vec<_> = name_bytes().collect();
+    if all_bytes.len() < name_max(ctx) {
+        assert_round_trip(&dir, &OsString::from_vec(all_bytes));
+    }
+}
+
+crate::test_case! {
+    /// Creating a name which is not valid UTF-8 returns EILSEQ on a file system accepting only UTF-8 names
+    non_utf8_eilseq, FileSystemFeature::Utf8OnlyNames
+}
+fn non_utf8_eilseq(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    for name in [&b"\xff"[..], b"pjdfstest\xc3", b"\xc0\xaf"] {
+        assert_errno_for(
+            ctx,
+            ctx.new_file(FileType::Regular)
+                .name(dir.join(OsStr::from_bytes(name)))
+                .create(),
+            Errno::EILSEQ,
+            format_args!("{name:?}"),
+        );
+    }
+    assert!(entries(&dir).is_empty());
+}
+
+crate::test_case! {
+    /// Names with leading or trailing spaces and dots are kept as is
+    spaces_and_dots
+}
+fn spaces_and_dots(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    for name in [
+        "pjdfstest ",
+        "pjdfstest.",
+        "pjdfstest...",
+        "pjdfstest. .",
+        " pjdfstest",
+        "  ",
+        "...",
+        ". ",
+        " .",
+    ] {
+        assert_round_trip(&dir, OsStr::new(name));
+    }
+}
+
+crate::test_case! {
+    /// Names made of multibyte UTF-8 sequences up to {NAME_MAX} bytes are kept as is
+    long_multibyte
+}
+fn long_multibyte(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    // Keep one byte for the suffix added on rename
+    let max_len = name_max(ctx) - 1;
+
+    for c in ['\u{e9}', '\u{20ac}', '\u{1f600}'] {
+        let name = c.to_string().repeat(max_len / c.len_utf8());
+        assert_round_trip(&dir, OsStr::new(&name));
+    }
+}
+
+/// Multibyte characters of the Basic Multilingual Plane, which count as one character
+/// whether the file system counts Unicode code points or UTF-16 code units.
+const MULTIBYTE_CHARS: [char; 2] = ['\u{e9}', '\u{20ac}'];
+
+crate::test_case! {
+    /// Names of {NAME_MAX} multibyte characters are accepted when {NAME_MAX} counts characters, and longer ones return ENAMETOOLONG
+    name_max_characters, FileSystemFeature::NameMaxCharacters
+}
+fn name_max_characters(ctx: &mut TestContext) {
+    let name_max = name_max(ctx);
+
+    for c in MULTIBYTE_CHARS {
+        let file = ctx.create_name_max_chars(FileType::Regular, c).unwrap();
+        let name = file.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name.chars().count(), name_max);
+        assert!(name.len() > name_max);
+        assert_eq!(entries(ctx.base_path()).len(), 1);
+        fs::remove_file(&file).unwrap();
+
+        let too_long = c.to_string().repeat(name_max + 1);
+        assert_errno_for(
+            ctx,
+            ctx.new_file(FileType::Regular).name(too_long).create(),
+            Errno::ENAMETOOLONG,
+            format_args!("{c:?}"),
+        );
+    }
+}
+
+crate::test_case! {
+    /// Names of {NAME_MAX} multibyte characters return ENAMETOOLONG when {NAME_MAX} counts bytes, while names of {NAME_MAX} bytes are accepted
+    name_max_bytes; name_max_counts_bytes
+}
+fn name_max_bytes(ctx: &mut TestContext) {
+    let name_max = name_max(ctx);
+
+    for c in MULTIBYTE_CHARS {
+        assert_errno_for(
+            ctx,
+            ctx.create_name_max_chars(FileType::Regular, c),
+            Errno::ENAMETOOLONG,
+            format_args!("{c:?}"),
+        );
+
+        // Complete the multibyte characters with single bytes up to {NAME_MAX} bytes
+        let mut name = c.to_string().repeat(name_max / c.len_utf8());
+        name.push_str(&"x".repeat(name_max % c.len_utf8()));
+        assert_eq!(name.len(), name_max);
+        let path = ctx.base_path().join(&name);
+        File::create(&path).unwrap_or_else(|e| panic!("cannot create {name:?}: {e}"));
+        fs::remove_file(&path).unwrap();
+
+        name.push('x');
+        assert_errno_for(
+            ctx,
+            ctx.new_file(FileType::Regular).name(&name).create(),
+            Errno::ENAMETOOLONG,
+            format_args!("{c:?}"),
+        );
+    }
+}
@@ -1,20 +1,27 @@
-use std::{fs::File, io::Write};
+use std::{fs::File, io::Write, path::Path, process::Command};
 
-use nix::{
-    errno::Errno,
-    fcntl::OFlag,
-    sys::stat::{lstat, Mode},
-    unistd::ftruncate,
-};
-use rand::random;
+use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode, unistd::ftruncate};
 
 use crate::{
     context::FileType,
+    rng::random,
     test::{SerializedTestContext, TestContext},
     tests::{assert_ctime_changed, assert_ctime_unchanged},
-    utils::{chmod, open},
+    utils::{chmod, lstat, open},
+};
+
+use super::{
+    assert_errno,
+    errors::etxtbsy::{copy_executable, exec_mounted, RunningExecutable},
+    truncate::{assert_grow_zero_filled, large_files, LARGE_SIZES},
 };
 
+/// Grow the file with ftruncate, through a descriptor opened for writing.
+fn ftruncate_path(path: &Path, size: nix::libc::off_t) {
+    let file = open(path, OFlag::O_WRONLY, Mode::empty()).unwrap();
+    ftruncate(file, size).unwrap();
+}
+
 crate::test_case! {
     /// ftruncate should extend a file, and shrink a sparse file
     // ftruncate/00.t
@@ -36,6 +43,24 @@ fn extend_file_shrink_sparse(ctx: &mut TestContext) {
     assert_eq!(actual_size, size);
 }
 
+crate::test_case! {
+    /// ftruncate growing a file preserves its data, fills it with zeroes and updates mtime and ctime
+    grow_zero_filled
+}
+fn grow_zero_filled(ctx: &mut TestContext) {
+    assert_grow_zero_filled(ctx, 56789, ftruncate_path);
+}
+
+crate::test_case! {
+    /// ftruncate can grow a file beyond 2 GiB and 4 GiB
+    grow_large_offsets; large_files
+}
+fn grow_large_offsets(ctx: &mut TestContext) {
+    for size in LARGE_SIZES {
+        assert_grow_zero_filled(ctx, size, ftruncate_path);
+    }
+}
+
 crate::test_case! {
     /// ftruncate should shrink the file if the specified size is less than the actual one
     // ftruncate/00.t
@@ -84,7 +109,7 @@ fn unchanged_ctime_failed(ctx: &mut TestContext) {
     let (path, file) = ctx.create_file(OFlag::O_RDONLY, Some(0o644)).unwrap();
 
     assert_ctime_unchanged(ctx, &path, || {
-        assert_eq!(ftruncate(file, 123).unwrap_err(), Errno::EINVAL);
+        assert_errno(ctx, ftruncate(file, 123), Errno::EINVAL);
     });
 }
 
@@ -123,7 +148,33 @@ fn einval_negative_length(ctx: &mut TestContext) {
     let path = ctx.create(FileType::Regular).unwrap();
 
     let file = open(&path, OFlag::O_RDWR, Mode::empty()).unwrap();
-    assert_eq!(ftruncate(file, -1), Err(Errno::EINVAL));
+    assert_errno(ctx, ftruncate(file, -1), Errno::EINVAL);
     let file = open(&path, OFlag::O_WRONLY, Mode::empty()).unwrap();
-    assert_eq!(ftruncate(file, nix::libc::off_t::MIN), Err(Errno::EINVAL));
+    assert_errno(ctx, ftruncate(file, nix::libc::off_t::MIN), Errno::EINVAL);
+}
+
+crate::test_case! {
+    /// An executable which is running cannot be opened for writing to be truncated,
+    /// and a file opened for writing to be truncated cannot be executed,
+    /// both returning ETXTBSY
+    etxtbsy; exec_mounted
+}
+fn etxtbsy(ctx: &mut TestContext) {
+    let executable = RunningExecutable::spawn(ctx);
+    assert_errno(
+        ctx,
+        open(executable.path(), OFlag::O_WRONLY, Mode::empty()),
+        Errno::ETXTBSY,
+    );
+    drop(executable);
+
+    let path = copy_executable(ctx);
+    let file = open(&path, OFlag::O_WRONLY, Mode::empty()).unwrap();
+    ftruncate(&file, lstat(&path).unwrap().st_size).unwrap();
+
+    let res = Command::new(&path)
+        .arg("0")
+        .status()
+        .map_err(|err| Errno::from_raw(err.raw_os_error().unwrap_or_default()));
+    assert_errno(ctx, res, Errno::ETXTBSY);
 }
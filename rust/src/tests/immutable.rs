@@ -0,0 +1,167 @@
+//! Tests for the data path of files with an immutable flag (`UF_IMMUTABLE`/`SF_IMMUTABLE`).
+
+use std::{fs, os::fd::AsFd, path::Path};
+
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        stat::{utimensat, FileFlag, Mode, UtimensatFlags},
+        time::TimeSpec,
+        uio::pwrite,
+    },
+    unistd::chflags,
+};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::{FileFlags, FileSystemFeature},
+    utils::{lstat, open},
+};
+
+use super::assert_errno_for;
+use super::chflags::declared_flags;
+
+const IMMUTABLE_FLAGS: [FileFlags; 2] = [FileFlags::UF_IMMUTABLE, FileFlags::SF_IMMUTABLE];
+
+/// Guard to check that an immutable flag is supported.
+fn has_immutable_flags(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if declared_flags(&config.features, &IMMUTABLE_FLAGS).is_empty() {
+        anyhow::bail!("neither UF_IMMUTABLE nor SF_IMMUTABLE is declared in file_flags")
+    }
+
+    Ok(())
+}
+
+/// Call `f` for each supported immutable flag with a regular file containing data,
+/// after setting the flag on the file, and check that the file is writable again
+/// once the flag has been cleared.
+fn for_each_immutable_flag<F>(ctx: &TestContext, f: F)
+where
+    F: Fn(FileFlags, &Path),
+{
+    for flag in declared_flags(ctx.features_config(), &IMMUTABLE_FLAGS) {
+        let file = ctx.create(FileType::Regular).unwrap();
+        fs::write(&file, b"data").unwrap();
+        chflags(&file, flag.into()).unwrap();
+
+        f(flag, &file);
+
+        chflags(&file, FileFlag::empty()).unwrap();
+        assert!(
+            open(&file, OFlag::O_RDWR, Mode::empty()).is_ok(),
+            "{flag} has been cleared"
+        );
+    }
+}
+
+crate::test_case! {
+    /// open returns EPERM when opening an immutable file for writing, and succeeds once the flag is cleared
+    open_for_writing_eperm, root, FileSystemFeature::Chflags; has_immutable_flags
+}
+fn open_for_writing_eperm(ctx: &mut TestContext) {
+    for_each_immutable_flag(ctx, |flag, file| {
+        for oflag in [
+            OFlag::O_WRONLY,
+            OFlag::O_RDWR,
+            OFlag::O_WRONLY | OFlag::O_APPEND,
+            OFlag::O_WRONLY | OFlag::O_TRUNC,
+        ] {
+            assert_errno_for(
+                ctx,
+                open(file, oflag, Mode::empty()).map(drop),
+                Errno::EPERM,
+                format_args!("{oflag:?} with {flag}"),
+            );
+        }
+        assert!(open(file, OFlag::O_RDONLY, Mode::empty()).is_ok());
+        assert_eq!(fs::read(file).unwrap(), b"data");
+    });
+}
+
+crate::test_case! {
+    /// write through a descriptor opened before the immutable flag was set
+    /// either returns EPERM without modifying the file, or succeeds
+    write_preopened, root, FileSystemFeature::Chflags; has_immutable_flags
+}
+fn write_preopened(ctx: &mut TestContext) {
+    for flag in declared_flags(ctx.features_config(), &IMMUTABLE_FLAGS) {
+        let file = ctx.create(FileType::Regular).unwrap();
+        fs::write(&file, b"data").unwrap();
+        let fd = open(&file, OFlag::O_WRONLY, Mode::empty()).unwrap();
+        chflags(&file, flag.into()).unwrap();
+
+        // The flag is checked on open, and file systems differ on whether writes
+        // through existing descriptors are rejected (ZFS does, UFS does not)
+        match pwrite(fd.as_fd(), b"DATA", 0) {
+            Ok(n) => {
+                assert_eq!(n, 4);
+                assert_eq!(fs::read(&file).unwrap(), b"DATA");
+            }
+            res => {
+                assert_errno_for(ctx, res, Errno::EPERM, flag);
+                assert_eq!(fs::read(&file).unwrap(), b"data");
+            }
+        }
+
+        chflags(&file, FileFlag::empty()).unwrap();
+        assert_eq!(
+            pwrite(fd.as_fd(), b"new", 0),
+            Ok(3),
+            "{flag} has been cleared"
+        );
+    }
+}
+
+crate::test_case! {
+    /// utimensat returns EPERM on an immutable file, and succeeds once the flag is cleared
+    utimensat_eperm, root, FileSystemFeature::Chflags; has_immutable_flags
+}
+fn utimensat_eperm(ctx: &mut TestContext) {
+    let time = TimeSpec::new(1_900_000_000, 0);
+
+    for_each_immutable_flag(ctx, |flag, file| {
+        let original_stat = lstat(file).unwrap();
+        assert_errno_for(
+            ctx,
+            utimensat(None, file, &time, &time, UtimensatFlags::FollowSymlink),
+            Errno::EPERM,
+            format_args!("{flag}"),
+        );
+        let new_stat = lstat(file).unwrap();
+        assert_eq!(new_stat.st_atime, original_stat.st_atime);
+        assert_eq!(new_stat.st_mtime, original_stat.st_mtime);
+
+        chflags(file, FileFlag::empty()).unwrap();
+        assert!(utimensat(None, file, &time, &time, UtimensatFlags::FollowSymlink).is_ok());
+        assert_eq!(lstat(file).unwrap().st_mtime, time.tv_sec());
+        chflags(file, flag.into()).unwrap();
+    });
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+crate::test_case! {
+    /// Setting an extended attribute returns EPERM on an immutable file,
+    /// and succeeds once the flag is cleared
+    xattr_eperm, root, FileSystemFeature::Chflags, FileSystemFeature::UserXattrs; has_immutable_flags
+}
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn xattr_eperm(ctx: &mut TestContext) {
+    use crate::utils::set_user_xattr;
+
+    let name = c"pjdfstest";
+
+    for_each_immutable_flag(ctx, |flag, file| {
+        assert_errno_for(
+            ctx,
+            set_user_xattr(file, name, b"value"),
+            Errno::EPERM,
+            format_args!("{flag}"),
+        );
+
+        chflags(file, FileFlag::empty()).unwrap();
+        assert!(set_user_xattr(file, name, b"value").is_ok());
+        chflags(file, flag.into()).unwrap();
+    });
+}
@@ -0,0 +1,278 @@
+//! Tests for the interruption of blocking operations by a signal,
+//! which fail with EINTR, or are restarted if the handler has been installed with `SA_RESTART`.
+//!
+//! They are opt-in, as they install a handler for the whole process and take a few seconds,
+//! and enabled by `--interrupt-tests` or `interrupt_tests` in the `[settings]` section of the configuration.
+
+use std::{
+    os::fd::{AsRawFd, OwnedFd},
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, OFlag},
+    libc::{self, c_int},
+    sys::{
+        pthread::{pthread_kill, pthread_self, Pthread},
+        signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+        stat::Mode,
+        wait::waitpid,
+    },
+    unistd::{fork, pipe, read, write, ForkResult},
+};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    utils::open,
+};
+
+use super::assert_errno_for;
+
+/// Signal interrupting the operations.
+const SIGNAL: Signal = Signal::SIGUSR1;
+
+/// Interval between the signals sent to the blocked thread.
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of signals sent before the operation is released, when it is restarted.
+const SIGNALS: usize = 3;
+
+/// Time after which the operation is released, when it has not been interrupted.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of signals handled.
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn count_signal(_: c_int) {
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn interrupt_tests_enabled(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if !config.settings.interrupt_tests {
+        anyhow::bail!(
+            "Interruption tests are not enabled (--interrupt-tests or interrupt_tests in [settings])"
+        );
+    }
+
+    Ok(())
+}
+
+/// Execute the function with a handler counting the signals installed for [`SIGNAL`],
+/// with `SA_RESTART` if `restart` is set.
+/// The previous handler is restored afterwards, even if the function panics.
+fn with_handler<T, F: FnOnce() -> T>(restart: bool, f: F) -> T {
+    let flags = if restart {
+        SaFlags::SA_RESTART
+    } else {
+        SaFlags::empty()
+    };
+    let action = SigAction::new(SigHandler::Handler(count_signal), flags, SigSet::empty());
+    // SAFETY: The handler only updates an atomic counter, which is async-signal-safe
+    let previous = unsafe { sigaction(SIGNAL, &action) }.unwrap();
+    HANDLED.store(0, Ordering::SeqCst);
+
+    let res = catch_unwind(AssertUnwindSafe(f));
+
+    // SAFETY: The previous handler was installed by the process
+    unsafe { sigaction(SIGNAL, &previous) }.unwrap();
+    res.unwrap_or_else(|e| resume_unwind(e))
+}
+
+/// Execute the blocking operation while another thread sends [`SIGNAL`] to it at regular intervals,
+/// and return its result.
+/// The operation is released with `release` after [`SIGNALS`] signals if `restart` is set,
+/// after [`TIMEOUT`] if it is still blocked, or once it has returned.
+fn interrupt<T, B, R>(restart: bool, block: B, release: R) -> nix::Result<T>
+where
+    B: FnOnce() -> nix::Result<T>,
+    R: FnOnce() + Send,
+{
+    let release = Mutex::new(Some(release));
+    let release = || {
+        if let Some(release) = release.lock().unwrap().take() {
+            release()
+        }
+    };
+    let done = AtomicBool::new(false);
+    // pthread_t is a pointer on some platforms, which cannot be sent to another thread
+    let target = pthread_self() as usize;
+
+    let res = with_handler(restart, || {
+        thread::scope(|s| {
+            s.spawn(|| {
+                let start = Instant::now();
+                let mut sent = 0;
+                while !done.load(Ordering::SeqCst) {
+                    thread::sleep(INTERVAL);
+                    if done.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    pthread_kill(target as Pthread, SIGNAL).unwrap();
+                    sent += 1;
+                    if (restart && sent == SIGNALS) || start.elapsed() > TIMEOUT {
+                        release();
+                    }
+                }
+            });
+
+            // The signals have to stop even if the operation panics
+            let res = catch_unwind(AssertUnwindSafe(block));
+            done.store(true, Ordering::SeqCst);
+            res.unwrap_or_else(|e| resume_unwind(e))
+        })
+    });
+    release();
+
+    res
+}
+
+/// Assert that the operation fails with EINTR when interrupted by a signal,
+/// and is restarted until it succeeds with `SA_RESTART`.
+/// `setup` returns the blocking operation and what releases it.
+fn assert_interrupted<T, S, B, R>(ctx: &mut TestContext, mut setup: S)
+where
+    T: std::fmt::Debug,
+    S: FnMut(&mut TestContext) -> (B, R),
+    B: FnOnce() -> nix::Result<T>,
+    R: FnOnce() + Send,
+{
+    let (block, release) = setup(ctx);
+    let res = interrupt(false, block, release);
+    assert_errno_for(ctx, res, Errno::EINTR, "without SA_RESTART");
+    assert!(HANDLED.load(Ordering::SeqCst) > 0);
+
+    let (block, release) = setup(ctx);
+    let res = interrupt(true, block, release);
+    assert!(res.is_ok(), "with SA_RESTART: {res:?}");
+    assert!(
+        HANDLED.load(Ordering::SeqCst) >= SIGNALS,
+        "the operation completed before being interrupted"
+    );
+}
+
+crate::test_case! {
+    /// open of a FIFO blocked until a writer arrives is interrupted by a signal,
+    /// and restarted with SA_RESTART
+    interrupt_fifo_open; interrupt_tests_enabled
+}
+fn interrupt_fifo_open(ctx: &mut TestContext) {
+    let writer = Mutex::new(None);
+
+    assert_interrupted(ctx, |ctx| {
+        let fifo = ctx.create(FileType::Fifo).unwrap();
+        let block = {
+            let fifo = fifo.clone();
+            move || open(&fifo, OFlag::O_RDONLY, Mode::empty())
+        };
+        // The writer is kept open until the reader has returned
+        let writer = &writer;
+        let release = move || {
+            *writer.lock().unwrap() =
+                open(&fifo, OFlag::O_WRONLY | OFlag::O_NONBLOCK, Mode::empty()).ok()
+        };
+        (block, release)
+    });
+}
+
+/// Fill the FIFO until a write would block.
+fn fill(writer: &OwnedFd) {
+    fcntl(writer.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).unwrap();
+    for chunk in [4096, 1] {
+        while write(writer, &vec![0; chunk]).is_ok() {}
+    }
+    fcntl(writer.as_raw_fd(), FcntlArg::F_SETFL(OFlag::empty())).unwrap();
+}
+
+/// Read all the data of the FIFO.
+fn drain(reader: &OwnedFd) {
+    fcntl(reader.as_raw_fd(), FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).unwrap();
+    let mut buf = [0; 4096];
+    while matches!(read(reader.as_raw_fd(), &mut buf), Ok(1..)) {}
+}
+
+crate::test_case! {
+    /// write to a full FIFO is interrupted by a signal, and restarted with SA_RESTART
+    interrupt_fifo_write; interrupt_tests_enabled
+}
+fn interrupt_fifo_write(ctx: &mut TestContext) {
+    let readers = Mutex::new(vec![]);
+
+    assert_interrupted(ctx, |ctx| {
+        let fifo = ctx.create(FileType::Fifo).unwrap();
+        let (reader, writer) = ctx.fifo_pair(&fifo, TIMEOUT);
+        fill(&writer);
+
+        let block = move || write(&writer, &[0]);
+        // The reader is kept open until the writer has returned, which would otherwise get EPIPE
+        let readers = &readers;
+        let release = move || {
+            drain(&reader);
+            readers.lock().unwrap().push(reader);
+        };
+        (block, release)
+    });
+}
+
+/// Return a lock of the whole file for writing.
+fn write_lock() -> libc::flock {
+    // SAFETY: flock is a plain C struct, for which zeroes are valid
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    lock
+}
+
+crate::test_case! {
+    /// fcntl(F_SETLKW) waiting for a lock held by another process is interrupted by a signal,
+    /// and restarted with SA_RESTART
+    interrupt_fcntl_setlkw; interrupt_tests_enabled
+}
+fn interrupt_fcntl_setlkw(ctx: &mut TestContext) {
+    assert_interrupted(ctx, |ctx| {
+        let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+        let (locked_rx, locked_tx) = pipe().unwrap();
+        let (release_rx, release_tx) = pipe().unwrap();
+
+        // SAFETY: The runner is single-threaded when the tests are run,
+        // and the child only calls async-signal-safe functions before exiting
+        let child = match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                // The pipe has to be closed by the parent only to release the child
+                drop(release_tx);
+                let status = match fcntl(file.as_raw_fd(), FcntlArg::F_SETLK(&write_lock())) {
+                    Ok(_) => {
+                        let _ = write(&locked_tx, &[0]);
+                        let _ = read(release_rx.as_raw_fd(), &mut [0]);
+                        0
+                    }
+                    Err(_) => 1,
+                };
+                // SAFETY: Exit without running the destructors of the state shared with the parent
+                unsafe { libc::_exit(status) }
+            }
+            ForkResult::Parent { child } => child,
+        };
+        drop((locked_tx, release_rx));
+        assert_eq!(
+            read(locked_rx.as_raw_fd(), &mut [0]),
+            Ok(1),
+            "the child could not lock the file"
+        );
+
+        let block = move || fcntl(file.as_raw_fd(), FcntlArg::F_SETLKW(&write_lock()));
+        let release = move || {
+            drop(release_tx);
+            waitpid(child, None).unwrap();
+        };
+        (block, release)
+    });
+}
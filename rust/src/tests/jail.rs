@@ -0,0 +1,186 @@
+//! Privilege checks for the super-user inside a FreeBSD jail.
+//! The operations are allowed for the host super-user, but should fail with EPERM
+//! when executed by the jailed super-user with restricted privileges.
+
+use std::{
+    ffi::{OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    process::Command,
+};
+
+use ::jail::{param::Value, process::Jailed, RunningJail, StoppedJail};
+use nix::{
+    libc::fflags_t,
+    sys::stat::{FileFlag, Mode, SFlag},
+    unistd::chflags,
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    utils::{chown, lstat, mknod},
+};
+
+/// Start a jail rooted at `/` with the given parameters.
+/// The jail is set on the context, so it is destroyed during teardown.
+fn start_jail(ctx: &mut TestContext, name: &str, params: &[(&str, Value)]) -> RunningJail {
+    let jail = params.iter().fold(
+        StoppedJail::new("/").name(format!("pjdfstest_{name}")),
+        |jail, (param, value)| jail.param(*param, value.clone()),
+    );
+    let jail = jail.start().unwrap();
+    ctx.set_jail(jail);
+
+    jail
+}
+
+/// Execute the program inside the jail.
+/// Since this is a multithreaded application, we can't simply fork and call the syscall.
+/// Instead, we execute a child process to test the operation.
+fn run_in_jail<I, S>(jail: &RunningJail, program: &str, args: I) -> std::process::Output
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    Command::new(program)
+        .args(args)
+        .jail(jail)
+        .output()
+        .unwrap()
+}
+
+/// Assert that the program failed inside the jail with EPERM.
+fn assert_eperm(output: &std::process::Output) {
+    assert!(!output.status.success());
+    assert!(OsStr::from_bytes(&output.stderr)
+        .to_string_lossy()
+        .contains("Operation not permitted"));
+}
+
+crate::test_case! {
+    /// The jailed super-user cannot set system flags if allow.chflags is disabled
+    chflags_disallowed, root, FileSystemFeature::Chflags
+}
+fn chflags_disallowed(ctx: &mut TestContext) {
+    let jail = start_jail(
+        ctx,
+        "chflags_disallowed",
+        &[("allow.chflags", Value::Int(0))],
+    );
+
+    let file = ctx.create(FileType::Regular).unwrap();
+    let output = run_in_jail(
+        &jail,
+        "/bin/chflags",
+        [OsStr::new("schg"), file.as_os_str()],
+    );
+    assert_eperm(&output);
+    assert_eq!(lstat(&file).unwrap().st_flags, 0);
+
+    // The host super-user can
+    chflags(&file, FileFlag::SF_IMMUTABLE).unwrap();
+    chflags(&file, FileFlag::empty()).unwrap();
+}
+
+crate::test_case! {
+    /// The jailed super-user can set system flags on a file which has none,
+    /// but can only change them once they are set if the securelevel is not greater than 0
+    securelevel_variations, root, FileSystemFeature::Chflags
+}
+fn securelevel_variations(ctx: &mut TestContext) {
+    for securelevel in [0, 1, 2] {
+        let jail = start_jail(
+            ctx,
+            &format!("securelevel_{securelevel}"),
+            &[
+                ("allow.chflags", Value::Int(1)),
+                ("securelevel", Value::Int(securelevel)),
+            ],
+        );
+
+        // Setting a system flag on a file without any is allowed at every securelevel
+        let file = ctx.create(FileType::Regular).unwrap();
+        let output = run_in_jail(
+            &jail,
+            "/bin/chflags",
+            [OsStr::new("sappnd"), file.as_os_str()],
+        );
+        assert!(output.status.success());
+        assert_eq!(
+            lstat(&file).unwrap().st_flags,
+            FileFlag::SF_APPEND.bits() as fflags_t
+        );
+        chflags(&file, FileFlag::empty()).unwrap();
+
+        // Once a system flag has been set by the host, it can neither be cleared nor changed
+        for flags in ["nosappnd", "schg"] {
+            chflags(&file, FileFlag::SF_APPEND).unwrap();
+            let output = run_in_jail(&jail, "/bin/chflags", [OsStr::new(flags), file.as_os_str()]);
+            if securelevel > 0 {
+                assert_eperm(&output);
+                assert_eq!(
+                    lstat(&file).unwrap().st_flags,
+                    FileFlag::SF_APPEND.bits() as fflags_t
+                );
+            } else {
+                assert!(output.status.success());
+            }
+            chflags(&file, FileFlag::empty()).unwrap();
+        }
+
+        jail.kill().unwrap();
+    }
+}
+
+crate::test_case! {
+    /// The jailed super-user cannot change the owner of a file if allow.suser is disabled
+    chown_unprivileged_root, root
+}
+fn chown_unprivileged_root(ctx: &mut TestContext) {
+    let jail = start_jail(
+        ctx,
+        "chown_unprivileged_root",
+        &[("allow.suser", Value::Int(0))],
+    );
+    let user = ctx.get_new_user();
+
+    let file = ctx.create(FileType::Regular).unwrap();
+    let output = run_in_jail(
+        &jail,
+        "/usr/sbin/chown",
+        [
+            OsString::from(user.uid.to_string()),
+            file.clone().into_os_string(),
+        ],
+    );
+    assert_eperm(&output);
+    assert_eq!(lstat(&file).unwrap().st_uid, 0);
+
+    // The host super-user can
+    chown(&file, Some(user.uid), None).unwrap();
+}
+
+crate::test_case! {
+    /// The jailed super-user cannot create device nodes
+    mknod_device, root
+}
+fn mknod_device(ctx: &mut TestContext) {
+    let jail = start_jail(ctx, "mknod_device", &[]);
+
+    let path = ctx.gen_path();
+    let output = run_in_jail(
+        &jail,
+        "/sbin/mknod",
+        [
+            path.as_os_str(),
+            OsStr::new("c"),
+            OsStr::new("0"),
+            OsStr::new("0"),
+        ],
+    );
+    assert_eperm(&output);
+    assert!(lstat(&path).is_err());
+
+    // The host super-user can
+    mknod(&path, SFlag::S_IFCHR, Mode::from_bits_truncate(0o644), 0).unwrap();
+}
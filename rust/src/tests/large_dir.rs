@@ -0,0 +1,96 @@
+//! Tests for directories with tens of thousands of entries,
+//! which exercise the code paths of the file systems for large directories
+//! (e.g. hashed or B-tree indexes, readdir cookies spanning several blocks).
+//!
+//! They are opt-in because of their runtime, and enabled by `--large-tests`
+//! or `large_tests` in the `[settings]` section of the configuration.
+
+use std::{collections::HashMap, fs::read_dir, path::Path};
+
+use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+use rand::Rng;
+
+use super::assert_errno;
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    rng::with_rng,
+    space,
+    utils::{lstat, open, rmdir, unlink},
+};
+
+/// Number of entries of the large directory.
+const ENTRIES: usize = 50_000;
+
+/// Number of entries looked up at random.
+const LOOKUPS: usize = 1000;
+
+/// Estimate of the space used by an entry, its inode and its name taking at most a block each.
+const ENTRY_SPACE: u64 = 2 * 4096;
+
+fn large_tests_enabled(config: &Config, base_path: &Path) -> anyhow::Result<()> {
+    if !config.settings.large_tests {
+        anyhow::bail!("Large tests are not enabled (--large-tests or large_tests in [settings])");
+    }
+
+    space::require(config, base_path, ENTRIES as u64 * ENTRY_SPACE)
+}
+
+/// Return the name of the entry with the given number.
+fn entry_name(n: usize) -> String {
+    format!("entry{n}")
+}
+
+crate::test_case! {
+    /// readdir returns each entry of a directory with tens of thousands of entries exactly once,
+    /// the entries can be looked up, and the directory can be removed once they have been unlinked
+    large_directory; large_tests_enabled
+}
+fn large_directory(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    for n in 0..ENTRIES {
+        open(
+            &dir.join(entry_name(n)),
+            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+            Mode::from_bits_truncate(0o644),
+        )
+        .unwrap_or_else(|e| panic!("cannot create {}: {e}", entry_name(n)));
+    }
+
+    let mut listed: HashMap<String, usize> = HashMap::with_capacity(ENTRIES);
+    for entry in read_dir(&dir).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        *listed.entry(name).or_default() += 1;
+    }
+    let duplicated: Vec<_> = listed.iter().filter(|(_, count)| **count > 1).collect();
+    assert!(
+        duplicated.is_empty(),
+        "entries listed several times: {duplicated:?}"
+    );
+    let missing: Vec<_> = (0..ENTRIES)
+        .map(entry_name)
+        .filter(|name| !listed.contains_key(name))
+        .collect();
+    assert!(missing.is_empty(), "entries not listed: {missing:?}");
+    assert_eq!(listed.len(), ENTRIES, "unknown entries have been listed");
+
+    for _ in 0..LOOKUPS {
+        let n = with_rng(|rng| rng.gen_range(0..ENTRIES));
+        lstat(&dir.join(entry_name(n)))
+            .unwrap_or_else(|e| panic!("cannot look up {}: {e}", entry_name(n)));
+        // A name which sorts among the entries but does not exist
+        assert_errno(
+            ctx,
+            lstat(&dir.join(format!("{}~", entry_name(n)))).map(drop),
+            Errno::ENOENT,
+        );
+    }
+
+    for n in 0..ENTRIES {
+        unlink(&dir.join(entry_name(n)))
+            .unwrap_or_else(|e| panic!("cannot unlink {}: {e}", entry_name(n)));
+    }
+    assert_eq!(read_dir(&dir).unwrap().count(), 0);
+    rmdir(&dir).unwrap();
+}
@@ -0,0 +1,87 @@
+//! Tests for lchmod, which changes the mode of a symlink itself.
+
+use nix::sys::stat::Mode;
+
+use crate::{
+    context::FileType,
+    test::TestContext,
+    utils::{lchmod, lstat, stat, ALLPERMS},
+};
+
+use super::{
+    assert_times_changed, assert_times_unchanged,
+    errors::{
+        efault::efault_path_test_case,
+        eloop::eloop_comp_test_case,
+        enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case},
+        enoent::{enoent_comp_test_case, enoent_named_file_test_case},
+        enotdir::enotdir_comp_test_case,
+        erofs::erofs_named_test_case,
+    },
+    CTIME,
+};
+
+// chmod/01.t
+enotdir_comp_test_case!(lchmod(~path, Mode::empty()));
+
+// chmod/04.t
+enoent_named_file_test_case!(lchmod(~path, Mode::empty()));
+enoent_comp_test_case!(lchmod(~path, Mode::empty()));
+
+// chmod/06.t#L25
+eloop_comp_test_case!(lchmod(~path, Mode::empty()));
+
+enametoolong_comp_test_case!(lchmod(~path, Mode::empty()));
+enametoolong_path_test_case!(lchmod(~path, Mode::empty()));
+
+// chmod/09.t
+erofs_named_test_case!(lchmod(~path, Mode::empty()));
+
+// chmod/10.t
+// TODO: lchmod is missing in libc
+efault_path_test_case!(lchmod, |ptr| nix::libc::fchmodat(
+    0,
+    ptr,
+    0,
+    nix::libc::AT_SYMLINK_NOFOLLOW
+));
+
+crate::test_case! {
+    /// lchmod changes the mode of the symlink but not of its target
+    // chmod/00.t
+    change_link_only => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn change_link_only(ctx: &mut TestContext, ft: FileType) {
+    let target = ctx.create(ft).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(target.clone()))).unwrap();
+    let target_mode = stat(&target).unwrap().st_mode;
+    let expected_mode = Mode::from_bits_truncate(0o321);
+
+    lchmod(&link, expected_mode).unwrap();
+
+    let link_mode = lstat(&link).unwrap().st_mode;
+    assert_eq!(link_mode & ALLPERMS, expected_mode.bits());
+    assert_eq!(stat(&target).unwrap().st_mode, target_mode);
+}
+
+crate::test_case! {
+    /// lchmod updates the ctime of the symlink but not of its target
+    // chmod/00.t
+    update_link_ctime
+}
+fn update_link_ctime(ctx: &mut TestContext) {
+    let target = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(target.clone()))).unwrap();
+
+    assert_times_changed()
+        .path(&link, CTIME)
+        .execute(ctx, true, || {
+            lchmod(&link, Mode::from_bits_truncate(0o111)).unwrap();
+        });
+
+    assert_times_unchanged()
+        .path(&target, CTIME)
+        .execute(ctx, false, || {
+            lchmod(&link, Mode::from_bits_truncate(0o222)).unwrap();
+        });
+}
@@ -0,0 +1,171 @@
+use std::path::Path;
+
+use nix::{
+    errno::Errno,
+    unistd::{Gid, Uid},
+};
+
+use crate::{
+    context::{FileType, SerializedTestContext, TestContext},
+    test::root_privileges,
+    utils::{lchown, lstat, stat},
+};
+
+use super::chown::chown_restricted;
+use super::errors::efault::efault_path_test_case;
+use super::errors::eloop::{eloop_comp_test_case, eloop_symloop_max_test_case};
+use super::errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case};
+use super::errors::enoent::{enoent_comp_test_case, enoent_named_file_test_case};
+use super::errors::enotdir::enotdir_comp_test_case;
+use super::errors::erofs::erofs_named_test_case;
+use super::{assert_errno, assert_times_changed, assert_times_unchanged, CTIME};
+
+fn lchown_wrapper<P: AsRef<Path>>(ctx: &mut TestContext, path: P) -> nix::Result<()> {
+    let path = path.as_ref();
+    let user = ctx.get_new_user();
+    lchown(path, Some(user.uid), Some(user.gid))
+}
+
+// chown/01.t
+enotdir_comp_test_case!(lchown, lchown_wrapper);
+
+// chown/04.t
+enoent_named_file_test_case!(lchown, lchown_wrapper);
+enoent_comp_test_case!(lchown, lchown_wrapper);
+
+// chown/06.t#L25
+eloop_comp_test_case!(lchown, lchown_wrapper);
+eloop_symloop_max_test_case!(lchown, lchown_wrapper);
+
+// chown/02.t
+enametoolong_comp_test_case!(lchown, lchown_wrapper);
+
+// chown/03.t
+enametoolong_path_test_case!(lchown, lchown_wrapper);
+
+// chown/09.t
+erofs_named_test_case!(lchown, lchown_wrapper);
+
+// chown/10.t
+efault_path_test_case!(lchown, |ptr| nix::libc::lchown(ptr, 0, 0));
+
+crate::test_case! {
+    /// lchown changes the ownership of the symlink but not of its target
+    // chown/00.t
+    change_link_only, root; root_privileges => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn change_link_only(ctx: &mut TestContext, ft: FileType) {
+    let target = ctx.create(ft).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(target.clone()))).unwrap();
+    let target_stat = stat(&target).unwrap();
+    let user = ctx.get_new_user();
+
+    lchown(&link, Some(user.uid), Some(user.gid)).unwrap();
+
+    let link_stat = lstat(&link).unwrap();
+    assert_eq!(link_stat.st_uid, user.uid.as_raw());
+    assert_eq!(link_stat.st_gid, user.gid.as_raw());
+
+    let new_target_stat = stat(&target).unwrap();
+    assert_eq!(new_target_stat.st_uid, target_stat.st_uid);
+    assert_eq!(new_target_stat.st_gid, target_stat.st_gid);
+}
+
+crate::test_case! {
+    /// lchown updates the ctime of the symlink but not of its target
+    // chown/00.t
+    update_link_ctime, root; root_privileges
+}
+fn update_link_ctime(ctx: &mut TestContext) {
+    let target = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(target.clone()))).unwrap();
+    let user = ctx.get_new_user();
+
+    assert_times_changed()
+        .path(&link, CTIME)
+        .execute(ctx, true, || {
+            lchown(&link, Some(user.uid), Some(user.gid)).unwrap();
+        });
+
+    let user = ctx.get_new_user();
+    assert_times_unchanged()
+        .path(&target, CTIME)
+        .execute(ctx, false, || {
+            lchown(&link, Some(user.uid), Some(user.gid)).unwrap();
+        });
+}
+
+crate::test_case! {
+    /// lchown clears the SUID/SGID bits of a regular file
+    /// when called successfully by a non-privileged user
+    // chown/00.t
+    clear_isuid_isgid, serialized, root; root_privileges
+}
+fn clear_isuid_isgid(ctx: &mut SerializedTestContext) {
+    let (user, group) = ctx.get_new_entry();
+    let other_group = ctx.get_new_group();
+
+    let file = ctx
+        .new_file(FileType::Regular)
+        .mode(0o6555)
+        .owner(user.uid, group.gid)
+        .create()
+        .unwrap();
+
+    ctx.as_user(user, Some(&[group.gid, other_group.gid]), || {
+        lchown(&file, None, Some(other_group.gid)).unwrap();
+    });
+
+    let file_stat = lstat(&file).unwrap();
+    assert_eq!(file_stat.st_mode & 0o7777, 0o555);
+    assert_eq!(file_stat.st_uid, user.uid.as_raw());
+    assert_eq!(file_stat.st_gid, other_group.gid.as_raw());
+}
+
+crate::test_case! {
+    /// lchown returns EPERM if a non-privileged user tries to give away the symlink,
+    /// to change its group to a group it is not a member of,
+    /// or to change the ownership of a symlink it doesn't own
+    // chown/05.t
+    eperm_restricted, serialized, root; chown_restricted, root_privileges
+}
+fn eperm_restricted(ctx: &mut SerializedTestContext) {
+    let (user, group) = ctx.get_new_entry();
+    let (other_user, other_group) = ctx.get_new_entry();
+
+    let link = ctx
+        .new_file(FileType::Symlink(None))
+        .owner(user.uid, group.gid)
+        .create()
+        .unwrap();
+    let snapshot = ctx.snapshot();
+
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, lchown(&link, Some(other_user.uid), None), Errno::EPERM);
+        assert_errno(
+            ctx,
+            lchown(&link, None, Some(other_group.gid)),
+            Errno::EPERM,
+        );
+    });
+
+    ctx.as_user(other_user, None, || {
+        assert_errno(
+            ctx,
+            lchown(&link, None, Some(other_group.gid)),
+            Errno::EPERM,
+        );
+    });
+
+    let link_stat = lstat(&link).unwrap();
+    assert_eq!(Uid::from_raw(link_stat.st_uid), user.uid);
+    assert_eq!(Gid::from_raw(link_stat.st_gid), group.gid);
+    snapshot.verify_unchanged();
+
+    // The owner can still change the group to one it belongs to
+    ctx.as_user(user, Some(&[group.gid, other_group.gid]), || {
+        lchown(&link, Some(user.uid), Some(other_group.gid)).unwrap();
+    });
+    let link_stat = lstat(&link).unwrap();
+    assert_eq!(Gid::from_raw(link_stat.st_gid), other_group.gid);
+}
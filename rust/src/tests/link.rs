@@ -1,30 +1,32 @@
-use nix::{
-    errno::Errno,
-    sys::stat::{lstat, Mode},
-    unistd::pathconf,
-    unistd::{chown, unlink},
-};
+use nix::{errno::Errno, fcntl::AtFlags, sys::stat::Mode, unistd::linkat, unistd::pathconf};
 
 use std::path::Path;
 
 use super::{
+    assert_errno, assert_errno_choice,
     errors::{
         efault::efault_either_test_case,
         eloop::eloop_either_test_case,
         enametoolong::{enametoolong_either_comp_test_case, enametoolong_either_path_test_case},
         erofs::erofs_named_test_case,
-        exdev::exdev_target_test_case,
+        exdev::{exdev_dirfd_test_case, exdev_target_test_case, secondary_fs_available},
     },
+    mksyscalls::create_sgid_dir,
     CTIME, MTIME,
 };
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    test::root_privileges,
+    utils::{chown, lstat, unlink},
+};
 use crate::{
     context::{FileType, SerializedTestContext, TestContext},
     tests::{
         assert_times_changed, assert_times_unchanged,
         errors::enoent::enoent_either_named_file_test_case,
-        errors::enotdir::enotdir_comp_either_test_case, AsTimeInvariant,
+        errors::enotdir::{enotdir_comp_either_test_case, enotdir_trailing_slash_test_case},
+        AsTimeInvariant,
     },
     utils::{chmod, link},
 };
@@ -32,7 +34,7 @@ use crate::{
 crate::test_case! {
     /// link creates hardlinks which share the same metadata
     // link/00.t#23-41
-    share_metadata, root => [Regular, Fifo, Block, Char, Socket]
+    share_metadata, root; root_privileges => [Regular, Fifo, Block, Char, Socket]
 }
 fn share_metadata(ctx: &mut TestContext, ft: FileType) {
     let file = ctx.create(ft).unwrap();
@@ -85,6 +87,31 @@ fn share_metadata(ctx: &mut TestContext, ft: FileType) {
     );
 }
 
+crate::test_case! {
+    /// A hard link created in a directory with the SGID bit set
+    /// keeps the group ID of the linked file
+    sgid_dir_keeps_gid, serialized, root; root_privileges
+}
+fn sgid_dir_keeps_gid(ctx: &mut SerializedTestContext) {
+    let (user, group) = ctx.get_new_entry();
+    let dir_group = ctx.get_new_group();
+    let dir = create_sgid_dir(ctx, dir_group.gid);
+
+    let file = ctx
+        .new_file(FileType::Regular)
+        .owner(user.uid, group.gid)
+        .create()
+        .unwrap();
+
+    let path = dir.join("link");
+    ctx.as_user(user, None, || {
+        link(&*file, &*path).unwrap();
+    });
+
+    let link_stat = lstat(&path).unwrap();
+    assert_eq!(link_stat.st_gid, group.gid.as_raw());
+}
+
 crate::test_case! {
     /// Removing a link should only change the number of links
     // link/00.t
@@ -150,10 +177,7 @@ fn unchanged_ctime_fails(ctx: &mut SerializedTestContext, ft: FileType) {
         .path(ctx.base_path(), CTIME | MTIME)
         .execute(ctx, false, || {
             ctx.as_user(user, None, || {
-                assert!(matches!(
-                    link(&file, &new_path),
-                    Err(Errno::EPERM | Errno::EACCES)
-                ));
+                assert_errno_choice(ctx, link(&file, &new_path), &[Errno::EPERM, Errno::EACCES]);
             })
         });
 }
@@ -161,11 +185,15 @@ fn unchanged_ctime_fails(ctx: &mut SerializedTestContext, ft: FileType) {
 // link/01.t
 enotdir_comp_either_test_case!(link);
 
+enotdir_trailing_slash_test_case!(link, |ctx: &mut TestContext, path: &Path| {
+    link(path, &*ctx.gen_path())
+});
+
 const LINK_MAX_LIMIT: i64 = 65535;
 
 // BUG: Some systems return bogus value, and testing directories
 // might give different result than trying directly on the file
-fn has_reasonable_link_max(_: &Config, base_path: &Path) -> anyhow::Result<()> {
+pub(super) fn has_reasonable_link_max(_: &Config, base_path: &Path) -> anyhow::Result<()> {
     let link_max = pathconf(base_path, nix::unistd::PathconfVar::LINK_MAX)?
         .ok_or_else(|| anyhow::anyhow!("Failed to get LINK_MAX value"))?;
 
@@ -199,7 +227,7 @@ fn link_count_max(ctx: &mut TestContext) {
         link(&file, &ctx.gen_path()).unwrap();
     }
 
-    assert_eq!(link(&file, &ctx.gen_path()), Err(Errno::EMLINK));
+    assert_errno(ctx, link(&file, &ctx.gen_path()), Errno::EMLINK);
 }
 
 // link/02.t
@@ -229,7 +257,7 @@ fn enoent_source_not_exists(ctx: &mut TestContext) {
     let source = ctx.gen_path();
     let dest = ctx.gen_path();
 
-    assert_eq!(link(&source, &dest), Err(Errno::ENOENT));
+    assert_errno(ctx, link(&source, &dest), Errno::ENOENT);
 }
 
 crate::test_case! {
@@ -241,11 +269,43 @@ fn eexist_dest_exists(ctx: &mut TestContext, ft: FileType) {
     let path = ctx.create(ft).unwrap();
     let regular_file = ctx.create(FileType::Regular).unwrap();
 
-    assert_eq!(link(&regular_file, &path), Err(nix::errno::Errno::EEXIST));
+    assert_errno(ctx, link(&regular_file, &path), nix::errno::Errno::EEXIST);
 }
 
 // link/14.t
 exdev_target_test_case!(link);
 
+exdev_dirfd_test_case!(linkat, |from_fd, from, to_fd, to| {
+    linkat(Some(from_fd), from, Some(to_fd), to, AtFlags::empty())
+});
+
+crate::test_case! {
+    /// linkat with AT_SYMLINK_FOLLOW returns EXDEV when the target of the symlink
+    /// is on a different file-system, while the symlink itself can be linked
+    exdev_symlink_target_follow; secondary_fs_available
+}
+fn exdev_symlink_target_follow(ctx: &mut TestContext) {
+    let secondary_fs = ctx.features_config().secondary_fs.clone().unwrap();
+    let target = tempfile::NamedTempFile::new_in(secondary_fs).unwrap();
+    let symlink = ctx
+        .create(FileType::Symlink(Some(target.path().to_path_buf())))
+        .unwrap();
+    let new_path = ctx.gen_path();
+
+    assert_errno(
+        ctx,
+        linkat(None, &symlink, None, &new_path, AtFlags::AT_SYMLINK_FOLLOW),
+        Errno::EXDEV,
+    );
+    assert!(std::fs::symlink_metadata(&new_path).is_err());
+
+    linkat(None, &symlink, None, &new_path, AtFlags::empty()).unwrap();
+    assert!(std::fs::symlink_metadata(&new_path).unwrap().is_symlink());
+    assert_eq!(
+        lstat(&new_path).unwrap().st_ino,
+        lstat(&symlink).unwrap().st_ino
+    );
+}
+
 // link/17.t
 efault_either_test_case!(link, nix::libc::link);
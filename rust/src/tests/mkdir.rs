@@ -1,8 +1,16 @@
 use std::fs::FileType;
 
-use nix::{sys::stat::Mode, unistd::mkdir};
+use nix::{
+    errno::Errno,
+    sys::stat::{mkdirat, Mode},
+    unistd::{pathconf, PathconfVar},
+};
 
-use crate::context::{SerializedTestContext, TestContext};
+use crate::{
+    context::{SerializedTestContext, TestContext},
+    test::root_privileges,
+    utils::{lstat, mkdir},
+};
 
 use super::errors::eexist::eexist_file_exists_test_case;
 use super::errors::efault::efault_path_test_case;
@@ -10,8 +18,15 @@ use super::errors::eloop::eloop_comp_test_case;
 use super::errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case};
 use super::errors::enoent::enoent_comp_test_case;
 use super::errors::erofs::erofs_new_file_test_case;
-use super::mksyscalls::{assert_perms_from_mode_and_umask, assert_uid_gid};
-use super::{assert_times_changed, errors::enotdir::enotdir_comp_test_case, ATIME, CTIME, MTIME};
+use super::link::has_reasonable_link_max;
+use super::mksyscalls::{
+    assert_dirfd_errors, assert_perms_from_mode_and_umask, assert_sgid_dir_inheritance,
+    assert_uid_gid, with_cwd, AtDirFd,
+};
+use super::{
+    assert_errno, assert_times_changed, errors::enotdir::enotdir_comp_test_case, ATIME, CTIME,
+    MTIME,
+};
 
 crate::test_case! {
     /// POSIX: The file permission bits of the new directory shall be initialized from
@@ -24,17 +39,79 @@ fn permission_bits_from_mode(ctx: &mut SerializedTestContext) {
     assert_perms_from_mode_and_umask(ctx, mkdir, FileType::is_dir);
 }
 
+crate::test_case! {
+    /// The file permission bits of the new directory created with mkdirat are
+    /// initialized from mode, modified by the process' file creation mask,
+    /// with either a directory file descriptor or AT_FDCWD
+    permission_bits_from_mode_at, serialized
+}
+fn permission_bits_from_mode_at(ctx: &mut SerializedTestContext) {
+    for dirfd in AtDirFd::ALL {
+        assert_perms_from_mode_and_umask(
+            ctx,
+            |path, mode| dirfd.call(path, |fd, name| mkdirat(fd, name, mode)),
+            FileType::is_dir,
+        );
+    }
+}
+
+crate::test_case! {
+    /// The owner and group of the new directory created with mkdirat
+    /// and a directory file descriptor are set as with mkdir
+    uid_gid_eq_euid_egid_at, serialized, root; root_privileges
+}
+fn uid_gid_eq_euid_egid_at(ctx: &mut SerializedTestContext) {
+    assert_uid_gid(ctx, |path, mode| {
+        AtDirFd::Parent.call(path, |fd, name| mkdirat(fd, name, mode))
+    });
+}
+
+crate::test_case! {
+    /// The owner and group of the new directory created with mkdirat
+    /// and AT_FDCWD are set as with mkdir
+    uid_gid_eq_euid_egid_at_fdcwd, serialized, root; root_privileges
+}
+fn uid_gid_eq_euid_egid_at_fdcwd(ctx: &mut SerializedTestContext) {
+    // The working directory of the runner might not be searchable by the other users
+    let base_path = ctx.base_path().to_path_buf();
+    with_cwd(&base_path, || {
+        assert_uid_gid(ctx, |path, mode| {
+            AtDirFd::Cwd.call(path, |fd, name| mkdirat(fd, name, mode))
+        })
+    });
+}
+
+crate::test_case! {
+    /// mkdirat returns EBADF if the directory file descriptor is not open
+    /// and ENOTDIR if it does not refer to a directory
+    dirfd_errors
+}
+fn dirfd_errors(ctx: &mut TestContext) {
+    assert_dirfd_errors(ctx, |fd, name| {
+        mkdirat(fd, name, Mode::from_bits_truncate(0o755))
+    });
+}
+
 crate::test_case! {
     /// POSIX: The directory's user ID shall be set to the process' effective user ID.
     /// The directory's group ID shall be set to the group ID of the parent directory
     /// or to the effective group ID of the process.
     // mkdir/00.t
-    uid_gid_eq_euid_egid, serialized, root
+    uid_gid_eq_euid_egid, serialized, root; root_privileges
 }
 fn uid_gid_eq_euid_egid(ctx: &mut SerializedTestContext) {
     assert_uid_gid(ctx, mkdir);
 }
 
+crate::test_case! {
+    /// A directory created in a directory with the SGID bit set gets the group ID of the directory
+    /// (and also the SGID bit on Linux)
+    sgid_dir_inheritance, serialized, root; root_privileges
+}
+fn sgid_dir_inheritance(ctx: &mut SerializedTestContext) {
+    assert_sgid_dir_inheritance(ctx, mkdir);
+}
+
 crate::test_case! {
     /// POSIX: Upon successful completion, mkdir() shall mark for update the st_atime,
     /// st_ctime, and st_mtime fields of the directory. Also, the st_ctime and
@@ -54,6 +131,29 @@ fn changed_time_fields_success(ctx: &mut TestContext) {
         });
 }
 
+crate::test_case! {
+    /// mkdir returns EMLINK if the link count of the parent directory would exceed {LINK_MAX}
+    link_count_max; has_reasonable_link_max
+}
+fn link_count_max(ctx: &mut TestContext) {
+    let parent = ctx.create(crate::context::FileType::Dir).unwrap();
+    let link_max = pathconf(&parent, PathconfVar::LINK_MAX).unwrap().unwrap();
+
+    // The parent starts with two links, its entry and its "." entry,
+    // and each subdirectory adds one with its ".." entry
+    for i in 0..link_max - 2 {
+        mkdir(&parent.join(i.to_string()), Mode::from_bits_truncate(0o755)).unwrap();
+    }
+    assert_eq!(lstat(&parent).unwrap().st_nlink as i64, link_max);
+
+    // ext4 with dir_nlink does not enforce the limit for subdirectories,
+    // and stops counting them by setting the link count of the parent to 1
+    match mkdir(&parent.join("last"), Mode::from_bits_truncate(0o755)) {
+        Ok(()) => assert_eq!(lstat(&parent).unwrap().st_nlink, 1),
+        res => assert_errno(ctx, res, Errno::EMLINK),
+    }
+}
+
 // mkdir/01.t
 enotdir_comp_test_case!(mkdir(~path, Mode::empty()));
 
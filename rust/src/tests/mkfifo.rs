@@ -1,8 +1,14 @@
 use std::{fs::FileType, os::unix::fs::FileTypeExt};
 
-use nix::{sys::stat::Mode, unistd::mkfifo};
+use nix::sys::stat::Mode;
+#[cfg(not(target_vendor = "apple"))]
+use nix::unistd::mkfifoat;
 
-use crate::context::{SerializedTestContext, TestContext};
+use crate::{
+    context::{SerializedTestContext, TestContext},
+    test::root_privileges,
+    utils::mkfifo,
+};
 
 use super::errors::eexist::eexist_file_exists_test_case;
 use super::errors::efault::efault_path_test_case;
@@ -11,7 +17,11 @@ use super::errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path
 use super::errors::enoent::enoent_comp_test_case;
 use super::errors::enotdir::enotdir_comp_test_case;
 use super::errors::erofs::erofs_new_file_test_case;
-use super::mksyscalls::{assert_perms_from_mode_and_umask, assert_uid_gid};
+#[cfg(not(target_vendor = "apple"))]
+use super::mksyscalls::{assert_dirfd_errors, with_cwd, AtDirFd};
+use super::mksyscalls::{
+    assert_perms_from_mode_and_umask, assert_sgid_dir_inheritance, assert_uid_gid,
+};
 use super::{assert_times_changed, ATIME, CTIME, MTIME};
 
 crate::test_case! {
@@ -25,17 +35,86 @@ fn permission_bits_from_mode(ctx: &mut SerializedTestContext) {
     assert_perms_from_mode_and_umask(ctx, mkfifo, FileType::is_fifo);
 }
 
+#[cfg(not(target_vendor = "apple"))]
+crate::test_case! {
+    /// The file permission bits of the new FIFO created with mkfifoat are
+    /// initialized from mode, modified by the process' file creation mask,
+    /// with either a directory file descriptor or AT_FDCWD
+    permission_bits_from_mode_at, serialized
+}
+#[cfg(not(target_vendor = "apple"))]
+fn permission_bits_from_mode_at(ctx: &mut SerializedTestContext) {
+    for dirfd in AtDirFd::ALL {
+        assert_perms_from_mode_and_umask(
+            ctx,
+            |path, mode| dirfd.call(path, |fd, name| mkfifoat(fd, name, mode)),
+            FileType::is_fifo,
+        );
+    }
+}
+
 crate::test_case! {
     /// POSIX: The FIFO's user ID shall be set to the process' effective user ID.
     /// The FIFO's group ID shall be set to the group ID of the parent directory or to
     /// the effective group ID of the process.
     // mkfifo/00.t
-    uid_gid_eq_euid_egid, serialized, root
+    uid_gid_eq_euid_egid, serialized, root; root_privileges
 }
 fn uid_gid_eq_euid_egid(ctx: &mut SerializedTestContext) {
     assert_uid_gid(ctx, mkfifo);
 }
 
+#[cfg(not(target_vendor = "apple"))]
+crate::test_case! {
+    /// The owner and group of the new FIFO created with mkfifoat
+    /// and a directory file descriptor are set as with mkfifo
+    uid_gid_eq_euid_egid_at, serialized, root; root_privileges
+}
+#[cfg(not(target_vendor = "apple"))]
+fn uid_gid_eq_euid_egid_at(ctx: &mut SerializedTestContext) {
+    assert_uid_gid(ctx, |path, mode| {
+        AtDirFd::Parent.call(path, |fd, name| mkfifoat(fd, name, mode))
+    });
+}
+
+#[cfg(not(target_vendor = "apple"))]
+crate::test_case! {
+    /// The owner and group of the new FIFO created with mkfifoat
+    /// and AT_FDCWD are set as with mkfifo
+    uid_gid_eq_euid_egid_at_fdcwd, serialized, root; root_privileges
+}
+#[cfg(not(target_vendor = "apple"))]
+fn uid_gid_eq_euid_egid_at_fdcwd(ctx: &mut SerializedTestContext) {
+    // The working directory of the runner might not be searchable by the other users
+    let base_path = ctx.base_path().to_path_buf();
+    with_cwd(&base_path, || {
+        assert_uid_gid(ctx, |path, mode| {
+            AtDirFd::Cwd.call(path, |fd, name| mkfifoat(fd, name, mode))
+        })
+    });
+}
+
+#[cfg(not(target_vendor = "apple"))]
+crate::test_case! {
+    /// mkfifoat returns EBADF if the directory file descriptor is not open
+    /// and ENOTDIR if it does not refer to a directory
+    dirfd_errors
+}
+#[cfg(not(target_vendor = "apple"))]
+fn dirfd_errors(ctx: &mut TestContext) {
+    assert_dirfd_errors(ctx, |fd, name| {
+        mkfifoat(fd, name, Mode::from_bits_truncate(0o644))
+    });
+}
+
+crate::test_case! {
+    /// A FIFO created in a directory with the SGID bit set gets the group ID of the directory
+    sgid_dir_inheritance, serialized, root; root_privileges
+}
+fn sgid_dir_inheritance(ctx: &mut SerializedTestContext) {
+    assert_sgid_dir_inheritance(ctx, mkfifo);
+}
+
 crate::test_case! {
     /// POSIX: Upon successful completion, mkfifo() shall mark for update the st_atime,
     /// st_ctime, and st_mtime fields of the file. Also, the st_ctime and
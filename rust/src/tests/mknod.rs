@@ -1,9 +1,15 @@
 use std::{fs::FileType as StdFileType, os::unix::fs::FileTypeExt, path::Path};
 
 use nix::errno::Errno;
-use nix::sys::stat::{mknod, Mode, SFlag};
+#[cfg(not(target_os = "macos"))]
+use nix::sys::stat::mknodat;
+use nix::sys::stat::{Mode, SFlag};
 
-use crate::context::{FileType, SerializedTestContext, TestContext};
+use crate::{
+    context::{FileType, SerializedTestContext, TestContext},
+    test::root_privileges,
+    utils::mknod,
+};
 
 use super::errors::eexist::eexist_file_exists_test_case;
 use super::errors::efault::efault_path_test_case;
@@ -11,8 +17,13 @@ use super::errors::eloop::eloop_comp_test_case;
 use super::errors::enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case};
 use super::errors::enoent::enoent_comp_test_case;
 use super::errors::enotdir::enotdir_comp_test_case;
-use super::mksyscalls::{assert_perms_from_mode_and_umask, assert_uid_gid};
-use super::{assert_times_changed, ATIME, CTIME, MTIME};
+use super::errors::erofs::erofs_new_file_test_case;
+#[cfg(not(target_os = "macos"))]
+use super::mksyscalls::{assert_dirfd_errors, with_cwd, AtDirFd};
+use super::mksyscalls::{
+    assert_perms_from_mode_and_umask, assert_sgid_dir_inheritance, assert_uid_gid,
+};
+use super::{assert_errno, assert_times_changed, ATIME, CTIME, MTIME};
 
 fn mknod_wrapper(path: &Path, mode: Mode) -> nix::Result<()> {
     mknod(path, SFlag::S_IFIFO, mode, 0)
@@ -29,17 +40,86 @@ fn permission_bits_from_mode(ctx: &mut SerializedTestContext) {
     assert_perms_from_mode_and_umask(ctx, mknod_wrapper, StdFileType::is_fifo);
 }
 
+#[cfg(not(target_os = "macos"))]
+crate::test_case! {
+    /// The file permission bits of the new FIFO created with mknodat are
+    /// initialized from mode, modified by the process' file creation mask,
+    /// with either a directory file descriptor or AT_FDCWD
+    permission_bits_from_mode_at, serialized
+}
+#[cfg(not(target_os = "macos"))]
+fn permission_bits_from_mode_at(ctx: &mut SerializedTestContext) {
+    for dirfd in AtDirFd::ALL {
+        assert_perms_from_mode_and_umask(
+            ctx,
+            |path, mode| dirfd.call(path, |fd, name| mknodat(fd, name, SFlag::S_IFIFO, mode, 0)),
+            StdFileType::is_fifo,
+        );
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+crate::test_case! {
+    /// The owner and group of the new FIFO created with mknodat
+    /// and a directory file descriptor are set as with mknod
+    uid_gid_eq_euid_egid_at, serialized, root; root_privileges
+}
+#[cfg(not(target_os = "macos"))]
+fn uid_gid_eq_euid_egid_at(ctx: &mut SerializedTestContext) {
+    assert_uid_gid(ctx, |path, mode| {
+        AtDirFd::Parent.call(path, |fd, name| mknodat(fd, name, SFlag::S_IFIFO, mode, 0))
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+crate::test_case! {
+    /// The owner and group of the new FIFO created with mknodat
+    /// and AT_FDCWD are set as with mknod
+    uid_gid_eq_euid_egid_at_fdcwd, serialized, root; root_privileges
+}
+#[cfg(not(target_os = "macos"))]
+fn uid_gid_eq_euid_egid_at_fdcwd(ctx: &mut SerializedTestContext) {
+    // The working directory of the runner might not be searchable by the other users
+    let base_path = ctx.base_path().to_path_buf();
+    with_cwd(&base_path, || {
+        assert_uid_gid(ctx, |path, mode| {
+            AtDirFd::Cwd.call(path, |fd, name| mknodat(fd, name, SFlag::S_IFIFO, mode, 0))
+        })
+    });
+}
+
+#[cfg(not(target_os = "macos"))]
+crate::test_case! {
+    /// mknodat returns EBADF if the directory file descriptor is not open
+    /// and ENOTDIR if it does not refer to a directory
+    dirfd_errors
+}
+#[cfg(not(target_os = "macos"))]
+fn dirfd_errors(ctx: &mut TestContext) {
+    assert_dirfd_errors(ctx, |fd, name| {
+        mknodat(fd, name, SFlag::S_IFIFO, Mode::from_bits_truncate(0o644), 0)
+    });
+}
+
 crate::test_case! {
     /// POSIX: The FIFO's user ID shall be set to the process' effective user ID.
     /// The FIFO's group ID shall be set to the group ID of the parent directory or to
     /// the effective group ID of the process.
     // mknod/00.t
-    uid_gid_eq_euid_egid, serialized, root
+    uid_gid_eq_euid_egid, serialized, root; root_privileges
 }
 fn uid_gid_eq_euid_egid(ctx: &mut SerializedTestContext) {
     assert_uid_gid(ctx, mknod_wrapper);
 }
 
+crate::test_case! {
+    /// A FIFO created in a directory with the SGID bit set gets the group ID of the directory
+    sgid_dir_inheritance, serialized, root; root_privileges
+}
+fn sgid_dir_inheritance(ctx: &mut SerializedTestContext) {
+    assert_sgid_dir_inheritance(ctx, mknod_wrapper);
+}
+
 crate::test_case! {
     /// POSIX: Upon successful completion, mkfifo() shall mark for update the st_atime,
     /// st_ctime, and st_mtime fields of the file. Also, the st_ctime and
@@ -73,13 +153,15 @@ fn enotdir_comp_char_block(ctx: &mut TestContext, ft: FileType) {
     let base_path = ctx.create(ft).unwrap();
     let path = base_path.join("previous_not_dir");
 
-    assert_eq!(
-        mknod(&path, SFlag::S_IFCHR, Mode::empty(), 0).unwrap_err(),
-        Errno::ENOTDIR
+    assert_errno(
+        ctx,
+        mknod(&path, SFlag::S_IFCHR, Mode::empty(), 0),
+        Errno::ENOTDIR,
     );
-    assert_eq!(
-        mknod(&path, SFlag::S_IFBLK, Mode::empty(), 0).unwrap_err(),
-        Errno::ENOTDIR
+    assert_errno(
+        ctx,
+        mknod(&path, SFlag::S_IFBLK, Mode::empty(), 0),
+        Errno::ENOTDIR,
     );
 }
 
@@ -187,14 +269,15 @@ fn create_old_new_device(ctx: &mut TestContext) {
 
         let file = ctx.gen_path();
 
-        assert_eq!(
+        assert_errno(
+            ctx,
             mknod(
                 &file,
                 argument,
                 Mode::from_bits_truncate(mode),
-                makedev(4096, 262144)
+                makedev(4096, 262144),
             ),
-            Err(Errno::EINVAL)
+            Errno::EINVAL,
         );
     }
 }
@@ -214,6 +297,8 @@ eloop_comp_test_case!(mknod(~path, SFlag::S_IFIFO, Mode::empty(), 0));
 // mknod/08.t
 eexist_file_exists_test_case!(mknod(~path, SFlag::S_IFIFO, Mode::empty(), 0));
 
+erofs_new_file_test_case!(mknod(~path, SFlag::S_IFIFO, Mode::empty(), 0));
+
 // mknod/10.t
 efault_path_test_case!(mknod, |ptr| nix::libc::mknod(
     ptr,
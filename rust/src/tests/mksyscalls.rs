@@ -1,24 +1,37 @@
 //! Builder functions for `mk`-family syscalls tests.
 
 use std::{
+    ffi::OsStr,
+    fmt::Debug,
     fs::{metadata, FileType},
+    os::fd::{AsRawFd, RawFd},
     os::unix::prelude::PermissionsExt,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
 use nix::{
-    sys::stat::{lstat, mode_t, Mode},
-    unistd::{chown, Gid, Uid, User},
+    errno::Errno,
+    fcntl::OFlag,
+    sys::stat::{mode_t, Mode},
+    unistd::{chdir, getcwd, Gid, Uid, User},
 };
 
+use super::assert_errno;
 use crate::{
-    context::SerializedTestContext,
-    utils::{chmod, ALLPERMS},
+    context::{SerializedTestContext, TestContext},
+    utils::{chmod, chown, lstat, open, ALLPERMS},
 };
 
+/// Umask values for which every creation syscall is checked.
+const UMASKS: [mode_t; 4] = [0, 0o022, 0o077, 0o777];
+
+/// Modes for which every creation syscall is checked with each of the [`UMASKS`].
+const MODES: [mode_t; 4] = [0o777, 0o755, 0o640, 0o501];
+
 /// Assert that the created entry gets its permission bits from the mode
 /// provided to the function negated by the process's file creation mask
 /// (umask), and its file type is equal to the expected one.
+/// Every combination of [`MODES`] and [`UMASKS`] is also checked.
 pub(super) fn assert_perms_from_mode_and_umask<F, T, C>(
     ctx: &mut SerializedTestContext,
     f: F,
@@ -76,7 +89,13 @@ pub(super) fn assert_perms_from_mode_and_umask<F, T, C>(
     assert_perm_mode(ctx, 0o151, &f, &f_type_check);
     assert_perm_umask(ctx, 0o151, 0o77, &f, &f_type_check);
     assert_perm_umask(ctx, 0o345, 0o70, &f, &f_type_check);
-    assert_perm_umask(ctx, 0o501, 0o345, f, f_type_check);
+    assert_perm_umask(ctx, 0o501, 0o345, &f, &f_type_check);
+
+    for umask in UMASKS {
+        for mode in MODES {
+            assert_perm_umask(ctx, mode, umask, &f, &f_type_check);
+        }
+    }
 }
 
 /// Assert that the entry's user ID is set to the process' effective user ID and
@@ -118,3 +137,124 @@ where
     let group = ctx.get_new_group();
     doit(ctx, other_user, Some(group.gid), f);
 }
+
+/// Call an `*at` syscall with a file descriptor for the parent directory of `path`
+/// and the final component of `path`.
+pub(super) fn with_parent_dirfd<F, T>(path: &Path, f: F) -> nix::Result<T>
+where
+    F: FnOnce(RawFd, &OsStr) -> nix::Result<T>,
+{
+    let dir = open(
+        path.parent().unwrap(),
+        OFlag::O_DIRECTORY | OFlag::O_RDONLY,
+        Mode::empty(),
+    )?;
+    f(dir.as_raw_fd(), path.file_name().unwrap())
+}
+
+/// Directory file descriptor with which an `*at` syscall is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AtDirFd {
+    /// A file descriptor for the parent directory of the path.
+    Parent,
+    /// `AT_FDCWD`, with the working directory changed to the parent directory of the path.
+    /// The working directory is changed back afterwards, which requires search permission on it
+    /// (see [`with_cwd`] to call it as another user).
+    Cwd,
+}
+
+impl AtDirFd {
+    pub const ALL: [AtDirFd; 2] = [AtDirFd::Parent, AtDirFd::Cwd];
+
+    /// Call an `*at` syscall with this directory file descriptor
+    /// and the final component of `path`.
+    pub fn call<F, T>(self, path: &Path, f: F) -> nix::Result<T>
+    where
+        F: FnOnce(Option<RawFd>, &OsStr) -> nix::Result<T>,
+    {
+        match self {
+            AtDirFd::Parent => with_parent_dirfd(path, |fd, name| f(Some(fd), name)),
+            AtDirFd::Cwd => {
+                let cwd = getcwd()?;
+                chdir(path.parent().unwrap())?;
+                let res = f(None, path.file_name().unwrap());
+                chdir(&cwd)?;
+                res
+            }
+        }
+    }
+}
+
+/// Call `f` with the working directory changed to `dir`, and restore it afterwards.
+pub(super) fn with_cwd<F, T>(dir: &Path, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let cwd = getcwd().unwrap();
+    chdir(dir).unwrap();
+    let res = f();
+    chdir(&cwd).unwrap();
+    res
+}
+
+/// Assert that an `*at` syscall called with a relative path returns EBADF
+/// when the directory file descriptor is not open,
+/// and ENOTDIR when it does not refer to a directory.
+pub(super) fn assert_dirfd_errors<F, T>(ctx: &TestContext, f: F)
+where
+    F: Fn(Option<RawFd>, &OsStr) -> nix::Result<T>,
+    T: Debug,
+{
+    let name = OsStr::new("entry");
+
+    let closed_fd = open(
+        ctx.base_path(),
+        OFlag::O_DIRECTORY | OFlag::O_RDONLY,
+        Mode::empty(),
+    )
+    .unwrap()
+    .as_raw_fd();
+    assert_errno(ctx, f(Some(closed_fd), name), Errno::EBADF);
+
+    let file = ctx.create(crate::context::FileType::Regular).unwrap();
+    let file_fd = open(&file, OFlag::O_RDONLY, Mode::empty()).unwrap();
+    assert_errno(ctx, f(Some(file_fd.as_raw_fd()), name), Errno::ENOTDIR);
+    assert!(!ctx.base_path().join(name).exists());
+}
+
+/// Create a directory owned by `gid` with the SGID bit set and which is writable by everyone.
+pub(super) fn create_sgid_dir(ctx: &SerializedTestContext, gid: Gid) -> PathBuf {
+    let dir = ctx.create(crate::context::FileType::Dir).unwrap();
+    chown(&dir, None, Some(gid)).unwrap();
+    chmod(&dir, Mode::from_bits_truncate(0o777) | Mode::S_ISGID).unwrap();
+
+    dir
+}
+
+/// Assert that an entry created inside a directory with the SGID bit set
+/// gets the group ID of the directory, even if the process' effective group ID is different.
+/// On Linux, a new directory also inherits the SGID bit.
+pub(super) fn assert_sgid_dir_inheritance<F, T>(ctx: &mut SerializedTestContext, f: F)
+where
+    F: Fn(&Path, Mode) -> nix::Result<T>,
+{
+    let user = ctx.get_new_user();
+    let group = ctx.get_new_group();
+    let dir = create_sgid_dir(ctx, group.gid);
+
+    let path = dir.join("entry");
+    ctx.as_user(user, None, || {
+        f(&path, Mode::from_bits_truncate(0o755)).unwrap();
+    });
+
+    let entry_stat = lstat(&path).unwrap();
+    assert_eq!(entry_stat.st_uid, user.uid.as_raw());
+    assert_eq!(entry_stat.st_gid, group.gid.as_raw());
+
+    #[cfg(target_os = "linux")]
+    if entry_stat.st_mode & nix::sys::stat::SFlag::S_IFMT.bits()
+        == nix::sys::stat::SFlag::S_IFDIR.bits()
+    {
+        assert_ne!(entry_stat.st_mode & Mode::S_ISGID.bits(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+//! Tests for shared mappings of files whose size changes after they are mapped.
+
+use std::{ffi::c_void, num::NonZeroUsize, ptr::NonNull};
+
+use nix::{
+    fcntl::OFlag,
+    sys::{
+        mman::{mmap, munmap, MapFlags, ProtFlags},
+        signal::Signal,
+        uio::pread,
+    },
+    unistd::{ftruncate, sysconf, SysconfVar},
+};
+
+use crate::{context::TestContext, isolation::ChildError};
+
+/// A shared mapping of a file, unmapped when dropped.
+struct Mapping {
+    addr: NonNull<c_void>,
+    len: usize,
+}
+
+impl Mapping {
+    /// Return a pointer to the byte at `offset`.
+    fn byte(&self, offset: usize) -> *mut u8 {
+        assert!(offset < self.len);
+        // SAFETY: The offset stays in the mapping
+        unsafe { self.addr.as_ptr().cast::<u8>().add(offset) }
+    }
+
+    /// Read the byte at `offset`, which raises SIGBUS if it is beyond the end of the file.
+    fn read(&self, offset: usize) -> u8 {
+        // SAFETY: The pointer is in the mapping
+        unsafe { self.byte(offset).read_volatile() }
+    }
+
+    /// Write the byte at `offset`, which raises SIGBUS if it is beyond the end of the file.
+    fn write(&self, offset: usize, byte: u8) {
+        // SAFETY: The pointer is in the mapping
+        unsafe { self.byte(offset).write_volatile(byte) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: The mapping is not used afterwards
+        let _ = unsafe { munmap(self.addr, self.len) };
+    }
+}
+
+/// Assert that the access in a child process is terminated by SIGBUS.
+fn assert_sigbus<F: FnOnce()>(ctx: &TestContext, access: &str, f: F) {
+    match ctx.in_child(f) {
+        Err(ChildError::Signaled {
+            signal: Signal::SIGBUS,
+            ..
+        }) => (),
+        Err(e) => panic!("{access} beyond the end of the file was {e}, expected SIGBUS"),
+        Ok(()) => panic!("{access} beyond the end of the file succeeded, expected SIGBUS"),
+    }
+}
+
+crate::test_case! {
+    /// Accessing the pages of a shared mapping beyond the end of a truncated file raises SIGBUS,
+    /// while the data within the new size is still accessible through the mapping
+    truncated_mapping_sigbus
+}
+fn truncated_mapping_sigbus(ctx: &mut TestContext) {
+    let page_size = sysconf(SysconfVar::PAGE_SIZE).unwrap().unwrap() as usize;
+    let len = 3 * page_size;
+    let (_, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    ftruncate(&file, len as _).unwrap();
+
+    // SAFETY: The file is only accessed through the mapping and the descriptor in this test
+    let addr = unsafe {
+        mmap(
+            None,
+            NonZeroUsize::new(len).unwrap(),
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_SHARED,
+            &file,
+            0,
+        )
+    }
+    .unwrap();
+    let mapping = Mapping { addr, len };
+    for offset in (0..len).step_by(page_size) {
+        mapping.write(offset, b'a');
+    }
+
+    // The file ends in the middle of the second page
+    let size = page_size + page_size / 2;
+    ftruncate(&file, size as _).unwrap();
+
+    // The pages which still contain data of the file are accessible
+    assert_eq!(mapping.read(0), b'a');
+    assert_eq!(mapping.read(page_size), b'a');
+    mapping.write(size - 1, b'b');
+    let mut buf = [0];
+    assert_eq!(pread(&file, &mut buf, (size - 1) as _), Ok(1));
+    assert_eq!(buf, [b'b']);
+
+    // The remainder of the partial page is zero-filled, and is not written to the file
+    assert_eq!(mapping.read(size), 0);
+    mapping.write(size, b'c');
+    assert_eq!(pread(&file, &mut buf, size as _), Ok(0));
+
+    // The pages entirely beyond the end of the file are not
+    let beyond = 2 * page_size;
+    assert_sigbus(ctx, "read", || {
+        mapping.read(beyond);
+    });
+    assert_sigbus(ctx, "write", || mapping.write(beyond, b'd'));
+
+    // Growing the file again makes them accessible, zero-filled
+    ftruncate(&file, len as _).unwrap();
+    assert_eq!(mapping.read(beyond), 0);
+}
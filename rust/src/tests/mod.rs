@@ -13,31 +13,82 @@ use std::os::unix::fs::MetadataExt as StdMetadataExt;
 
 use std::{fs::metadata, path::Path};
 
-use nix::sys::time::TimeSpec;
+use nix::{errno::Errno, sys::time::TimeSpec};
 
-use crate::test::TestContext;
+use crate::{
+    conformance::{ConformanceProfile, Timestamp},
+    test::TestContext,
+};
 
+pub mod access;
+#[cfg(chflags)]
+pub mod append_only;
+pub mod atime;
+pub mod blocks;
+#[cfg(target_os = "linux")]
+pub mod capabilities;
 #[cfg(chflags)]
 pub mod chflags;
+#[cfg(chflagsat)]
+pub mod chflagsat;
 pub mod chmod;
 pub mod chown;
+#[cfg(target_os = "macos")]
+pub mod clonefile;
+pub mod deep_nesting;
+#[cfg(any(target_os = "linux", target_os = "freebsd", target_os = "illumos"))]
+pub mod devices;
+pub mod dir_permissions;
+pub mod dots;
+pub mod dup;
 pub mod errors;
+#[cfg(any(target_os = "linux", target_os = "freebsd"))]
+pub mod fallocate;
+pub mod fcntl;
+pub mod filenames;
 pub mod ftruncate;
+#[cfg(chflags)]
+pub mod immutable;
+pub mod interrupt;
+#[cfg(target_os = "freebsd")]
+pub mod jail;
+pub mod large_dir;
+#[cfg(lchmod)]
+pub mod lchmod;
+pub mod lchown;
 pub mod link;
 pub mod mkdir;
 pub mod mkfifo;
 pub mod mknod;
 mod mksyscalls;
+pub mod mmap;
+pub mod mountpoint;
+pub mod name_equivalence;
+pub mod nfs;
 #[cfg(any(target_os = "macos", target_os = "freebsd"))]
 pub mod nfsv4acl;
+pub mod o_append;
+pub mod o_sync;
 pub mod open;
+pub mod open_unlinked;
+pub mod path_length;
 pub mod posix_fallocate;
+pub mod pread_pwrite;
+#[cfg(target_os = "linux")]
+pub mod reflink;
 pub mod rename;
 pub mod rmdir;
+pub mod sandbox;
+pub mod stress;
+pub mod suid_sgid;
 pub mod symlink;
+pub mod trailing_slash;
 pub mod truncate;
 pub mod unlink;
 pub mod utimensat;
+pub mod write;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub mod xattr;
 
 /// Argument to set which fields should be compared for [`TimeAssertion::path`].
 #[derive(Debug, Clone, Copy)]
@@ -69,6 +120,20 @@ impl BitOr for TimestampField {
     }
 }
 
+impl TimestampField {
+    /// Return the fields whose updates are guaranteed by the conformance profile.
+    fn guaranteed(self, profile: ConformanceProfile) -> Self {
+        [
+            (ATIME, Timestamp::Atime),
+            (CTIME, Timestamp::Ctime),
+            (MTIME, Timestamp::Mtime),
+        ]
+        .into_iter()
+        .filter(|(field, timestamp)| self & *field != 0 && profile.guarantees(*timestamp))
+        .fold(Self(0), |fields, (field, _)| fields | field)
+    }
+}
+
 /// A handy extention to std::os::unix::fs::MetadataExt
 trait MetadataExt: StdMetadataExt {
     /// Return the file's last accessed time as a `TimeSpec`, including
@@ -132,12 +197,23 @@ impl AsTimeInvariant for nix::sys::stat::FileStat {
 // Note: can't be a method of MetadataExt, because StdMetadataExt lacks a
 // birthtime() method.
 fn birthtime_ts(path: &Path) -> TimeSpec {
-    use nix::sys::stat::stat;
+    use crate::utils::stat;
 
     let sb = stat(path).unwrap();
     TimeSpec::new(sb.st_birthtime, sb.st_birthtime_nsec)
 }
 
+/// Return the names of the entries of a directory, sorted.
+fn entries(dir: &Path) -> Vec<std::ffi::OsString> {
+    let mut names: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+
+    names
+}
+
 #[derive(Debug)]
 #[must_use]
 /// Builder to create a time metadata assertion,
@@ -184,9 +260,16 @@ impl<'a> TimeAssertion<'a> {
         } else {
             metadata
         };
-
-        let metas_before: Vec<_> = self
+        // The timestamps which may not be updated are not compared
+        let profile = ctx.conformance_profile();
+        let compared_paths: Vec<_> = self
             .compared_paths
+            .into_iter()
+            .map(|(before, after, fields)| (before, after, fields.guaranteed(profile)))
+            .filter(|(_, _, fields)| *fields != 0)
+            .collect();
+
+        let metas_before: Vec<_> = compared_paths
             .iter()
             .map(|&(path, _, fields)| {
                 let meta = get_metadata(path).unwrap();
@@ -202,8 +285,7 @@ impl<'a> TimeAssertion<'a> {
 
         f();
 
-        let metas_after: Vec<_> = self
-            .compared_paths
+        let metas_after: Vec<_> = compared_paths
             .into_iter()
             .map(|(_, path, fields)| {
                 let meta = get_metadata(path).unwrap();
@@ -284,3 +366,69 @@ where
         .path(path, CTIME)
         .execute(ctx, true, f)
 }
+
+/// Check that an operation failed with one of the accepted errors,
+/// or one of those the conformance profile accepts in their place,
+/// and record the one returned in the behavior profile of the file system
+/// when there was a choice. Return the description of the mismatch otherwise.
+fn check_errno<T: std::fmt::Debug>(
+    ctx: &TestContext,
+    res: nix::Result<T>,
+    accepted: &[Errno],
+) -> Result<(), String> {
+    let profile = ctx.conformance_profile();
+    let mut tolerated = Vec::with_capacity(accepted.len());
+    for errno in accepted
+        .iter()
+        .flat_map(|errno| profile.accepted_errors(*errno))
+    {
+        if !tolerated.contains(&errno) {
+            tolerated.push(errno);
+        }
+    }
+    match (res, tolerated.as_slice()) {
+        (Err(errno), tolerated) if tolerated.contains(&errno) => {
+            if tolerated.len() > 1 {
+                crate::profile::record(accepted, errno);
+            }
+            Ok(())
+        }
+        (res, [expected]) => Err(format!("expected {expected}, got {res:?}")),
+        (res, tolerated) => Err(format!("expected one of {tolerated:?}, got {res:?}")),
+    }
+}
+
+/// Assert that an operation failed with one of the accepted errors,
+/// or one of those the conformance profile accepts in their place,
+/// and record the one returned in the behavior profile of the file system.
+#[track_caller]
+fn assert_errno_choice<T: std::fmt::Debug>(
+    ctx: &TestContext,
+    res: nix::Result<T>,
+    accepted: &[Errno],
+) {
+    if let Err(mismatch) = check_errno(ctx, res, accepted) {
+        panic!("{mismatch}");
+    }
+}
+
+/// Assert that an operation failed with the expected error,
+/// or one of those the conformance profile accepts in its place.
+#[track_caller]
+fn assert_errno<T: std::fmt::Debug>(ctx: &TestContext, res: nix::Result<T>, expected: Errno) {
+    assert_errno_choice(ctx, res, &[expected]);
+}
+
+/// Assert that an operation failed with the expected error, like [`assert_errno`],
+/// describing the case which is checked (e.g. the flags in a loop) if it did not.
+#[track_caller]
+fn assert_errno_for<T: std::fmt::Debug>(
+    ctx: &TestContext,
+    res: nix::Result<T>,
+    expected: Errno,
+    case: impl std::fmt::Display,
+) {
+    if let Err(mismatch) = check_errno(ctx, res, &[expected]) {
+        panic!("{mismatch} ({case})");
+    }
+}
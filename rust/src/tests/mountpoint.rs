@@ -0,0 +1,182 @@
+//! Tests of the operations on mount points and across them,
+//! which mount a directory of the secondary file system inside the test directory.
+
+use std::{
+    ffi::OsStr,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use nix::errno::Errno;
+use tempfile::TempDir;
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    utils::{link, rename, rmdir, unlink},
+};
+
+use super::{assert_errno, assert_errno_choice, errors::exdev::secondary_fs_available};
+
+/// Dummy mountpoint, unmounted when dropped.
+pub(super) struct DummyMnt {
+    pub path: PathBuf,
+}
+
+impl DummyMnt {
+    /// Mount a directory of the test directory on another one.
+    pub fn new(ctx: &mut TestContext) -> anyhow::Result<Self> {
+        // We don't really care about a specific type of file system here, the directory just have to be a mount point
+        let from = ctx.create(FileType::Dir)?;
+        let path = ctx.create(FileType::Dir)?;
+
+        Self::bind(&from, path)
+    }
+
+    /// Mount `from` on `path`, which has to exist and be of the same type.
+    /// Only directories can be mounted on FreeBSD.
+    pub fn bind(from: &Path, path: PathBuf) -> anyhow::Result<Self> {
+        let mut mount = Command::new("mount");
+
+        if cfg!(target_os = "linux") {
+            mount.arg("--bind");
+        } else {
+            mount.args(["-t", "nullfs"]);
+        }
+
+        let result = mount.arg(from).arg(&path).output()?;
+        let stderr = OsStr::from_bytes(&result.stderr).to_string_lossy();
+        assert!(result.status.success(), "{}", stderr);
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for DummyMnt {
+    fn drop(&mut self) {
+        let umount = Command::new("umount").arg(&self.path).output();
+        if !std::thread::panicking() {
+            assert!(matches!(umount, Ok(res) if res.status.success()));
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(super) fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
+    use caps::{has_cap, CapSet, Capability};
+
+    if !has_cap(None, CapSet::Effective, Capability::CAP_SYS_ADMIN)? {
+        anyhow::bail!("process doesn't have the CAP_SYS_ADMIN cap to mount the dummy file system")
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "freebsd")]
+pub(super) fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
+    use nix::unistd::Uid;
+    use sysctl::{Ctl, CtlValue, Sysctl};
+
+    const MOUNT_CTL: &str = "vfs.usermount";
+
+    let ctl = Ctl::new(MOUNT_CTL)?;
+
+    if !Uid::effective().is_root() && ctl.value()? == CtlValue::Int(0) {
+        anyhow::bail!("process doesn't have the rights to mount the dummy file system")
+    }
+    if !Uid::effective().is_root()
+        && !OsStr::from_bytes(&Command::new("lsvfs").output().unwrap().stdout)
+            .to_string_lossy()
+            .contains("nullfs")
+    {
+        anyhow::bail!("nullfs module is not loaded")
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+pub(super) fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
+    if !nix::unistd::Uid::effective().is_root() {
+        anyhow::bail!("process is not root, cannot mount dummy file system")
+    }
+
+    Ok(())
+}
+
+/// Mount a new directory of the secondary file system on a directory of the test directory.
+/// The returned directory has to outlive the mount point.
+fn mount_secondary_dir(ctx: &mut TestContext) -> (TempDir, DummyMnt) {
+    let secondary_fs = ctx.features_config().secondary_fs.clone().unwrap();
+    let source = TempDir::new_in(secondary_fs).unwrap();
+    let path = ctx.create(FileType::Dir).unwrap();
+    let mnt = DummyMnt::bind(source.path(), path).unwrap();
+
+    (source, mnt)
+}
+
+crate::test_case! {
+    /// rmdir returns EBUSY on a mount point, and rename returns EBUSY
+    /// (or EXDEV, as the mounted directory is on another file system)
+    /// when it is either the source or the target
+    ebusy_dir, root; has_mount_cap, secondary_fs_available
+}
+fn ebusy_dir(ctx: &mut TestContext) {
+    let (_source, mnt) = mount_secondary_dir(ctx);
+
+    assert_errno(ctx, rmdir(&mnt.path), Errno::EBUSY);
+    assert_errno_choice(
+        ctx,
+        rename(&mnt.path, &ctx.gen_path()),
+        &[Errno::EBUSY, Errno::EXDEV],
+    );
+    let dir = ctx.create(FileType::Dir).unwrap();
+    assert_errno_choice(ctx, rename(&dir, &mnt.path), &[Errno::EBUSY, Errno::EXDEV]);
+
+    assert!(mnt.path.is_dir());
+    assert!(dir.is_dir());
+}
+
+#[cfg(target_os = "linux")]
+crate::test_case! {
+    /// unlink and rename return EBUSY on a regular file which is a mount point
+    ebusy_file, root; has_mount_cap, secondary_fs_available
+}
+#[cfg(target_os = "linux")]
+fn ebusy_file(ctx: &mut TestContext) {
+    let secondary_fs = ctx.features_config().secondary_fs.clone().unwrap();
+    let source = tempfile::NamedTempFile::new_in(secondary_fs).unwrap();
+    let path = ctx.create(FileType::Regular).unwrap();
+    let mnt = DummyMnt::bind(source.path(), path).unwrap();
+
+    assert_errno(ctx, unlink(&mnt.path), Errno::EBUSY);
+    assert_errno(ctx, rename(&mnt.path, &ctx.gen_path()), Errno::EBUSY);
+    let file = ctx.create(FileType::Regular).unwrap();
+    assert_errno(ctx, rename(&file, &mnt.path), Errno::EBUSY);
+
+    assert!(mnt.path.is_file());
+}
+
+crate::test_case! {
+    /// rename and link return EXDEV when crossing a mount point in either direction
+    exdev_across_mount, root; has_mount_cap, secondary_fs_available
+}
+fn exdev_across_mount(ctx: &mut TestContext) {
+    let (_source, mnt) = mount_secondary_dir(ctx);
+    let file = ctx.create(FileType::Regular).unwrap();
+    let inner = ctx
+        .new_file(FileType::Regular)
+        .name(mnt.path.join("inner"))
+        .create()
+        .unwrap();
+
+    assert_errno(ctx, rename(&file, &mnt.path.join("file")), Errno::EXDEV);
+    assert_errno(ctx, link(&file, &mnt.path.join("file")), Errno::EXDEV);
+    assert_errno(ctx, rename(&inner, &ctx.gen_path()), Errno::EXDEV);
+    assert_errno(ctx, link(&inner, &ctx.gen_path()), Errno::EXDEV);
+
+    assert!(file.is_file());
+    assert!(inner.is_file());
+    unlink(&inner).unwrap();
+}
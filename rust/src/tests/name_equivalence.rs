@@ -0,0 +1,182 @@
+//! Tests for the lookup of names which differ only by case or by Unicode normalization form,
+//! which refer to the same file on some file systems.
+
+use std::{
+    fs::{self, File},
+    io::ErrorKind,
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+};
+
+use super::entries;
+
+/// "é" in Normalization Form C (precomposed).
+const NFC_NAME: &str = "caf\u{e9}";
+/// "é" in Normalization Form D (decomposed).
+const NFD_NAME: &str = "cafe\u{301}";
+
+/// Guard to check that the file system has not been declared as case-insensitive.
+fn case_sensitive(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::CaseInsensitive)
+    {
+        anyhow::bail!("the file system has been declared as case-insensitive")
+    }
+
+    Ok(())
+}
+
+/// Guard to check that the file system has not been declared as normalizing Unicode names.
+fn no_unicode_normalization(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::UnicodeNormalization)
+    {
+        anyhow::bail!("the file system has been declared as normalizing Unicode names")
+    }
+
+    Ok(())
+}
+
+/// Assert that `name` and `variant` refer to the same file,
+/// and that creating `variant` exclusively fails.
+fn assert_same_file(dir: &Path, name: &str, variant: &str) {
+    File::create(dir.join(name)).unwrap();
+
+    let ino = fs::symlink_metadata(dir.join(name)).unwrap().ino();
+    assert_eq!(
+        fs::symlink_metadata(dir.join(variant)).unwrap().ino(),
+        ino,
+        "{variant:?} does not refer to {name:?}"
+    );
+    assert_eq!(
+        File::options()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(variant))
+            .unwrap_err()
+            .kind(),
+        ErrorKind::AlreadyExists
+    );
+    assert_eq!(entries(dir).len(), 1);
+}
+
+/// Assert that `name` and `variant` can be created as two distinct files.
+fn assert_distinct_files(dir: &Path, name: &str, variant: &str) {
+    File::create(dir.join(name)).unwrap();
+    assert_eq!(
+        fs::symlink_metadata(dir.join(variant)).unwrap_err().kind(),
+        ErrorKind::NotFound
+    );
+
+    File::create(dir.join(variant)).unwrap();
+    assert_ne!(
+        fs::symlink_metadata(dir.join(name)).unwrap().ino(),
+        fs::symlink_metadata(dir.join(variant)).unwrap().ino()
+    );
+    assert_eq!(entries(dir).len(), 2);
+}
+
+/// Assert that renaming `name` to `variant` keeps the same file under the new name.
+fn assert_rename_variant(dir: &Path, name: &str, variant: &str) {
+    File::create(dir.join(name)).unwrap();
+    let ino = fs::symlink_metadata(dir.join(name)).unwrap().ino();
+
+    fs::rename(dir.join(name), dir.join(variant)).unwrap();
+    assert_eq!(fs::symlink_metadata(dir.join(variant)).unwrap().ino(), ino);
+    assert_eq!(entries(dir).len(), 1);
+}
+
+crate::test_case! {
+    /// Names differing only by case refer to the same file on a case-insensitive file system,
+    /// which preserves the case used on creation
+    case_insensitive_lookup, FileSystemFeature::CaseInsensitive
+}
+fn case_insensitive_lookup(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_same_file(&dir, "PjdFsTest", "pjdfstest");
+    assert_eq!(entries(&dir), ["PjdFsTest"]);
+}
+
+crate::test_case! {
+    /// rename between names differing only by case changes the case of the name of the file
+    case_insensitive_rename, FileSystemFeature::CaseInsensitive
+}
+fn case_insensitive_rename(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_rename_variant(&dir, "pjdfstest", "PJDFSTEST");
+    assert_eq!(entries(&dir), ["PJDFSTEST"]);
+}
+
+crate::test_case! {
+    /// open with O_CREAT and O_EXCL returns EEXIST for a name differing only by case
+    /// from an existing one on a case-insensitive file system
+    case_insensitive_o_excl, FileSystemFeature::CaseInsensitive
+}
+fn case_insensitive_o_excl(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    File::create(dir.join("pjdfstest")).unwrap();
+
+    for variant in ["PJDFSTEST", "PjdFsTest"] {
+        let err = File::options()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(variant))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists, "{variant}");
+    }
+    assert_eq!(entries(&dir), ["pjdfstest"]);
+}
+
+crate::test_case! {
+    /// Names differing only by case refer to distinct files on a case-sensitive file system
+    case_sensitive_lookup; case_sensitive
+}
+fn case_sensitive_lookup(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_distinct_files(&dir, "PjdFsTest", "pjdfstest");
+}
+
+crate::test_case! {
+    /// Names differing only by their normalization form refer to the same file
+    /// on a file system normalizing Unicode names
+    normalization_lookup, FileSystemFeature::UnicodeNormalization
+}
+fn normalization_lookup(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_same_file(&dir, NFC_NAME, NFD_NAME);
+}
+
+crate::test_case! {
+    /// rename between names differing only by their normalization form keeps the same file
+    normalization_rename, FileSystemFeature::UnicodeNormalization
+}
+fn normalization_rename(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_rename_variant(&dir, NFC_NAME, NFD_NAME);
+}
+
+crate::test_case! {
+    /// Names differing only by their normalization form refer to distinct files
+    /// on a file system which does not normalize Unicode names
+    distinct_normalization_forms; no_unicode_normalization
+}
+fn distinct_normalization_forms(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    assert_distinct_files(&dir, NFC_NAME, NFD_NAME);
+}
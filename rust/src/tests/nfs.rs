@@ -0,0 +1,93 @@
+//! Tests for the behavior of file systems mounted by an NFS client,
+//! enabled with the `nfs_client` and `root_squash` features.
+//!
+//! When the server squashes root, root is mapped to an anonymous user
+//! and its privileged operations fail like those of any other user.
+
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+};
+
+use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+
+use crate::{
+    context::{FileType, TestContext},
+    features::FileSystemFeature,
+    tests::MetadataExt,
+    utils::{chown, lstat, open},
+};
+
+use super::assert_errno;
+
+crate::test_case! {
+    /// The files created by root are owned by the anonymous user when root is squashed
+    root_squash_owner, root, FileSystemFeature::RootSquash
+}
+fn root_squash_owner(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    assert_ne!(lstat(&path).unwrap().st_uid, 0);
+}
+
+crate::test_case! {
+    /// root cannot give away a file when it is squashed
+    root_squash_chown, root, FileSystemFeature::RootSquash
+}
+fn root_squash_chown(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let user = ctx.get_new_user();
+    assert_errno(ctx, chown(&path, Some(user.uid), None), Errno::EPERM);
+}
+
+crate::test_case! {
+    /// root cannot override the permissions of a file when it is squashed
+    root_squash_no_dac_override, root, FileSystemFeature::RootSquash
+}
+fn root_squash_no_dac_override(ctx: &mut TestContext) {
+    let path = ctx
+        .new_file(FileType::Regular)
+        .mode(0o000)
+        .create()
+        .unwrap();
+    assert_errno(
+        ctx,
+        open(&path, OFlag::O_RDONLY, Mode::empty()).map(drop),
+        Errno::EACCES,
+    );
+    assert_errno(
+        ctx,
+        open(&path, OFlag::O_WRONLY, Mode::empty()).map(drop),
+        Errno::EACCES,
+    );
+}
+
+crate::test_case! {
+    /// The data written to a file is flushed when it is closed (close-to-open consistency),
+    /// so its size and mtime do not change afterwards and are seen when it is opened again
+    close_to_open_times, FileSystemFeature::NfsClient
+}
+fn close_to_open_times(ctx: &mut TestContext) {
+    const DATA: &[u8] = b"close-to-open";
+    let (path, fd) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
+    let created = fs::symlink_metadata(&path).unwrap();
+
+    ctx.nap();
+    let mut file = File::from(fd);
+    file.write_all(DATA).unwrap();
+    drop(file);
+    let closed = fs::symlink_metadata(&path).unwrap();
+    assert_eq!(closed.len(), DATA.len() as u64);
+    assert!(closed.mtime_ts() > created.mtime_ts());
+
+    // The writes are not applied again by the server after the close
+    ctx.nap();
+    let mut file = File::from(open(&path, OFlag::O_RDONLY, Mode::empty()).unwrap());
+    let reopened = file.metadata().unwrap();
+    assert_eq!(reopened.len(), closed.len());
+    assert_eq!(reopened.mtime_ts(), closed.mtime_ts());
+    assert_eq!(reopened.ctime_ts(), closed.ctime_ts());
+
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).unwrap();
+    assert_eq!(data, DATA);
+}
@@ -1,11 +1,12 @@
 //! Tests for ACL_APPEND_DATA
-use nix::{errno::Errno, unistd::unlink};
+use nix::errno::Errno;
 
 use super::prependacl;
 use crate::{
     context::{FileBuilder, FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::{rename, rmdir},
+    tests::assert_errno,
+    utils::{rename, rmdir, unlink},
 };
 
 crate::test_case! {
@@ -19,7 +20,7 @@ fn can_create_directories(ctx: &mut SerializedTestContext) {
 
     prependacl(&path, &format!("allow::user:{}:append", user.gid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         FileBuilder::new(FileType::Dir, &path).create().unwrap();
     });
 }
@@ -36,9 +37,8 @@ fn cant_create_files(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&path, &format!("allow::user:{}:append", user.gid));
 
-    ctx.as_user(user, None, move || {
-        let e = FileBuilder::new(ft, &path).create().unwrap_err();
-        assert_eq!(Errno::EACCES, e);
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, FileBuilder::new(ft, &path).create(), Errno::EACCES);
     });
 }
 
@@ -57,9 +57,8 @@ fn cant_rename_files(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:append", user.uid));
 
-    ctx.as_user(user, None, move || {
-        let e = rename(&oldpath, &newpath).unwrap_err();
-        assert_eq!(Errno::EACCES, e);
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, rename(&oldpath, &newpath), Errno::EACCES);
     });
 }
 
@@ -82,7 +81,7 @@ fn can_rename_directories(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:append", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rename(&oldpath, &newpath).unwrap();
     });
 }
@@ -100,8 +99,8 @@ fn rmdir_err(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:append", user.uid));
 
-    ctx.as_user(user, None, move || {
-        assert_eq!(Err(Errno::EACCES), rmdir(&path));
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, rmdir(&path), Errno::EACCES);
     });
 }
 
@@ -119,7 +118,7 @@ fn unlink_err(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&dir, &format!("allow::user:{}:append", user.uid));
 
-    ctx.as_user(user, None, move || {
-        assert_eq!(Err(Errno::EACCES), unlink(&path));
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, unlink(&path), Errno::EACCES);
     });
 }
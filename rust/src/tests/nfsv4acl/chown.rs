@@ -4,14 +4,15 @@ use std::{fs, os::unix::fs::MetadataExt};
 use nix::{
     errno::Errno,
     fcntl::OFlag,
-    unistd::{chown, Gid, Uid},
+    unistd::{Gid, Uid},
 };
 
 use super::prependacl;
 use crate::{
     context::{FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::chmod,
+    tests::assert_errno,
+    utils::{chmod, chown},
     Mode,
 };
 
@@ -90,14 +91,14 @@ fn gid(ctx: &mut SerializedTestContext) {
 
     // Without any ACL, user0 can't change the gid
     ctx.as_user(user0, None, || {
-        assert_eq!(Err(Errno::EPERM), chown(&path, None, Some(user0.gid)));
+        assert_errno(ctx, chown(&path, None, Some(user0.gid)), Errno::EPERM);
     });
 
     prependacl(&path, &format!("allow::user:{}:chown", user0.uid));
 
     // Even with the ACL, user0 can't change gid to somebody else's
     ctx.as_user(user0, None, || {
-        assert_eq!(Err(Errno::EPERM), chown(&path, None, Some(user1.gid)));
+        assert_errno(ctx, chown(&path, None, Some(user1.gid)), Errno::EPERM);
     });
 
     // But he can change it to his own
@@ -118,14 +119,14 @@ fn uid(ctx: &mut SerializedTestContext) {
 
     // Without any ACL, user0 can't change the uid
     ctx.as_user(user0, None, || {
-        assert_eq!(Err(Errno::EPERM), chown(&path, Some(user0.uid), None));
+        assert_errno(ctx, chown(&path, Some(user0.uid), None), Errno::EPERM);
     });
 
     prependacl(&path, &format!("allow::user:{}:chown", user0.uid));
 
     // Even with the ACL, user0 can't change uid to somebody else's
     ctx.as_user(user0, None, || {
-        assert_eq!(Err(Errno::EPERM), chown(&path, Some(user1.uid), None));
+        assert_errno(ctx, chown(&path, Some(user1.uid), None), Errno::EPERM);
     });
 
     // But he can change it to his own
@@ -1,11 +1,10 @@
 //! Tests for ACL_WRITE_DATA
-use nix::unistd::unlink;
 
 use super::prependacl;
 use crate::{
     context::{FileBuilder, FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::{rename, rmdir},
+    utils::{rename, rmdir, unlink},
 };
 
 crate::test_case! {
@@ -25,7 +24,7 @@ fn denied_delete_does_not_prohibit_rmdir(ctx: &mut SerializedTestContext) {
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
     prependacl(&file, &format!("deny::user:{}:delete", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rmdir(&file).unwrap();
     });
 }
@@ -47,7 +46,7 @@ fn denied_delete_does_not_prohibit_unlink(ctx: &mut SerializedTestContext) {
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
     prependacl(&file, &format!("deny::user:{}:delete", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         unlink(&file).unwrap();
     });
 }
@@ -77,7 +76,7 @@ fn denied_delete_does_not_prohibit_rename(ctx: &mut SerializedTestContext, ft: F
         prependacl(&dir0, &format!("allow::user:{}:append", user.uid));
     }
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rename(&file, &newpath).unwrap();
         rename(&newpath, &file).unwrap();
     });
@@ -95,7 +94,7 @@ fn delete_rmdir(ctx: &mut SerializedTestContext) {
 
     prependacl(&path, &format!("allow::user:{}:delete", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rmdir(&path).unwrap();
     });
 }
@@ -112,7 +111,7 @@ fn delete_unlink(ctx: &mut SerializedTestContext) {
 
     prependacl(&path, &format!("allow::user:{}:delete", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         unlink(&path).unwrap();
     });
 }
@@ -134,7 +133,7 @@ fn delete_rename(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&file, &format!("allow::user:{}:delete", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rename(&file, &newpath).unwrap();
     });
 }
@@ -1,11 +1,12 @@
 //! Tests for ACL_DELETE_CHILD
-use nix::{errno::Errno, unistd::unlink};
+use nix::errno::Errno;
 
 use super::prependacl;
 use crate::{
     context::{FileBuilder, FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::{rename, rmdir},
+    tests::assert_errno,
+    utils::{rename, rmdir, unlink},
 };
 
 crate::test_case! {
@@ -25,9 +26,9 @@ fn allows_rename(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&dir0, &format!("allow::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rename(&file, &newpath).unwrap();
-        assert_eq!(Err(Errno::EACCES), rename(&newpath, &file));
+        assert_errno(ctx, rename(&newpath, &file), Errno::EACCES);
     });
 }
 
@@ -47,7 +48,7 @@ fn allows_rmdir(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir0, &format!("allow::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rmdir(&dir1).unwrap();
     });
 }
@@ -65,7 +66,7 @@ fn allows_unlink(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         unlink(&file).unwrap();
     });
 }
@@ -83,8 +84,8 @@ fn denied_unlink(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("deny::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
-        assert_eq!(Errno::EPERM, unlink(&file).unwrap_err());
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, unlink(&file), Errno::EPERM);
     });
 }
 
@@ -101,8 +102,8 @@ fn denied_rmdir(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("deny::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
-        assert_eq!(Errno::EPERM, rmdir(&file).unwrap_err());
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, rmdir(&file), Errno::EPERM);
     });
 }
 
@@ -122,7 +123,7 @@ fn denied_rename(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&dir0, &format!("deny::user:{}:delete_child", user.uid));
 
-    ctx.as_user(user, None, move || {
-        assert_eq!(Err(Errno::EPERM), rename(&file, &newpath));
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, rename(&file, &newpath), Errno::EPERM);
     });
 }
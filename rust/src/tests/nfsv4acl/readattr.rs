@@ -1,10 +1,12 @@
 //! Tests for readattr (called ACL_READ_ATTRIBUTES) on FreeBSD
-use nix::{errno::Errno, sys::stat::stat};
+use nix::errno::Errno;
 
 use super::prependacl;
 use crate::{
     context::{FileType, SerializedTestContext},
     test::FileSystemFeature,
+    tests::assert_errno,
+    utils::stat,
 };
 
 crate::test_case! {
@@ -23,8 +25,7 @@ fn allowed(ctx: &mut SerializedTestContext) {
     prependacl(&path, &format!("deny::group:{}:readattr", user.gid));
 
     ctx.as_user(user, None, || {
-        let e = stat(&path).unwrap_err(); // "user" can no longer stat it
-        assert_eq!(Errno::EACCES, e);
+        assert_errno(ctx, stat(&path), Errno::EACCES); // "user" can no longer stat it
     });
 
     prependacl(&path, &format!("allow::user:{}:readattr", user.uid));
@@ -52,7 +53,6 @@ fn denied(ctx: &mut SerializedTestContext) {
     stat(&path).unwrap(); // Owner can still stat it
 
     ctx.as_user(user, None, || {
-        let e = stat(&path).unwrap_err(); // "user" can no longer stat it
-        assert_eq!(Errno::EACCES, e);
+        assert_errno(ctx, stat(&path), Errno::EACCES); // "user" can no longer stat it
     });
 }
@@ -4,12 +4,12 @@
 use std::io::ErrorKind;
 
 use exacl::{getfacl, AclOption};
-use nix::{sys::stat::stat, unistd::chown};
 
 use super::prependacl;
 use crate::{
     context::{FileType, SerializedTestContext},
     test::FileSystemFeature,
+    utils::{chown, stat},
 };
 
 crate::test_case! {
@@ -1,11 +1,12 @@
 //! Tests for ACL_WRITE_DATA
-use nix::{errno::Errno, unistd::unlink};
+use nix::errno::Errno;
 
 use super::prependacl;
 use crate::{
     context::{FileBuilder, FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::{rename, rmdir},
+    tests::assert_errno,
+    utils::{rename, rmdir, unlink},
 };
 
 crate::test_case! {
@@ -20,7 +21,7 @@ fn can_create_files(ctx: &mut SerializedTestContext, ft: FileType) {
 
     prependacl(&path, &format!("allow::user:{}:write_data", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         FileBuilder::new(ft, &path).create().unwrap();
     });
 }
@@ -38,8 +39,11 @@ fn cant_create_directories(ctx: &mut SerializedTestContext) {
     prependacl(&path, &format!("allow::user:{}:write_data", user.uid));
 
     ctx.as_user(user, None, || {
-        let e = FileBuilder::new(FileType::Dir, &path).create().unwrap_err();
-        assert_eq!(Errno::EACCES, e);
+        assert_errno(
+            ctx,
+            FileBuilder::new(FileType::Dir, &path).create(),
+            Errno::EACCES,
+        );
     });
 }
 
@@ -58,7 +62,7 @@ fn can_rename_files(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rename(&oldpath, &newpath).unwrap();
     });
 }
@@ -78,9 +82,8 @@ fn cant_rename_directories(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
 
-    ctx.as_user(user, None, move || {
-        let e = rename(&oldpath, &newpath).unwrap_err();
-        assert_eq!(Errno::EACCES, e);
+    ctx.as_user(user, None, || {
+        assert_errno(ctx, rename(&oldpath, &newpath), Errno::EACCES);
     });
 }
 
@@ -97,7 +100,7 @@ fn rmdir_ok(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         rmdir(&path).unwrap();
     });
 }
@@ -114,7 +117,7 @@ fn unlink_ok(ctx: &mut SerializedTestContext) {
 
     prependacl(&dir, &format!("allow::user:{}:write_data", user.uid));
 
-    ctx.as_user(user, None, move || {
+    ctx.as_user(user, None, || {
         unlink(&path).unwrap();
     });
 }
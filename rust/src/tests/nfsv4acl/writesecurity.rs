@@ -4,17 +4,14 @@
 use std::{io::ErrorKind, str::FromStr};
 
 use exacl::{AclEntry, AclOption};
-use nix::{
-    errno::Errno,
-    sys::stat::{stat, Mode},
-    unistd::chown,
-};
+use nix::{errno::Errno, sys::stat::Mode};
 
 use super::prependacl;
 use crate::{
     context::{FileType, SerializedTestContext},
     test::FileSystemFeature,
-    utils::{chmod, ALLPERMS},
+    tests::assert_errno,
+    utils::{chmod, chown, stat, ALLPERMS},
 };
 
 crate::test_case! {
@@ -34,7 +31,7 @@ fn cannot_set_sgid(ctx: &mut SerializedTestContext) {
 
     ctx.as_user(user, None, || {
         let e = chmod(&path, Mode::from_bits_truncate(0o2777));
-        assert_eq!(Err(Errno::EPERM), e);
+        assert_errno(ctx, e, Errno::EPERM);
     });
 }
 
@@ -55,7 +52,7 @@ fn cannot_set_suid(ctx: &mut SerializedTestContext) {
 
     ctx.as_user(user, None, || {
         let e = chmod(&path, Mode::from_bits_truncate(0o4777));
-        assert_eq!(Err(Errno::EPERM), e);
+        assert_errno(ctx, e, Errno::EPERM);
     });
 }
 
@@ -215,8 +212,11 @@ fn write_mode(ctx: &mut SerializedTestContext) {
 
     // by default, non-owners may not write ACLs
     ctx.as_user(user, None, || {
-        let e = chmod(&path, Mode::from_bits_truncate(0o777)).unwrap_err();
-        assert_eq!(Errno::EPERM, e);
+        assert_errno(
+            ctx,
+            chmod(&path, Mode::from_bits_truncate(0o777)),
+            Errno::EPERM,
+        );
     });
     prependacl(&path, &format!("allow::user:{}:writesecurity", user.uid));
 
@@ -0,0 +1,101 @@
+//! Tests for the semantics of descriptors opened with O_APPEND,
+//! for which each write atomically sets the offset to the end of the file.
+
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use nix::{
+    fcntl::OFlag,
+    sys::stat::{fstat, Mode},
+    unistd::{lseek, read, write, Whence},
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    utils::open,
+};
+
+/// Size of the records written by the concurrent writers.
+const RECORD_SIZE: usize = 4096;
+/// Number of records written by each concurrent writer.
+const RECORDS: usize = 256;
+
+/// Open a file with O_APPEND and the given access mode.
+fn open_append(path: &std::path::Path, oflag: OFlag) -> OwnedFd {
+    open(path, oflag | OFlag::O_APPEND, Mode::empty()).unwrap()
+}
+
+crate::test_case! {
+    /// Concurrent writes through distinct O_APPEND descriptors never overwrite each other
+    /// nor interleave within a write
+    concurrent_writers
+}
+fn concurrent_writers(ctx: &mut TestContext) {
+    const WRITERS: usize = 2;
+
+    let path = ctx.create(FileType::Regular).unwrap();
+    let fds: Vec<_> = (0..WRITERS)
+        .map(|_| open_append(&path, OFlag::O_WRONLY))
+        .collect();
+
+    ctx.race(WRITERS, |i| {
+        let record = vec![b'a' + i as u8; RECORD_SIZE];
+        for _ in 0..RECORDS {
+            assert_eq!(write(fds[i].as_fd(), &record), Ok(RECORD_SIZE));
+        }
+    });
+
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(data.len(), WRITERS * RECORDS * RECORD_SIZE);
+
+    let mut counts = [0; WRITERS];
+    for (n, record) in data.chunks(RECORD_SIZE).enumerate() {
+        let writer = (record[0] - b'a') as usize;
+        assert!(writer < WRITERS, "record {n} has unexpected data");
+        assert!(
+            record.iter().all(|b| *b == record[0]),
+            "record {n} has been torn"
+        );
+        counts[writer] += 1;
+    }
+    assert_eq!(counts, [RECORDS; WRITERS]);
+}
+
+crate::test_case! {
+    /// write through an O_APPEND descriptor appends to the file even after seeking backwards
+    lseek_then_write
+}
+fn lseek_then_write(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&path, b"data").unwrap();
+    let fd = open_append(&path, OFlag::O_WRONLY);
+
+    assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekSet), Ok(0));
+    assert_eq!(write(fd.as_fd(), b"more"), Ok(4));
+
+    assert_eq!(std::fs::read(&path).unwrap(), b"datamore");
+    assert_eq!(lseek(fd.as_raw_fd(), 0, Whence::SeekCur), Ok(8));
+    assert_eq!(fstat(fd.as_raw_fd()).unwrap().st_size, 8);
+}
+
+crate::test_case! {
+    /// read through an O_RDWR|O_APPEND descriptor reads from the current offset,
+    /// which is moved to the end of the file by each write
+    rdwr_read_offset
+}
+fn rdwr_read_offset(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&path, b"data").unwrap();
+    let fd = open_append(&path, OFlag::O_RDWR);
+    let mut buf = [0; 8];
+
+    // The offset starts at the beginning of the file
+    assert_eq!(read(fd.as_raw_fd(), &mut buf[..2]), Ok(2));
+    assert_eq!(&buf[..2], b"da");
+
+    assert_eq!(write(fd.as_fd(), b"more"), Ok(4));
+    assert_eq!(read(fd.as_raw_fd(), &mut buf), Ok(0));
+
+    assert_eq!(lseek(fd.as_raw_fd(), 2, Whence::SeekSet), Ok(2));
+    assert_eq!(read(fd.as_raw_fd(), &mut buf), Ok(6));
+    assert_eq!(&buf[..6], b"tamore");
+}
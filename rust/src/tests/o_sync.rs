@@ -0,0 +1,90 @@
+//! Tests for the synchronized I/O flags of open (O_SYNC, O_DSYNC and O_RSYNC).
+
+use std::os::fd::{AsFd, AsRawFd};
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, OFlag},
+    sys::stat::Mode,
+    unistd::{read, write},
+};
+
+use super::assert_errno_for;
+use crate::{
+    context::{FileType, TestContext},
+    features::FileSystemFeature,
+    utils::open,
+};
+
+/// Return the synchronized I/O flags defined by the platform, with their name.
+pub(crate) fn sync_flags() -> Vec<(&'static str, OFlag)> {
+    vec![
+        ("O_SYNC", OFlag::O_SYNC),
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_vendor = "apple",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        ("O_DSYNC", OFlag::O_DSYNC),
+        #[cfg(any(target_os = "linux", target_os = "netbsd", target_os = "openbsd"))]
+        ("O_RSYNC", OFlag::O_RSYNC),
+    ]
+}
+
+crate::test_case! {
+    /// open either accepts the synchronized I/O flags or returns EINVAL
+    sync_flags_accepted_or_einval
+}
+fn sync_flags_accepted_or_einval(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+
+    for (name, flag) in sync_flags() {
+        let res = open(&path, OFlag::O_WRONLY | flag, Mode::empty());
+        if res.is_err() {
+            assert_errno_for(ctx, res, Errno::EINVAL, name);
+        }
+    }
+}
+
+crate::test_case! {
+    /// open accepts the synchronized I/O flags, and fcntl(F_GETFL) reports them
+    sync_flags_reported, FileSystemFeature::SyncFlags
+}
+fn sync_flags_reported(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+
+    for (name, flag) in sync_flags() {
+        let file = open(&path, OFlag::O_WRONLY | flag, Mode::empty()).unwrap();
+        let flags = OFlag::from_bits_truncate(fcntl(file.as_raw_fd(), FcntlArg::F_GETFL).unwrap());
+        assert!(flags.contains(flag), "{name} is not reported: {flags:?}");
+    }
+}
+
+crate::test_case! {
+    /// Data written through a descriptor opened with a synchronized I/O flag
+    /// can be read immediately through another descriptor
+    sync_flags_data_visible, FileSystemFeature::SyncFlags
+}
+fn sync_flags_data_visible(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Regular).unwrap();
+
+    for (name, flag) in sync_flags() {
+        let data = format!("written with {name}");
+        let writer = open(
+            &path,
+            OFlag::O_WRONLY | OFlag::O_TRUNC | flag,
+            Mode::empty(),
+        )
+        .unwrap();
+        let reader = open(&path, OFlag::O_RDONLY | flag, Mode::empty()).unwrap();
+
+        assert_eq!(write(writer.as_fd(), data.as_bytes()), Ok(data.len()));
+
+        let mut buf = vec![0; data.len() + 1];
+        assert_eq!(read(reader.as_raw_fd(), &mut buf), Ok(data.len()), "{name}");
+        assert_eq!(&buf[..data.len()], data.as_bytes(), "{name}");
+    }
+}
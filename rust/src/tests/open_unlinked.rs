@@ -0,0 +1,124 @@
+//! Tests for files which are still open after their last name has been removed,
+//! and which must stay usable through their descriptors until they are closed.
+
+use std::{os::fd::AsRawFd, path::Path, thread, time::Duration};
+
+use nix::{
+    fcntl::OFlag,
+    sys::{
+        stat::fstat,
+        statvfs::statvfs,
+        uio::{pread, pwrite},
+    },
+    unistd::{fsync, sync},
+};
+use rand::Rng;
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    rng::with_rng,
+    space,
+    utils::{rename, unlink},
+};
+
+/// Size of the file whose space is checked to be reclaimed.
+const RECLAIMED_SIZE: usize = 16 * 1024 * 1024;
+
+/// Guard to check that the file system has space for the file whose space is checked
+/// to be reclaimed, without filling it.
+fn reclaimable_space(config: &Config, base_path: &Path) -> anyhow::Result<()> {
+    space::require(config, base_path, RECLAIMED_SIZE as u64)
+}
+
+/// Return the space available on the file system, in bytes.
+fn available_space(ctx: &TestContext) -> u64 {
+    let stat = statvfs(ctx.base_path()).unwrap();
+    stat.blocks_available() as u64 * stat.fragment_size() as u64
+}
+
+crate::test_case! {
+    /// The data of an unlinked file can still be read and written through an open descriptor,
+    /// and fstat reports a link count of 0
+    data_accessible
+}
+fn data_accessible(ctx: &mut TestContext) {
+    let (path, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    pwrite(&file, b"before", 0).unwrap();
+
+    unlink(&path).unwrap();
+    assert!(!path.exists());
+
+    let mut buf = [0; 6];
+    assert_eq!(pread(&file, &mut buf, 0).unwrap(), 6);
+    assert_eq!(&buf, b"before");
+
+    assert_eq!(pwrite(&file, b"after", 6).unwrap(), 5);
+    let mut buf = [0; 11];
+    assert_eq!(pread(&file, &mut buf, 0).unwrap(), 11);
+    assert_eq!(&buf, b"beforeafter");
+
+    let st = fstat(file.as_raw_fd()).unwrap();
+    assert_eq!(st.st_nlink, 0);
+    assert_eq!(st.st_size, 11);
+}
+
+crate::test_case! {
+    /// The file displaced by a rename stays readable through an open descriptor,
+    /// and fstat reports a link count of 0
+    rename_over_open
+}
+fn rename_over_open(ctx: &mut TestContext) {
+    let from = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&from, b"new").unwrap();
+    let (to, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    pwrite(&file, b"old", 0).unwrap();
+
+    rename(&from, &to).unwrap();
+
+    assert_eq!(std::fs::read(&to).unwrap(), b"new");
+    let mut buf = [0; 3];
+    assert_eq!(pread(&file, &mut buf, 0).unwrap(), 3);
+    assert_eq!(&buf, b"old");
+
+    // Writes through the descriptor do not reach the new file
+    pwrite(&file, b"OLD", 0).unwrap();
+    assert_eq!(std::fs::read(&to).unwrap(), b"new");
+    assert_eq!(fstat(file.as_raw_fd()).unwrap().st_nlink, 0);
+}
+
+crate::test_case! {
+    /// The space of an unlinked file is kept while it is open,
+    /// and reclaimed once its last descriptor is closed
+    space_reclaimed_on_close; reclaimable_space
+}
+fn space_reclaimed_on_close(ctx: &mut TestContext) {
+    // Random data, so that the space is actually used on compressing file systems
+    let mut data = vec![0; RECLAIMED_SIZE];
+    with_rng(|rng| rng.fill(&mut data[..]));
+
+    let (path, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
+    pwrite(&file, &data, 0).unwrap();
+    fsync(file.as_raw_fd()).unwrap();
+    unlink(&path).unwrap();
+    sync();
+    let unlinked = available_space(ctx);
+
+    let mut buf = vec![0; RECLAIMED_SIZE];
+    assert_eq!(pread(&file, &mut buf, 0).unwrap(), RECLAIMED_SIZE);
+    assert!(buf == data, "the data of the unlinked file changed");
+
+    drop(file);
+
+    // Some file systems release the space asynchronously
+    let mut reclaimed = 0;
+    for _ in 0..50 {
+        sync();
+        reclaimed = available_space(ctx).saturating_sub(unlinked);
+        if reclaimed >= RECLAIMED_SIZE as u64 / 2 {
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("only {reclaimed} bytes were reclaimed after closing the unlinked file of {RECLAIMED_SIZE} bytes");
+}
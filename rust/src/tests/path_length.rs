@@ -0,0 +1,176 @@
+//! Property-style tests for the boundaries of the name and path lengths.
+//!
+//! Unlike the `enametoolong_*` test cases, which check a single length past the limit,
+//! these tests sample many lengths around `{NAME_MAX}` and `{PATH_MAX}`,
+//! with paths made of deeply nested short components or of a few long ones,
+//! and assert that each syscall succeeds up to the limit and returns `ENAMETOOLONG` past it.
+
+use std::path::Path;
+
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::stat::Mode,
+    unistd::{pathconf, PathconfVar},
+};
+use rand::{
+    distributions::{Alphanumeric, DistString},
+    Rng,
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    rng::with_rng,
+    utils::{chmod, link, lstat, mkdir, mkfifo, open, rename, rmdir, symlink, unlink},
+};
+
+use super::assert_errno_for;
+
+/// Length of the components of the deeply nested paths.
+const SHORT_COMPONENT_LEN: usize = 8;
+
+/// Number of lengths sampled at random on each side of a limit, in addition to the boundaries.
+const RANDOM_SAMPLES: usize = 2;
+
+/// Operation creating an entry at the path.
+type Creation = fn(&TestContext, &Path) -> nix::Result<()>;
+
+/// Operations creating an entry, with whether they create a directory.
+const CREATIONS: [(&str, Creation, bool); 6] = [
+    (
+        "open",
+        |_, path| open(path, OFlag::O_CREAT | OFlag::O_WRONLY, Mode::empty()).map(drop),
+        false,
+    ),
+    (
+        "mkdir",
+        |_, path| mkdir(path, Mode::from_bits_truncate(0o755)),
+        true,
+    ),
+    ("mkfifo", |_, path| mkfifo(path, Mode::empty()), false),
+    ("symlink", |_, path| symlink(Path::new("test"), path), false),
+    (
+        "link",
+        |ctx, path| link(ctx.create(FileType::Regular)?.as_path(), path),
+        false,
+    ),
+    (
+        "rename",
+        |ctx, path| rename(ctx.create(FileType::Regular)?.as_path(), path),
+        false,
+    ),
+];
+
+/// Return the value of a `pathconf` variable for the test directory.
+fn limit(ctx: &TestContext, var: PathconfVar) -> usize {
+    pathconf(ctx.base_path(), var).unwrap().unwrap() as usize
+}
+
+/// Return the boundaries of the limit (`max - 1`, `max` and `max + 1`),
+/// followed by lengths sampled at random in `min..max - 1`
+/// and, if `excess` is at least 2, in `max + 2..=max + excess`.
+fn sample_lengths(min: usize, max: usize, excess: usize) -> Vec<usize> {
+    let mut lengths = vec![max - 1, max, max + 1];
+    with_rng(|rng| {
+        for _ in 0..RANDOM_SAMPLES {
+            lengths.push(rng.gen_range(min..max - 1));
+            if excess >= 2 {
+                lengths.push(rng.gen_range(max + 2..=max + excess));
+            }
+        }
+    });
+
+    lengths
+}
+
+/// Assert that the syscalls succeed on `path` if its `len` is at most `max`,
+/// and return `ENAMETOOLONG` otherwise.
+fn assert_boundary(ctx: &TestContext, path: &Path, len: usize, max: usize, what: &str) {
+    let expected = if len <= max {
+        Ok(())
+    } else {
+        Err(Errno::ENAMETOOLONG)
+    };
+
+    for (name, create, is_dir) in CREATIONS {
+        let case = format!("with a {what} of {len} bytes (max {max})");
+        assert_outcome(
+            ctx,
+            create(ctx, path),
+            expected,
+            format_args!("{name} {case}"),
+        );
+        assert_outcome(
+            ctx,
+            lstat(path).map(drop),
+            expected,
+            format_args!("lstat after {name} {case}"),
+        );
+        assert_outcome(
+            ctx,
+            chmod(path, Mode::from_bits_truncate(0o644)),
+            // chmod follows the symlink, whose target doesn't exist
+            if name == "symlink" && expected.is_ok() {
+                Err(Errno::ENOENT)
+            } else {
+                expected
+            },
+            format_args!("chmod after {name} {case}"),
+        );
+        let remove = if is_dir { rmdir } else { unlink };
+        assert_outcome(
+            ctx,
+            remove(path),
+            expected,
+            format_args!("removal after {name} {case}"),
+        );
+    }
+}
+
+/// Assert that the syscall succeeds if `expected` is `Ok`, or fails with the expected error.
+fn assert_outcome(
+    ctx: &TestContext,
+    res: nix::Result<()>,
+    expected: nix::Result<()>,
+    case: std::fmt::Arguments,
+) {
+    match expected {
+        Ok(()) => assert!(res.is_ok(), "{case}: {res:?}"),
+        Err(errno) => assert_errno_for(ctx, res, errno, case),
+    }
+}
+
+crate::test_case! {
+    /// Syscalls succeed with names of up to {NAME_MAX} bytes and return ENAMETOOLONG past it
+    name_length_boundary
+}
+fn name_length_boundary(ctx: &mut TestContext) {
+    let name_max = limit(ctx, PathconfVar::NAME_MAX);
+
+    for len in sample_lengths(1, name_max, name_max) {
+        let name = with_rng(|rng| Alphanumeric.sample_string(rng, len));
+        let path = ctx.base_path().join(name);
+        assert_boundary(ctx, &path, len, name_max, "name");
+    }
+}
+
+crate::test_case! {
+    /// Syscalls succeed with paths shorter than {PATH_MAX} bytes, nested deeply or made of long components, and return ENAMETOOLONG past it
+    path_length_boundary
+}
+fn path_length_boundary(ctx: &mut TestContext) {
+    let name_max = limit(ctx, PathconfVar::NAME_MAX);
+    // {PATH_MAX} includes the terminating null byte
+    let path_max = limit(ctx, PathconfVar::PATH_MAX) - 1;
+    let min = ctx.base_path().as_os_str().len() + 2;
+
+    for component_len in [SHORT_COMPONENT_LEN, name_max / 2, name_max] {
+        // The parent directories of longer paths could not be created
+        for len in sample_lengths(min, path_max, 1) {
+            let path = ctx.gen_path_with_len(len, component_len).unwrap();
+            assert_eq!(path.as_os_str().len(), len);
+            let what = format!("path made of components of up to {component_len} bytes");
+            assert_boundary(ctx, &path, len, path_max, &what);
+        }
+    }
+}
@@ -3,14 +3,15 @@ use std::{fs::File, io::Write, os::fd::AsRawFd};
 use nix::{
     errno::Errno,
     fcntl::{open, posix_fallocate, OFlag},
-    sys::stat::{lstat, Mode},
+    sys::stat::Mode,
 };
 
+use super::assert_errno;
 use crate::{
     context::{FileType, SerializedTestContext},
     test::{FileSystemFeature, TestContext},
     tests::{assert_ctime_changed, assert_ctime_unchanged},
-    utils::chmod,
+    utils::{chmod, lstat},
 };
 
 crate::test_case! {
@@ -39,7 +40,7 @@ fn increase_not_empty(ctx: &mut TestContext) {
 
     let (path, file) = ctx.create_file(OFlag::O_RDWR, None).unwrap();
     let mut std_file = File::create(&path).unwrap();
-    let random_data: [u8; 1234] = rand::random();
+    let random_data: [u8; 1234] = crate::rng::random();
     std_file.write_all(&random_data).unwrap();
 
     assert!(posix_fallocate(file.as_raw_fd(), offset, size).is_ok());
@@ -70,7 +71,7 @@ fn no_update_ctime_fail(ctx: &mut TestContext) {
     let (path, file) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
 
     assert_ctime_unchanged(ctx, &path, || {
-        assert_eq!(posix_fallocate(file.as_raw_fd(), 0, 0), Err(Errno::EINVAL));
+        assert_errno(ctx, posix_fallocate(file.as_raw_fd(), 0, 0), Errno::EINVAL);
     })
 }
 
@@ -0,0 +1,129 @@
+//! Tests for pread(2) and pwrite(2), which access a file at a given offset
+//! without using nor modifying the offset of the descriptor.
+
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use nix::{
+    errno::Errno,
+    fcntl::OFlag,
+    sys::{
+        socket::{socketpair, AddressFamily, SockFlag, SockType},
+        stat::{fstat, Mode},
+        uio::{pread, pwrite},
+    },
+    unistd::{lseek, Whence},
+};
+
+use crate::{
+    context::{FileType, TestContext},
+    utils::open,
+};
+
+use super::assert_errno;
+use super::truncate::{large_files, LARGE_SIZES};
+
+/// Create a regular file containing `data` and open it for reading and writing.
+fn create_with_data(ctx: &TestContext, data: &[u8]) -> OwnedFd {
+    let path = ctx.create(FileType::Regular).unwrap();
+    std::fs::write(&path, data).unwrap();
+
+    open(&path, OFlag::O_RDWR, Mode::empty()).unwrap()
+}
+
+/// Return the current offset of a descriptor.
+fn current_offset(fd: &OwnedFd) -> i64 {
+    lseek(fd.as_raw_fd(), 0, Whence::SeekCur).unwrap()
+}
+
+crate::test_case! {
+    /// pread and pwrite do not change the offset of the descriptor
+    offset_unchanged
+}
+fn offset_unchanged(ctx: &mut TestContext) {
+    let fd = create_with_data(ctx, b"pjdfstest");
+    assert_eq!(lseek(fd.as_raw_fd(), 3, Whence::SeekSet), Ok(3));
+    let mut buf = [0; 4];
+
+    assert_eq!(pread(fd.as_fd(), &mut buf, 0), Ok(4));
+    assert_eq!(&buf, b"pjdf");
+    assert_eq!(current_offset(&fd), 3);
+
+    assert_eq!(pwrite(fd.as_fd(), b"DATA", 20), Ok(4));
+    assert_eq!(current_offset(&fd), 3);
+    assert_eq!(fstat(fd.as_raw_fd()).unwrap().st_size, 24);
+
+    assert_eq!(pread(fd.as_fd(), &mut buf, 20), Ok(4));
+    assert_eq!(&buf, b"DATA");
+    assert_eq!(current_offset(&fd), 3);
+}
+
+crate::test_case! {
+    /// pread returns zero at and beyond the end of the file, and partial reads before it
+    eof
+}
+fn eof(ctx: &mut TestContext) {
+    let fd = create_with_data(ctx, b"pjdfstest");
+    let mut buf = [0; 4];
+
+    assert_eq!(pread(fd.as_fd(), &mut buf, 7), Ok(2));
+    assert_eq!(&buf[..2], b"st");
+    assert_eq!(pread(fd.as_fd(), &mut buf, 9), Ok(0));
+    assert_eq!(pread(fd.as_fd(), &mut buf, 1 << 20), Ok(0));
+}
+
+crate::test_case! {
+    /// pread and pwrite access data beyond 2 GiB and 4 GiB
+    large_offsets; large_files
+}
+fn large_offsets(ctx: &mut TestContext) {
+    const DATA: &[u8] = b"pjdfstest";
+
+    for offset in LARGE_SIZES {
+        let fd = create_with_data(ctx, b"");
+
+        assert_eq!(pwrite(fd.as_fd(), DATA, offset), Ok(DATA.len()));
+        assert_eq!(
+            fstat(fd.as_raw_fd()).unwrap().st_size,
+            offset + DATA.len() as i64
+        );
+
+        let mut buf = [0; DATA.len()];
+        assert_eq!(pread(fd.as_fd(), &mut buf, offset), Ok(DATA.len()));
+        assert_eq!(buf, DATA);
+
+        // The bytes before have never been written and read as zeroes
+        let mut buf = [0xff; 16];
+        assert_eq!(pread(fd.as_fd(), &mut buf, offset - 16), Ok(16));
+        assert_eq!(buf, [0; 16]);
+        assert_eq!(current_offset(&fd), 0);
+    }
+}
+
+crate::test_case! {
+    /// pread and pwrite return ESPIPE on a FIFO
+    espipe_fifo
+}
+fn espipe_fifo(ctx: &mut TestContext) {
+    let fifo = ctx.create(FileType::Fifo).unwrap();
+    let fd = open(&fifo, OFlag::O_RDWR | OFlag::O_NONBLOCK, Mode::empty()).unwrap();
+
+    assert_errno(ctx, pwrite(fd.as_fd(), b"data", 0), Errno::ESPIPE);
+    assert_errno(ctx, pread(fd.as_fd(), &mut [0; 4], 0), Errno::ESPIPE);
+}
+
+crate::test_case! {
+    /// pread and pwrite return ESPIPE on a socket
+    espipe_socket
+}
+fn espipe_socket(_ctx: &mut TestContext) {
+    let (fd, _peer) = socketpair(
+        AddressFamily::Unix,
+        SockType::Stream,
+        None,
+        SockFlag::empty(),
+    )
+    .unwrap();
+
+    assert_errno(_ctx, pwrite(fd.as_fd(), b"data", 0), Errno::ESPIPE);
+    assert_errno(_ctx, pread(fd.as_fd(), &mut [0; 4], 0), Errno::ESPIPE);
+}
@@ -0,0 +1,237 @@
+//! Tests for the ioctls sharing data between files on Linux
+//! (`FICLONE`, `FICLONERANGE` and `FIDEDUPERANGE`).
+
+use std::{
+    fs::{File, OpenOptions},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::fs::FileExt,
+    },
+    path::Path,
+};
+
+use nix::{errno::Errno, sys::stat::fstat};
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    utils::write_blocks,
+};
+
+use super::assert_errno;
+use super::errors::exdev::secondary_fs_available;
+
+/// `struct file_dedupe_range_info` from `linux/fs.h`.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct FileDedupeRangeInfo {
+    dest_fd: i64,
+    dest_offset: u64,
+    bytes_deduped: u64,
+    status: i32,
+    reserved: u32,
+}
+
+/// `struct file_dedupe_range` from `linux/fs.h`, with a single destination.
+#[repr(C)]
+#[derive(Debug, Default)]
+struct FileDedupeRange {
+    src_offset: u64,
+    src_length: u64,
+    dest_count: u16,
+    reserved1: u16,
+    reserved2: u32,
+    info: [FileDedupeRangeInfo; 1],
+}
+
+/// The range has been deduplicated.
+const FILE_DEDUPE_RANGE_SAME: i32 = 0;
+/// The range differs and has not been deduplicated.
+const FILE_DEDUPE_RANGE_DIFFERS: i32 = 1;
+
+/// Request code of `FIDEDUPERANGE`, whose size is the one of `struct file_dedupe_range`
+/// without its flexible array member.
+const FIDEDUPERANGE: nix::sys::ioctl::ioctl_num_type =
+    nix::request_code_readwrite!(0x94, 54, 24) as nix::sys::ioctl::ioctl_num_type;
+
+/// Share all the data of `src` with `dest`.
+pub(crate) fn ficlone(dest: RawFd, src: RawFd) -> nix::Result<()> {
+    let res = unsafe { nix::libc::ioctl(dest, nix::libc::FICLONE as _, src) };
+    Errno::result(res).map(drop)
+}
+
+/// Share a range of the data of `src` with `dest`.
+fn ficlonerange(
+    dest: RawFd,
+    src: RawFd,
+    src_offset: u64,
+    src_length: u64,
+    dest_offset: u64,
+) -> nix::Result<()> {
+    let range = nix::libc::file_clone_range {
+        src_fd: src.into(),
+        src_offset,
+        src_length,
+        dest_offset,
+    };
+    let res = unsafe { nix::libc::ioctl(dest, nix::libc::FICLONERANGE as _, &range) };
+    Errno::result(res).map(drop)
+}
+
+/// Deduplicate a range of `src` with the same range of `dest`,
+/// and return the status and the number of deduplicated bytes.
+fn fideduperange(src: RawFd, dest: RawFd, offset: u64, length: u64) -> nix::Result<(i32, u64)> {
+    let mut range = FileDedupeRange {
+        src_offset: offset,
+        src_length: length,
+        dest_count: 1,
+        info: [FileDedupeRangeInfo {
+            dest_fd: dest.into(),
+            dest_offset: offset,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let res = unsafe { nix::libc::ioctl(src, FIDEDUPERANGE as _, &mut range) };
+    Errno::result(res)?;
+
+    Ok((range.info[0].status, range.info[0].bytes_deduped))
+}
+
+/// Guard to check that the file system does not support reflinks,
+/// that is it has not been declared as supporting them and cloning an empty file fails.
+fn reflink_unsupported(config: &Config, base_path: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::Reflink)
+    {
+        anyhow::bail!("the file system has been declared as supporting reflinks")
+    }
+
+    let dir = tempfile::tempdir_in(base_path)?;
+    let src = File::create(dir.path().join("src"))?;
+    let dest = File::create(dir.path().join("dest"))?;
+    if ficlone(dest.as_raw_fd(), src.as_raw_fd()).is_ok() {
+        anyhow::bail!("the file system supports reflinks")
+    }
+
+    Ok(())
+}
+
+/// Create a regular file with `blocks` blocks of data, each block being filled with its number (from 1),
+/// and return it with its block size.
+fn create_with_blocks(ctx: &TestContext, blocks: u8) -> (File, u64) {
+    let path = ctx.create(FileType::Regular).unwrap();
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    let block_size = write_blocks(&file, blocks).unwrap();
+
+    (file, block_size)
+}
+
+/// Read the content of a file.
+fn read_all(file: &File) -> Vec<u8> {
+    let size = file.metadata().unwrap().len() as usize;
+    let mut data = vec![0; size];
+    file.read_exact_at(&mut data, 0).unwrap();
+
+    data
+}
+
+crate::test_case! {
+    /// FICLONE shares the data of a file, which stay isolated on subsequent writes
+    ficlone_file, FileSystemFeature::Reflink
+}
+fn ficlone_file(ctx: &mut TestContext) {
+    let (src, block_size) = create_with_blocks(ctx, 2);
+    let (dest, _) = create_with_blocks(ctx, 0);
+
+    assert!(ficlone(dest.as_raw_fd(), src.as_raw_fd()).is_ok());
+    assert_eq!(read_all(&dest), read_all(&src));
+    assert_ne!(
+        fstat(src.as_raw_fd()).unwrap().st_ino,
+        fstat(dest.as_raw_fd()).unwrap().st_ino
+    );
+
+    let original = read_all(&src);
+    dest.write_all_at(&vec![0xff; block_size as usize], 0)
+        .unwrap();
+    assert_eq!(read_all(&src), original);
+}
+
+crate::test_case! {
+    /// FICLONERANGE shares a range of the data of a file at the given offset
+    ficlonerange_blocks, FileSystemFeature::Reflink
+}
+fn ficlonerange_blocks(ctx: &mut TestContext) {
+    let (src, block_size) = create_with_blocks(ctx, 2);
+    let (dest, _) = create_with_blocks(ctx, 2);
+
+    // Clone the second block of the source over the first block of the destination
+    assert!(ficlonerange(dest.as_raw_fd(), src.as_raw_fd(), block_size, block_size, 0).is_ok());
+
+    assert_eq!(read_all(&dest), vec![2; 2 * block_size as usize]);
+}
+
+crate::test_case! {
+    /// FIDEDUPERANGE deduplicates identical ranges and leaves different ones untouched
+    fideduperange_ranges, FileSystemFeature::Reflink
+}
+fn fideduperange_ranges(ctx: &mut TestContext) {
+    let (src, block_size) = create_with_blocks(ctx, 2);
+    let (dest, _) = create_with_blocks(ctx, 2);
+
+    assert_eq!(
+        fideduperange(src.as_raw_fd(), dest.as_raw_fd(), 0, 2 * block_size),
+        Ok((FILE_DEDUPE_RANGE_SAME, 2 * block_size))
+    );
+    assert_eq!(read_all(&dest), read_all(&src));
+
+    dest.write_all_at(&vec![0xff; block_size as usize], 0)
+        .unwrap();
+    let data = read_all(&dest);
+    assert_eq!(
+        fideduperange(src.as_raw_fd(), dest.as_raw_fd(), 0, block_size)
+            .unwrap()
+            .0,
+        FILE_DEDUPE_RANGE_DIFFERS
+    );
+    assert_eq!(read_all(&dest), data);
+}
+
+crate::test_case! {
+    /// FICLONE returns EOPNOTSUPP if the file system does not support reflinks
+    ficlone_eopnotsupp; reflink_unsupported
+}
+fn ficlone_eopnotsupp(ctx: &mut TestContext) {
+    let (src, _) = create_with_blocks(ctx, 1);
+    let (dest, _) = create_with_blocks(ctx, 0);
+
+    assert_errno(
+        ctx,
+        ficlone(dest.as_raw_fd(), src.as_raw_fd()),
+        Errno::EOPNOTSUPP,
+    );
+    assert!(read_all(&dest).is_empty());
+}
+
+crate::test_case! {
+    /// FICLONE returns EXDEV if the files are not on the same file system
+    ficlone_exdev; secondary_fs_available
+}
+fn ficlone_exdev(ctx: &mut TestContext) {
+    let (src, _) = create_with_blocks(ctx, 1);
+    let secondary_fs = ctx.features_config().secondary_fs.as_ref().unwrap();
+    let dest = tempfile::tempfile_in(secondary_fs).unwrap();
+
+    assert_errno(
+        ctx,
+        ficlone(dest.as_raw_fd(), src.as_raw_fd()),
+        Errno::EXDEV,
+    );
+}
@@ -1,27 +1,35 @@
-use std::fs::symlink_metadata;
+use std::{
+    collections::HashSet,
+    fs::symlink_metadata,
+    os::fd::AsRawFd,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 use nix::{
     errno::Errno,
-    sys::stat::{lstat, stat},
+    fcntl::{renameat, OFlag},
+    sys::stat::{fstat, Mode},
 };
 
 use crate::{
-    context::{FileType, SerializedTestContext, TestContext},
+    context::{FileType, SerializedTestContext, TestContext, TreeEntry},
     test::FileSystemFeature,
     tests::{assert_symlink_ctime_unchanged, AsTimeInvariant, MetadataExt},
-    utils::{link, rename},
+    utils::{lstat, open, rename, stat},
 };
 
 use super::{
-    assert_ctime_changed,
+    assert_ctime_changed, assert_errno, assert_errno_choice,
     errors::{
         efault::efault_either_test_case,
         eloop::eloop_either_test_case,
         enametoolong::{enametoolong_either_comp_test_case, enametoolong_either_path_test_case},
         enoent::enoent_either_named_file_test_case,
-        enotdir::enotdir_comp_either_test_case,
+        enotdir::{enotdir_comp_either_test_case, enotdir_trailing_slash_test_case},
         erofs::erofs_named_test_case,
-        exdev::exdev_target_test_case,
+        exdev::{exdev_dir_tree_test_case, exdev_dirfd_test_case, exdev_target_test_case},
+        sticky::sticky_either_test_case,
     },
 };
 
@@ -45,8 +53,9 @@ fn preserve_metadata(ctx: &mut TestContext, ft: FileType) {
         new_path_stat.as_time_invariant()
     );
 
-    let link_path = ctx.base_path().join("link");
-    link(&new_path, &link_path).unwrap();
+    let link_path = ctx
+        .create(FileType::HardLink(Some(new_path.clone())))
+        .unwrap();
 
     let link_stat = lstat(&link_path).unwrap();
     let new_path_stat = lstat(&new_path).unwrap();
@@ -146,16 +155,16 @@ crate::test_case! {
     write_access_required_subdir, serialized, root
 }
 fn write_access_required_subdir(ctx: &mut SerializedTestContext) {
-    let dir = ctx.new_file(FileType::Dir).mode(0o777).create().unwrap();
-    let subdir = ctx
-        .new_file(FileType::Dir)
-        .name(dir.join("subdir"))
-        .mode(0o700)
-        .create()
+    let tree = ctx
+        .build_tree([
+            TreeEntry::dir("dir", [TreeEntry::dir("subdir", []).mode(0o700)]).mode(0o777),
+            TreeEntry::dir("new_dir", []).mode(0o777),
+        ])
         .unwrap();
+    let (dir, subdir) = (tree.path("dir"), tree.path("dir/subdir"));
     let another_subdir_path = dir.join("another_subdir_path");
 
-    let new_dir = ctx.new_file(FileType::Dir).mode(0o777).create().unwrap();
+    let new_dir = tree.path("new_dir");
     let new_dir_subpath = new_dir.join("subpath");
 
     let user = ctx.get_new_user();
@@ -163,25 +172,25 @@ fn write_access_required_subdir(ctx: &mut SerializedTestContext) {
         // Check that write permission on containing directory is enough
         // to rename subdirectory. If we rename directory write access
         // to this directory may also be required.
-        assert!(matches!(
-            rename(&subdir, &another_subdir_path),
-            Ok(_) | Err(Errno::EACCES)
-        ));
+        let res = rename(&subdir, &another_subdir_path);
+        if res.is_err() {
+            assert_errno(ctx, res, Errno::EACCES);
+        }
 
-        assert!(matches!(
-            rename(&another_subdir_path, &subdir),
-            Ok(_) | Err(Errno::EACCES)
-        ));
+        let res = rename(&another_subdir_path, &subdir);
+        if res.is_err() {
+            assert_errno(ctx, res, Errno::EACCES);
+        }
 
         //TODO: Is it really useful?
         // Check that write permission on containing directory is not enough
         // to move subdirectory from that directory.
         // Actually POSIX says that write access to `dir` and `new_dir` may be enough
         // to move `subdir`.
-        assert!(matches!(
-            rename(&subdir, &new_dir_subpath),
-            Ok(_) | Err(Errno::EACCES)
-        ));
+        let res = rename(&subdir, &new_dir_subpath);
+        if res.is_err() {
+            assert_errno(ctx, res, Errno::EACCES);
+        }
     });
 
     // Check that write permission on containing directory (${n2}) is enough
@@ -228,8 +237,7 @@ fn to_multiply_linked(ctx: &mut TestContext, ft: FileType) {
     let src = ctx.create(ft.clone()).unwrap();
     let dst = ctx.create(ft).unwrap();
 
-    let dst_link = ctx.base_path().join("dst_link");
-    link(&dst, &dst_link).unwrap();
+    let dst_link = ctx.create(FileType::HardLink(Some(dst.clone()))).unwrap();
     let dst_link_stat = lstat(&dst_link).unwrap();
     assert_eq!(dst_link_stat.st_nlink, 2);
 
@@ -247,14 +255,15 @@ crate::test_case! {
     updates_link_parent
 }
 fn updates_link_parent(ctx: &mut TestContext) {
-    let src_parent = ctx.create(FileType::Dir).unwrap();
-    let dst_parent = ctx.create(FileType::Dir).unwrap();
-    let dst = dst_parent.join("dst");
-    let src = ctx
-        .new_file(FileType::Dir)
-        .name(src_parent.join("src"))
-        .create()
+    let tree = ctx
+        .build_tree([
+            TreeEntry::dir("src_parent", [TreeEntry::dir("src", [])]),
+            TreeEntry::dir("dst_parent", []),
+        ])
         .unwrap();
+    let (src_parent, dst_parent) = (tree.path("src_parent"), tree.path("dst_parent"));
+    let src = tree.path("src_parent/src");
+    let dst = dst_parent.join("dst");
 
     // Initial conditions
     let src_parent_stat = lstat(&src_parent).unwrap();
@@ -276,9 +285,135 @@ fn updates_link_parent(ctx: &mut TestContext) {
     assert_eq!(dst_parent_stat.st_ino, dotdot_stat.st_ino);
 }
 
+crate::test_case! {
+    /// rename of a directory onto an empty directory replaces it,
+    /// keeps the inode of the source and updates the link counts of both parents
+    // rename/20.t
+    dir_onto_empty_dir
+}
+fn dir_onto_empty_dir(ctx: &mut TestContext) {
+    let tree = ctx
+        .build_tree([
+            TreeEntry::dir("src_parent", [TreeEntry::dir("src", [])]),
+            TreeEntry::dir("dst_parent", [TreeEntry::dir("dst", [])]),
+        ])
+        .unwrap();
+    let (src_parent, dst_parent) = (tree.path("src_parent"), tree.path("dst_parent"));
+    let (src, dst) = (tree.path("src_parent/src"), tree.path("dst_parent/dst"));
+
+    let src_ino = lstat(&src).unwrap().st_ino;
+    let dst_fd = open(&dst, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()).unwrap();
+    assert_eq!(lstat(&src_parent).unwrap().st_nlink, 3);
+    assert_eq!(lstat(&dst_parent).unwrap().st_nlink, 3);
+
+    rename(&src, &dst).unwrap();
+
+    assert!(!src.exists());
+    let dst_stat = lstat(&dst).unwrap();
+    assert_eq!(dst_stat.st_ino, src_ino);
+    assert_eq!(dst_stat.st_nlink, 2);
+    assert_eq!(
+        lstat(&dst.join("..")).unwrap().st_ino,
+        lstat(&dst_parent).unwrap().st_ino
+    );
+    assert_eq!(lstat(&src_parent).unwrap().st_nlink, 2);
+    assert_eq!(lstat(&dst_parent).unwrap().st_nlink, 3);
+
+    // The replaced directory is released once its last descriptor is closed
+    assert_eq!(fstat(dst_fd.as_raw_fd()).unwrap().st_nlink, 0);
+}
+
+crate::test_case! {
+    /// rename of a directory onto an empty directory of the same parent replaces it,
+    /// and the link count of the parent loses the replaced directory
+    // rename/20.t
+    dir_onto_empty_dir_same_parent
+}
+fn dir_onto_empty_dir_same_parent(ctx: &mut TestContext) {
+    let tree = ctx
+        .build_tree([TreeEntry::dir(
+            "parent",
+            [TreeEntry::dir("src", []), TreeEntry::dir("dst", [])],
+        )])
+        .unwrap();
+    let parent = tree.path("parent");
+    let (src, dst) = (tree.path("parent/src"), tree.path("parent/dst"));
+
+    let src_ino = lstat(&src).unwrap().st_ino;
+    let dst_fd = open(&dst, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()).unwrap();
+    assert_eq!(lstat(&parent).unwrap().st_nlink, 4);
+
+    rename(&src, &dst).unwrap();
+
+    assert!(!src.exists());
+    assert_eq!(lstat(&dst).unwrap().st_ino, src_ino);
+    assert_eq!(lstat(&parent).unwrap().st_nlink, 3);
+    assert_eq!(fstat(dst_fd.as_raw_fd()).unwrap().st_nlink, 0);
+}
+
+crate::test_case! {
+    /// rename atomically replaces the 'to' argument: concurrent lookups of it
+    /// always find either the old or the new file, and never fail with ENOENT
+    atomic_replace
+}
+fn atomic_replace(ctx: &mut TestContext) {
+    const RENAMES: usize = 256;
+    const READERS: usize = 3;
+
+    let to = ctx.create(FileType::Regular).unwrap();
+    let sources: Vec<_> = (0..RENAMES)
+        .map(|_| ctx.create(FileType::Regular).unwrap())
+        .collect();
+    let inodes: HashSet<_> = sources
+        .iter()
+        .chain([&to])
+        .map(|path| lstat(path).unwrap().st_ino)
+        .collect();
+    let last_ino = lstat(sources.last().unwrap()).unwrap().st_ino;
+    let done = AtomicBool::new(false);
+
+    let seen = ctx.race(READERS + 1, |i| {
+        let mut seen = HashSet::new();
+
+        if i == 0 {
+            let res = catch_unwind(AssertUnwindSafe(|| {
+                for from in &sources {
+                    rename(from, &to).unwrap();
+                }
+            }));
+            // Stop the readers even if a rename failed
+            done.store(true, Ordering::Release);
+            if let Err(e) = res {
+                resume_unwind(e)
+            }
+        } else {
+            while !done.load(Ordering::Acquire) {
+                let st = stat(&to).unwrap_or_else(|e| panic!("stat failed during rename: {e}"));
+                seen.insert(st.st_ino);
+
+                let fd = open(&to, OFlag::O_RDONLY, Mode::empty())
+                    .unwrap_or_else(|e| panic!("open failed during rename: {e}"));
+                seen.insert(fstat(fd.as_raw_fd()).unwrap().st_ino);
+            }
+        }
+
+        seen
+    });
+
+    for ino in seen.iter().flatten() {
+        assert!(inodes.contains(ino), "unknown inode {ino} found");
+    }
+    assert_eq!(lstat(&to).unwrap().st_ino, last_ino);
+    assert!(sources.iter().all(|from| !from.exists()));
+}
+
 // rename/12.t
 enotdir_comp_either_test_case!(rename);
 
+enotdir_trailing_slash_test_case!(rename, |ctx: &mut TestContext, path: &std::path::Path| {
+    rename(path, &*ctx.gen_path())
+});
+
 crate::test_case! {
     /// rename returns ENOTDIR when the 'from' argument is a directory,
     /// but 'to' is not a directory
@@ -289,7 +424,7 @@ fn enotdir_from_dir_to_not_dir(ctx: &mut TestContext, ft: FileType) {
     let path = ctx.create(ft).unwrap();
     let dir = ctx.create(FileType::Dir).unwrap();
 
-    assert_eq!(rename(&dir, &path).unwrap_err(), Errno::ENOTDIR);
+    assert_errno(ctx, rename(&dir, &path), Errno::ENOTDIR);
 }
 
 // rename/01.t
@@ -312,7 +447,7 @@ crate::test_case! {
 fn eisdir_to_dir_from_not_dir(ctx: &mut TestContext, ft: FileType) {
     let dir = ctx.create(FileType::Dir).unwrap();
     let not_dir_file = ctx.create(ft).unwrap();
-    assert_eq!(rename(&not_dir_file, &dir), Err(Errno::EISDIR));
+    assert_errno(ctx, rename(&not_dir_file, &dir), Errno::EISDIR);
 }
 
 // rename/16.t
@@ -337,8 +472,8 @@ fn einval_parent_from_subdir_to(ctx: &mut TestContext) {
         .create()
         .unwrap();
 
-    assert_eq!(rename(ctx.base_path(), &subdir), Err(Errno::EINVAL));
-    assert_eq!(rename(ctx.base_path(), &nested_subdir), Err(Errno::EINVAL));
+    assert_errno(ctx, rename(ctx.base_path(), &subdir), Errno::EINVAL);
+    assert_errno(ctx, rename(ctx.base_path(), &nested_subdir), Errno::EINVAL);
 }
 
 crate::test_case! {
@@ -349,14 +484,16 @@ crate::test_case! {
 fn einval_ebusy_dot_dotdot(ctx: &mut TestContext) {
     let subdir = ctx.create(FileType::Dir).unwrap();
 
-    assert!(matches!(
+    assert_errno_choice(
+        ctx,
         rename(&subdir.join("."), &ctx.gen_path()),
-        Err(Errno::EINVAL | Errno::EBUSY)
-    ));
-    assert!(matches!(
+        &[Errno::EINVAL, Errno::EBUSY],
+    );
+    assert_errno_choice(
+        ctx,
         rename(&subdir.join(".."), &ctx.gen_path()),
-        Err(Errno::EINVAL | Errno::EBUSY)
-    ));
+        &[Errno::EINVAL, Errno::EBUSY],
+    );
 }
 
 crate::test_case! {
@@ -369,11 +506,22 @@ fn eexist_enotempty_to_non_empty(ctx: &mut TestContext, ft: FileType) {
     let to_dir = ctx.create(FileType::Dir).unwrap();
     ctx.new_file(ft).name(to_dir.join("test")).create().unwrap();
 
-    assert!(matches!(
+    assert_errno_choice(
+        ctx,
         rename(&from_dir, &to_dir),
-        Err(Errno::EEXIST | Errno::ENOTEMPTY)
-    ));
+        &[Errno::EEXIST, Errno::ENOTEMPTY],
+    );
 }
 
 // rename/15.t
 exdev_target_test_case!(rename);
+
+exdev_dirfd_test_case!(renameat, |from_fd, from, to_fd, to| {
+    renameat(Some(from_fd), from, Some(to_fd), to)
+});
+
+exdev_dir_tree_test_case!(rename);
+
+// rename/09.t
+// rename/10.t
+sticky_either_test_case!(rename => [Regular, Dir, Fifo, Block, Char, Socket, Symlink(None)]);
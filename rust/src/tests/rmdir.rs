@@ -1,21 +1,35 @@
 use std::{
-    ffi::OsStr,
     fs::metadata,
-    os::unix::ffi::OsStrExt,
-    path::{Path, PathBuf},
-    process::Command,
+    os::fd::{AsRawFd, BorrowedFd},
+    path::Path,
 };
 
-use nix::errno::Errno;
+use nix::{
+    dir::Dir,
+    errno::Errno,
+    fcntl::{openat, OFlag},
+    sys::stat::{fstat, Mode},
+};
 
-use crate::{config::Config, context::TestContext, tests::assert_mtime_changed, utils::rmdir};
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    test::FileSystemFeature,
+    tests::assert_mtime_changed,
+    utils::rmdir,
+};
 
+use super::mountpoint::{has_mount_cap, DummyMnt};
 use super::{
-    assert_ctime_changed,
+    assert_ctime_changed, assert_errno, assert_errno_choice,
     errors::efault::efault_path_test_case,
+    errors::sticky::sticky_test_case,
     errors::{eloop::eloop_comp_test_case, erofs::erofs_named_test_case},
     errors::{enametoolong::enametoolong_comp_test_case, enoent::enoent_named_file_test_case},
-    errors::{enametoolong::enametoolong_path_test_case, enotdir::enotdir_comp_test_case},
+    errors::{
+        enametoolong::enametoolong_path_test_case,
+        enotdir::{enotdir_comp_test_case, enotdir_trailing_slash_test_case},
+    },
 };
 
 crate::test_case! {
@@ -46,84 +60,7 @@ fn changed_time_parent_success(ctx: &mut TestContext) {
 
 // rmdir/01.t
 enotdir_comp_test_case!(rmdir);
-
-/// Dummy mountpoint to check that rmdir returns EBUSY when using it on a mountpoint.
-struct DummyMnt {
-    path: PathBuf,
-}
-
-impl DummyMnt {
-    fn new(ctx: &mut TestContext) -> anyhow::Result<Self> {
-        // We don't really care about a specific type of file system here, the directory just have to be a mount point
-        let from = ctx.create(crate::context::FileType::Dir)?;
-        let path = ctx.create(crate::context::FileType::Dir)?;
-        let mut mount = Command::new("mount");
-
-        if cfg!(target_os = "linux") {
-            mount.arg("--bind");
-        } else {
-            mount.args(["-t", "nullfs"]);
-        }
-
-        let result = mount.arg(&from).arg(&path).output()?;
-        let stderr = OsStr::from_bytes(&result.stderr).to_string_lossy();
-        assert!(result.status.success(), "{}", stderr);
-
-        Ok(Self { path })
-    }
-}
-
-impl Drop for DummyMnt {
-    fn drop(&mut self) {
-        let umount = Command::new("umount").arg(&self.path).output();
-        if !std::thread::panicking() {
-            assert!(matches!(umount, Ok(res) if res.status.success()));
-        }
-    }
-}
-
-#[cfg(target_os = "linux")]
-fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
-    use caps::{has_cap, CapSet, Capability};
-
-    if !has_cap(None, CapSet::Effective, Capability::CAP_SYS_ADMIN)? {
-        anyhow::bail!("process doesn't have the CAP_SYS_ADMIN cap to mount the dummy file system")
-    }
-
-    Ok(())
-}
-
-#[cfg(target_os = "freebsd")]
-fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
-    use nix::unistd::Uid;
-    use sysctl::{Ctl, CtlValue, Sysctl};
-
-    const MOUNT_CTL: &str = "vfs.usermount";
-
-    let ctl = Ctl::new(MOUNT_CTL)?;
-
-    if !Uid::effective().is_root() && ctl.value()? == CtlValue::Int(0) {
-        anyhow::bail!("process doesn't have the rights to mount the dummy file system")
-    }
-    if !Uid::effective().is_root()
-        && !OsStr::from_bytes(&Command::new("lsvfs").output().unwrap().stdout)
-            .to_string_lossy()
-            .contains("nullfs")
-    {
-        anyhow::bail!("nullfs module is not loaded")
-    }
-
-    Ok(())
-}
-
-#[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
-fn has_mount_cap(_: &Config, _: &Path) -> anyhow::Result<()> {
-    if !Uid::effective().is_root() {
-        anyhow::bail!("process is not root, cannot mount dummy file system")
-    }
-
-    Ok(())
-}
+enotdir_trailing_slash_test_case!(rmdir);
 
 // rmdir/02.t
 enametoolong_comp_test_case!(rmdir);
@@ -149,10 +86,11 @@ fn eexist_enotempty_non_empty_dir(ctx: &mut TestContext, ft: crate::context::Fil
         .create()
         .unwrap();
 
-    assert!(matches!(
+    assert_errno_choice(
+        ctx,
         rmdir(ctx.base_path()),
-        Err(Errno::EEXIST | Errno::ENOTEMPTY)
-    ));
+        &[Errno::EEXIST, Errno::ENOTEMPTY],
+    );
 }
 
 crate::test_case! {
@@ -161,7 +99,7 @@ crate::test_case! {
     einval_dot
 }
 fn einval_dot(ctx: &mut TestContext) {
-    assert_eq!(rmdir(&ctx.base_path().join(".")), Err(Errno::EINVAL));
+    assert_errno(ctx, rmdir(&ctx.base_path().join(".")), Errno::EINVAL);
 }
 
 crate::test_case! {
@@ -178,10 +116,11 @@ fn eexist_enotempty_dotdot(ctx: &mut TestContext) {
     // or there are hard links to the directory other than dot or a single entry in dot-dot.
     #[cfg(not(target_os = "freebsd"))]
     {
-        assert!(matches!(
+        assert_errno_choice(
+            ctx,
             rmdir(&ctx.base_path().join("..")),
-            Err(Errno::ENOTEMPTY | Errno::EEXIST)
-        ));
+            &[Errno::ENOTEMPTY, Errno::EEXIST],
+        );
     }
 }
 
@@ -192,7 +131,73 @@ crate::test_case! {
 }
 fn ebusy(ctx: &mut TestContext) {
     let dummy_mount = DummyMnt::new(ctx).unwrap();
-    assert_eq!(rmdir(&dummy_mount.path), Err(Errno::EBUSY));
+    assert_errno(ctx, rmdir(&dummy_mount.path), Errno::EBUSY);
+}
+
+/// Guard to check that the file system has not been declared as refusing to remove open directories.
+fn open_dir_removable(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::RmdirOpenDirBusy)
+    {
+        anyhow::bail!("the file system has been declared as refusing to remove open directories")
+    }
+
+    Ok(())
+}
+
+crate::test_case! {
+    /// rmdir removes a directory which is open, after which the directory is empty
+    /// and no entry can be created in it through the descriptor
+    open_dir; open_dir_removable
+}
+fn open_dir(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Dir).unwrap();
+    let mut dir = Dir::open(&path, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()).unwrap();
+    let fd = dir.as_raw_fd();
+
+    rmdir(&path).unwrap();
+    assert!(!path.exists());
+    assert_eq!(fstat(fd).unwrap().st_nlink, 0);
+
+    // SAFETY: The descriptor is owned by `dir`, which outlives the borrow
+    let dirfd = unsafe { BorrowedFd::borrow_raw(fd) };
+    for ft in [FileType::Regular, FileType::Dir] {
+        assert_errno(
+            ctx,
+            ctx.new_file(ft).at(&dirfd).name("entry").create(),
+            Errno::ENOENT,
+        );
+    }
+    assert_errno(
+        ctx,
+        openat(Some(fd), "file", OFlag::O_RDONLY, Mode::empty()),
+        Errno::ENOENT,
+    );
+
+    // The dot and dot-dot entries may also have been removed
+    for entry in dir.iter() {
+        let entry = entry.unwrap();
+        let name = entry.file_name().to_bytes();
+        assert!(
+            name == b"." || name == b"..",
+            "unexpected entry {:?} in the removed directory",
+            entry.file_name()
+        );
+    }
+}
+
+crate::test_case! {
+    /// rmdir returns EBUSY if the directory is open, when the file system refuses to remove it
+    open_dir_ebusy, FileSystemFeature::RmdirOpenDirBusy
+}
+fn open_dir_ebusy(ctx: &mut TestContext) {
+    let path = ctx.create(FileType::Dir).unwrap();
+    let _dir = Dir::open(&path, OFlag::O_RDONLY | OFlag::O_DIRECTORY, Mode::empty()).unwrap();
+
+    assert_errno(ctx, rmdir(&path), Errno::EBUSY);
+    assert!(path.is_dir());
 }
 
 // rmdir/14.t
@@ -200,3 +205,6 @@ erofs_named_test_case!(rmdir);
 
 // rmdir/15.t
 efault_path_test_case!(rmdir, nix::libc::rmdir);
+
+// rmdir/11.t
+sticky_test_case!(rmdir => [Dir]);
@@ -0,0 +1,38 @@
+//! Tests for the error mapping of sandboxes (Landlock, seccomp, pledge, unveil).
+//! They are only run if the process is restricted by a sandbox.
+
+use std::path::Path;
+
+use crate::{
+    config::Config,
+    context::TestContext,
+    sandbox::{restrictions, Denial},
+};
+
+/// Guard to check that the file system operations are restricted by a sandbox.
+fn sandboxed(_: &Config, base_path: &Path) -> anyhow::Result<()> {
+    if restrictions(base_path).is_none() {
+        anyhow::bail!("no sandbox restriction has been detected")
+    }
+
+    Ok(())
+}
+
+crate::test_case! {
+    /// Operations denied by a sandbox fail with the errors it documents
+    /// (EACCES for Landlock, ENOSYS for pledge, EACCES or ENOENT for unveil...),
+    /// rather than killing the process
+    denied_errors; sandboxed
+}
+fn denied_errors(ctx: &mut TestContext) {
+    let restrictions = restrictions(ctx.base_path()).unwrap();
+
+    for (syscall, denial) in &restrictions.denied {
+        // Only the documented errors are taken as denials, the other ones are the file system's
+        assert!(
+            matches!(denial, Denial::Error(_)),
+            "{syscall} is denied by {} with {denial}",
+            restrictions.sandbox()
+        );
+    }
+}
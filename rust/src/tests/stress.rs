@@ -0,0 +1,152 @@
+//! Stress tests running concurrent operations on the entries of a shared directory,
+//! after which the consistency of the directory is checked.
+//!
+//! They are opt-in, and enabled by the `[settings.stress]` section of the configuration.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{read_dir, symlink_metadata},
+    os::unix::fs::MetadataExt,
+    path::Path,
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{open, OFlag},
+    sys::stat::Mode,
+    unistd::close,
+};
+use rand::Rng;
+
+use crate::{
+    config::Config,
+    context::{FileType, TestContext},
+    rng::with_rng,
+    utils::{link, mkdir, rename, rmdir, unlink},
+};
+
+/// Number of names used by the operations, small enough for them to collide often.
+const NAMES: usize = 16;
+
+fn stress_enabled(config: &Config, _: &Path) -> anyhow::Result<()> {
+    if config.settings.stress.is_none() {
+        anyhow::bail!("Stress tests are not enabled in the configuration ([settings.stress])");
+    }
+
+    Ok(())
+}
+
+/// Return one of the names used by the operations.
+fn random_name() -> String {
+    format!("entry{}", with_rng(|rng| rng.gen_range(0..NAMES)))
+}
+
+/// Run a random operation on random entries of the directory.
+fn random_operation(dir: &Path) -> (&'static str, nix::Result<()>) {
+    let (from, to) = (dir.join(random_name()), dir.join(random_name()));
+
+    match with_rng(|rng| rng.gen_range(0..6)) {
+        0 => (
+            "create",
+            open(
+                &from,
+                OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_WRONLY,
+                Mode::from_bits_truncate(0o644),
+            )
+            .and_then(close),
+        ),
+        1 => ("mkdir", mkdir(&from, Mode::from_bits_truncate(0o755))),
+        2 => ("rename", rename(&from, &to)),
+        3 => ("link", link(&from, &to)),
+        4 => ("unlink", unlink(&from)),
+        _ => ("rmdir", rmdir(&from)),
+    }
+}
+
+/// Assert that the entries of the directory are consistent: the names listed by readdir
+/// are exactly the ones which can be stat'ed, with the same type,
+/// and the link counts match the number of names of the files and of subdirectories.
+fn assert_consistent(dir: &Path) {
+    let mut names = HashSet::new();
+    let mut links: HashMap<u64, (u64, usize)> = HashMap::new();
+    let mut subdirs = 0;
+
+    for entry in read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name().into_string().unwrap();
+        assert!(names.insert(name.clone()), "{name} is listed twice");
+
+        let metadata = symlink_metadata(entry.path())
+            .unwrap_or_else(|e| panic!("{name} is listed but cannot be stat'ed: {e}"));
+        assert_eq!(
+            entry.file_type().unwrap(),
+            metadata.file_type(),
+            "type of {name} differs between readdir and stat"
+        );
+
+        if metadata.is_dir() {
+            subdirs += 1;
+            assert_eq!(read_dir(entry.path()).unwrap().count(), 0);
+            if metadata.nlink() != 1 {
+                assert_eq!(metadata.nlink(), 2, "link count of directory {name}");
+            }
+        } else {
+            links
+                .entry(metadata.ino())
+                .or_insert((metadata.nlink(), 0))
+                .1 += 1;
+        }
+    }
+
+    for i in 0..NAMES {
+        let name = format!("entry{i}");
+        assert_eq!(
+            symlink_metadata(dir.join(&name)).is_ok(),
+            names.contains(&name),
+            "{name} can be stat'ed but is not listed, or the other way around"
+        );
+    }
+    assert!(names.len() <= NAMES, "unexpected entries: {names:?}");
+
+    for (ino, (nlink, count)) in links {
+        assert_eq!(
+            nlink, count as u64,
+            "link count of inode {ino} differs from its number of names"
+        );
+    }
+
+    // Some file systems do not count the subdirectories in the link count
+    let nlink = symlink_metadata(dir).unwrap().nlink();
+    if nlink != 1 {
+        assert_eq!(nlink, 2 + subdirs, "link count of the shared directory");
+    }
+}
+
+crate::test_case! {
+    /// Concurrent create, mkdir, rename, link, unlink and rmdir operations on the entries
+    /// of a directory leave it consistent
+    metadata_races; stress_enabled
+}
+fn metadata_races(ctx: &mut TestContext) {
+    let stress = ctx.stress_config().unwrap();
+    let dir = ctx.create(FileType::Dir).unwrap();
+
+    ctx.stress(stress.threads, stress.iterations, |_, _| {
+        let (operation, res) = random_operation(&dir);
+        assert!(
+            matches!(
+                res,
+                Ok(())
+                    | Err(Errno::ENOENT
+                        | Errno::EEXIST
+                        | Errno::ENOTDIR
+                        | Errno::EISDIR
+                        | Errno::EPERM
+                        | Errno::ENOTEMPTY)
+            ),
+            "{operation} failed unexpectedly: {res:?}"
+        );
+    });
+
+    assert_consistent(&dir);
+}
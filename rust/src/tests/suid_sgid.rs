@@ -0,0 +1,65 @@
+//! Tests for the clearing of the SUID/SGID bits when a regular file is modified
+//! by a non-privileged user.
+
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+use nix::{sys::stat::Mode, unistd::ftruncate};
+
+use crate::{
+    context::{FileType, SerializedTestContext},
+    features::FileSystemFeature,
+    test::root_privileges,
+    utils::{chmod, chown, lstat, truncate},
+};
+
+/// Assert that `f`, executed by a non-owner with write permission,
+/// clears the SUID/SGID bits of a regular file.
+fn assert_suid_sgid_cleared<F>(ctx: &mut SerializedTestContext, f: F)
+where
+    F: Fn(&Path),
+{
+    let owner = ctx.get_new_user();
+    let user = ctx.get_new_user();
+
+    let file = ctx.create(FileType::Regular).unwrap();
+    chown(&file, Some(owner.uid), Some(owner.gid)).unwrap();
+    // The group execute bit is set, otherwise S_ISGID marks mandatory locking
+    chmod(&file, Mode::from_bits_truncate(0o6777)).unwrap();
+
+    ctx.as_user(user, None, || f(&file));
+
+    let file_stat = lstat(&file).unwrap();
+    assert_eq!(file_stat.st_mode & 0o7777, 0o777);
+}
+
+crate::test_case! {
+    /// write clears the SUID/SGID bits of a regular file when done by a non-privileged user
+    clear_on_write, serialized, root, FileSystemFeature::ClearSuidSgidOnWrite; root_privileges
+}
+fn clear_on_write(ctx: &mut SerializedTestContext) {
+    assert_suid_sgid_cleared(ctx, |path| {
+        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        file.write_all(b"data").unwrap();
+    });
+}
+
+crate::test_case! {
+    /// ftruncate clears the SUID/SGID bits of a regular file when done by a non-privileged user
+    clear_on_ftruncate, serialized, root, FileSystemFeature::ClearSuidSgidOnWrite; root_privileges
+}
+fn clear_on_ftruncate(ctx: &mut SerializedTestContext) {
+    assert_suid_sgid_cleared(ctx, |path| {
+        let file = OpenOptions::new().write(true).open(path).unwrap();
+        ftruncate(file, 123).unwrap();
+    });
+}
+
+crate::test_case! {
+    /// truncate clears the SUID/SGID bits of a regular file when done by a non-privileged user
+    clear_on_truncate, serialized, root, FileSystemFeature::ClearSuidSgidOnWrite; root_privileges
+}
+fn clear_on_truncate(ctx: &mut SerializedTestContext) {
+    assert_suid_sgid_cleared(ctx, |path| {
+        truncate(path, 123).unwrap();
+    });
+}
@@ -1,15 +1,25 @@
 use std::{
+    ffi::OsStr,
     fs::{metadata, remove_dir, remove_file, symlink_metadata},
-    os::unix::prelude::FileTypeExt,
+    os::{fd::AsRawFd, unix::prelude::FileTypeExt},
     path::Path,
 };
 
+use nix::{
+    errno::Errno,
+    fcntl::{readlinkat, OFlag},
+    sys::stat::Mode,
+    unistd::symlinkat,
+};
+
 use crate::{
-    context::{FileType, TestContext},
+    context::{FileType, SerializedTestContext, TestContext},
+    test::root_privileges,
     tests::{assert_times_changed, errors::enoent::enoent_comp_test_case, CTIME, MTIME},
-    utils::symlink,
+    utils::{open, readlink, rename, symlink},
 };
 
+use super::assert_errno;
 use super::errors::{
     eexist::eexist_file_exists_test_case,
     efault::efault_either_test_case,
@@ -17,6 +27,7 @@ use super::errors::{
     enotdir::enotdir_comp_test_case,
     erofs::erofs_new_file_test_case,
 };
+use super::mksyscalls::{assert_dirfd_errors, assert_sgid_dir_inheritance};
 
 crate::test_case! {
     /// symlink creates symbolic links
@@ -49,6 +60,14 @@ fn create_symlink(ctx: &mut TestContext, ft: FileType) {
     assert!(!link.exists());
 }
 
+crate::test_case! {
+    /// A symlink created in a directory with the SGID bit set gets the group ID of the directory
+    sgid_dir_inheritance, serialized, root; root_privileges
+}
+fn sgid_dir_inheritance(ctx: &mut SerializedTestContext) {
+    assert_sgid_dir_inheritance(ctx, |path, _| symlink(Path::new("target"), path));
+}
+
 crate::test_case! {
     /// symlink create a symbolic link to a symbolic link
     // symlink/00.t
@@ -85,6 +104,106 @@ fn changed_parent_time_success(ctx: &mut TestContext) {
         });
 }
 
+crate::test_case! {
+    /// readlink returns the content of the symlink as it was given,
+    /// without resolving it
+    readlink_content
+}
+fn readlink_content(ctx: &mut TestContext) {
+    let target = ctx.create(FileType::Regular).unwrap();
+    let long_target = "x/".repeat(64) + "target";
+
+    for content in [
+        target.as_os_str(),
+        OsStr::new("nonexistent"),
+        OsStr::new("../relative/./path"),
+        OsStr::new(&long_target),
+    ] {
+        let link = ctx.gen_path();
+        symlink(Path::new(content), &link).unwrap();
+        assert_eq!(readlink(&link).unwrap(), content);
+    }
+}
+
+crate::test_case! {
+    /// symlinkat and readlinkat resolve the path of the link relative to the directory file descriptor
+    symlinkat_readlinkat
+}
+fn symlinkat_readlinkat(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let dirfd = open(&dir, OFlag::O_DIRECTORY | OFlag::O_RDONLY, Mode::empty()).unwrap();
+
+    symlinkat("target", Some(dirfd.as_raw_fd()), "link").unwrap();
+
+    assert!(symlink_metadata(dir.join("link")).unwrap().is_symlink());
+    assert!(!ctx.base_path().join("link").exists());
+    assert_eq!(
+        readlinkat(Some(dirfd.as_raw_fd()), "link").unwrap(),
+        "target"
+    );
+    assert_eq!(readlink(&dir.join("link")).unwrap(), "target");
+}
+
+crate::test_case! {
+    /// symlinkat and readlinkat look up the path through the directory file descriptor,
+    /// even after the directory has been renamed
+    symlinkat_readlinkat_renamed_dir
+}
+fn symlinkat_readlinkat_renamed_dir(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let dirfd = open(&dir, OFlag::O_DIRECTORY | OFlag::O_RDONLY, Mode::empty()).unwrap();
+    let new_dir = ctx.gen_path();
+    rename(&dir, &new_dir).unwrap();
+
+    symlinkat("target", Some(dirfd.as_raw_fd()), "link").unwrap();
+
+    assert!(symlink_metadata(new_dir.join("link")).unwrap().is_symlink());
+    assert!(!dir.exists());
+    assert_eq!(
+        readlinkat(Some(dirfd.as_raw_fd()), "link").unwrap(),
+        "target"
+    );
+
+    // A directory created at the old path is not used by the descriptor
+    ctx.new_file(FileType::Dir).name(&dir).create().unwrap();
+    assert_eq!(
+        readlinkat(Some(dirfd.as_raw_fd()), "link").unwrap(),
+        "target"
+    );
+    assert!(symlink_metadata(dir.join("link")).is_err());
+}
+
+crate::test_case! {
+    /// readlink and readlinkat return EINVAL if the file is not a symlink
+    readlink_einval => [Regular, Dir, Fifo, Block, Char, Socket]
+}
+fn readlink_einval(ctx: &mut TestContext, ft: FileType) {
+    let file = ctx.create(ft).unwrap();
+    assert_errno(ctx, readlink(&file), Errno::EINVAL);
+
+    let dirfd = open(
+        ctx.base_path(),
+        OFlag::O_DIRECTORY | OFlag::O_RDONLY,
+        Mode::empty(),
+    )
+    .unwrap();
+    assert_errno(
+        ctx,
+        readlinkat(Some(dirfd.as_raw_fd()), file.file_name().unwrap()),
+        Errno::EINVAL,
+    );
+}
+
+crate::test_case! {
+    /// symlinkat and readlinkat return EBADF if the directory file descriptor is not open
+    /// and ENOTDIR if it does not refer to a directory
+    dirfd_errors
+}
+fn dirfd_errors(ctx: &mut TestContext) {
+    assert_dirfd_errors(ctx, |fd, name| symlinkat("target", fd, name));
+    assert_dirfd_errors(ctx, readlinkat);
+}
+
 // symlink/01.t
 enotdir_comp_test_case!(symlink(Path::new("test"), ~path));
 
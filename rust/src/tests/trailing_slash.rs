@@ -0,0 +1,121 @@
+//! Tests for the resolution of paths with a trailing slash, which have to resolve to directories.
+//! The ENOTDIR cases of each syscall are declared with `enotdir_trailing_slash_test_case`.
+
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use nix::{
+    errno::Errno,
+    fcntl::{open, OFlag},
+    sys::stat::Mode,
+    unistd::close,
+};
+
+use super::assert_errno;
+use crate::{
+    context::{FileType, TestContext},
+    utils::{lstat, mkdir, rename, rmdir, stat},
+};
+
+/// Return `path` with a trailing slash.
+fn with_slash(path: &Path) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push("/");
+
+    path.into()
+}
+
+/// Return whether a file is a directory, according to its mode.
+fn is_dir(mode: nix::libc::mode_t) -> bool {
+    mode & nix::libc::S_IFMT == nix::libc::S_IFDIR
+}
+
+crate::test_case! {
+    /// mkdir and rmdir accept a path with a trailing slash
+    mkdir_rmdir
+}
+fn mkdir_rmdir(ctx: &mut TestContext) {
+    let path = ctx.gen_path();
+
+    assert!(mkdir(&with_slash(&path), Mode::from_bits_truncate(0o755)).is_ok());
+    assert!(is_dir(lstat(&path).unwrap().st_mode));
+
+    assert!(rmdir(&with_slash(&path)).is_ok());
+    assert_errno(ctx, lstat(&path), Errno::ENOENT);
+}
+
+crate::test_case! {
+    /// open and stat resolve a directory with a trailing slash
+    open_stat_dir
+}
+fn open_stat_dir(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let dir_slash = with_slash(&dir);
+
+    let fd = open(
+        &dir_slash,
+        OFlag::O_RDONLY | OFlag::O_DIRECTORY,
+        Mode::empty(),
+    )
+    .unwrap();
+    close(fd).unwrap();
+    assert_eq!(stat(&dir_slash).unwrap().st_ino, stat(&dir).unwrap().st_ino);
+}
+
+crate::test_case! {
+    /// A symbolic link to a directory with a trailing slash resolves to the directory,
+    /// even with lstat
+    symlink_to_dir
+}
+fn symlink_to_dir(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(dir.clone()))).unwrap();
+    let link_slash = with_slash(&link);
+    let dir_ino = stat(&dir).unwrap().st_ino;
+
+    assert_eq!(stat(&link_slash).unwrap().st_ino, dir_ino);
+    let link_stat = lstat(&link_slash).unwrap();
+    assert!(is_dir(link_stat.st_mode));
+    assert_eq!(link_stat.st_ino, dir_ino);
+
+    let fd = open(&link_slash, OFlag::O_RDONLY, Mode::empty()).unwrap();
+    close(fd).unwrap();
+}
+
+crate::test_case! {
+    /// A symbolic link to a file which is not a directory with a trailing slash returns ENOTDIR
+    symlink_to_file_enotdir
+}
+fn symlink_to_file_enotdir(ctx: &mut TestContext) {
+    let file = ctx.create(FileType::Regular).unwrap();
+    let link = ctx.create(FileType::Symlink(Some(file))).unwrap();
+    let link_slash = with_slash(&link);
+
+    assert_errno(ctx, stat(&link_slash), Errno::ENOTDIR);
+    assert_errno(ctx, lstat(&link_slash), Errno::ENOTDIR);
+    assert_errno(
+        ctx,
+        open(&link_slash, OFlag::O_RDONLY, Mode::empty()),
+        Errno::ENOTDIR,
+    );
+}
+
+crate::test_case! {
+    /// rename accepts directories with trailing slashes
+    rename_dir
+}
+fn rename_dir(ctx: &mut TestContext) {
+    let dir = ctx.create(FileType::Dir).unwrap();
+    let ino = stat(&dir).unwrap().st_ino;
+    let new_path = ctx.gen_path();
+
+    assert!(rename(&with_slash(&dir), &with_slash(&new_path)).is_ok());
+    assert_errno(ctx, lstat(&dir), Errno::ENOENT);
+    assert_eq!(lstat(&new_path).unwrap().st_ino, ino);
+
+    let target = ctx.create(FileType::Dir).unwrap();
+    assert!(rename(&with_slash(&new_path), &with_slash(&target)).is_ok());
+    assert_eq!(lstat(&target).unwrap().st_ino, ino);
+}
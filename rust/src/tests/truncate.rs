@@ -1,24 +1,114 @@
-use std::{fs::File, io::Write};
+use std::{
+    fs::{self, File},
+    io::Write,
+    os::unix::fs::FileExt,
+    path::Path,
+};
 
-use nix::{errno::Errno, sys::stat::lstat, unistd::truncate};
-use rand::random;
+use nix::{errno::Errno, libc::off_t};
 
 use crate::{
+    config::Config,
     context::{FileType, SerializedTestContext},
+    features::FileSystemFeature,
+    rng::random,
+    space,
     test::TestContext,
-    tests::{assert_ctime_changed, assert_ctime_unchanged},
+    tests::{assert_ctime_changed, assert_ctime_unchanged, assert_times_changed, CTIME, MTIME},
+    utils::{lstat, truncate},
 };
 
+use super::assert_errno;
 use super::errors::{
     efault::efault_path_test_case,
-    eloop::eloop_comp_test_case,
+    eloop::{eloop_comp_test_case, eloop_symloop_max_test_case},
     enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case},
     enoent::{enoent_comp_test_case, enoent_named_file_test_case},
-    enotdir::enotdir_comp_test_case,
+    enotdir::{enotdir_comp_test_case, enotdir_trailing_slash_test_case},
     erofs::erofs_named_test_case,
     etxtbsy::etxtbsy_test_case,
 };
 
+/// Sizes beyond 2 GiB and 4 GiB, to check that offsets are not truncated to 32 bits.
+pub(super) const LARGE_SIZES: [off_t; 2] = [(1 << 31) + 123, (1 << 32) + 123];
+
+/// Return `{FILESIZEBITS}` for a directory, which nix doesn't provide on every platform.
+fn file_size_bits(path: &Path) -> nix::Result<Option<nix::libc::c_long>> {
+    #[cfg(not(target_vendor = "apple"))]
+    return nix::unistd::pathconf(path, nix::unistd::PathconfVar::FILESIZEBITS);
+
+    #[cfg(target_vendor = "apple")]
+    {
+        use nix::NixPath;
+
+        // SAFETY: The path is a valid C string, and errno is cleared to tell no limit from an error
+        let raw = path.with_nix_path(|path| unsafe {
+            Errno::clear();
+            nix::libc::pathconf(path.as_ptr(), nix::libc::_PC_FILESIZEBITS)
+        })?;
+        match (raw, Errno::last_raw()) {
+            (-1, 0) => Ok(None),
+            (-1, _) => Err(Errno::last()),
+            (bits, _) => Ok(Some(bits)),
+        }
+    }
+}
+
+/// Guard to check that the file system supports files larger than 4 GiB,
+/// and has enough space for them unless they are sparse.
+pub(super) fn large_files(config: &Config, base_path: &Path) -> anyhow::Result<()> {
+    match file_size_bits(base_path)? {
+        Some(bits) if bits > 33 => (),
+        _ => anyhow::bail!("files larger than 4 GiB are not supported"),
+    }
+
+    let required = if config
+        .features
+        .fs_features
+        .contains_key(&FileSystemFeature::SparseFiles)
+    {
+        space::BASE_REQUIREMENT
+    } else {
+        // A file of each size is created, whose holes may be allocated
+        LARGE_SIZES.iter().sum::<off_t>() as u64
+    };
+    space::require(config, base_path, required)
+}
+
+/// Assert that growing a file with `grow` to `new_size` preserves its data,
+/// fills the new bytes with zeroes, and updates mtime and ctime.
+pub(super) fn assert_grow_zero_filled<F>(ctx: &TestContext, new_size: off_t, grow: F)
+where
+    F: FnOnce(&Path, off_t),
+{
+    const CHECKED_LEN: off_t = 8192;
+    let path = ctx.create(FileType::Regular).unwrap();
+    let data: [u8; 1234] = random();
+    fs::write(&path, data).unwrap();
+    let old_size = data.len() as off_t;
+
+    assert_times_changed()
+        .path(&path, CTIME | MTIME)
+        .execute(ctx, false, || grow(&path, new_size));
+    assert_eq!(lstat(&path).unwrap().st_size, new_size);
+
+    let file = File::open(&path).unwrap();
+    let mut read_data = [0; 1234];
+    file.read_exact_at(&mut read_data, 0).unwrap();
+    assert_eq!(read_data, data);
+
+    // Check the bytes after the previous end of file and before the new one
+    let len = CHECKED_LEN.min(new_size - old_size);
+    for offset in [old_size, new_size - len] {
+        let mut zeroes = vec![0xff; len as usize];
+        file.read_exact_at(&mut zeroes, offset as u64).unwrap();
+        assert!(
+            zeroes.iter().all(|b| *b == 0),
+            "the file is not filled with zeroes at offset {offset}"
+        );
+    }
+}
+
 crate::test_case! {
     /// truncate should extend a file, and shrink a sparse file
     // truncate/00.t
@@ -39,6 +129,28 @@ fn extend_file_shrink_sparse(ctx: &mut TestContext) {
     assert_eq!(actual_size, size);
 }
 
+crate::test_case! {
+    /// truncate growing a file preserves its data, fills it with zeroes and updates mtime and ctime
+    grow_zero_filled
+}
+fn grow_zero_filled(ctx: &mut TestContext) {
+    assert_grow_zero_filled(ctx, 56789, |path, size| {
+        truncate(path, size).unwrap();
+    });
+}
+
+crate::test_case! {
+    /// truncate can grow a file beyond 2 GiB and 4 GiB
+    grow_large_offsets; large_files
+}
+fn grow_large_offsets(ctx: &mut TestContext) {
+    for size in LARGE_SIZES {
+        assert_grow_zero_filled(ctx, size, |path, size| {
+            truncate(path, size).unwrap();
+        });
+    }
+}
+
 crate::test_case! {
     /// truncate should shrink the file if the specified size is less than the actual one
     // truncate/00.t
@@ -87,13 +199,14 @@ fn unchanged_ctime_failed(ctx: &mut SerializedTestContext) {
 
     assert_ctime_unchanged(ctx, &file, || {
         ctx.as_user(user, None, || {
-            assert_eq!(truncate(&file, 123), Err(Errno::EACCES));
+            assert_errno(ctx, truncate(&file, 123), Errno::EACCES);
         });
     });
 }
 
 // (f)truncate/01.t
 enotdir_comp_test_case!(truncate(~path, 0));
+enotdir_trailing_slash_test_case!(truncate(~path, 0));
 
 // truncate/02.t
 enametoolong_comp_test_case!(truncate(~path, 0));
@@ -107,6 +220,7 @@ enoent_comp_test_case!(truncate(~path, 0));
 
 // truncate/07.t
 eloop_comp_test_case!(truncate(~path, 0));
+eloop_symloop_max_test_case!(truncate(~path, 0));
 
 crate::test_case! {
     /// truncate returns EISDIR if the named file is a directory
@@ -115,7 +229,7 @@ crate::test_case! {
 }
 fn eisdir(ctx: &mut TestContext) {
     let path = ctx.create(FileType::Dir).unwrap();
-    assert_eq!(truncate(&path, 0), Err(Errno::EISDIR));
+    assert_errno(ctx, truncate(&path, 0), Errno::EISDIR);
 }
 
 // (f)truncate/10.t
@@ -132,8 +246,8 @@ crate::test_case! {
 fn einval_negative_length(ctx: &mut TestContext) {
     let path = ctx.create(FileType::Regular).unwrap();
 
-    assert_eq!(truncate(&path, -1), Err(Errno::EINVAL));
-    assert_eq!(truncate(&path, nix::libc::off_t::MIN), Err(Errno::EINVAL));
+    assert_errno(ctx, truncate(&path, -1), Errno::EINVAL);
+    assert_errno(ctx, truncate(&path, nix::libc::off_t::MIN), Errno::EINVAL);
 }
 
 // (f)truncate/14.t
@@ -1,22 +1,24 @@
 use std::os::fd::AsRawFd;
 
-use nix::{sys::stat::fstat, unistd::unlink};
+use nix::{errno::Errno, sys::stat::fstat};
 
 use crate::{
     context::{FileType, SerializedTestContext, TestContext},
     tests::{assert_ctime_changed, assert_ctime_unchanged},
-    utils::link,
+    utils::unlink,
 };
 
 use super::{
-    assert_mtime_changed,
+    assert_errno, assert_mtime_changed,
     errors::{
         efault::efault_path_test_case,
-        eloop::eloop_comp_test_case,
+        eloop::{eloop_comp_test_case, eloop_symloop_max_test_case},
         enametoolong::{enametoolong_comp_test_case, enametoolong_path_test_case},
         enoent::enoent_named_file_test_case,
-        enotdir::enotdir_comp_test_case,
+        enotdir::{enotdir_comp_test_case, enotdir_trailing_slash_test_case},
         erofs::erofs_named_test_case,
+        etxtbsy::{exec_mounted, RunningExecutable},
+        sticky::sticky_test_case,
     },
 };
 
@@ -40,8 +42,7 @@ crate::test_case! {
 fn update_ctime_success(ctx: &mut TestContext, ft: FileType) {
     let path = ctx.create(ft).unwrap();
 
-    let link_path = ctx.base_path().join("link");
-    link(&path, &link_path).unwrap();
+    let link_path = ctx.create(FileType::HardLink(Some(path.clone()))).unwrap();
 
     assert_ctime_changed(ctx, &link_path, || {
         assert!(unlink(&path).is_ok());
@@ -70,8 +71,7 @@ crate::test_case! {
 fn unchanged_ctime_failed(ctx: &mut SerializedTestContext, ft: FileType) {
     let path = ctx.create(ft).unwrap();
 
-    let link_path = ctx.base_path().join("link");
-    link(&path, &link_path).unwrap();
+    let link_path = ctx.create(FileType::HardLink(Some(path.clone()))).unwrap();
 
     let user = ctx.get_new_user();
 
@@ -144,6 +144,7 @@ fn open_file_not_freed(ctx: &mut TestContext) {
 
 // unlink/01.t
 enotdir_comp_test_case!(unlink);
+enotdir_trailing_slash_test_case!(unlink);
 
 // unlink/02.t
 enametoolong_comp_test_case!(unlink);
@@ -156,9 +157,30 @@ enoent_named_file_test_case!(unlink);
 
 // unlink/07.t
 eloop_comp_test_case!(unlink);
+eloop_symloop_max_test_case!(unlink);
 
 // unlink/12.t
 erofs_named_test_case!(unlink);
 
+// unlink/11.t
+sticky_test_case!(unlink => [Regular, Fifo, Block, Char, Socket, Symlink(None)]);
+
 // unlink/13.t
 efault_path_test_case!(unlink, nix::libc::unlink);
+
+crate::test_case! {
+    /// unlink removes an executable while it is running, without stopping it,
+    /// unless the system returns ETXTBSY as allowed by POSIX
+    running_executable; exec_mounted
+}
+fn running_executable(ctx: &mut TestContext) {
+    let mut executable = RunningExecutable::spawn(ctx);
+
+    match unlink(executable.path()) {
+        Ok(()) => {
+            assert!(!executable.path().exists());
+            assert!(executable.is_running());
+        }
+        res => assert_errno(ctx, res, Errno::ETXTBSY),
+    }
+}
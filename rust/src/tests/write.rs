@@ -0,0 +1,27 @@
+use std::{fs::metadata, os::unix::fs::MetadataExt};
+
+use nix::{fcntl::OFlag, unistd::write};
+
+use crate::{context::TestContext, test::FileSystemFeature};
+
+crate::test_case! {
+    /// write updates ctime and mtime with a sub-second precision
+    subsecond_times_update, FileSystemFeature::NsecTimestamps
+}
+fn subsecond_times_update(ctx: &mut TestContext) {
+    let (path, file) = ctx.create_file(OFlag::O_WRONLY, None).unwrap();
+
+    // A single update might legitimately happen on a second boundary,
+    // so we only require one of several updates to carry a fractional part.
+    let has_subsecond = (0..4).any(|_| {
+        ctx.nap();
+        write(&file, b"data").unwrap();
+        let md = metadata(&path).unwrap();
+        md.mtime_nsec() != 0 && md.ctime_nsec() != 0
+    });
+
+    assert!(
+        has_subsecond,
+        "ctime and mtime updates do not have a sub-second precision"
+    );
+}
@@ -0,0 +1,16 @@
+//! Tests for the syscalls setting and removing the extended attributes of the user namespace.
+
+use std::path::Path;
+
+use crate::{
+    context::TestContext,
+    utils::{remove_user_xattr, set_user_xattr},
+};
+
+use super::errors::erofs::erofs_named_test_case;
+
+erofs_named_test_case!(
+    xattr,
+    |_: &mut TestContext, path: &Path| set_user_xattr(path, c"pjdfstest", b"value"),
+    |_: &mut TestContext, path: &Path| remove_user_xattr(path, c"pjdfstest")
+);
@@ -0,0 +1,263 @@
+//! Trace of the file system operations of the tests.
+//!
+//! When tracing is enabled, the backend of the process (see [`crate::ops`]) is wrapped
+//! to record every operation with its arguments and result,
+//! and the runner reports the operations of the failed tests,
+//! so a failure comes with the exact sequence which led to it.
+
+use std::{
+    ffi::{CStr, OsString},
+    fmt::Debug,
+    os::fd::{AsRawFd, OwnedFd},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{
+    fcntl::OFlag,
+    libc::{dev_t, mode_t, off_t},
+    sys::stat::{FileStat, Mode, SFlag},
+    unistd::{Gid, Uid},
+};
+
+use crate::{
+    ops::{configured_backend, FsOps},
+    recorder::{Record, Recorder},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Operations recorded by the current test, taken by the runner after it returns.
+pub(crate) static RECORDER: Recorder<String> = Recorder::new();
+
+/// Enable or disable the tracing of the operations.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Return whether the operations are traced.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Take the operations recorded since the last call.
+pub fn take() -> Vec<String> {
+    RECORDER.take()
+}
+
+impl Record for String {
+    fn to_line(&self) -> String {
+        self.clone()
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        Some(line.to_owned())
+    }
+}
+
+/// Result of an operation, as written in the trace.
+trait TraceResult {
+    fn trace(&self) -> String;
+}
+
+impl TraceResult for () {
+    fn trace(&self) -> String {
+        String::from("0")
+    }
+}
+
+impl TraceResult for OwnedFd {
+    fn trace(&self) -> String {
+        self.as_raw_fd().to_string()
+    }
+}
+
+impl TraceResult for FileStat {
+    fn trace(&self) -> String {
+        format!(
+            "0 {{mode=0o{:o}, uid={}, gid={}, size={}}}",
+            self.st_mode, self.st_uid, self.st_gid, self.st_size
+        )
+    }
+}
+
+impl TraceResult for OsString {
+    fn trace(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Write an operation in the trace, e.g. `mkdir("/tmp/a", 0o755) = -1 EEXIST`.
+fn line<T: TraceResult>(name: &str, args: &[&dyn Debug], result: &nix::Result<T>) -> String {
+    let args = args
+        .iter()
+        .map(|arg| format!("{arg:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let result = match result {
+        Ok(value) => value.trace(),
+        Err(errno) => format!("-1 {errno:?}"),
+    };
+
+    format!("{name}({args}) = {result}")
+}
+
+/// Record an operation, and return its result.
+fn record<T: TraceResult>(
+    name: &str,
+    args: &[&dyn Debug],
+    result: nix::Result<T>,
+) -> nix::Result<T> {
+    RECORDER.record(line(name, args, &result));
+    result
+}
+
+/// Arguments written as octal numbers.
+struct Octal(mode_t);
+
+impl Debug for Octal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0o{:o}", self.0)
+    }
+}
+
+fn mode(mode: Mode) -> Octal {
+    Octal(mode.bits())
+}
+
+/// Flags written with their names, e.g. `O_CREAT|O_WRONLY`.
+struct Flags(String);
+
+impl Debug for Flags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn oflag(oflag: OFlag) -> Flags {
+    let names: Vec<_> = oflag.iter_names().map(|(name, _)| name).collect();
+    if names.is_empty() {
+        Flags(String::from("O_RDONLY"))
+    } else {
+        Flags(names.join("|"))
+    }
+}
+
+/// Owner or group, `-1` if unchanged.
+fn id(id: Option<u32>) -> Flags {
+    Flags(id.map_or_else(|| String::from("-1"), |id| id.to_string()))
+}
+
+/// Backend recording the operations of the configured backend.
+pub(crate) struct TracedOps;
+
+impl FsOps for TracedOps {
+    fn open(&self, path: &CStr, flags: OFlag, perm: Mode) -> nix::Result<OwnedFd> {
+        let result = configured_backend().open(path, flags, perm);
+        record("open", &[&path, &oflag(flags), &mode(perm)], result)
+    }
+
+    fn stat(&self, path: &CStr) -> nix::Result<FileStat> {
+        record("stat", &[&path], configured_backend().stat(path))
+    }
+
+    fn lstat(&self, path: &CStr) -> nix::Result<FileStat> {
+        record("lstat", &[&path], configured_backend().lstat(path))
+    }
+
+    fn mkdir(&self, path: &CStr, perm: Mode) -> nix::Result<()> {
+        let result = configured_backend().mkdir(path, perm);
+        record("mkdir", &[&path, &mode(perm)], result)
+    }
+
+    fn rmdir(&self, path: &CStr) -> nix::Result<()> {
+        record("rmdir", &[&path], configured_backend().rmdir(path))
+    }
+
+    fn unlink(&self, path: &CStr) -> nix::Result<()> {
+        record("unlink", &[&path], configured_backend().unlink(path))
+    }
+
+    fn rename(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()> {
+        let result = configured_backend().rename(old_path, new_path);
+        record("rename", &[&old_path, &new_path], result)
+    }
+
+    fn link(&self, old_path: &CStr, new_path: &CStr) -> nix::Result<()> {
+        let result = configured_backend().link(old_path, new_path);
+        record("link", &[&old_path, &new_path], result)
+    }
+
+    fn symlink(&self, target: &CStr, path: &CStr) -> nix::Result<()> {
+        let result = configured_backend().symlink(target, path);
+        record("symlink", &[&target, &path], result)
+    }
+
+    fn readlink(&self, path: &CStr) -> nix::Result<OsString> {
+        record("readlink", &[&path], configured_backend().readlink(path))
+    }
+
+    fn chmod(&self, path: &CStr, perm: Mode) -> nix::Result<()> {
+        let result = configured_backend().chmod(path, perm);
+        record("chmod", &[&path, &mode(perm)], result)
+    }
+
+    fn lchmod(&self, path: &CStr, perm: Mode) -> nix::Result<()> {
+        let result = configured_backend().lchmod(path, perm);
+        record("lchmod", &[&path, &mode(perm)], result)
+    }
+
+    fn chown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()> {
+        let result = configured_backend().chown(path, owner, group);
+        let (owner, group) = (owner.map(Uid::as_raw), group.map(Gid::as_raw));
+        record("chown", &[&path, &id(owner), &id(group)], result)
+    }
+
+    fn lchown(&self, path: &CStr, owner: Option<Uid>, group: Option<Gid>) -> nix::Result<()> {
+        let result = configured_backend().lchown(path, owner, group);
+        let (owner, group) = (owner.map(Uid::as_raw), group.map(Gid::as_raw));
+        record("lchown", &[&path, &id(owner), &id(group)], result)
+    }
+
+    fn truncate(&self, path: &CStr, len: off_t) -> nix::Result<()> {
+        let result = configured_backend().truncate(path, len);
+        record("truncate", &[&path, &len], result)
+    }
+
+    fn mknod(&self, path: &CStr, kind: SFlag, perm: Mode, dev: dev_t) -> nix::Result<()> {
+        let result = configured_backend().mknod(path, kind, perm, dev);
+        let kind = Flags(kind.iter_names().map(|(name, _)| name).collect());
+        record("mknod", &[&path, &kind, &mode(perm), &dev], result)
+    }
+
+    fn mkfifo(&self, path: &CStr, perm: Mode) -> nix::Result<()> {
+        let result = configured_backend().mkfifo(path, perm);
+        record("mkfifo", &[&path, &mode(perm)], result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::{errno::Errno, fcntl::OFlag, sys::stat::Mode};
+
+    use super::{line, mode, oflag};
+
+    #[test]
+    fn format() {
+        assert_eq!(
+            line(
+                "mkdir",
+                &[&c"/tmp/a", &mode(Mode::from_bits_truncate(0o755))],
+                &Err::<(), _>(Errno::EEXIST)
+            ),
+            r#"mkdir("/tmp/a", 0o755) = -1 EEXIST"#
+        );
+        assert_eq!(
+            line(
+                "open",
+                &[&c"/tmp/a", &oflag(OFlag::empty()), &mode(Mode::empty())],
+                &Ok(())
+            ),
+            r#"open("/tmp/a", O_RDONLY, 0o0) = 0"#
+        );
+    }
+}
@@ -1,65 +1,174 @@
 //! Utility functions for filesystem operations.
 //!
 //! This module provides utility functions for filesystem operations which are not available in the standard library.
+//! The wrappers of the path-based syscalls call the backend of the process (see [`crate::ops`]),
+//! and measure their latency when enabled (see [`crate::latency`]).
+//! The entries they create are counted in the budget of the test, if any.
 
 use std::{
-    os::fd::{FromRawFd, OwnedFd},
+    ffi::{CStr, OsStr, OsString},
+    fs::{symlink_metadata, File},
+    os::{
+        fd::OwnedFd,
+        unix::{ffi::OsStrExt, fs::MetadataExt},
+    },
     path::Path,
+    thread,
+    time::Duration,
 };
 
 use nix::{
-    fcntl::{renameat, AtFlags, OFlag},
-    sys::stat::{fchmodat, lstat, FchmodatFlags, Mode},
-    unistd::{fchownat, linkat, symlinkat, Gid, Uid},
+    fcntl::OFlag,
+    libc::{dev_t, off_t},
+    sys::stat::{FileStat, Mode, SFlag},
+    unistd::{Gid, Uid},
+    NixPath,
 };
 
+use crate::{budget, latency::measure, ops::backend};
+
 pub mod dev;
 
-/// Wrapper for `fchmodat(None, path, mode, FchmodatFlags::FollowSymlink)`.
-pub fn chmod<P: ?Sized + nix::NixPath>(path: &P, mode: nix::sys::stat::Mode) -> nix::Result<()> {
-    fchmodat(None, path, mode, FchmodatFlags::FollowSymlink)
+/// Count the entry created at `path` in the budget of the test, if it has been created.
+fn created<T>(path: &CStr, result: nix::Result<T>) -> nix::Result<T> {
+    if result.is_ok() {
+        budget::created(Path::new(OsStr::from_bytes(path.to_bytes())));
+    }
+    result
 }
 
-/// Wrapper for `fchmodat(None, path, mode, FchmodatFlags::NoFollowSymlink)`.
-pub fn lchmod<P: ?Sized + nix::NixPath>(path: &P, mode: nix::sys::stat::Mode) -> nix::Result<()> {
-    fchmodat(None, path, mode, FchmodatFlags::NoFollowSymlink)
+/// Wrapper for `open` which returns [`OwnedFd`] instead of [`RawFd`](std::os::fd::RawFd).
+pub fn open<P: ?Sized + NixPath>(path: &P, oflag: OFlag, mode: Mode) -> nix::Result<OwnedFd> {
+    path.with_nix_path(|path| {
+        let result = measure("open", || backend().open(path, oflag, mode));
+        if oflag.contains(OFlag::O_CREAT) {
+            created(path, result)
+        } else {
+            result
+        }
+    })?
 }
 
-/// Wrapper for `fchownat(None, path, mode, FchownatFlags::NoFollowSymlink)`.
-pub fn lchown<P: ?Sized + nix::NixPath>(
-    path: &P,
-    owner: Option<Uid>,
-    group: Option<Gid>,
-) -> nix::Result<()> {
-    fchownat(None, path, owner, group, AtFlags::AT_SYMLINK_NOFOLLOW)
+/// Wrapper for `stat`.
+pub fn stat<P: ?Sized + NixPath>(path: &P) -> nix::Result<FileStat> {
+    path.with_nix_path(|path| measure("stat", || backend().stat(path)))?
+}
+
+/// Wrapper for `lstat`.
+pub fn lstat<P: ?Sized + NixPath>(path: &P) -> nix::Result<FileStat> {
+    path.with_nix_path(|path| measure("stat", || backend().lstat(path)))?
+}
+
+/// Wrapper for `mkdir`.
+pub fn mkdir<P: ?Sized + NixPath>(path: &P, mode: Mode) -> nix::Result<()> {
+    path.with_nix_path(|path| created(path, measure("mkdir", || backend().mkdir(path, mode))))?
 }
 
 /// Wrapper for `rmdir`.
-pub fn rmdir<P: ?Sized + nix::NixPath>(path: &P) -> nix::Result<()> {
-    let res = path.with_nix_path(|cstr| unsafe { nix::libc::rmdir(cstr.as_ptr()) })?;
-    nix::errno::Errno::result(res).map(std::mem::drop)
+pub fn rmdir<P: ?Sized + NixPath>(path: &P) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("rmdir", || backend().rmdir(path)))?
+}
+
+/// Wrapper for `unlink`.
+pub fn unlink<P: ?Sized + NixPath>(path: &P) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("unlink", || backend().unlink(path)))?
 }
 
 pub const ALLPERMS: nix::sys::stat::mode_t = 0o7777;
 
 /// Wrapper for `renameat(None, old_path, None, new_path)`.
-pub fn rename<P: ?Sized + nix::NixPath>(old_path: &P, new_path: &P) -> nix::Result<()> {
-    renameat(None, old_path, None, new_path)
+pub fn rename<P: ?Sized + NixPath>(old_path: &P, new_path: &P) -> nix::Result<()> {
+    old_path.with_nix_path(|old_path| {
+        new_path
+            .with_nix_path(|new_path| measure("rename", || backend().rename(old_path, new_path)))
+    })??
 }
 
 /// Wrapper for `linkat(None, old_path, None, new_path)`.
-pub fn link<P: ?Sized + nix::NixPath>(old_path: &P, new_path: &P) -> nix::Result<()> {
-    linkat(None, old_path, None, new_path, AtFlags::empty())
+pub fn link<P: ?Sized + NixPath>(old_path: &P, new_path: &P) -> nix::Result<()> {
+    old_path.with_nix_path(|old_path| {
+        new_path.with_nix_path(|new_path| {
+            created(
+                new_path,
+                measure("link", || backend().link(old_path, new_path)),
+            )
+        })
+    })??
 }
 
 /// Wrapper for `symlinkat(path1, None, path2)`.
-pub fn symlink<P: ?Sized + nix::NixPath>(path1: &P, path2: &P) -> nix::Result<()> {
-    symlinkat(path1, None, path2)
+pub fn symlink<P: ?Sized + NixPath>(path1: &P, path2: &P) -> nix::Result<()> {
+    path1.with_nix_path(|path1| {
+        path2.with_nix_path(|path2| {
+            created(
+                path2,
+                measure("symlink", || backend().symlink(path1, path2)),
+            )
+        })
+    })??
+}
+
+/// Wrapper for `readlink`.
+pub fn readlink<P: ?Sized + NixPath>(path: &P) -> nix::Result<OsString> {
+    path.with_nix_path(|path| measure("readlink", || backend().readlink(path)))?
+}
+
+/// Wrapper for `fchmodat(None, path, mode, FchmodatFlags::FollowSymlink)`.
+pub fn chmod<P: ?Sized + NixPath>(path: &P, mode: Mode) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("chmod", || backend().chmod(path, mode)))?
+}
+
+/// Wrapper for `fchmodat(None, path, mode, FchmodatFlags::NoFollowSymlink)`.
+pub fn lchmod<P: ?Sized + NixPath>(path: &P, mode: Mode) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("chmod", || backend().lchmod(path, mode)))?
+}
+
+/// Wrapper for `chown`.
+pub fn chown<P: ?Sized + NixPath>(
+    path: &P,
+    owner: Option<Uid>,
+    group: Option<Gid>,
+) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("chown", || backend().chown(path, owner, group)))?
+}
+
+/// Wrapper for `fchownat(None, path, mode, FchownatFlags::NoFollowSymlink)`.
+pub fn lchown<P: ?Sized + NixPath>(
+    path: &P,
+    owner: Option<Uid>,
+    group: Option<Gid>,
+) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("chown", || backend().lchown(path, owner, group)))?
+}
+
+/// Wrapper for `truncate`.
+pub fn truncate<P: ?Sized + NixPath>(path: &P, len: off_t) -> nix::Result<()> {
+    path.with_nix_path(|path| measure("truncate", || backend().truncate(path, len)))?
+}
+
+/// Wrapper for `mknod`.
+pub fn mknod<P: ?Sized + NixPath>(
+    path: &P,
+    kind: SFlag,
+    perm: Mode,
+    dev: dev_t,
+) -> nix::Result<()> {
+    path.with_nix_path(|path| {
+        created(
+            path,
+            measure("mknod", || backend().mknod(path, kind, perm, dev)),
+        )
+    })?
+}
+
+/// Wrapper for `mkfifo`.
+pub fn mkfifo<P: ?Sized + NixPath>(path: &P, mode: Mode) -> nix::Result<()> {
+    path.with_nix_path(|path| created(path, measure("mknod", || backend().mkfifo(path, mode))))?
 }
 
 /// Get mountpoint.
 pub fn get_mountpoint(base_path: &Path) -> Result<&Path, anyhow::Error> {
-    let base_dev = lstat(base_path)?.st_dev;
+    let base_dev = nix::sys::stat::lstat(base_path)?.st_dev;
 
     let mut mountpoint = base_path;
     loop {
@@ -68,7 +177,7 @@ pub fn get_mountpoint(base_path: &Path) -> Result<&Path, anyhow::Error> {
             // Root
             _ => return Ok(mountpoint),
         };
-        let current_dev = lstat(current)?.st_dev;
+        let current_dev = nix::sys::stat::lstat(current)?.st_dev;
 
         if current_dev != base_dev {
             break;
@@ -80,6 +189,60 @@ pub fn get_mountpoint(base_path: &Path) -> Result<&Path, anyhow::Error> {
     Ok(mountpoint)
 }
 
+/// Number of files created for each round of [`timestamp_granularity`].
+const GRANULARITY_SAMPLES: u32 = 64;
+/// Maximum duration of a sampling round of [`timestamp_granularity`].
+const GRANULARITY_MAX_WINDOW: Duration = Duration::from_secs(16);
+
+/// Measure the timestamp granularity of the file system on which `base_path` resides.
+///
+/// Files are created at regular intervals without querying their metadata in between,
+/// since some systems (e.g. Linux with multigrain timestamps) only record fine-grained
+/// timestamps after they have been observed.
+/// The smallest difference between the distinct modification times is then returned.
+/// The interval is increased until at least three distinct timestamps are observed.
+pub fn timestamp_granularity(base_path: &Path) -> Result<Duration, anyhow::Error> {
+    let mut interval = Duration::from_micros(100);
+
+    while interval * GRANULARITY_SAMPLES <= GRANULARITY_MAX_WINDOW {
+        let dir = tempfile::tempdir_in(base_path)?;
+
+        let mut mtimes = (0..GRANULARITY_SAMPLES)
+            .map(|i| {
+                let path = dir.path().join(i.to_string());
+                File::create(&path)?;
+                thread::sleep(interval);
+                Ok(path)
+            })
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .map(|path| {
+                let meta = symlink_metadata(path)?;
+                Ok(i128::from(meta.mtime()) * 1_000_000_000 + i128::from(meta.mtime_nsec()))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        mtimes.sort_unstable();
+        mtimes.dedup();
+
+        if mtimes.len() >= 3 {
+            let granularity = mtimes
+                .windows(2)
+                .map(|pair| pair[1] - pair[0])
+                .min()
+                .unwrap();
+
+            return Ok(Duration::from_nanos(granularity.try_into()?));
+        }
+
+        interval *= 4;
+    }
+
+    anyhow::bail!(
+        "Timestamps did not change within {}s, naptime has to be set manually",
+        GRANULARITY_MAX_WINDOW.as_secs()
+    )
+}
+
 /// Safe wrapper for `lchflags`.
 #[cfg(lchflags)]
 pub fn lchflags<P: ?Sized + nix::NixPath>(
@@ -93,9 +256,165 @@ pub fn lchflags<P: ?Sized + nix::NixPath>(
     Errno::result(res).map(drop)
 }
 
-/// Wrapper for open which returns [`Ownedfd`] instead of [`RawFd`].
-pub fn open<P: ?Sized + nix::NixPath>(path: &P, oflag: OFlag, mode: Mode) -> nix::Result<OwnedFd> {
-    // SAFETY: The file descriptor was initialized only by open and isn't used anywhere else,
-    // leaving the ownership to the caller.
-    nix::fcntl::open(path, oflag, mode).map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+/// Safe wrapper for `chflagsat`.
+#[cfg(chflagsat)]
+pub fn chflagsat<P: ?Sized + nix::NixPath>(
+    dirfd: std::os::fd::RawFd,
+    path: &P,
+    flags: nix::sys::stat::FileFlag,
+    atflag: nix::fcntl::AtFlags,
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    let res = path.with_nix_path(|cstr| unsafe {
+        nix::libc::chflagsat(dirfd, cstr.as_ptr(), flags.bits(), atflag.bits())
+    })?;
+
+    Errno::result(res).map(drop)
+}
+
+/// Safe wrapper for `clonefile`.
+#[cfg(target_os = "macos")]
+pub fn clonefile<P: ?Sized + nix::NixPath, Q: ?Sized + nix::NixPath>(
+    src: &P,
+    dst: &Q,
+    flags: u32,
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    let res = src.with_nix_path(|src| {
+        dst.with_nix_path(|dst| unsafe { nix::libc::clonefile(src.as_ptr(), dst.as_ptr(), flags) })
+    })??;
+
+    Errno::result(res).map(drop)
+}
+
+/// Safe wrapper for `clonefileat`.
+#[cfg(target_os = "macos")]
+pub fn clonefileat<P: ?Sized + nix::NixPath, Q: ?Sized + nix::NixPath>(
+    src_dirfd: std::os::fd::RawFd,
+    src: &P,
+    dst_dirfd: std::os::fd::RawFd,
+    dst: &Q,
+    flags: u32,
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    let res = src.with_nix_path(|src| {
+        dst.with_nix_path(|dst| unsafe {
+            nix::libc::clonefileat(src_dirfd, src.as_ptr(), dst_dirfd, dst.as_ptr(), flags)
+        })
+    })??;
+
+    Errno::result(res).map(drop)
+}
+
+/// Write `blocks` blocks of data to a file, each block being filled with its number (from 1),
+/// and return the block size of the file.
+pub fn write_blocks(file: &File, blocks: u8) -> std::io::Result<u64> {
+    use std::os::unix::fs::FileExt;
+
+    let block_size = file.metadata()?.blksize();
+    for block in 0..blocks {
+        file.write_all_at(
+            &vec![block + 1; block_size as usize],
+            u64::from(block) * block_size,
+        )?;
+    }
+
+    Ok(block_size)
+}
+
+/// Safe wrapper for `fspacectl` with `SPACECTL_DEALLOC`, which deallocates a range of a file.
+#[cfg(target_os = "freebsd")]
+pub fn fspacectl_dealloc(
+    fd: std::os::fd::RawFd,
+    offset: nix::libc::off_t,
+    len: nix::libc::off_t,
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    let range = nix::libc::spacectl_range {
+        r_offset: offset,
+        r_len: len,
+    };
+    let mut remaining = nix::libc::spacectl_range {
+        r_offset: 0,
+        r_len: 0,
+    };
+    let res =
+        unsafe { nix::libc::fspacectl(fd, nix::libc::SPACECTL_DEALLOC, &range, 0, &mut remaining) };
+
+    Errno::result(res).map(drop)
+}
+
+/// Safe wrapper to set an extended attribute of the user namespace,
+/// with `setxattr` on Linux and macOS and `extattr_set_file` on FreeBSD.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub fn set_user_xattr<P: ?Sized + nix::NixPath>(
+    path: &P,
+    name: &std::ffi::CStr,
+    value: &[u8],
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    #[cfg(target_os = "linux")]
+    let name = &user_xattr_name(name);
+    let res = path.with_nix_path(|path| unsafe {
+        #[cfg(target_os = "linux")]
+        return nix::libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        ) as nix::libc::ssize_t;
+        #[cfg(target_os = "macos")]
+        return nix::libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+            0,
+        ) as nix::libc::ssize_t;
+        #[cfg(target_os = "freebsd")]
+        return nix::libc::extattr_set_file(
+            path.as_ptr(),
+            nix::libc::EXTATTR_NAMESPACE_USER,
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+        );
+    })?;
+
+    Errno::result(res).map(drop)
+}
+
+/// Safe wrapper to remove an extended attribute of the user namespace,
+/// with `removexattr` on Linux and macOS and `extattr_delete_file` on FreeBSD.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+pub fn remove_user_xattr<P: ?Sized + nix::NixPath>(
+    path: &P,
+    name: &std::ffi::CStr,
+) -> nix::Result<()> {
+    use nix::errno::Errno;
+    #[cfg(target_os = "linux")]
+    let name = &user_xattr_name(name);
+    let res = path.with_nix_path(|path| unsafe {
+        #[cfg(target_os = "linux")]
+        return nix::libc::removexattr(path.as_ptr(), name.as_ptr());
+        #[cfg(target_os = "macos")]
+        return nix::libc::removexattr(path.as_ptr(), name.as_ptr(), 0);
+        #[cfg(target_os = "freebsd")]
+        return nix::libc::extattr_delete_file(
+            path.as_ptr(),
+            nix::libc::EXTATTR_NAMESPACE_USER,
+            name.as_ptr(),
+        );
+    })?;
+
+    Errno::result(res).map(drop)
+}
+
+/// Return the name of an extended attribute in the user namespace,
+/// which is given by a prefix on Linux.
+#[cfg(target_os = "linux")]
+fn user_xattr_name(name: &std::ffi::CStr) -> std::ffi::CString {
+    std::ffi::CString::new([b"user.", name.to_bytes()].concat()).unwrap()
 }